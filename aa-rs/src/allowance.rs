@@ -0,0 +1,124 @@
+use ethers::types::{Address, U256};
+use std::collections::BTreeMap;
+
+/// An active subscription that will pull `price` of `token` from the account every period.
+#[derive(Debug, Clone)]
+pub struct UpcomingCharge {
+    pub subscription_id: U256,
+    pub plan_id: U256,
+    pub token: Address,
+    pub price: U256,
+    pub paid_through: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AllowanceProjection {
+    pub charge: UpcomingCharge,
+    /// `price * periods` for this subscription.
+    pub required: U256,
+    /// Part of `required` not covered by the current allowance.
+    pub shortfall: U256,
+    /// Current allowance for the subscription's token (shared across plans).
+    pub allowance: U256,
+    /// Suggested `approve` amount for the subscription's token (shared across plans).
+    pub suggested_approve: U256,
+}
+
+/// Project per-subscription allowance shortfalls over the next `periods` charges.
+///
+/// OpenSub is the only spender, so every subscription on the same token draws from one allowance.
+/// That allowance is assigned to the soonest-due subscriptions first. Since `approve` overwrites
+/// rather than adds, the suggested amount is the token's total requirement, and never less than
+/// what is already approved.
+pub fn project_allowance(
+    mut charges: Vec<UpcomingCharge>,
+    allowances: &BTreeMap<Address, U256>,
+    periods: u64,
+) -> Vec<AllowanceProjection> {
+    charges.sort_by_key(|c| (c.token, c.paid_through, c.subscription_id));
+
+    let mut required_by_token: BTreeMap<Address, U256> = BTreeMap::new();
+    for c in &charges {
+        let total = required_by_token.entry(c.token).or_default();
+        *total = total.saturating_add(c.price.saturating_mul(U256::from(periods)));
+    }
+
+    let mut remaining = allowances.clone();
+    charges
+        .into_iter()
+        .map(|charge| {
+            let allowance = allowances.get(&charge.token).copied().unwrap_or_default();
+            let left = remaining.entry(charge.token).or_default();
+            let required = charge.price.saturating_mul(U256::from(periods));
+            let covered = required.min(*left);
+            *left -= covered;
+
+            let total = required_by_token[&charge.token];
+            AllowanceProjection {
+                required,
+                shortfall: required - covered,
+                allowance,
+                suggested_approve: total.max(allowance),
+                charge,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{project_allowance, UpcomingCharge};
+    use ethers::types::{Address, U256};
+    use std::collections::BTreeMap;
+
+    fn charge(id: u64, token: Address, price: u64, paid_through: u64) -> UpcomingCharge {
+        UpcomingCharge {
+            subscription_id: U256::from(id),
+            plan_id: U256::from(id * 10),
+            token,
+            price: U256::from(price),
+            paid_through,
+        }
+    }
+
+    #[test]
+    fn shortfall_is_shared_per_token_and_due_first() {
+        let usdc = Address::repeat_byte(0x01);
+        let dai = Address::repeat_byte(0x02);
+        let charges = vec![
+            // Due later: should absorb the shortfall.
+            charge(1, usdc, 100, 2_000),
+            charge(2, usdc, 50, 1_000),
+            charge(3, dai, 10, 1_000),
+        ];
+        let allowances = BTreeMap::from([(usdc, U256::from(400)), (dai, U256::from(1_000))]);
+
+        let rows = project_allowance(charges, &allowances, 3);
+        let by_id = |id: u64| {
+            rows.iter()
+                .find(|r| r.charge.subscription_id == U256::from(id))
+                .unwrap()
+        };
+
+        // usdc needs 150 + 300 = 450 against 400 approved; sub 2 (due first) is fully covered.
+        assert_eq!(by_id(2).required, U256::from(150));
+        assert_eq!(by_id(2).shortfall, U256::zero());
+        assert_eq!(by_id(1).required, U256::from(300));
+        assert_eq!(by_id(1).shortfall, U256::from(50));
+        assert_eq!(by_id(1).suggested_approve, U256::from(450));
+        assert_eq!(by_id(2).suggested_approve, U256::from(450));
+
+        // dai is over-approved: no shortfall, and never suggest lowering the allowance.
+        assert_eq!(by_id(3).shortfall, U256::zero());
+        assert_eq!(by_id(3).suggested_approve, U256::from(1_000));
+    }
+
+    #[test]
+    fn missing_allowance_means_full_shortfall() {
+        let token = Address::repeat_byte(0x03);
+        let rows = project_allowance(vec![charge(1, token, 7, 0)], &BTreeMap::new(), 2);
+        assert_eq!(rows[0].allowance, U256::zero());
+        assert_eq!(rows[0].shortfall, U256::from(14));
+        assert_eq!(rows[0].suggested_approve, U256::from(14));
+    }
+}
@@ -1,13 +1,24 @@
 use crate::encoding::{parse_h256, parse_u256_quantity};
+use crate::http::{is_endpoint_unavailable, ClientOptions, JsonRpcHttp, RpcDump};
 use anyhow::{anyhow, Context, Result};
 use ethers::types::{Address, H256, U256};
 use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
+/// ERC-4337 bundler JSON-RPC client.
+///
+/// With fallbacks (`--bundler-fallback`), a call that finds its bundler unavailable (connection
+/// error, timeout, HTTP 5xx) is retried on the next one. JSON-RPC errors, such as an AA
+/// rejection of the op, are the op's fault and are returned as they are.
 #[derive(Debug, Clone)]
 pub struct BundlerClient {
-    url: String,
-    http: reqwest::Client,
+    /// The primary bundler, then the fallbacks in order.
+    endpoints: Vec<JsonRpcHttp>,
+    /// Index of the bundler calls start at: the last one that answered. After a send, that is
+    /// the bundler that accepted the op.
+    active: Arc<AtomicUsize>,
 }
 
 #[derive(Debug, Clone)]
@@ -17,14 +28,65 @@ pub struct GasEstimates {
     pub pre_verification_gas: U256,
 }
 
+/// Bundler-preferred userOp fees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserOpGasPrice {
+    /// `None` when the method only returns a priority fee (e.g. `rundler_maxPriorityFeePerGas`).
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: U256,
+}
+
 impl BundlerClient {
     pub fn new(url: String) -> Self {
+        Self::with_fallbacks(url, Vec::new())
+    }
+
+    /// `url` first, then each of `fallbacks` in order while the previous ones are unavailable.
+    pub fn with_fallbacks(url: String, fallbacks: Vec<String>) -> Self {
         Self {
-            url,
-            http: reqwest::Client::new(),
+            endpoints: std::iter::once(url)
+                .chain(fallbacks)
+                .map(JsonRpcHttp::new)
+                .collect(),
+            active: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// Log request/response bodies at debug level (`--http-trace`).
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.endpoints = self
+            .endpoints
+            .into_iter()
+            .map(|rpc| rpc.with_trace(trace))
+            .collect();
+        self
+    }
+
+    /// Write every request/response to `--dump-rpc-dir`.
+    pub fn with_dump(mut self, dump: Option<RpcDump>) -> Self {
+        self.endpoints = self
+            .endpoints
+            .into_iter()
+            .map(|rpc| rpc.with_dump(dump.clone()))
+            .collect();
+        self
+    }
+
+    pub fn with_client_options(mut self, options: &ClientOptions) -> Result<Self> {
+        self.endpoints = self
+            .endpoints
+            .into_iter()
+            .map(|rpc| rpc.with_client_options(options))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    /// Index of the bundler that answered last (0 is the primary, 1 the first fallback, ...).
+    /// After [`Self::send_user_operation`], the one that accepted the op.
+    pub fn active_endpoint(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
     pub async fn estimate_user_operation_gas(
         &self,
         user_op: Value,
@@ -47,6 +109,19 @@ impl BundlerClient {
         })
     }
 
+    /// Ask the bundler for its preferred fees via a vendor method (no params), e.g.
+    /// `pimlico_getUserOperationGasPrice` or `rundler_maxPriorityFeePerGas`.
+    pub async fn get_user_operation_gas_price(&self, method: &str) -> Result<UserOpGasPrice> {
+        let res = self
+            .rpc(method, serde_json::json!([]))
+            .await
+            .with_context(|| format!("{method} failed"))?;
+        parse_user_operation_gas_price(&res)
+    }
+
+    /// Send the op. A bundler that fails without answering may still have received it, so a
+    /// fallback can be handed an op the primary also holds; both share its userOpHash and nonce,
+    /// so at most one of them gets it included.
     pub async fn send_user_operation(&self, user_op: Value, entrypoint: Address) -> Result<H256> {
         let params = serde_json::json!([user_op, fmt_addr(entrypoint)]);
         let res = self
@@ -56,15 +131,20 @@ impl BundlerClient {
         parse_userop_hash(&res)
     }
 
-    /// Poll for a receipt until timeout.
+    /// Poll for a receipt until `timeout` (a zero `timeout` polls until the receipt arrives).
+    /// Polls start at the active bundler (the one that accepted the op) and fail over like any
+    /// other call if it goes down: `eth_getUserOperationReceipt` is answered from the
+    /// EntryPoint's logs, so a bundler that never saw the op can still report it once included.
     pub async fn wait_user_operation_receipt(
         &self,
         user_op_hash: H256,
         timeout: Duration,
     ) -> Result<Value> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(1500);
+
         let start = std::time::Instant::now();
         loop {
-            if timeout.as_secs() > 0 && start.elapsed() > timeout {
+            if !timeout.is_zero() && start.elapsed() >= timeout {
                 return Err(anyhow!(
                     "timed out waiting for userOp receipt after {:?}",
                     timeout
@@ -86,40 +166,41 @@ impl BundlerClient {
                 }
             }
 
-            tokio::time::sleep(Duration::from_millis(1500)).await;
+            // Don't sleep past the timeout, however short it is.
+            let pause = if timeout.is_zero() {
+                POLL_INTERVAL
+            } else {
+                POLL_INTERVAL.min(timeout.saturating_sub(start.elapsed()))
+            };
+            tokio::time::sleep(pause).await;
         }
     }
 
+    /// Call the active bundler, moving on to the next ones (wrapping around) while they are
+    /// unavailable. The bundler that answers becomes the active one.
     async fn rpc(&self, method: &str, params: Value) -> Result<Value> {
-        let req = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": method,
-            "params": params,
-        });
-
-        let resp = self
-            .http
-            .post(&self.url)
-            .json(&req)
-            .send()
-            .await
-            .with_context(|| format!("POST {} failed", self.url))?;
-
-        let status = resp.status();
-        let body: Value = resp.json().await.context("failed to decode JSON")?;
-
-        if !status.is_success() {
-            return Err(anyhow!("HTTP {}: {}", status, body));
-        }
-
-        if let Some(err) = body.get("error") {
-            return Err(anyhow!("RPC error: {}", err));
+        let start = self.active_endpoint();
+        let count = self.endpoints.len();
+        for attempt in 0..count {
+            let at = (start + attempt) % count;
+            let result = self.endpoints[at].call(method, params.clone()).await;
+            match &result {
+                Err(err) if attempt + 1 < count && is_endpoint_unavailable(err) => {
+                    tracing::warn!(
+                        method,
+                        bundler = at,
+                        next = (at + 1) % count,
+                        error = %err,
+                        "bundler unavailable; trying the next one"
+                    );
+                }
+                _ => {
+                    self.active.store(at, Ordering::Relaxed);
+                    return result;
+                }
+            }
         }
-
-        body.get("result")
-            .cloned()
-            .ok_or_else(|| anyhow!("missing result field"))
+        unreachable!("a bundler client has at least one endpoint")
     }
 }
 
@@ -135,6 +216,28 @@ fn parse_u256_field(v: &Value, key: &str) -> Result<U256> {
     parse_u256_quantity(s)
 }
 
+fn parse_user_operation_gas_price(res: &Value) -> Result<UserOpGasPrice> {
+    // rundler_maxPriorityFeePerGas: a bare quantity (priority fee only).
+    if let Some(s) = res.as_str() {
+        return Ok(UserOpGasPrice {
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: parse_u256_quantity(s)?,
+        });
+    }
+
+    // pimlico_getUserOperationGasPrice: { slow, standard, fast } tiers; take `standard`.
+    // Otherwise accept the two fields at the top level.
+    let tier = ["standard", "fast", "slow"]
+        .iter()
+        .find_map(|k| res.get(*k))
+        .unwrap_or(res);
+
+    Ok(UserOpGasPrice {
+        max_fee_per_gas: Some(parse_u256_field(tier, "maxFeePerGas")?),
+        max_priority_fee_per_gas: parse_u256_field(tier, "maxPriorityFeePerGas")?,
+    })
+}
+
 fn parse_userop_hash(res: &Value) -> Result<H256> {
     // Most bundlers return the userOpHash directly as a JSON string.
     // Alchemy's docs (and sometimes responses) wrap it in an object: { "result": "0x..." }.
@@ -159,9 +262,16 @@ fn parse_userop_hash(res: &Value) -> Result<H256> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_userop_hash;
+    use super::{parse_user_operation_gas_price, parse_userop_hash, BundlerClient, UserOpGasPrice};
     use crate::encoding::parse_h256;
+    use crate::http::RpcDump;
+    use ethers::types::{Address, U256};
     use serde_json::json;
+    use serde_json::Value;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     const HASH: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
 
@@ -198,4 +308,316 @@ mod tests {
         let res = json!({ "foo": "bar" });
         assert!(parse_userop_hash(&res).is_err());
     }
+
+    #[test]
+    fn parse_gas_price_pimlico_tiers_uses_standard() {
+        let res = json!({
+            "slow": { "maxFeePerGas": "0x1", "maxPriorityFeePerGas": "0x1" },
+            "standard": { "maxFeePerGas": "0x3b9aca00", "maxPriorityFeePerGas": "0x5f5e100" },
+            "fast": { "maxFeePerGas": "0xffffffff", "maxPriorityFeePerGas": "0xffffffff" }
+        });
+        assert_eq!(
+            parse_user_operation_gas_price(&res).unwrap(),
+            UserOpGasPrice {
+                max_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+                max_priority_fee_per_gas: U256::from(100_000_000u64),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_gas_price_top_level_and_priority_only() {
+        let res = json!({ "maxFeePerGas": "0x64", "maxPriorityFeePerGas": "0xa" });
+        let out = parse_user_operation_gas_price(&res).unwrap();
+        assert_eq!(out.max_fee_per_gas, Some(U256::from(100)));
+        assert_eq!(out.max_priority_fee_per_gas, U256::from(10));
+
+        let res = json!("0xa");
+        let out = parse_user_operation_gas_price(&res).unwrap();
+        assert_eq!(out.max_fee_per_gas, None);
+        assert_eq!(out.max_priority_fee_per_gas, U256::from(10));
+
+        assert!(parse_user_operation_gas_price(&json!({ "maxFeePerGas": "0x1" })).is_err());
+    }
+
+    /// Answer a single HTTP request on a local port with `body`; returns the base URL.
+    fn serve_once(body: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut req = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read until the headers plus Content-Length bytes of body have arrived.
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                req.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&req);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let len = text[..end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if req.len() >= end + 4 + len {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(resp.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    /// Bundler on a local port answering every request with HTTP `status` and `answer(request)`
+    /// as its JSON-RPC response. Returns the URL and the methods called so far.
+    fn bundler_server(
+        status: u16,
+        answer: fn(&Value) -> Value,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        bundler_server_with(move |request| (status, answer(request)))
+    }
+
+    /// [`bundler_server`] with the HTTP status picked per request.
+    fn bundler_server_with(
+        answer: impl Fn(&Value) -> (u16, Value) + Send + Sync + 'static,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let answer = Arc::new(answer);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let methods = Arc::new(Mutex::new(Vec::new()));
+        let called = methods.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { return };
+                let called = called.clone();
+                let answer = answer.clone();
+                std::thread::spawn(move || {
+                    let mut req = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    while let Ok(n) = stream.read(&mut buf) {
+                        if n == 0 {
+                            return;
+                        }
+                        req.extend_from_slice(&buf[..n]);
+                        let Some(end) = req.windows(4).position(|w| w == b"\r\n\r\n") else {
+                            continue;
+                        };
+                        let head = String::from_utf8_lossy(&req[..end]).to_ascii_lowercase();
+                        let len: usize = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .map_or(0, |v| v.trim().parse().unwrap());
+                        if req.len() < end + 4 + len {
+                            continue;
+                        }
+                        let request: Value =
+                            serde_json::from_slice(&req[end + 4..end + 4 + len]).unwrap();
+                        req.drain(..end + 4 + len);
+                        called
+                            .lock()
+                            .unwrap()
+                            .push(request["method"].as_str().unwrap().to_string());
+
+                        let (status, body) = answer(&request);
+                        let body = body.to_string();
+                        let resp = format!(
+                            "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                            body.len()
+                        );
+                        if stream.write_all(resp.as_bytes()).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (url, methods)
+    }
+
+    fn working_bundler(request: &Value) -> Value {
+        let result = match request["method"].as_str() {
+            Some("eth_sendUserOperation") => json!(HASH),
+            _ => json!({ "userOpHash": HASH, "success": true }),
+        };
+        json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+    }
+
+    #[tokio::test]
+    async fn unavailable_primary_fails_over_and_receipt_polls_the_accepting_bundler() {
+        let (primary, primary_calls) = bundler_server(503, |_| json!("service unavailable"));
+        let (fallback, fallback_calls) = bundler_server(200, working_bundler);
+        let bundler = BundlerClient::with_fallbacks(primary, vec![fallback]);
+
+        let hash = bundler
+            .send_user_operation(json!({ "sender": "0x01" }), Address::zero())
+            .await
+            .unwrap();
+        assert_eq!(hash, parse_h256(HASH).unwrap());
+        assert_eq!(bundler.active_endpoint(), 1);
+
+        let receipt = bundler
+            .wait_user_operation_receipt(hash, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(receipt["success"], true);
+
+        assert_eq!(
+            *primary_calls.lock().unwrap(),
+            vec!["eth_sendUserOperation"]
+        );
+        assert_eq!(
+            *fallback_calls.lock().unwrap(),
+            vec!["eth_sendUserOperation", "eth_getUserOperationReceipt"]
+        );
+
+        // A primary that cannot even be connected to fails over the same way.
+        let closed = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let (fallback, _) = bundler_server(200, working_bundler);
+        let bundler = BundlerClient::with_fallbacks(closed, vec![fallback]);
+        bundler
+            .send_user_operation(json!({ "sender": "0x01" }), Address::zero())
+            .await
+            .unwrap();
+        assert_eq!(bundler.active_endpoint(), 1);
+    }
+
+    #[tokio::test]
+    async fn receipt_polling_fails_over_when_the_accepting_bundler_goes_down() {
+        // The primary accepts the op, then is unavailable for every receipt poll.
+        let (primary, primary_calls) =
+            bundler_server_with(|request| match request["method"].as_str() {
+                Some("eth_sendUserOperation") => (200, working_bundler(request)),
+                _ => (503, json!("service unavailable")),
+            });
+        let (fallback, fallback_calls) = bundler_server(200, working_bundler);
+        let bundler = BundlerClient::with_fallbacks(primary, vec![fallback]);
+
+        let hash = bundler
+            .send_user_operation(json!({ "sender": "0x01" }), Address::zero())
+            .await
+            .unwrap();
+        assert_eq!(bundler.active_endpoint(), 0);
+
+        // Any bundler can serve the receipt from the EntryPoint's logs.
+        let receipt = bundler
+            .wait_user_operation_receipt(hash, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(receipt["success"], true);
+        assert_eq!(bundler.active_endpoint(), 1);
+        assert_eq!(
+            *primary_calls.lock().unwrap(),
+            vec!["eth_sendUserOperation", "eth_getUserOperationReceipt"]
+        );
+        assert_eq!(
+            *fallback_calls.lock().unwrap(),
+            vec!["eth_getUserOperationReceipt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn sub_second_timeout_still_times_out() {
+        // A bundler that never has the receipt.
+        let (url, _) = bundler_server(
+            200,
+            |request| json!({ "jsonrpc": "2.0", "id": request["id"], "result": null }),
+        );
+        let bundler = BundlerClient::new(url);
+
+        let started = std::time::Instant::now();
+        let waited = tokio::time::timeout(
+            Duration::from_secs(5),
+            bundler
+                .wait_user_operation_receipt(parse_h256(HASH).unwrap(), Duration::from_millis(300)),
+        )
+        .await
+        .expect("a 300ms timeout must not poll forever");
+        let err = waited.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{err}");
+        // Bounded by the timeout, not the 1.5s poll interval.
+        assert!(
+            started.elapsed() < Duration::from_secs(1),
+            "{:?}",
+            started.elapsed()
+        );
+    }
+
+    #[tokio::test]
+    async fn aa_rejection_does_not_fail_over() {
+        let (primary, _) = bundler_server(200, |request| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32500, "message": "AA21 didn't pay prefund" },
+            })
+        });
+        let (fallback, fallback_calls) = bundler_server(200, working_bundler);
+        let bundler = BundlerClient::with_fallbacks(primary, vec![fallback]);
+
+        let err = bundler
+            .send_user_operation(json!({ "sender": "0x01" }), Address::zero())
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("AA21"), "{err:#}");
+        assert_eq!(bundler.active_endpoint(), 0);
+        assert!(fallback_calls.lock().unwrap().is_empty());
+
+        // Neither does an HTTP 4xx, which is about the request (e.g. a bad API key).
+        let (primary, _) = bundler_server(401, |_| json!("unauthorized"));
+        let (fallback, fallback_calls) = bundler_server(200, working_bundler);
+        let bundler = BundlerClient::with_fallbacks(primary, vec![fallback]);
+        assert!(bundler
+            .send_user_operation(json!({ "sender": "0x01" }), Address::zero())
+            .await
+            .is_err());
+        assert!(fallback_calls.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dump_rpc_dir_records_request_and_response() {
+        let dir = std::env::temp_dir().join(format!("opensub-aa-dump-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let base = serve_once(json!({ "jsonrpc": "2.0", "id": 1, "result": HASH }).to_string());
+
+        let bundler = BundlerClient::new(format!("{base}/v2/secret-api-key"))
+            .with_dump(Some(RpcDump::new(&dir).unwrap()));
+        let hash = bundler
+            .send_user_operation(json!({ "sender": "0x01" }), Address::zero())
+            .await
+            .unwrap();
+        assert_eq!(hash, parse_h256(HASH).unwrap());
+
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        let name = files[0].file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.ends_with("-0000-eth_sendUserOperation.json"), "{name}");
+
+        let raw = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(!raw.contains("secret-api-key"));
+        let doc: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(doc["method"], "eth_sendUserOperation");
+        assert_eq!(doc["request"]["endpoint"], base);
+        assert_eq!(doc["request"]["body"]["params"][0]["sender"], "0x01");
+        assert_eq!(doc["response"]["status"], 200);
+        assert_eq!(doc["response"]["body"]["result"], HASH);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -28,6 +28,18 @@ pub struct DeploymentRaw {
     #[serde(default)]
     #[allow(dead_code)]
     pub tx_hashes: Option<serde_json::Value>,
+
+    /// Optional per-chain defaults for the tx subcommands (CLI flags / env vars win).
+    #[serde(default)]
+    pub bundler_url: Option<String>,
+    #[serde(default)]
+    pub paymaster_url: Option<String>,
+    #[serde(default)]
+    pub gas_manager_policy_id: Option<String>,
+
+    /// Block explorer for printed tx links, e.g. `https://sepolia.basescan.org`.
+    #[serde(default)]
+    pub explorer_base_url: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,8 +51,12 @@ pub struct Deployment {
     #[allow(dead_code)]
     pub decimals: u8,
     pub plan_id: ethers::types::U256,
-    #[allow(dead_code)]
     pub start_block: u64,
+    pub bundler_url: Option<String>,
+    pub paymaster_url: Option<String>,
+    pub gas_manager_policy_id: Option<String>,
+    /// `explorerBaseUrl`, or the built-in explorer for `chain_id` (none for unknown chains).
+    pub explorer_base_url: Option<String>,
 }
 
 pub fn load_deployment(path: &Path, rpc_override: Option<String>) -> Result<Deployment> {
@@ -68,10 +84,128 @@ pub fn load_deployment(path: &Path, rpc_override: Option<String>) -> Result<Depl
         decimals: raw.decimals,
         plan_id: ethers::types::U256::from(raw.plan_id),
         start_block: raw.start_block,
+        bundler_url: raw.bundler_url,
+        paymaster_url: raw.paymaster_url,
+        gas_manager_policy_id: raw.gas_manager_policy_id,
+        explorer_base_url: raw
+            .explorer_base_url
+            .or_else(|| known_explorer_base_url(raw.chain_id).map(str::to_string))
+            .map(|url| url.trim_end_matches('/').to_string()),
     })
 }
 
+/// Explorers of common chains, used when the deployment has no `explorerBaseUrl`.
+pub fn known_explorer_base_url(chain_id: u64) -> Option<&'static str> {
+    Some(match chain_id {
+        1 => "https://etherscan.io",
+        11155111 => "https://sepolia.etherscan.io",
+        8453 => "https://basescan.org",
+        84532 => "https://sepolia.basescan.org",
+        10 => "https://optimistic.etherscan.io",
+        11155420 => "https://sepolia-optimism.etherscan.io",
+        42161 => "https://arbiscan.io",
+        421614 => "https://sepolia.arbiscan.io",
+        137 => "https://polygonscan.com",
+        80002 => "https://amoy.polygonscan.com",
+        _ => return None,
+    })
+}
+
+/// `<explorer>/tx/<hash>`.
+pub fn explorer_tx_url(explorer_base_url: &str, tx_hash: &str) -> String {
+    format!("{explorer_base_url}/tx/{tx_hash}")
+}
+
 fn parse_addr(s: &str) -> Result<ethers::types::Address> {
     s.parse::<ethers::types::Address>()
         .map_err(|e| anyhow!("{e}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{explorer_tx_url, known_explorer_base_url, load_deployment};
+
+    #[test]
+    fn deployment_can_carry_bundler_and_paymaster() {
+        let dir = std::env::temp_dir().join(format!("opensub-aa-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("deployment.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "chainId": 84532,
+                "rpc": "https://sepolia.base.org",
+                "openSub": "0x27eD037baB2A178dCDD600Abb78E3C6165C3B57c",
+                "token": "0x310fE8788dCa65134bC750AF9080138B1fD4F2e1",
+                "decimals": 6,
+                "planId": 2,
+                "startBlock": 37917830,
+                "bundlerUrl": "https://bundler.example/base-sepolia",
+                "paymasterUrl": "https://paymaster.example/base-sepolia",
+                "gasManagerPolicyId": "policy-123"
+            }"#,
+        )
+        .unwrap();
+
+        let dep = load_deployment(&path, None).unwrap();
+        assert_eq!(
+            dep.bundler_url.as_deref(),
+            Some("https://bundler.example/base-sepolia")
+        );
+        assert_eq!(
+            dep.paymaster_url.as_deref(),
+            Some("https://paymaster.example/base-sepolia")
+        );
+        assert_eq!(dep.gas_manager_policy_id.as_deref(), Some("policy-123"));
+
+        // The committed artifacts don't have them; they stay optional.
+        let manifest = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let dep =
+            load_deployment(&manifest.join("../deployments/base-sepolia.json"), None).unwrap();
+        assert!(dep.bundler_url.is_none());
+        assert!(dep.paymaster_url.is_none());
+        assert!(dep.gas_manager_policy_id.is_none());
+    }
+
+    #[test]
+    fn explorer_links_come_from_the_deployment_or_the_chain_id() {
+        let hash = "0xa9d81f9e393342b35ff6dc3a6423195069ebfde065d38faacc062c3db2532881";
+
+        // Known chain, no `explorerBaseUrl`: the built-in explorer.
+        let manifest = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let dep =
+            load_deployment(&manifest.join("../deployments/base-sepolia.json"), None).unwrap();
+        let base = dep.explorer_base_url.as_deref().unwrap();
+        assert_eq!(
+            explorer_tx_url(base, hash),
+            format!("https://sepolia.basescan.org/tx/{hash}")
+        );
+
+        // Unknown chain (a local anvil): no link.
+        assert_eq!(known_explorer_base_url(31337), None);
+
+        // `explorerBaseUrl` wins, and works for chains without a built-in entry.
+        let dir = std::env::temp_dir().join(format!("opensub-aa-explorer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("deployment.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "chainId": 31337,
+                "rpc": "http://127.0.0.1:8545",
+                "openSub": "0x27eD037baB2A178dCDD600Abb78E3C6165C3B57c",
+                "token": "0x310fE8788dCa65134bC750AF9080138B1fD4F2e1",
+                "decimals": 6,
+                "planId": 1,
+                "startBlock": 0,
+                "explorerBaseUrl": "https://explorer.example/"
+            }"#,
+        )
+        .unwrap();
+        let dep = load_deployment(&path, None).unwrap();
+        assert_eq!(
+            explorer_tx_url(dep.explorer_base_url.as_deref().unwrap(), hash),
+            format!("https://explorer.example/tx/{hash}")
+        );
+    }
+}
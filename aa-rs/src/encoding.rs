@@ -76,3 +76,110 @@ pub fn parse_h256(s: &str) -> anyhow::Result<H256> {
     arr.copy_from_slice(&bytes);
     Ok(H256(arr))
 }
+
+/// Parse a userOp in its `eth_sendUserOperation` JSON shape.
+///
+/// The v0.7 unpacked fields (`factory`/`factoryData` and `paymaster`/
+/// `paymasterVerificationGasLimit`/`paymasterPostOpGasLimit`/`paymasterData`) are accepted in
+/// place of `initCode` / `paymasterAndData` and packed into them. Absent byte fields (including
+/// `signature`) are empty.
+pub fn user_op_from_json(v: &serde_json::Value) -> anyhow::Result<UserOperation> {
+    let field = |name: &str| v.get(name).and_then(|f| f.as_str());
+    let required =
+        |name: &str| field(name).ok_or_else(|| anyhow::anyhow!("userOp is missing \"{name}\""));
+    let quantity = |name: &str| {
+        parse_u256_quantity(required(name)?).map_err(|e| anyhow::anyhow!("userOp \"{name}\": {e}"))
+    };
+    let bytes = |name: &str| -> anyhow::Result<Vec<u8>> {
+        let Some(raw) = field(name) else {
+            return Ok(Vec::new());
+        };
+        hex::decode(raw.strip_prefix("0x").unwrap_or(raw))
+            .map_err(|e| anyhow::anyhow!("userOp \"{name}\": {e}"))
+    };
+    let address = |name: &str| -> anyhow::Result<Address> {
+        required(name)?
+            .parse()
+            .map_err(|e| anyhow::anyhow!("userOp \"{name}\": {e}"))
+    };
+
+    let init_code = if field("factory").is_some() {
+        [address("factory")?.as_bytes(), &bytes("factoryData")?].concat()
+    } else {
+        bytes("initCode")?
+    };
+    let paymaster_and_data = if field("paymaster").is_some() {
+        let mut packed = address("paymaster")?.as_bytes().to_vec();
+        for name in ["paymasterVerificationGasLimit", "paymasterPostOpGasLimit"] {
+            let limit = quantity(name)?;
+            if limit.bits() > 128 {
+                anyhow::bail!("userOp \"{name}\" does not fit in 128 bits");
+            }
+            let mut word = [0u8; 32];
+            limit.to_big_endian(&mut word);
+            packed.extend_from_slice(&word[16..]);
+        }
+        packed.extend(bytes("paymasterData")?);
+        packed
+    } else {
+        bytes("paymasterAndData")?
+    };
+
+    Ok(UserOperation {
+        sender: address("sender")?,
+        nonce: quantity("nonce")?,
+        init_code: init_code.into(),
+        call_data: bytes("callData")?.into(),
+        call_gas_limit: quantity("callGasLimit")?,
+        verification_gas_limit: quantity("verificationGasLimit")?,
+        pre_verification_gas: quantity("preVerificationGas")?,
+        max_fee_per_gas: quantity("maxFeePerGas")?,
+        max_priority_fee_per_gas: quantity("maxPriorityFeePerGas")?,
+        paymaster_and_data: paymaster_and_data.into(),
+        signature: bytes("signature")?.into(),
+    })
+}
+
+/// Whether runtime bytecode dispatches `selector`: a `PUSH4 <selector>` outside push data, the way
+/// solc's function dispatcher compares selectors. The keeper's OpenSub feature probes use it too.
+pub fn bytecode_has_selector(code: &[u8], selector: [u8; 4]) -> bool {
+    const PUSH1: u8 = 0x60;
+    const PUSH4: u8 = 0x63;
+    const PUSH32: u8 = 0x7f;
+
+    let mut i = 0;
+    while i < code.len() {
+        let op = code[i];
+        if op == PUSH4 && code.get(i + 1..i + 5) == Some(&selector[..]) {
+            return true;
+        }
+        i += 1;
+        if (PUSH1..=PUSH32).contains(&op) {
+            i += (op - PUSH1 + 1) as usize;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bytecode_has_selector;
+    use ethers::utils::id;
+
+    #[test]
+    fn selector_scan_skips_push_data() {
+        let selector = id("cancelAfter(uint256,uint256)");
+        // Dispatcher probe: DUP1 PUSH4 <selector> EQ.
+        let mut code = vec![0x80, 0x63];
+        code.extend_from_slice(&selector);
+        code.push(0x14);
+        assert!(bytecode_has_selector(&code, selector));
+        assert!(!bytecode_has_selector(&code, id("cancel(uint256)")));
+
+        // The same bytes inside PUSH32 data are not a dispatcher entry.
+        let mut hidden = vec![0x7f, 0x63];
+        hidden.extend_from_slice(&selector);
+        hidden.extend_from_slice(&[0u8; 27]);
+        assert!(!bytecode_has_selector(&hidden, selector));
+    }
+}
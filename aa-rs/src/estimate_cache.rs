@@ -0,0 +1,172 @@
+use crate::bundler::GasEstimates;
+use anyhow::{Context, Result};
+use ethers::abi::Token;
+use ethers::types::{Address, Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Cache key of an op's gas estimate: `keccak256(abi.encode(sender, callData, initCode, chainId))`.
+///
+/// Nonce, fees and signature are left out; they don't change what the bundler estimates.
+pub fn cache_key(sender: Address, call_data: &Bytes, init_code: &Bytes, chain_id: u64) -> H256 {
+    H256::from(ethers::utils::keccak256(ethers::abi::encode(&[
+        Token::Address(sender),
+        Token::Bytes(call_data.to_vec()),
+        Token::Bytes(init_code.to_vec()),
+        Token::Uint(U256::from(chain_id)),
+    ])))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    stored_at: u64,
+    call_gas_limit: U256,
+    verification_gas_limit: U256,
+    pre_verification_gas: U256,
+}
+
+/// `--estimate-cache`: the last `eth_estimateUserOperationGas` result per [`cache_key`], as one
+/// JSON object on disk. Entries older than `ttl` are ignored and dropped on the next write.
+#[derive(Debug, Clone)]
+pub struct EstimateCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl EstimateCache {
+    pub fn new(path: PathBuf, ttl: Duration) -> Self {
+        Self { path, ttl }
+    }
+
+    /// The cached estimate for `key`, if one was stored less than `ttl` before `now`.
+    pub fn get(&self, key: H256, now: u64) -> Result<Option<GasEstimates>> {
+        let entries = self.load()?;
+        Ok(entries
+            .get(&format!("{key:?}"))
+            .filter(|e| self.is_fresh(e, now))
+            .map(|e| GasEstimates {
+                call_gas_limit: e.call_gas_limit,
+                verification_gas_limit: e.verification_gas_limit,
+                pre_verification_gas: e.pre_verification_gas,
+            }))
+    }
+
+    /// Store `est` under `key`, dropping expired entries.
+    pub fn put(&self, key: H256, est: &GasEstimates, now: u64) -> Result<()> {
+        let mut entries = self.load()?;
+        entries.retain(|_, e| self.is_fresh(e, now));
+        entries.insert(
+            format!("{key:?}"),
+            Entry {
+                stored_at: now,
+                call_gas_limit: est.call_gas_limit,
+                verification_gas_limit: est.verification_gas_limit,
+                pre_verification_gas: est.pre_verification_gas,
+            },
+        );
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&entries)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    fn is_fresh(&self, entry: &Entry, now: u64) -> bool {
+        now.saturating_sub(entry.stored_at) < self.ttl.as_secs()
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, Entry>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let raw = fs::read(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))?;
+        serde_json::from_slice(&raw)
+            .with_context(|| format!("invalid estimate cache {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_key, EstimateCache};
+    use crate::bundler::GasEstimates;
+    use ethers::types::{Address, Bytes, U256};
+    use std::time::Duration;
+
+    #[test]
+    fn cache_key_covers_sender_calldata_initcode_and_chain() {
+        let sender = Address::repeat_byte(0x11);
+        let call_data = Bytes::from(vec![0xde, 0xad]);
+        let init_code = Bytes::default();
+        let key = cache_key(sender, &call_data, &init_code, 84532);
+
+        assert_eq!(key, cache_key(sender, &call_data, &init_code, 84532));
+        assert_ne!(
+            key,
+            cache_key(Address::repeat_byte(0x12), &call_data, &init_code, 84532)
+        );
+        assert_ne!(
+            key,
+            cache_key(sender, &Bytes::from(vec![0xde, 0xae]), &init_code, 84532)
+        );
+        assert_ne!(
+            key,
+            cache_key(sender, &call_data, &Bytes::from(vec![0x01]), 84532)
+        );
+        assert_ne!(key, cache_key(sender, &call_data, &init_code, 8453));
+        // Moving a byte between callData and initCode is a different op.
+        assert_ne!(
+            cache_key(
+                sender,
+                &Bytes::from(vec![0xde]),
+                &Bytes::from(vec![0xad]),
+                84532
+            ),
+            cache_key(sender, &call_data, &init_code, 84532)
+        );
+    }
+
+    #[test]
+    fn cached_estimates_expire_after_the_ttl() {
+        let dir = std::env::temp_dir().join(format!(
+            "opensub-aa-estimate-cache-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let cache = EstimateCache::new(dir.join("estimates.json"), Duration::from_secs(60));
+        let key = cache_key(Address::zero(), &Bytes::default(), &Bytes::default(), 1);
+        let other = cache_key(Address::zero(), &Bytes::from(vec![1]), &Bytes::default(), 1);
+        let est = GasEstimates {
+            call_gas_limit: U256::from(100_000),
+            verification_gas_limit: U256::from(200_000),
+            pre_verification_gas: U256::from(50_000),
+        };
+
+        // Missing file: a miss, not an error.
+        assert!(cache.get(key, 1_000).unwrap().is_none());
+
+        cache.put(key, &est, 1_000).unwrap();
+        let hit = cache.get(key, 1_059).unwrap().unwrap();
+        assert_eq!(hit.call_gas_limit, est.call_gas_limit);
+        assert_eq!(hit.verification_gas_limit, est.verification_gas_limit);
+        assert_eq!(hit.pre_verification_gas, est.pre_verification_gas);
+        assert!(cache.get(other, 1_059).unwrap().is_none());
+        assert!(cache.get(key, 1_060).unwrap().is_none());
+
+        // Writing another entry after expiry drops the stale one from disk.
+        cache.put(other, &est, 1_100).unwrap();
+        let raw = std::fs::read_to_string(dir.join("estimates.json")).unwrap();
+        assert!(!raw.contains(&format!("{key:?}")), "{raw}");
+        assert!(raw.contains(&format!("{other:?}")), "{raw}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
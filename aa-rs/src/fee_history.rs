@@ -0,0 +1,92 @@
+use ethers::types::{FeeHistory, U256};
+
+/// EIP-1559 caps the base fee rise at 12.5% per block; the headroom allows one such step on top
+/// of the highest base fee seen.
+const BASE_FEE_STEP_BPS: u64 = 11_250;
+
+/// Fees derived from `eth_feeHistory` for `--adaptive-gas`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveFees {
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    /// Base fee the `maxFeePerGas` leaves room for.
+    pub base_fee_ceiling: U256,
+}
+
+/// Fees from a fee history requested with a single reward percentile.
+///
+/// - `maxPriorityFeePerGas`: the median, over non-empty blocks, of that percentile's reward.
+///   Empty blocks report a zero reward and would drag it down.
+/// - `maxFeePerGas`: the highest base fee in the window (including the next block's, the last
+///   entry of `baseFeePerGas`) plus one 12.5% step, plus the priority fee. A window where the
+///   base fee moved a lot leaves more room than a flat one.
+///
+/// `None` when the history has no base fees or no non-empty block with a reward (pre-London
+/// chains, providers that return an empty history), so the caller falls back to the static path.
+pub fn adaptive_fees(history: &FeeHistory) -> Option<AdaptiveFees> {
+    let mut rewards: Vec<U256> = history
+        .reward
+        .iter()
+        .zip(&history.gas_used_ratio)
+        .filter(|(_, ratio)| **ratio > 0.0)
+        .filter_map(|(reward, _)| reward.first().copied())
+        .collect();
+    if rewards.is_empty() {
+        return None;
+    }
+    rewards.sort();
+    let max_priority_fee_per_gas = rewards[rewards.len() / 2];
+
+    let highest_base_fee = history.base_fee_per_gas.iter().max().copied()?;
+    let base_fee_ceiling = highest_base_fee * U256::from(BASE_FEE_STEP_BPS) / U256::from(10_000u64);
+
+    Some(AdaptiveFees {
+        max_fee_per_gas: base_fee_ceiling + max_priority_fee_per_gas,
+        max_priority_fee_per_gas,
+        base_fee_ceiling,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adaptive_fees, AdaptiveFees};
+    use ethers::types::{FeeHistory, U256};
+
+    #[test]
+    fn percentile_reward_and_base_fee_headroom_from_fee_history() {
+        // `eth_feeHistory(5, "latest", [60])`: 5 blocks, 6 base fees (the last is the next
+        // block's), one reward per block. Block 3 is empty.
+        let history: FeeHistory = serde_json::from_value(serde_json::json!({
+            "oldestBlock": "0x10",
+            "baseFeePerGas": ["0x3b9aca00", "0x42c1d800", "0x4a817c80", "0x4190ab00", "0x3b9aca00", "0x3b9aca00"],
+            "gasUsedRatio": [0.9, 0.8, 0.0, 0.1, 0.5],
+            "reward": [["0x5f5e100"], ["0x77359400"], ["0x0"], ["0x2faf080"], ["0xbebc200"]]
+        }))
+        .unwrap();
+
+        let fees = adaptive_fees(&history).unwrap();
+        // Rewards of the non-empty blocks: 0.05, 0.1, 0.2 and 2 gwei. The median ignores the
+        // 2 gwei outlier and the empty block.
+        assert_eq!(fees.max_priority_fee_per_gas, U256::from(200_000_000u64));
+        // Highest base fee 1.25 gwei, plus 12.5%.
+        assert_eq!(fees.base_fee_ceiling, U256::from(1_406_250_000u64));
+        assert_eq!(
+            fees,
+            AdaptiveFees {
+                max_fee_per_gas: U256::from(1_606_250_000u64),
+                max_priority_fee_per_gas: U256::from(200_000_000u64),
+                base_fee_ceiling: U256::from(1_406_250_000u64),
+            }
+        );
+
+        // Nothing usable: fall back.
+        let empty: FeeHistory = serde_json::from_value(serde_json::json!({
+            "oldestBlock": "0x10",
+            "baseFeePerGas": ["0x3b9aca00"],
+            "gasUsedRatio": [0.0],
+            "reward": [["0x0"]]
+        }))
+        .unwrap();
+        assert_eq!(adaptive_fees(&empty), None);
+    }
+}
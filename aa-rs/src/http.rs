@@ -0,0 +1,825 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::{HeaderValue, AUTHORIZATION, RETRY_AFTER, USER_AGENT};
+use reqwest::StatusCode;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Sent on every bundler / paymaster request so operators can identify our traffic.
+pub const USER_AGENT_VALUE: &str = concat!("opensub-aa/", env!("CARGO_PKG_VERSION"));
+
+/// Retries of a request answered with HTTP 429 before the 429 is returned as an error.
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Wait before a 429 retry when the response has no usable `Retry-After`; doubled per attempt.
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest wait honoured from a `Retry-After` header; a gateway asking for more is capped.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Parse a `Retry-After` value: delay seconds (`120`) or an HTTP-date
+/// (`Wed, 21 Oct 2015 07:28:00 GMT`, relative to `now`; a date in the past is no wait).
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(now).unwrap_or_default())
+}
+
+/// Wait before retry `attempt` (0-based) of a rate-limited request.
+fn rate_limit_delay(retry_after: Option<Duration>, attempt: u32) -> Duration {
+    retry_after
+        .unwrap_or(RATE_LIMIT_BACKOFF * 2u32.pow(attempt))
+        .min(MAX_RETRY_AFTER)
+}
+
+/// A non-success HTTP status from a JSON-RPC endpoint, with the response body.
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Whether a [`JsonRpcHttp`] error means the endpoint itself is failing: it could not be reached,
+/// timed out, answered HTTP 5xx or kept answering 429. A JSON-RPC `error` object (e.g. an AA
+/// rejection) or any other HTTP status is an answer to the request and is not.
+pub fn is_endpoint_unavailable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(status) = cause.downcast_ref::<HttpStatusError>() {
+            return status.status.is_server_error()
+                || status.status == StatusCode::TOO_MANY_REQUESTS;
+        }
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request() || e.is_body())
+    })
+}
+
+/// `--allowed-rpc-hosts` guardrail: `url` must be https and its host one of `allowed`
+/// (case-insensitive). An empty list allows anything.
+///
+/// `what` names the endpoint in the error ("bundler", "paymaster").
+pub fn check_allowed_host(what: &str, url: &str, allowed: &[String]) -> Result<()> {
+    if allowed.is_empty() {
+        return Ok(());
+    }
+
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("invalid {what} URL '{url}'"))?;
+    if parsed.scheme() != "https" {
+        return Err(anyhow!(
+            "{what} URL must use https when --allowed-rpc-hosts is set (got {})",
+            parsed.scheme()
+        ));
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| anyhow!("{what} URL has no host"))?;
+    if !allowed.iter().any(|h| h.trim().eq_ignore_ascii_case(host)) {
+        return Err(anyhow!(
+            "{what} host '{host}' is not in --allowed-rpc-hosts ({})",
+            allowed.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// `--resolve host:ip`: connect to `ip` for `host` instead of asking the system resolver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub ip: IpAddr,
+}
+
+/// Parse a `--resolve` value: `host:ip`, or curl's `host:port:ip` (the port is accepted for
+/// familiarity but the override applies to every port). IPv6 addresses may be bracketed,
+/// e.g. `bundler.internal:[fd00::7]`.
+pub fn parse_resolve(s: &str) -> std::result::Result<ResolveOverride, String> {
+    let parse_ip = |v: &str| {
+        v.trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .ok()
+    };
+    let (host, rest) = s
+        .split_once(':')
+        .filter(|(host, _)| !host.is_empty())
+        .ok_or_else(|| format!("invalid --resolve '{s}': expected host:ip"))?;
+    let ip = parse_ip(rest)
+        .or_else(|| {
+            let (port, ip) = rest.split_once(':')?;
+            port.parse::<u16>().ok()?;
+            parse_ip(ip)
+        })
+        .ok_or_else(|| format!("invalid --resolve '{s}': '{rest}' is not an IP address"))?;
+    Ok(ResolveOverride {
+        host: host.to_ascii_lowercase(),
+        ip,
+    })
+}
+
+/// How the bundler / paymaster (and, for `resolve`, chain RPC) HTTP client connects.
+///
+/// - `--bundler-ca` / `--pin-cert`: how TLS connections are verified. Both unset is reqwest's
+///   usual verification against the bundled web roots.
+/// - `--resolve`: fixed addresses for some hosts, for networks whose resolver misbehaves.
+/// - `pool_*`: keep-alive connection pool. Unset keeps reqwest's defaults (no cap on idle
+///   connections per host, closed after 90s idle).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientOptions {
+    /// PEM file with the root certificate(s) to trust *instead of* the web roots.
+    pub ca_file: Option<PathBuf>,
+    /// SHA-256 of the server's leaf certificate (DER). Checked after normal chain verification.
+    pub pin_sha256: Option<[u8; 32]>,
+    pub resolve: Vec<ResolveOverride>,
+    /// Idle keep-alive connections kept per host; 0 opens a new connection per request.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle pooled connection is kept before it is closed.
+    pub pool_idle_timeout: Option<Duration>,
+}
+
+impl ClientOptions {
+    /// An HTTP client configured per these options.
+    pub fn client(&self) -> Result<reqwest::Client> {
+        self.builder()?
+            .build()
+            .context("failed to build HTTP client")
+    }
+
+    /// The reqwest builder behind [`Self::client`].
+    pub fn builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder();
+        for o in &self.resolve {
+            // reqwest ignores the port here and uses the URL's.
+            builder = builder.resolve(&o.host, SocketAddr::new(o.ip, 0));
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        match self.tls_config()? {
+            Some(config) => Ok(builder.use_preconfigured_tls(config)),
+            None => Ok(builder),
+        }
+    }
+
+    fn tls_config(&self) -> Result<Option<ClientConfig>> {
+        if self.ca_file.is_none() && self.pin_sha256.is_none() {
+            return Ok(None);
+        }
+
+        let mut roots = RootCertStore::empty();
+        match self.ca_file.as_deref() {
+            Some(path) => {
+                let pem = std::fs::read(path)
+                    .with_context(|| format!("failed to read --bundler-ca {}", path.display()))?;
+                let certs = rustls_pemfile::certs(&mut pem.as_slice())
+                    .with_context(|| format!("invalid PEM in --bundler-ca {}", path.display()))?;
+                if certs.is_empty() {
+                    return Err(anyhow!(
+                        "--bundler-ca {} contains no certificates",
+                        path.display()
+                    ));
+                }
+                for der in certs {
+                    roots.add(&Certificate(der)).with_context(|| {
+                        format!("unusable certificate in --bundler-ca {}", path.display())
+                    })?;
+                }
+            }
+            None => roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            })),
+        }
+
+        let mut config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots.clone())
+            .with_no_client_auth();
+        if let Some(pin) = self.pin_sha256 {
+            config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(PinnedVerifier {
+                    inner: WebPkiVerifier::new(roots, None),
+                    pin,
+                }));
+        }
+
+        Ok(Some(config))
+    }
+}
+
+/// Parse a `--pin-cert` value: the certificate's SHA-256 as 64 hex digits, optionally
+/// colon-separated as printed by `openssl x509 -noout -fingerprint -sha256`.
+pub fn parse_cert_pin(s: &str) -> std::result::Result<[u8; 32], String> {
+    let trimmed = s.trim();
+    let trimmed = trimmed
+        .strip_prefix("sha256:")
+        .or_else(|| trimmed.strip_prefix("0x"))
+        .unwrap_or(trimmed);
+    let hex_digits: String = trimmed.chars().filter(|c| *c != ':').collect();
+    let bytes =
+        hex::decode(&hex_digits).map_err(|e| format!("invalid certificate pin '{s}': {e}"))?;
+    bytes.try_into().map_err(|b: Vec<u8>| {
+        format!(
+            "invalid certificate pin '{s}': expected 32 bytes (SHA-256), got {}",
+            b.len()
+        )
+    })
+}
+
+/// Normal web-PKI verification, then the leaf certificate's SHA-256 must equal the pin.
+struct PinnedVerifier {
+    inner: WebPkiVerifier,
+    pin: [u8; 32],
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        let presented: [u8; 32] = Sha256::digest(&end_entity.0).into();
+        if presented != self.pin {
+            return Err(rustls::Error::General(format!(
+                "certificate pin mismatch for {server_name:?}: --pin-cert is {}, server presented {}",
+                hex::encode(self.pin),
+                hex::encode(presented)
+            )));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// `--dump-rpc-dir`: one JSON file per request/response exchange, for sharing with a bundler's
+/// support.
+///
+/// Files are named `<unix-ms>-<seq>-<method>.json`. `Authorization` headers are redacted, and only
+/// the endpoint's scheme, host and port are kept, since providers put API keys in the path or
+/// query.
+#[derive(Debug, Clone)]
+pub struct RpcDump {
+    dir: PathBuf,
+    seq: Arc<AtomicU64>,
+}
+
+impl RpcDump {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create --dump-rpc-dir {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            seq: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn write(&self, method: &str, request: Value, response: Value) -> Result<PathBuf> {
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let path = self.dir.join(format!("{ts_ms}-{seq:04}-{method}.json"));
+        let doc = serde_json::json!({
+            "method": method,
+            "request": request,
+            "response": response,
+        });
+        std::fs::write(&path, serde_json::to_vec_pretty(&doc)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// JSON-RPC over HTTP, shared by the bundler and paymaster clients.
+///
+/// With `trace` (`--http-trace`), request and response bodies are logged at debug level, with
+/// any `Authorization` header redacted. With a `dump` sink each exchange is also written to disk.
+#[derive(Debug, Clone)]
+pub struct JsonRpcHttp {
+    url: String,
+    http: reqwest::Client,
+    trace: bool,
+    dump: Option<RpcDump>,
+}
+
+impl JsonRpcHttp {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            http: reqwest::Client::new(),
+            trace: false,
+            dump: None,
+        }
+    }
+
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    pub fn with_dump(mut self, dump: Option<RpcDump>) -> Self {
+        self.dump = dump;
+        self
+    }
+
+    /// Connect per `--bundler-ca` / `--pin-cert` / `--resolve` and the pool settings.
+    pub fn with_client_options(mut self, options: &ClientOptions) -> Result<Self> {
+        self.http = options.client()?;
+        Ok(self)
+    }
+
+    fn build_request(&self, method: &str, params: Value) -> Result<reqwest::Request> {
+        let req = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+
+        self.http
+            .post(&self.url)
+            .header(USER_AGENT, HeaderValue::from_static(USER_AGENT_VALUE))
+            .json(&req)
+            .build()
+            .with_context(|| format!("failed to build request to {}", self.url))
+    }
+
+    /// JSON-RPC call that keeps the `error` object (inner `Err`) separate from transport failures.
+    ///
+    /// An HTTP 429 is retried up to `RATE_LIMIT_RETRIES` times, after the response's
+    /// `Retry-After` (capped at `MAX_RETRY_AFTER`) or an exponential backoff without one.
+    pub async fn call_raw(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<std::result::Result<Value, Value>> {
+        let mut attempt = 0;
+        let (status, body) = loop {
+            let req = self.build_request(method, params.clone())?;
+            if self.trace {
+                trace_request(&req);
+            }
+            let dumped_request = self.dump.as_ref().map(|_| dump_request(&req));
+
+            let result = self.execute(req).await;
+            if let (Some(dump), Some(request)) = (self.dump.as_ref(), dumped_request) {
+                let response = match &result {
+                    Ok((status, _, body)) => {
+                        serde_json::json!({ "status": status.as_u16(), "body": body })
+                    }
+                    Err(err) => serde_json::json!({ "error": format!("{err:#}") }),
+                };
+                match dump.write(method, request, response) {
+                    Ok(path) => {
+                        tracing::debug!(method, path = %path.display(), "rpc exchange dumped")
+                    }
+                    Err(err) => {
+                        tracing::warn!(method, error = %err, "failed to dump rpc exchange")
+                    }
+                }
+            }
+
+            let (status, retry_after, body) = result?;
+            if self.trace {
+                tracing::debug!(method, %status, body = %body, "http response");
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS && attempt < RATE_LIMIT_RETRIES {
+                let wait = rate_limit_delay(retry_after, attempt);
+                tracing::warn!(
+                    method,
+                    retry_after = ?retry_after,
+                    wait_ms = wait.as_millis() as u64,
+                    attempt = attempt + 1,
+                    "rate limited (HTTP 429); retrying"
+                );
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+                continue;
+            }
+            break (status, body);
+        };
+
+        if !status.is_success() {
+            return Err(HttpStatusError { status, body }.into());
+        }
+
+        if let Some(err) = body.get("error") {
+            return Ok(Err(err.clone()));
+        }
+
+        body.get("result")
+            .cloned()
+            .map(Ok)
+            .ok_or_else(|| anyhow!("missing result field"))
+    }
+
+    /// Status, parsed `Retry-After` and body. Error responses that are not JSON (e.g. a
+    /// gateway's plain-text 429) keep their text as a JSON string.
+    async fn execute(
+        &self,
+        req: reqwest::Request,
+    ) -> Result<(StatusCode, Option<Duration>, Value)> {
+        let resp = self
+            .http
+            .execute(req)
+            .await
+            .with_context(|| format!("POST {} failed", self.url))?;
+
+        let status = resp.status();
+        let retry_after = resp
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| parse_retry_after(v, SystemTime::now()));
+        let raw = resp.bytes().await.context("failed to read response")?;
+        let body = match serde_json::from_slice(&raw) {
+            Ok(body) => body,
+            Err(_) if !status.is_success() => {
+                Value::String(String::from_utf8_lossy(&raw).into_owned())
+            }
+            Err(err) => return Err(anyhow!(err).context("failed to decode JSON")),
+        };
+        Ok((status, retry_after, body))
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        self.call_raw(method, params)
+            .await?
+            .map_err(|err| anyhow!("RPC error: {}", err))
+    }
+}
+
+/// `name: value` for every header, with `Authorization` redacted.
+fn redacted_headers(req: &reqwest::Request) -> Vec<String> {
+    req.headers()
+        .iter()
+        .map(|(name, value)| {
+            if name == AUTHORIZATION {
+                format!("{name}: <redacted>")
+            } else {
+                format!("{name}: {}", value.to_str().unwrap_or("<binary>"))
+            }
+        })
+        .collect()
+}
+
+fn request_body(req: &reqwest::Request) -> String {
+    req.body()
+        .and_then(|b| b.as_bytes())
+        .map(|b| String::from_utf8_lossy(b).into_owned())
+        .unwrap_or_default()
+}
+
+/// Scheme, host and port of `url`. Providers often put the API key in the path or query, so
+/// traces and dumps never show those.
+fn redacted_endpoint(url: &reqwest::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}://{host}:{port}", url.scheme()),
+        None => format!("{}://{host}", url.scheme()),
+    }
+}
+
+fn dump_request(req: &reqwest::Request) -> Value {
+    let body = request_body(req);
+    serde_json::json!({
+        "endpoint": redacted_endpoint(req.url()),
+        "headers": redacted_headers(req),
+        "body": serde_json::from_str::<Value>(&body).unwrap_or(Value::String(body)),
+    })
+}
+
+fn trace_request(req: &reqwest::Request) {
+    let headers = redacted_headers(req);
+    let body = request_body(req);
+    tracing::debug!(
+        method = %req.method(),
+        endpoint = %redacted_endpoint(req.url()),
+        headers = ?headers,
+        body = %body,
+        "http request"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_allowed_host, parse_cert_pin, parse_resolve, parse_retry_after, rate_limit_delay,
+        redacted_endpoint, ClientOptions, JsonRpcHttp, MAX_RETRY_AFTER, USER_AGENT_VALUE,
+    };
+    use reqwest::header::USER_AGENT;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn resolve_overrides_reach_the_client_builder() {
+        let v4 = parse_resolve("Bundler.Internal:10.0.0.7").unwrap();
+        assert_eq!(v4.host, "bundler.internal");
+        assert_eq!(v4.ip, "10.0.0.7".parse::<std::net::IpAddr>().unwrap());
+        // curl's host:port:ip, and bracketed / bare IPv6.
+        assert_eq!(parse_resolve("bundler.internal:443:10.0.0.7"), Ok(v4));
+        let v6 = parse_resolve("rpc.internal:[fd00::7]").unwrap();
+        assert_eq!(v6.ip, "fd00::7".parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(parse_resolve("rpc.internal:8545:[fd00::7]"), Ok(v6.clone()));
+        assert_eq!(parse_resolve("rpc.internal:fd00::7"), Ok(v6.clone()));
+        assert!(parse_resolve("bundler.internal").is_err());
+        assert!(parse_resolve(":10.0.0.7").is_err());
+        assert!(parse_resolve("bundler.internal:not-an-ip").is_err());
+
+        let options = ClientOptions {
+            resolve: vec![parse_resolve("bundler.internal:10.0.0.7").unwrap(), v6],
+            ..Default::default()
+        };
+        let builder = format!("{:?}", options.builder().unwrap());
+        assert!(builder.contains("dns_overrides"), "{builder}");
+        assert!(
+            builder.contains("\"bundler.internal\": [10.0.0.7:0]"),
+            "{builder}"
+        );
+        assert!(
+            builder.contains("\"rpc.internal\": [[fd00::7]:0]"),
+            "{builder}"
+        );
+        options.client().unwrap();
+
+        // No overrides: the system resolver.
+        let plain = format!("{:?}", ClientOptions::default().builder().unwrap());
+        assert!(!plain.contains("dns_overrides"), "{plain}");
+    }
+
+    /// Accept connections on a local port and answer every request on them with a keep-alive
+    /// JSON-RPC response. Returns the URL and the number of connections accepted so far.
+    fn keep_alive_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { return };
+                counter.fetch_add(1, Ordering::SeqCst);
+                std::thread::spawn(move || {
+                    let mut buf = [0u8; 4096];
+                    while let Ok(n) = stream.read(&mut buf) {
+                        if n == 0 {
+                            return;
+                        }
+                        let body = r#"{"jsonrpc":"2.0","id":1,"result":"0x1"}"#;
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                            body.len()
+                        );
+                        if stream.write_all(response.as_bytes()).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (url, accepted)
+    }
+
+    #[tokio::test]
+    async fn pool_settings_reach_the_client() {
+        let pooled = ClientOptions {
+            pool_max_idle_per_host: Some(4),
+            pool_idle_timeout: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let no_idle = ClientOptions {
+            pool_max_idle_per_host: Some(0),
+            ..Default::default()
+        };
+
+        // Sequential requests reuse the one kept-alive connection...
+        let (url, accepted) = keep_alive_server();
+        let rpc = JsonRpcHttp::new(url).with_client_options(&pooled).unwrap();
+        for _ in 0..3 {
+            rpc.call("eth_chainId", serde_json::json!([]))
+                .await
+                .unwrap();
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+        // ...unless no idle connection may be kept.
+        let (url, accepted) = keep_alive_server();
+        let rpc = JsonRpcHttp::new(url).with_client_options(&no_idle).unwrap();
+        for _ in 0..3 {
+            rpc.call("eth_chainId", serde_json::json!([]))
+                .await
+                .unwrap();
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn retry_after_seconds_and_http_date() {
+        // 2015-10-21T07:28:00Z
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(parse_retry_after(" 0 ", now), Some(Duration::ZERO));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:45 GMT", now),
+            Some(Duration::from_secs(45))
+        );
+        // A date already past means "retry now".
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", SystemTime::now()), None);
+        assert_eq!(parse_retry_after("-5", now), None);
+
+        // Capped; without a header the backoff doubles.
+        assert_eq!(
+            rate_limit_delay(Some(Duration::from_secs(3_600)), 0),
+            MAX_RETRY_AFTER
+        );
+        assert_eq!(
+            rate_limit_delay(Some(Duration::from_secs(2)), 2),
+            Duration::from_secs(2)
+        );
+        assert_eq!(rate_limit_delay(None, 0), Duration::from_secs(1));
+        assert_eq!(rate_limit_delay(None, 2), Duration::from_secs(4));
+    }
+
+    /// Self-signed P-256 CA, only used to check that it is accepted as a root.
+    const TEST_CA_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBkTCCATegAwIBAgIUDj5uNv7lL+LjxGxtOzxMkNx5y/cwCgYIKoZIzj0EAwIw
+HTEbMBkGA1UEAwwSb3BlbnN1Yi1hYSB0ZXN0IENBMCAXDTI2MTAxNjEzMDQxOFoY
+DzIxMjYwOTIyMTMwNDE4WjAdMRswGQYDVQQDDBJvcGVuc3ViLWFhIHRlc3QgQ0Ew
+WTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATDlQzc6njHr7N0jijyT6x+0y338Ktu
+eMAZLYSQaNVCPc3V16Q+o8eR/T9EP5F01rK3zc8gNPSh367gy1zifimGo1MwUTAd
+BgNVHQ4EFgQUL9O5mVsSOyJKz255yKeNPDCUR3owHwYDVR0jBBgwFoAUL9O5mVsS
+OyJKz255yKeNPDCUR3owDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBF
+AiEA5bOkuHmVnyXJ7pnbFQeVEDmfP5F73/SLYsC1zrhViEgCIC+cszXEyTHyg2Ap
+6NTZbpZ+yC4HmojAPB6Uh1OU8tmC
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn client_builds_with_a_custom_root_and_pin() {
+        let dir = std::env::temp_dir().join(format!(
+            "opensub-aa-tls-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ca = dir.join("ca.pem");
+        std::fs::write(&ca, TEST_CA_PEM).unwrap();
+
+        let pin = parse_cert_pin(
+            "07:67:E0:CC:CD:D8:E2:EE:36:4F:44:4C:30:55:F5:68:9D:9C:66:BC:52:9F:B9:C6:4F:28:87:67:15:6A:51:A9",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(pin),
+            "0767e0cccdd8e2ee364f444c3055f5689d9c66bc529fb9c64f288767156a51a9"
+        );
+        assert_eq!(
+            parse_cert_pin("0767e0cccdd8e2ee364f444c3055f5689d9c66bc529fb9c64f288767156a51a9"),
+            Ok(pin)
+        );
+        assert!(parse_cert_pin("07:67:e0").is_err());
+
+        let tls = ClientOptions {
+            ca_file: Some(ca.clone()),
+            pin_sha256: Some(pin),
+            ..Default::default()
+        };
+        JsonRpcHttp::new("https://bundler.example".to_string())
+            .with_client_options(&tls)
+            .unwrap();
+        // A pin alone keeps the web roots.
+        ClientOptions {
+            pin_sha256: Some(pin),
+            ..Default::default()
+        }
+        .client()
+        .unwrap();
+
+        // A CA file without certificates is refused up front.
+        std::fs::write(&ca, "not a certificate\n").unwrap();
+        let err = tls.client().unwrap_err();
+        assert!(err.to_string().contains("no certificates"), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn requests_carry_user_agent() {
+        let rpc = JsonRpcHttp::new("http://127.0.0.1:4337".to_string());
+        let req = rpc
+            .build_request("eth_chainId", serde_json::json!([]))
+            .unwrap();
+
+        let ua = req.headers().get(USER_AGENT).unwrap().to_str().unwrap();
+        assert_eq!(ua, USER_AGENT_VALUE);
+        assert!(ua.starts_with("opensub-aa/"));
+        assert_eq!(
+            req.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn redacted_endpoint_drops_path_and_query() {
+        let url = |s: &str| reqwest::Url::parse(s).unwrap();
+        assert_eq!(
+            redacted_endpoint(&url("https://api.pimlico.io/v2/84532/rpc?apikey=secret")),
+            "https://api.pimlico.io"
+        );
+        assert_eq!(
+            redacted_endpoint(&url("http://localhost:4337/rpc/secret-key")),
+            "http://localhost:4337"
+        );
+    }
+
+    #[test]
+    fn allowed_rpc_hosts_gate_scheme_and_host() {
+        let allowed = vec![
+            "base-sepolia.g.alchemy.com".to_string(),
+            "api.pimlico.io".to_string(),
+        ];
+
+        check_allowed_host(
+            "bundler",
+            "https://base-sepolia.g.alchemy.com/v2/key",
+            &allowed,
+        )
+        .unwrap();
+        check_allowed_host(
+            "paymaster",
+            "https://API.pimlico.io:443/v2/84532/rpc",
+            &allowed,
+        )
+        .unwrap();
+
+        let err = check_allowed_host("bundler", "https://evil.example/rpc", &allowed).unwrap_err();
+        assert!(
+            err.to_string().contains("bundler host 'evil.example'"),
+            "{err}"
+        );
+        // A listed host over plain http is still refused.
+        let err =
+            check_allowed_host("paymaster", "http://api.pimlico.io/v2/rpc", &allowed).unwrap_err();
+        assert!(err.to_string().contains("https"), "{err}");
+        // A lookalike that merely contains an allowed host.
+        assert!(check_allowed_host(
+            "bundler",
+            "https://api.pimlico.io.evil.example/rpc",
+            &allowed
+        )
+        .is_err());
+
+        // No list: no restriction (local bundlers over http keep working).
+        check_allowed_host("bundler", "http://127.0.0.1:4337", &[]).unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+//! ERC-4337 plumbing shared by the `opensub-aa` CLI and the keeper's `--relayer-mode`:
+//! bundler / paymaster JSON-RPC clients, the v0.6 `UserOperation`, single-call op building and
+//! the userOp signer abstraction.
+
+pub mod bundler;
+pub mod encoding;
+pub mod http;
+pub mod paymaster;
+pub mod signer;
+pub mod types;
+pub mod userop;
+
+/// Wall-clock seconds since the Unix epoch (0 if the clock is before it).
+pub fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
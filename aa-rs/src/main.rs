@@ -1,25 +1,35 @@
-mod bundler;
+mod allowance;
 mod config;
-mod encoding;
-mod paymaster;
-mod types;
+mod estimate_cache;
+mod fee_history;
+mod receipt_events;
 
+use allowance::{project_allowance, UpcomingCharge};
 use anyhow::{anyhow, Context, Result};
-use bundler::BundlerClient;
+use bundler::{BundlerClient, GasEstimates};
 use clap::{Args, Parser, Subcommand};
-use config::load_deployment;
-use ethers::abi::{Abi, AbiParser};
+use config::{load_deployment, Deployment};
+use estimate_cache::EstimateCache;
+use ethers::abi::{AbiParser, Token};
 use ethers::prelude::*;
 use ethers::providers::Middleware;
-use paymaster::PaymasterClient;
+use fee_history::AdaptiveFees;
+use futures::{StreamExt, TryStreamExt};
+use opensub_aa::{bundler, encoding, http, now_unix, paymaster, signer, types, userop};
+use paymaster::{PaymasterClient, SponsorshipCheck};
 use rand::rngs::OsRng;
 use rand::RngCore;
+use receipt_events::ReceiptEvents;
+use signer::UserOpSigner;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::PathBuf;
+use std::future::Future;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
-use types::UserOperation;
+use types::{EntryPointVersion, UserOperation};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum StdoutMode {
@@ -65,6 +75,38 @@ enum Command {
 
     /// Collect a due payment for a subscription.
     Collect(CollectArgs),
+
+    /// Report the token allowance the smart account's active subscriptions still need (read-only).
+    AllowanceReport(AllowanceReportArgs),
+
+    /// Approve OpenSub on several tokens at once: one userOp whose `executeBatch` runs an
+    /// `approve(openSub, amount)` per token.
+    BulkApprove(BulkApproveArgs),
+
+    /// Report a subscription's state and `hasAccess` as of a past block (read-only), e.g. to settle
+    /// an access dispute.
+    AccessAt(AccessAtArgs),
+
+    /// Print a plan's terms (price in token units, interval, collector fee) (read-only).
+    PlanInfo(PlanInfoArgs),
+
+    /// Quote what a plan costs per 30 days, in token units (read-only).
+    Quote(QuoteArgs),
+
+    /// Hand the smart account to a new owner (e.g. a hardware wallet). Irreversible.
+    ChangeOwner(ChangeOwnerArgs),
+
+    /// Check a deployment JSON against the chain (chainId, OpenSub code, plan, token, decimals)
+    /// and print a pass/fail report (read-only). Exits non-zero on any mismatch.
+    Verify(VerifyArgs),
+
+    /// Split a raw `paymasterAndData` hex blob into paymaster address, (v0.7) gas limits and
+    /// paymaster data, for debugging sponsored ops (offline).
+    DecodePaymasterData(DecodePaymasterDataArgs),
+
+    /// Print a userOp's `userOpHash` (what the owner signs): from `EntryPoint.getUserOpHash`, or
+    /// computed locally with `--offline`.
+    UseropHash(UseropHashArgs),
 }
 
 #[derive(Args, Debug)]
@@ -102,6 +144,13 @@ struct CommonArgs {
     #[arg(long, default_value_t = false)]
     new_owner: bool,
 
+    /// Derive the owner key deterministically from this hex seed instead of `--new-owner`'s random
+    /// key. The same seed always gives the same owner (and env file under .secrets/).
+    ///
+    /// TESTING ONLY (e.g. CI runs that reuse a funded owner): anyone with the seed has the key.
+    #[arg(long, conflicts_with = "new_owner")]
+    owner_seed: Option<String>,
+
     /// When used together with `--new-owner`, print the generated env file path to stdout as a
     /// single line (so scripts can `source "$(opensub-aa ... )"`).
     ///
@@ -109,6 +158,13 @@ struct CommonArgs {
     #[arg(long, default_value_t = false)]
     print_owner_env_path: bool,
 
+    /// Syntax of the generated owner env file.
+    ///
+    /// - `shell`: `export VAR=...` lines, for `source`.
+    /// - `dotenv`: plain `VAR=...` lines, for dotenv parsers.
+    #[arg(long, value_enum, default_value_t = OwnerEnvFormat::Shell)]
+    owner_env_format: OwnerEnvFormat,
+
     /// Print ONLY the owner address to stdout as a single line.
     ///
     /// In this mode, all other output is written to stderr.
@@ -124,7 +180,7 @@ struct CommonArgs {
     /// Print a single JSON object to stdout:
     /// `{ "owner": "0x...", "smartAccount": "0x...", "envPath": "/abs/path" }`
     ///
-    /// - `envPath` is `null` unless `--new-owner` is used.
+    /// - `envPath` is `null` unless `--new-owner` or `--owner-seed` is used.
     /// - All other logs are written to stderr.
     #[arg(long, default_value_t = false)]
     json: bool,
@@ -132,12 +188,124 @@ struct CommonArgs {
     /// CREATE2 salt for the smart account.
     #[arg(long, default_value_t = 0)]
     salt: u64,
+
+    /// Bound how long the signed UserOperation stays valid: `validUntil = now + n`.
+    ///
+    /// Sponsored ops (`--sponsor-gas`) pass `validUntil`/`validAfter` to the paymaster in the
+    /// ERC-7677 context. Otherwise the time range is signed and prefixed to the signature, which
+    /// only accounts implementing that layout accept. Stock SimpleAccount, including this repo's,
+    /// rejects the prefixed signature, so the op fails validation; see the README.
+    #[arg(long)]
+    valid_seconds: Option<u64>,
+
+    /// Log bundler / paymaster HTTP request and response bodies at debug level
+    /// (`RUST_LOG=debug`). `Authorization` headers are redacted and endpoint URLs are cut to
+    /// scheme, host and port.
+    #[arg(long, default_value_t = false)]
+    http_trace: bool,
+
+    /// Write every bundler / paymaster request and response to a timestamped JSON file in this
+    /// directory, e.g. to share a rejected `eth_sendUserOperation` with the bundler's support.
+    /// `Authorization` headers are redacted and endpoint URLs are cut to scheme, host and port.
+    #[arg(long)]
+    dump_rpc_dir: Option<PathBuf>,
+
+    /// After a send, write `{userOpHash, sender, receipt, summary}` as JSON to this file (with
+    /// `--no-wait`, `receipt` and `summary` are null). Written in every output mode.
+    #[arg(long)]
+    receipt_out: Option<PathBuf>,
+
+    /// Comma-separated hosts the bundler / paymaster URLs must point at (https only). Checked
+    /// before any bundler or paymaster request; unset means no restriction.
+    #[arg(long, env = "OPENSUB_AA_ALLOWED_RPC_HOSTS", value_delimiter = ',')]
+    allowed_rpc_hosts: Vec<String>,
+
+    /// PEM file of root certificate(s) to trust for the bundler / paymaster instead of the
+    /// bundled web roots, e.g. for a self-hosted bundler behind a private CA.
+    #[arg(long)]
+    bundler_ca: Option<PathBuf>,
+
+    /// Bundler to fall back to when the primary (and earlier fallbacks) can't be reached, time
+    /// out or answer HTTP 5xx. Repeatable; tried in order. An op the bundler rejects is not
+    /// retried elsewhere. The receipt is polled from the bundler that accepted the op.
+    #[arg(long, value_name = "URL")]
+    bundler_fallback: Vec<String>,
+
+    /// SHA-256 fingerprint of the bundler / paymaster leaf certificate (hex, colons optional).
+    /// Connections presenting any other certificate fail, even if the chain is valid.
+    #[arg(long, value_parser = http::parse_cert_pin)]
+    pin_cert: Option<[u8; 32]>,
+
+    /// Pin DNS for a host, curl-style: `host:ip` (or `host:port:ip`). Repeatable. Applies to the
+    /// chain RPC and the bundler / paymaster, for networks whose resolver misbehaves.
+    #[arg(long, value_parser = http::parse_resolve)]
+    resolve: Vec<http::ResolveOverride>,
+}
+
+/// UserOperation gas settings shared by the tx subcommands.
+#[derive(Args, Debug, Clone, Default)]
+struct GasArgs {
+    /// Bundler RPC method returning preferred userOp fees, e.g. `pimlico_getUserOperationGasPrice`
+    /// or `rundler_maxPriorityFeePerGas`. Falls back to the provider gas price if unset or failing.
+    #[arg(long, env = "OPENSUB_AA_BUNDLER_GAS_PRICE_METHOD")]
+    bundler_gas_price_method: Option<String>,
+
+    /// Set fees from `eth_feeHistory` instead of `gas price * --gas-multiplier-bps`: the priority
+    /// fee is a reward percentile over recent blocks, and maxFeePerGas leaves room above the
+    /// highest recent base fee. Falls back to the static path when fee history is unavailable.
+    #[arg(long)]
+    adaptive_gas: bool,
+
+    /// With `--adaptive-gas`: how many recent blocks to read.
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..=1024))]
+    fee_history_blocks: u64,
+
+    /// With `--adaptive-gas`: reward percentile (0-100) used for the priority fee.
+    #[arg(long, default_value_t = 50.0, value_parser = parse_percentile)]
+    priority_fee_percentile: f64,
+
+    // Manual gas limits: all three together skip `eth_estimateUserOperationGas`. clap rejects a
+    // partial set up front, before anything (e.g. `--fund-eth`) is sent.
+    /// callGasLimit to use instead of the bundler estimate.
+    #[arg(long, requires_all = ["verification_gas_limit", "pre_verification_gas"])]
+    call_gas_limit: Option<u64>,
+
+    /// verificationGasLimit to use instead of the bundler estimate.
+    #[arg(long, requires_all = ["call_gas_limit", "pre_verification_gas"])]
+    verification_gas_limit: Option<u64>,
+
+    /// preVerificationGas to use instead of the bundler estimate.
+    #[arg(long, requires_all = ["call_gas_limit", "verification_gas_limit"])]
+    pre_verification_gas: Option<u64>,
+
+    /// Reuse bundler gas estimates from this JSON file, keyed by (sender, callData, initCode,
+    /// chainId), e.g. for repeated `--dry-run`s. Ignored with `--sponsor-gas`, since the paymaster
+    /// data changes the estimate.
+    #[arg(long)]
+    estimate_cache: Option<PathBuf>,
+
+    /// Seconds a cached estimate stays valid.
+    #[arg(long, default_value_t = 300)]
+    estimate_cache_ttl: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OwnerEnvFormat {
+    Shell,
+    Dotenv,
 }
 
 #[derive(Args, Debug)]
 struct AccountArgs {
     #[command(flatten)]
     common: CommonArgs,
+
+    /// Derive accounts for a half-open salt range `<start>..<end>` instead of a single `--salt`.
+    ///
+    /// Prints (owner, salt, smartAccount, isDeployed) per salt, ordered by salt. With `--json`,
+    /// prints a single JSON array; with `--print-smart-account`, one address per line.
+    #[arg(long, value_parser = parse_salt_range, conflicts_with = "salt")]
+    salt_range: Option<Range<u64>>,
 }
 
 #[derive(Args, Debug)]
@@ -145,9 +313,13 @@ struct SubscribeArgs {
     #[command(flatten)]
     common: CommonArgs,
 
-    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods).
+    #[command(flatten)]
+    gas: GasArgs,
+
+    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods). Falls back to the deployment's
+    /// `bundlerUrl`.
     #[arg(long, env = "OPENSUB_AA_BUNDLER_URL")]
-    bundler: String,
+    bundler: Option<String>,
 
     /// Sponsor gas using an ERC-7677 paymaster web service (Milestone 6B).
     ///
@@ -159,11 +331,13 @@ struct SubscribeArgs {
 
     /// Paymaster RPC URL (ERC-7677 paymaster web service).
     ///
-    /// For Alchemy Gas Manager, this is an Alchemy HTTPS endpoint for the target chain.
+    /// For Alchemy Gas Manager, this is an Alchemy HTTPS endpoint for the target chain. Falls back
+    /// to the deployment's `paymasterUrl`.
     #[arg(long, env = "OPENSUB_AA_PAYMASTER_URL")]
     paymaster_url: Option<String>,
 
-    /// Gas Manager policy id (Alchemy Gas Manager).
+    /// Gas Manager policy id (Alchemy Gas Manager). Falls back to the deployment's
+    /// `gasManagerPolicyId`.
     #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_POLICY_ID")]
     policy_id: Option<String>,
 
@@ -171,20 +345,79 @@ struct SubscribeArgs {
     #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_WEBHOOK_DATA")]
     webhook_data: Option<String>,
 
+    /// Plan id to subscribe to (repeatable). Defaults to the deployment's planId.
+    ///
+    /// With more than one plan, one UserOperation is built per plan, each using its own 2D
+    /// nonce key (key = planId) so they do not serialize, and all are submitted concurrently.
+    /// Multi-plan subscribe requires an already-deployed smart account.
+    #[arg(long = "plan-id")]
+    plan_ids: Vec<u64>,
+
     /// Allowance in units of "periods" (allowance = price * periods).
+    ///
+    /// With multiple plans, the per-token allowance is the sum across all plans, since every
+    /// plan shares the same spender (OpenSub).
     #[arg(long, default_value_t = 12)]
     allowance_periods: u64,
 
+    /// Extra periods added to the allowance computed from --allowance-periods, as a buffer so
+    /// the last paid period's collect does not race the allowance running out.
+    #[arg(long, default_value_t = 0, conflicts_with = "allowance_amount")]
+    allowance_headroom_periods: u64,
+
     /// Optional explicit allowance amount (overrides allowance-periods).
     #[arg(long)]
     allowance_amount: Option<String>,
 
+    /// The token's approve function; must take `(address, uint256)`. Only the selector and
+    /// arguments are encoded, so tokens whose `approve` returns nothing (e.g. USDT) work as-is.
+    #[arg(long, default_value = DEFAULT_APPROVE_SIG)]
+    approve_sig: String,
+
     /// Optional: mint this many tokens (raw base units) to the smart account.
     ///
     /// Works on the repo's MockERC20 (mUSDC). Not valid for real tokens.
     #[arg(long)]
     mint: Option<String>,
 
+    /// Native-token plans: send this much wei (raw integer) with `subscribe` as `msg.value`.
+    ///
+    /// When set, the op calls `execute(openSub, value, subscribe(planId))` directly and skips the
+    /// ERC-20 approve (and the deployment token check).
+    #[arg(long, conflicts_with_all = ["mint", "allowance_amount", "allowance_periods", "allowance_headroom_periods"])]
+    value: Option<String>,
+
+    /// Override the OpenSub subscribe function, for contract versions with extra parameters.
+    ///
+    /// Human-readable ABI, e.g. `subscribe(uint256 planId, address beneficiary)`. Defaults to
+    /// `subscribe(uint256 planId) returns (uint256)`.
+    #[arg(long)]
+    subscribe_sig: Option<String>,
+
+    /// Arguments for `--subscribe-sig`, one per parameter. `{planId}` is replaced with the plan
+    /// being subscribed to. If omitted, the function must take a single parameter (the planId).
+    #[arg(long, num_args = 1.., requires = "subscribe_sig")]
+    subscribe_args: Vec<String>,
+
+    /// Plans charging more often than this (seconds) are flagged before subscribing.
+    #[arg(long, default_value_t = 3_600)]
+    min_interval: u64,
+
+    /// Plans charging less often than this (seconds, default 366 days) are flagged before
+    /// subscribing.
+    #[arg(long, default_value_t = 366 * 86_400)]
+    max_interval: u64,
+
+    /// Refuse to subscribe to a plan that fails the sanity preflight (zero price, interval outside
+    /// `[--min-interval, --max-interval]`, collectorFeeBps > 10000) instead of only warning.
+    #[arg(long)]
+    strict_plan: bool,
+
+    /// Refuse to subscribe when `--mint`, `--allowance-amount` or `--allowance-periods` cannot
+    /// cover one charge of the plan price, instead of only warning.
+    #[arg(long)]
+    strict: bool,
+
     /// Optional: fund the smart account with ETH (amount in ETH, decimal string).
     ///
     /// This is used to pay the prefund for the UserOperation (no paymaster in 6A).
@@ -201,6 +434,13 @@ struct SubscribeArgs {
     #[arg(long)]
     dry_run: bool,
 
+    /// With `--sponsor-gas`: only check whether the paymaster would sponsor the op.
+    ///
+    /// Builds the op, calls `pm_getPaymasterStubData` and reports stub data (sponsorship likely)
+    /// or the policy rejection reason. Never calls the bundler or sends anything.
+    #[arg(long, requires = "sponsor_gas", conflicts_with = "fund_eth")]
+    paymaster_dry_run: bool,
+
     /// Do not wait for the userOp receipt.
     #[arg(long)]
     no_wait: bool,
@@ -208,6 +448,11 @@ struct SubscribeArgs {
     /// Max seconds to wait for userOp receipt. Use 0 to disable timeout.
     #[arg(long, default_value_t = 180)]
     max_wait_seconds: u64,
+
+    /// After the receipt, re-read `activeSubscriptionOf` up to N more times (2s apart) while it
+    /// still returns 0, for RPCs whose state lags the bundler's receipt. 0 reads once.
+    #[arg(long, default_value_t = 5)]
+    post_subscribe_retries: u32,
 }
 
 #[derive(Args, Debug)]
@@ -215,19 +460,25 @@ struct CancelArgs {
     #[command(flatten)]
     common: CommonArgs,
 
-    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods).
+    #[command(flatten)]
+    gas: GasArgs,
+
+    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods). Falls back to the deployment's
+    /// `bundlerUrl`.
     #[arg(long, env = "OPENSUB_AA_BUNDLER_URL")]
-    bundler: String,
+    bundler: Option<String>,
 
     /// Sponsor gas using an ERC-7677 paymaster web service (Milestone 6B).
     #[arg(long, default_value_t = false)]
     sponsor_gas: bool,
 
-    /// Paymaster RPC URL (ERC-7677 paymaster web service).
+    /// Paymaster RPC URL (ERC-7677 paymaster web service). Falls back to the deployment's
+    /// `paymasterUrl`.
     #[arg(long, env = "OPENSUB_AA_PAYMASTER_URL")]
     paymaster_url: Option<String>,
 
-    /// Gas Manager policy id (Alchemy Gas Manager).
+    /// Gas Manager policy id (Alchemy Gas Manager). Falls back to the deployment's
+    /// `gasManagerPolicyId`.
     #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_POLICY_ID")]
     policy_id: Option<String>,
 
@@ -243,6 +494,25 @@ struct CancelArgs {
     #[arg(long, default_value_t = false)]
     at_period_end: bool,
 
+    /// Cancel effective after N more paid periods, via `cancelAfter(subscriptionId, periods)`.
+    /// Only OpenSub deployments implementing that function accept it; the CLI checks the
+    /// deployed bytecode first.
+    #[arg(
+        long,
+        conflicts_with = "at_period_end",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    after_periods: Option<u64>,
+
+    /// Also revoke the OpenSub token allowance: one `executeBatch` runs `cancel` then
+    /// `approve(openSub, 0)` on the plan's token, atomically.
+    #[arg(long, default_value_t = false)]
+    revoke: bool,
+
+    /// The token's approve function used by `--revoke`; must take `(address, uint256)`.
+    #[arg(long, default_value = DEFAULT_APPROVE_SIG, requires = "revoke")]
+    approve_sig: String,
+
     /// Gas price multiplier in basis points (e.g. 15000 = 1.5x).
     #[arg(long, default_value_t = 10000, env = "OPENSUB_AA_GAS_MULTIPLIER_BPS")]
     gas_multiplier_bps: u64,
@@ -265,19 +535,25 @@ struct ResumeArgs {
     #[command(flatten)]
     common: CommonArgs,
 
-    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods).
+    #[command(flatten)]
+    gas: GasArgs,
+
+    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods). Falls back to the deployment's
+    /// `bundlerUrl`.
     #[arg(long, env = "OPENSUB_AA_BUNDLER_URL")]
-    bundler: String,
+    bundler: Option<String>,
 
     /// Sponsor gas using an ERC-7677 paymaster web service (Milestone 6B).
     #[arg(long, default_value_t = false)]
     sponsor_gas: bool,
 
-    /// Paymaster RPC URL (ERC-7677 paymaster web service).
+    /// Paymaster RPC URL (ERC-7677 paymaster web service). Falls back to the deployment's
+    /// `paymasterUrl`.
     #[arg(long, env = "OPENSUB_AA_PAYMASTER_URL")]
     paymaster_url: Option<String>,
 
-    /// Gas Manager policy id (Alchemy Gas Manager).
+    /// Gas Manager policy id (Alchemy Gas Manager). Falls back to the deployment's
+    /// `gasManagerPolicyId`.
     #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_POLICY_ID")]
     policy_id: Option<String>,
 
@@ -311,19 +587,25 @@ struct CollectArgs {
     #[command(flatten)]
     common: CommonArgs,
 
-    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods).
+    #[command(flatten)]
+    gas: GasArgs,
+
+    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods). Falls back to the deployment's
+    /// `bundlerUrl`.
     #[arg(long, env = "OPENSUB_AA_BUNDLER_URL")]
-    bundler: String,
+    bundler: Option<String>,
 
     /// Sponsor gas using an ERC-7677 paymaster web service (Milestone 6B).
     #[arg(long, default_value_t = false)]
     sponsor_gas: bool,
 
-    /// Paymaster RPC URL (ERC-7677 paymaster web service).
+    /// Paymaster RPC URL (ERC-7677 paymaster web service). Falls back to the deployment's
+    /// `paymasterUrl`.
     #[arg(long, env = "OPENSUB_AA_PAYMASTER_URL")]
     paymaster_url: Option<String>,
 
-    /// Gas Manager policy id (Alchemy Gas Manager).
+    /// Gas Manager policy id (Alchemy Gas Manager). Falls back to the deployment's
+    /// `gasManagerPolicyId`.
     #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_POLICY_ID")]
     policy_id: Option<String>,
 
@@ -331,9 +613,10 @@ struct CollectArgs {
     #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_WEBHOOK_DATA")]
     webhook_data: Option<String>,
 
-    /// Subscription id to collect.
-    #[arg(long)]
-    subscription_id: u64,
+    /// Subscription id to collect. Repeat to collect several in one userOp (`executeBatch`); if
+    /// any of them is not due, the whole batch reverts.
+    #[arg(long = "subscription-id", required = true)]
+    subscription_ids: Vec<u64>,
 
     /// Gas price multiplier in basis points (e.g. 15000 = 1.5x).
     #[arg(long, default_value_t = 10000, env = "OPENSUB_AA_GAS_MULTIPLIER_BPS")]
@@ -352,165 +635,1055 @@ struct CollectArgs {
     max_wait_seconds: u64,
 }
 
-#[derive(Clone, Debug)]
-struct TxArgs {
-    bundler: String,
+#[derive(Args, Debug)]
+struct ChangeOwnerArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    #[command(flatten)]
+    gas: GasArgs,
+
+    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods). Falls back to the deployment's
+    /// `bundlerUrl`.
+    #[arg(long, env = "OPENSUB_AA_BUNDLER_URL")]
+    bundler: Option<String>,
+
+    /// Sponsor gas using an ERC-7677 paymaster web service (Milestone 6B).
+    #[arg(long, default_value_t = false)]
     sponsor_gas: bool,
+
+    /// Paymaster RPC URL (ERC-7677 paymaster web service). Falls back to the deployment's
+    /// `paymasterUrl`.
+    #[arg(long, env = "OPENSUB_AA_PAYMASTER_URL")]
     paymaster_url: Option<String>,
+
+    /// Gas Manager policy id (Alchemy Gas Manager). Falls back to the deployment's
+    /// `gasManagerPolicyId`.
+    #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_POLICY_ID")]
     policy_id: Option<String>,
+
+    /// Optional webhookData to include in paymaster requests.
+    #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_WEBHOOK_DATA")]
     webhook_data: Option<String>,
+
+    /// Address that will own the smart account afterwards.
+    #[arg(long)]
+    new_owner_address: Address,
+
+    /// The account's owner-update function, e.g. "transferOwnership(address)" on Ownable-style
+    /// accounts; must take a single `address`. The account calls it on itself through
+    /// `execute`. There is no default: the repo's SimpleAccount has no such function.
+    #[arg(long)]
+    owner_update_sig: String,
+
+    /// Required to actually send: the current owner loses control of the account.
+    #[arg(long, default_value_t = false)]
+    yes: bool,
+
+    /// Gas price multiplier in basis points (e.g. 15000 = 1.5x).
+    #[arg(long, default_value_t = 10000, env = "OPENSUB_AA_GAS_MULTIPLIER_BPS")]
     gas_multiplier_bps: u64,
+
+    /// Do not send the UserOperation; only build + estimate gas.
+    #[arg(long)]
     dry_run: bool,
+
+    /// Do not wait for the userOp receipt.
+    #[arg(long)]
     no_wait: bool,
+
+    /// Max seconds to wait for userOp receipt. Use 0 to disable timeout.
+    #[arg(long, default_value_t = 180)]
     max_wait_seconds: u64,
 }
 
-impl From<&SubscribeArgs> for TxArgs {
-    fn from(args: &SubscribeArgs) -> Self {
-        Self {
-            bundler: args.bundler.clone(),
-            sponsor_gas: args.sponsor_gas,
-            paymaster_url: args.paymaster_url.clone(),
-            policy_id: args.policy_id.clone(),
-            webhook_data: args.webhook_data.clone(),
-            gas_multiplier_bps: args.gas_multiplier_bps,
-            dry_run: args.dry_run,
-            no_wait: args.no_wait,
-            max_wait_seconds: args.max_wait_seconds,
-        }
-    }
-}
+#[derive(Args, Debug)]
+struct AllowanceReportArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-impl From<&CancelArgs> for TxArgs {
-    fn from(args: &CancelArgs) -> Self {
-        Self {
-            bundler: args.bundler.clone(),
-            sponsor_gas: args.sponsor_gas,
-            paymaster_url: args.paymaster_url.clone(),
-            policy_id: args.policy_id.clone(),
-            webhook_data: args.webhook_data.clone(),
-            gas_multiplier_bps: args.gas_multiplier_bps,
-            dry_run: args.dry_run,
-            no_wait: args.no_wait,
-            max_wait_seconds: args.max_wait_seconds,
-        }
-    }
-}
+    /// Number of upcoming charges per subscription the allowance should cover.
+    #[arg(long, default_value_t = 12)]
+    periods: u64,
 
-impl From<&ResumeArgs> for TxArgs {
-    fn from(args: &ResumeArgs) -> Self {
-        Self {
-            bundler: args.bundler.clone(),
-            sponsor_gas: args.sponsor_gas,
-            paymaster_url: args.paymaster_url.clone(),
-            policy_id: args.policy_id.clone(),
-            webhook_data: args.webhook_data.clone(),
-            gas_multiplier_bps: args.gas_multiplier_bps,
-            dry_run: args.dry_run,
-            no_wait: args.no_wait,
-            max_wait_seconds: args.max_wait_seconds,
-        }
-    }
+    /// Block range per eth_getLogs request when enumerating subscriptions.
+    #[arg(long, default_value_t = 5_000)]
+    log_chunk: u64,
 }
 
-impl From<&CollectArgs> for TxArgs {
-    fn from(args: &CollectArgs) -> Self {
-        Self {
-            bundler: args.bundler.clone(),
-            sponsor_gas: args.sponsor_gas,
-            paymaster_url: args.paymaster_url.clone(),
-            policy_id: args.policy_id.clone(),
-            webhook_data: args.webhook_data.clone(),
-            gas_multiplier_bps: args.gas_multiplier_bps,
-            dry_run: args.dry_run,
-            no_wait: args.no_wait,
-            max_wait_seconds: args.max_wait_seconds,
-        }
-    }
-}
+#[derive(Args, Debug)]
+struct BulkApproveArgs {
+    #[command(flatten)]
+    common: CommonArgs,
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    dotenvy::dotenv().ok();
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
-        )
-        // Always write logs to stderr so stdout can be used for script-friendly outputs.
-        .with_writer(std::io::stderr)
-        .init();
+    #[command(flatten)]
+    gas: GasArgs,
 
-    let cli = Cli::parse();
+    /// Bundler RPC URL (must support ERC-4337 JSON-RPC methods). Falls back to the deployment's
+    /// `bundlerUrl`.
+    #[arg(long, env = "OPENSUB_AA_BUNDLER_URL")]
+    bundler: Option<String>,
 
-    match cli.cmd {
-        Command::Account(args) => cmd_account(args).await,
-        Command::Subscribe(args) => cmd_subscribe(args).await,
-        Command::Cancel(args) => cmd_cancel(args).await,
-        Command::Resume(args) => cmd_resume(args).await,
-        Command::Collect(args) => cmd_collect(args).await,
-    }
-}
+    /// Sponsor gas using an ERC-7677 paymaster web service (Milestone 6B).
+    #[arg(long, default_value_t = false)]
+    sponsor_gas: bool,
 
-async fn cmd_account(args: AccountArgs) -> Result<()> {
-    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+    /// Paymaster RPC URL (ERC-7677 paymaster web service). Falls back to the deployment's
+    /// `paymasterUrl`.
+    #[arg(long, env = "OPENSUB_AA_PAYMASTER_URL")]
+    paymaster_url: Option<String>,
 
-    let mode = stdout_mode(&args.common)?;
-    let machine_mode = mode != StdoutMode::Normal;
+    /// Gas Manager policy id (Alchemy Gas Manager). Falls back to the deployment's
+    /// `gasManagerPolicyId`.
+    #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_POLICY_ID")]
+    policy_id: Option<String>,
 
-    let provider =
-        Provider::<Http>::try_from(dep.rpc_url.as_str())?.interval(Duration::from_millis(350));
+    /// Optional webhookData to include in paymaster requests.
+    #[arg(long, env = "OPENSUB_AA_GAS_MANAGER_WEBHOOK_DATA")]
+    webhook_data: Option<String>,
 
-    let chain_id = provider.get_chainid().await?.as_u64();
-    if chain_id != dep.chain_id {
-        return Err(anyhow!(
-            "chainId mismatch: deployment has {}, RPC returned {}",
-            dep.chain_id,
-            chain_id
-        ));
-    }
+    /// Token to approve (repeatable). The n-th `--token` is paired with the n-th `--amount`.
+    #[arg(long = "token", required = true)]
+    tokens: Vec<Address>,
 
-    let entrypoint =
-        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
-    let factory_addr =
-        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+    /// Allowance for the matching `--token`, in the token's smallest unit (integer, repeatable).
+    #[arg(long = "amount", required = true)]
+    amounts: Vec<String>,
 
-    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
-    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
-    if let Some(p) = owner_env_path.as_ref() {
-        match mode {
-            StdoutMode::OwnerEnvPath => {
-                // stdout: single line for scripting
-                println!("{}", p.display());
-                // stderr: human log
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            StdoutMode::Json => {
-                // JSON mode prints envPath inside the JSON object; keep logs on stderr.
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            _ => {
-                outln!(
-                    machine_mode,
-                    "generated new owner key; saved to {}",
-                    p.display()
-                );
-            }
-        }
-    }
+    /// The token's approve function; must take `(address, uint256)`. Only the selector and
+    /// arguments are encoded, so tokens whose `approve` returns nothing (e.g. USDT) work as-is.
+    #[arg(long, default_value = DEFAULT_APPROVE_SIG)]
+    approve_sig: String,
 
-    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet));
+    /// Gas price multiplier in basis points (e.g. 15000 = 1.5x).
+    #[arg(long, default_value_t = 10000, env = "OPENSUB_AA_GAS_MULTIPLIER_BPS")]
+    gas_multiplier_bps: u64,
 
-    let (account, deployed) = compute_account_address(
-        client.clone(),
-        factory_addr,
-        owner,
-        U256::from(args.common.salt),
-    )
-    .await?;
+    /// Do not send the UserOperation; only build + estimate gas.
+    #[arg(long)]
+    dry_run: bool,
 
-    // Script-friendly JSON: print once to stdout.
-    if mode == StdoutMode::Json {
-        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
+    /// Do not wait for the userOp receipt.
+    #[arg(long)]
+    no_wait: bool,
+
+    /// Max seconds to wait for userOp receipt. Use 0 to disable timeout.
+    #[arg(long, default_value_t = 180)]
+    max_wait_seconds: u64,
+}
+
+#[derive(Args, Debug)]
+struct AccessAtArgs {
+    /// Deployment artifact (OpenSub address + RPC).
+    #[arg(long, default_value = "deployments/base-sepolia.json")]
+    deployment: PathBuf,
+
+    /// Override the chain RPC URL (otherwise uses deployment JSON). Blocks older than the node's
+    /// pruning window need an archive RPC.
+    #[arg(long, env = "OPENSUB_AA_RPC_URL")]
+    rpc: Option<String>,
+
+    /// Pin DNS for the chain RPC host, curl-style: `host:ip` (or `host:port:ip`). Repeatable.
+    #[arg(long, value_parser = http::parse_resolve)]
+    resolve: Vec<http::ResolveOverride>,
+
+    /// Subscription to check.
+    #[arg(long)]
+    subscription_id: u64,
+
+    /// Block to evaluate `hasAccess` / `subscriptions` at.
+    #[arg(long)]
+    at_block: u64,
+
+    /// Print the report as a single JSON object on stdout.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct PlanInfoArgs {
+    /// Deployment artifact (OpenSub address, RPC and token decimals).
+    #[arg(long, default_value = "deployments/base-sepolia.json")]
+    deployment: PathBuf,
+
+    /// Override the chain RPC URL (otherwise uses deployment JSON).
+    #[arg(long, env = "OPENSUB_AA_RPC_URL")]
+    rpc: Option<String>,
+
+    /// Pin DNS for the chain RPC host, curl-style: `host:ip` (or `host:port:ip`). Repeatable.
+    #[arg(long, value_parser = http::parse_resolve)]
+    resolve: Vec<http::ResolveOverride>,
+
+    /// Plan to inspect. Defaults to the deployment's planId.
+    #[arg(long)]
+    plan_id: Option<u64>,
+
+    /// Print the plan as a single JSON object on stdout.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct QuoteArgs {
+    /// Deployment artifact (OpenSub address, RPC and token decimals).
+    #[arg(long, default_value = "deployments/base-sepolia.json")]
+    deployment: PathBuf,
+
+    /// Override the chain RPC URL (otherwise uses deployment JSON).
+    #[arg(long, env = "OPENSUB_AA_RPC_URL")]
+    rpc: Option<String>,
+
+    /// Pin DNS for the chain RPC host, curl-style: `host:ip` (or `host:port:ip`). Repeatable.
+    #[arg(long, value_parser = http::parse_resolve)]
+    resolve: Vec<http::ResolveOverride>,
+
+    /// Plan to quote. Defaults to the deployment's planId.
+    #[arg(long)]
+    plan_id: Option<u64>,
+
+    /// Print the quote as a single JSON object on stdout.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct VerifyArgs {
+    /// Deployment artifact to verify.
+    #[arg(long, default_value = "deployments/base-sepolia.json")]
+    deployment: PathBuf,
+
+    /// Override the chain RPC URL (otherwise uses deployment JSON).
+    #[arg(long, env = "OPENSUB_AA_RPC_URL")]
+    rpc: Option<String>,
+
+    /// Pin DNS for the chain RPC host, curl-style: `host:ip` (or `host:port:ip`). Repeatable.
+    #[arg(long, value_parser = http::parse_resolve)]
+    resolve: Vec<http::ResolveOverride>,
+
+    /// Print the report as a single JSON object on stdout.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct DecodePaymasterDataArgs {
+    /// The `paymasterAndData` blob, 0x-prefixed hex.
+    #[arg(long)]
+    data: String,
+
+    /// EntryPoint version the op was built for (0.6 or 0.7); decides the layout.
+    #[arg(long, default_value = "0.6")]
+    entrypoint_version: EntryPointVersion,
+
+    /// Print the fields as a single JSON object on stdout.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct UseropHashArgs {
+    /// The userOp as JSON in its `eth_sendUserOperation` shape: a file, or `-` for stdin.
+    #[arg(long)]
+    op: PathBuf,
+
+    /// EntryPoint version the op targets (0.6 or 0.7); decides how it is packed and hashed.
+    #[arg(long, default_value = "0.6")]
+    entrypoint_version: EntryPointVersion,
+
+    /// EntryPoint the hash is bound to. Defaults to the canonical one for --entrypoint-version.
+    #[arg(long)]
+    entrypoint: Option<Address>,
+
+    /// Hash locally instead of calling `EntryPoint.getUserOpHash` (no RPC).
+    #[arg(long, default_value_t = false)]
+    offline: bool,
+
+    /// With --offline: chain the hash is bound to. Defaults to the deployment's chainId.
+    #[arg(long, requires = "offline")]
+    chain_id: Option<u64>,
+
+    /// Deployment artifact (chainId and RPC).
+    #[arg(long, default_value = "deployments/base-sepolia.json")]
+    deployment: PathBuf,
+
+    /// Override the chain RPC URL (otherwise uses deployment JSON).
+    #[arg(long, env = "OPENSUB_AA_RPC_URL")]
+    rpc: Option<String>,
+
+    /// Pin DNS for the chain RPC host, curl-style: `host:ip` (or `host:port:ip`). Repeatable.
+    #[arg(long, value_parser = http::parse_resolve)]
+    resolve: Vec<http::ResolveOverride>,
+
+    /// Print the hash as a single JSON object on stdout.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Clone, Debug)]
+struct TxArgs {
+    bundler: Option<String>,
+    bundler_fallbacks: Vec<String>,
+    sponsor_gas: bool,
+    paymaster_url: Option<String>,
+    policy_id: Option<String>,
+    webhook_data: Option<String>,
+    gas_multiplier_bps: u64,
+    dry_run: bool,
+    paymaster_dry_run: bool,
+    no_wait: bool,
+    max_wait_seconds: u64,
+    valid_seconds: Option<u64>,
+    http_trace: bool,
+    dump_rpc_dir: Option<PathBuf>,
+    receipt_out: Option<PathBuf>,
+    allowed_rpc_hosts: Vec<String>,
+    client_options: http::ClientOptions,
+    gas: GasArgs,
+}
+
+impl TxArgs {
+    /// Fill the bundler / paymaster / policy id the CLI and env left unset from the deployment
+    /// JSON. A bundler is required either way, and the endpoints that will be called must pass
+    /// `--allowed-rpc-hosts`.
+    fn with_deployment(mut self, dep: &Deployment) -> Result<Self> {
+        self.bundler = self.bundler.or_else(|| dep.bundler_url.clone());
+        self.paymaster_url = self.paymaster_url.or_else(|| dep.paymaster_url.clone());
+        self.policy_id = self.policy_id.or_else(|| dep.gas_manager_policy_id.clone());
+        let Some(bundler) = self.bundler.as_deref() else {
+            return Err(anyhow!(
+                "no bundler URL: pass --bundler, set OPENSUB_AA_BUNDLER_URL, or add bundlerUrl to the deployment JSON"
+            ));
+        };
+
+        http::check_allowed_host("bundler", bundler, &self.allowed_rpc_hosts)?;
+        for url in &self.bundler_fallbacks {
+            http::check_allowed_host("bundler fallback", url, &self.allowed_rpc_hosts)?;
+        }
+        if self.sponsor_gas {
+            if let Some(url) = self.paymaster_url.as_deref() {
+                http::check_allowed_host("paymaster", url, &self.allowed_rpc_hosts)?;
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl From<&SubscribeArgs> for TxArgs {
+    fn from(args: &SubscribeArgs) -> Self {
+        Self {
+            bundler: args.bundler.clone(),
+            bundler_fallbacks: args.common.bundler_fallback.clone(),
+            sponsor_gas: args.sponsor_gas,
+            paymaster_url: args.paymaster_url.clone(),
+            policy_id: args.policy_id.clone(),
+            webhook_data: args.webhook_data.clone(),
+            gas_multiplier_bps: args.gas_multiplier_bps,
+            dry_run: args.dry_run,
+            paymaster_dry_run: args.paymaster_dry_run,
+            no_wait: args.no_wait,
+            max_wait_seconds: args.max_wait_seconds,
+            valid_seconds: args.common.valid_seconds,
+            http_trace: args.common.http_trace,
+            dump_rpc_dir: args.common.dump_rpc_dir.clone(),
+            receipt_out: args.common.receipt_out.clone(),
+            allowed_rpc_hosts: args.common.allowed_rpc_hosts.clone(),
+            client_options: http::ClientOptions {
+                ca_file: args.common.bundler_ca.clone(),
+                pin_sha256: args.common.pin_cert,
+                resolve: args.common.resolve.clone(),
+                ..Default::default()
+            },
+            gas: args.gas.clone(),
+        }
+    }
+}
+
+impl From<&CancelArgs> for TxArgs {
+    fn from(args: &CancelArgs) -> Self {
+        Self {
+            bundler: args.bundler.clone(),
+            bundler_fallbacks: args.common.bundler_fallback.clone(),
+            sponsor_gas: args.sponsor_gas,
+            paymaster_url: args.paymaster_url.clone(),
+            policy_id: args.policy_id.clone(),
+            webhook_data: args.webhook_data.clone(),
+            gas_multiplier_bps: args.gas_multiplier_bps,
+            dry_run: args.dry_run,
+            paymaster_dry_run: false,
+            no_wait: args.no_wait,
+            max_wait_seconds: args.max_wait_seconds,
+            valid_seconds: args.common.valid_seconds,
+            http_trace: args.common.http_trace,
+            dump_rpc_dir: args.common.dump_rpc_dir.clone(),
+            receipt_out: args.common.receipt_out.clone(),
+            allowed_rpc_hosts: args.common.allowed_rpc_hosts.clone(),
+            client_options: http::ClientOptions {
+                ca_file: args.common.bundler_ca.clone(),
+                pin_sha256: args.common.pin_cert,
+                resolve: args.common.resolve.clone(),
+                ..Default::default()
+            },
+            gas: args.gas.clone(),
+        }
+    }
+}
+
+impl From<&BulkApproveArgs> for TxArgs {
+    fn from(args: &BulkApproveArgs) -> Self {
+        Self {
+            bundler: args.bundler.clone(),
+            bundler_fallbacks: args.common.bundler_fallback.clone(),
+            sponsor_gas: args.sponsor_gas,
+            paymaster_url: args.paymaster_url.clone(),
+            policy_id: args.policy_id.clone(),
+            webhook_data: args.webhook_data.clone(),
+            gas_multiplier_bps: args.gas_multiplier_bps,
+            dry_run: args.dry_run,
+            paymaster_dry_run: false,
+            no_wait: args.no_wait,
+            max_wait_seconds: args.max_wait_seconds,
+            valid_seconds: args.common.valid_seconds,
+            http_trace: args.common.http_trace,
+            dump_rpc_dir: args.common.dump_rpc_dir.clone(),
+            receipt_out: args.common.receipt_out.clone(),
+            allowed_rpc_hosts: args.common.allowed_rpc_hosts.clone(),
+            client_options: http::ClientOptions {
+                ca_file: args.common.bundler_ca.clone(),
+                pin_sha256: args.common.pin_cert,
+                resolve: args.common.resolve.clone(),
+                ..Default::default()
+            },
+            gas: args.gas.clone(),
+        }
+    }
+}
+
+impl From<&ResumeArgs> for TxArgs {
+    fn from(args: &ResumeArgs) -> Self {
+        Self {
+            bundler: args.bundler.clone(),
+            bundler_fallbacks: args.common.bundler_fallback.clone(),
+            sponsor_gas: args.sponsor_gas,
+            paymaster_url: args.paymaster_url.clone(),
+            policy_id: args.policy_id.clone(),
+            webhook_data: args.webhook_data.clone(),
+            gas_multiplier_bps: args.gas_multiplier_bps,
+            dry_run: args.dry_run,
+            paymaster_dry_run: false,
+            no_wait: args.no_wait,
+            max_wait_seconds: args.max_wait_seconds,
+            valid_seconds: args.common.valid_seconds,
+            http_trace: args.common.http_trace,
+            dump_rpc_dir: args.common.dump_rpc_dir.clone(),
+            receipt_out: args.common.receipt_out.clone(),
+            allowed_rpc_hosts: args.common.allowed_rpc_hosts.clone(),
+            client_options: http::ClientOptions {
+                ca_file: args.common.bundler_ca.clone(),
+                pin_sha256: args.common.pin_cert,
+                resolve: args.common.resolve.clone(),
+                ..Default::default()
+            },
+            gas: args.gas.clone(),
+        }
+    }
+}
+
+impl From<&CollectArgs> for TxArgs {
+    fn from(args: &CollectArgs) -> Self {
+        Self {
+            bundler: args.bundler.clone(),
+            bundler_fallbacks: args.common.bundler_fallback.clone(),
+            sponsor_gas: args.sponsor_gas,
+            paymaster_url: args.paymaster_url.clone(),
+            policy_id: args.policy_id.clone(),
+            webhook_data: args.webhook_data.clone(),
+            gas_multiplier_bps: args.gas_multiplier_bps,
+            dry_run: args.dry_run,
+            paymaster_dry_run: false,
+            no_wait: args.no_wait,
+            max_wait_seconds: args.max_wait_seconds,
+            valid_seconds: args.common.valid_seconds,
+            http_trace: args.common.http_trace,
+            dump_rpc_dir: args.common.dump_rpc_dir.clone(),
+            receipt_out: args.common.receipt_out.clone(),
+            allowed_rpc_hosts: args.common.allowed_rpc_hosts.clone(),
+            client_options: http::ClientOptions {
+                ca_file: args.common.bundler_ca.clone(),
+                pin_sha256: args.common.pin_cert,
+                resolve: args.common.resolve.clone(),
+                ..Default::default()
+            },
+            gas: args.gas.clone(),
+        }
+    }
+}
+
+impl From<&ChangeOwnerArgs> for TxArgs {
+    fn from(args: &ChangeOwnerArgs) -> Self {
+        Self {
+            bundler: args.bundler.clone(),
+            bundler_fallbacks: args.common.bundler_fallback.clone(),
+            sponsor_gas: args.sponsor_gas,
+            paymaster_url: args.paymaster_url.clone(),
+            policy_id: args.policy_id.clone(),
+            webhook_data: args.webhook_data.clone(),
+            gas_multiplier_bps: args.gas_multiplier_bps,
+            dry_run: args.dry_run,
+            paymaster_dry_run: false,
+            no_wait: args.no_wait,
+            max_wait_seconds: args.max_wait_seconds,
+            valid_seconds: args.common.valid_seconds,
+            http_trace: args.common.http_trace,
+            dump_rpc_dir: args.common.dump_rpc_dir.clone(),
+            receipt_out: args.common.receipt_out.clone(),
+            allowed_rpc_hosts: args.common.allowed_rpc_hosts.clone(),
+            client_options: http::ClientOptions {
+                ca_file: args.common.bundler_ca.clone(),
+                pin_sha256: args.common.pin_cert,
+                resolve: args.common.resolve.clone(),
+                ..Default::default()
+            },
+            gas: args.gas.clone(),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+        )
+        // Always write logs to stderr so stdout can be used for script-friendly outputs.
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+
+    match cli.cmd {
+        Command::Account(args) => cmd_account(args).await,
+        Command::Subscribe(args) => cmd_subscribe(args).await,
+        Command::Cancel(args) => cmd_cancel(args).await,
+        Command::Resume(args) => cmd_resume(args).await,
+        Command::Collect(args) => cmd_collect(args).await,
+        Command::AllowanceReport(args) => cmd_allowance_report(args).await,
+        Command::BulkApprove(args) => cmd_bulk_approve(args).await,
+        Command::AccessAt(args) => cmd_access_at(args).await,
+        Command::PlanInfo(args) => cmd_plan_info(args).await,
+        Command::Quote(args) => cmd_quote(args).await,
+        Command::ChangeOwner(args) => cmd_change_owner(args).await,
+        Command::Verify(args) => cmd_verify(args).await,
+        Command::DecodePaymasterData(args) => cmd_decode_paymaster_data(args),
+        Command::UseropHash(args) => cmd_userop_hash(args).await,
+    }
+}
+
+async fn cmd_account(args: AccountArgs) -> Result<()> {
+    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+
+    let mode = stdout_mode(&args.common)?;
+    let machine_mode = mode != StdoutMode::Normal;
+
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != dep.chain_id {
+        return Err(anyhow!(
+            "chainId mismatch: deployment has {}, RPC returned {}",
+            dep.chain_id,
+            chain_id
+        ));
+    }
+
+    let entrypoint =
+        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
+    let factory_addr =
+        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+
+    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
+    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
+    if let Some(p) = owner_env_path.as_ref() {
+        report_generated_owner_key(mode, p);
+    }
+
+    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet));
+
+    if let Some(salts) = args.salt_range.clone() {
+        let accounts = map_salts_ordered(salts, SALT_RANGE_CONCURRENCY, |salt| {
+            let client = client.clone();
+            async move {
+                let (account, deployed) =
+                    compute_account_address(client, factory_addr, owner, U256::from(salt)).await?;
+                Ok((salt, account, deployed))
+            }
+        })
+        .await?;
+
+        match mode {
+            StdoutMode::Json => {
+                let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
+                let out: Vec<serde_json::Value> = accounts
+                    .iter()
+                    .map(|(salt, account, deployed)| {
+                        serde_json::json!({
+                            "owner": encoding::fmt_address(owner),
+                            "salt": salt,
+                            "smartAccount": encoding::fmt_address(*account),
+                            "isDeployed": deployed,
+                            "envPath": env_path,
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::Value::Array(out));
+            }
+            StdoutMode::OwnerAddress => println!("{}", owner),
+            StdoutMode::SmartAccountAddress => {
+                for (_, account, _) in &accounts {
+                    println!("{}", account);
+                }
+            }
+            _ => {}
+        }
+
+        outln!(machine_mode, "chainId:        {}", dep.chain_id);
+        outln!(machine_mode, "entryPoint:     {}", entrypoint);
+        outln!(machine_mode, "factory:        {}", factory_addr);
+        outln!(machine_mode, "owner:          {}", owner);
+        for (salt, account, deployed) in &accounts {
+            outln!(
+                machine_mode,
+                "salt {:>6}:    {} (deployed={})",
+                salt,
+                account,
+                deployed
+            );
+        }
+
+        return Ok(());
+    }
+
+    let (account, deployed) = compute_account_address(
+        client.clone(),
+        factory_addr,
+        owner,
+        U256::from(args.common.salt),
+    )
+    .await?;
+
+    // Script-friendly JSON: print once to stdout.
+    if mode == StdoutMode::Json {
+        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
+        let out = serde_json::json!({
+            "owner": encoding::fmt_address(owner),
+            "smartAccount": encoding::fmt_address(account),
+            "envPath": env_path,
+        });
+        println!("{}", out);
+    }
+
+    match mode {
+        StdoutMode::OwnerAddress => println!("{}", owner),
+        StdoutMode::SmartAccountAddress => println!("{}", account),
+        _ => {}
+    }
+
+    outln!(machine_mode, "chainId:        {}", dep.chain_id);
+    outln!(machine_mode, "entryPoint:     {}", entrypoint);
+    outln!(machine_mode, "factory:        {}", factory_addr);
+    outln!(machine_mode, "owner:          {}", owner);
+    outln!(machine_mode, "smartAccount:   {}", account);
+    outln!(machine_mode, "isDeployed:     {}", deployed);
+
+    Ok(())
+}
+
+async fn cmd_subscribe(args: SubscribeArgs) -> Result<()> {
+    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+    let tx_args = TxArgs::from(&args).with_deployment(&dep)?;
+    let subscribe_call = SubscribeCall::parse(args.subscribe_sig.as_deref(), &args.subscribe_args)?;
+
+    let mode = stdout_mode(&args.common)?;
+    let machine_mode = mode != StdoutMode::Normal;
+
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != dep.chain_id {
+        return Err(anyhow!(
+            "chainId mismatch: deployment has {}, RPC returned {}",
+            dep.chain_id,
+            chain_id
+        ));
+    }
+
+    let entrypoint =
+        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
+    let factory_addr =
+        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+
+    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
+    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
+
+    // Machine mode: allow scripts to capture the owner address without parsing logs.
+    if mode == StdoutMode::OwnerAddress {
+        println!("{}", owner);
+    }
+
+    if let Some(p) = owner_env_path.as_ref() {
+        report_generated_owner_key(mode, p);
+
+        if args.fund_eth.is_some() {
+            outln!(
+                machine_mode,
+                "note: --fund-eth requires the NEW owner EOA ({}) to have ETH for gas.",
+                owner
+            );
+        }
+    }
+
+    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+
+    // Load plan price/token from OpenSub.
+    let plan_ids = resolve_plan_ids(&args.plan_ids, dep.plan_id)?;
+    let native_value: Option<U256> = match args.value.as_deref() {
+        Some(v) => Some(
+            U256::from_dec_str(v)
+                .with_context(|| format!("invalid --value (expected integer wei): {v}"))?,
+        ),
+        None => None,
+    };
+    let multi_plan = plan_ids.len() > 1;
+
+    let mut plan_prices: Vec<(U256, U256)> = Vec::with_capacity(plan_ids.len());
+    for plan_id in &plan_ids {
+        let plan = read_plan(client.clone(), dep.open_sub, *plan_id).await?;
+        let plan_price = plan.price;
+        if native_value.is_none() && plan.token != dep.token {
+            return Err(anyhow!(
+                "deployment token {} does not match OpenSub plan {} token {}",
+                dep.token,
+                plan_id,
+                plan.token
+            ));
+        }
+        if !plan.active {
+            return Err(anyhow!("plan {} is inactive on-chain", plan_id));
+        }
+        let issues = plan_sanity_issues(&plan, args.min_interval, args.max_interval);
+        if args.strict_plan && !issues.is_empty() {
+            return Err(anyhow!(
+                "plan {} looks misconfigured: {} (drop --strict-plan to subscribe anyway)",
+                plan_id,
+                issues.join("; ")
+            ));
+        }
+        for issue in &issues {
+            tracing::warn!("plan {}: {}", plan_id, issue);
+        }
+        if let Some(v) = native_value {
+            if v < plan_price {
+                tracing::warn!(
+                    "--value {} is below plan {} price {}; subscribe will likely revert",
+                    v,
+                    plan_id,
+                    plan_price
+                );
+            }
+        }
+        plan_prices.push((*plan_id, plan_price));
+    }
+
+    let salt = U256::from(args.common.salt);
+    let (account, deployed) =
+        compute_account_address(client.clone(), factory_addr, owner, salt).await?;
+
+    // Machine mode: allow scripts to capture the smart account address without parsing logs.
+    if mode == StdoutMode::SmartAccountAddress {
+        println!("{}", account);
+    }
+
+    // Script-friendly JSON: print once to stdout early (before any long-running bundler calls).
+    if mode == StdoutMode::Json {
+        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
+        let out = serde_json::json!({
+            "owner": encoding::fmt_address(owner),
+            "smartAccount": encoding::fmt_address(account),
+            "envPath": env_path,
+        });
+        println!("{}", out);
+    }
+
+    outln!(
+        machine_mode,
+        "smartAccount: {} (deployed={})",
+        account,
+        deployed
+    );
+
+    if multi_plan && !deployed {
+        // Every op would carry the same initCode; only the first to land could deploy the account.
+        return Err(anyhow!(
+            "multiple --plan-id values require a deployed smart account; subscribe to one plan first"
+        ));
+    }
+    if multi_plan && args.mint.is_some() {
+        return Err(anyhow!(
+            "--mint cannot be combined with multiple --plan-id values"
+        ));
+    }
+
+    // Optional funding for prefund.
+    if let Some(eth) = args.fund_eth.clone() {
+        let amount_wei = ethers::utils::parse_ether(eth.clone())
+            .with_context(|| format!("invalid --fund-eth value: {eth}"))?;
+        fund_account_eth(client.clone(), account, amount_wei).await?;
+    }
+
+    // Optional mint amount (demo-only token).
+    //
+    // Important: this is now executed *inside the UserOperation* (as part of the executeBatch call),
+    // so it can be sponsored by a paymaster in Milestone 6B.
+    //
+    // This only works for the repo's MockERC20, which has an unrestricted `mint(address,uint256)`.
+    let mint_amount: Option<U256> = if let Some(mint_amount) = args.mint.clone() {
+        let amt = U256::from_dec_str(&mint_amount)
+            .with_context(|| format!("invalid --mint amount (expected integer): {mint_amount}"))?;
+        if amt.is_zero() {
+            None
+        } else {
+            Some(amt)
+        }
+    } else {
+        None
+    };
+
+    // Compute allowance.
+    //
+    // All plans share the same token and spender (OpenSub), and `approve` overwrites rather than
+    // adds. Every op therefore approves the combined amount so the final allowance does not depend
+    // on which op lands last.
+    let explicit_allowance = args
+        .allowance_amount
+        .as_deref()
+        .map(|a| {
+            U256::from_dec_str(a)
+                .with_context(|| format!("invalid --allowance-amount (expected integer): {a}"))
+        })
+        .transpose()?;
+    let allowance_amount = match explicit_allowance {
+        Some(a) => a,
+        None => derived_allowance(
+            plan_prices.iter().map(|(_, price)| *price),
+            args.allowance_periods,
+            args.allowance_headroom_periods,
+        )?,
+    };
+
+    if native_value.is_none() {
+        let price = plan_prices
+            .iter()
+            .fold(U256::zero(), |acc, (_, price)| acc.saturating_add(*price));
+        let issues = funding_issues(
+            price,
+            explicit_allowance,
+            args.allowance_periods,
+            mint_amount,
+        );
+        if args.strict && !issues.is_empty() {
+            return Err(anyhow!(
+                "{} (drop --strict to subscribe anyway)",
+                issues.join("; ")
+            ));
+        }
+        for issue in &issues {
+            tracing::warn!("{}", issue);
+        }
+        if let Some(warning) = headroom_warning(price, allowance_amount) {
+            tracing::warn!("{}", warning);
+        }
+    }
+
+    // Build, estimate, sign and send one op per plan concurrently.
+    let results = futures::future::join_all(plan_prices.iter().map(|(plan_id, _)| {
+        let client = client.clone();
+        let provider = &provider;
+        let wallet = &wallet;
+        let tx_args = &tx_args;
+        let subscribe_call = &subscribe_call;
+        let approve_sig = args.approve_sig.as_str();
+        let explorer = dep.explorer_base_url.as_deref();
+        let post_subscribe_retries = args.post_subscribe_retries;
+        let plan_id = *plan_id;
+        // Single-plan subscribe keeps the default nonce key (0) for compatibility.
+        let nonce_key = if multi_plan { plan_id } else { U256::zero() };
+        async move {
+            let (call_data, init_code, nonce) = build_userop_payload(
+                client.clone(),
+                entrypoint,
+                factory_addr,
+                dep.open_sub,
+                dep.token,
+                plan_id,
+                owner,
+                salt,
+                account,
+                deployed,
+                mint_amount,
+                allowance_amount,
+                native_value,
+                nonce_key,
+                subscribe_call,
+                approve_sig,
+            )
+            .await?;
+
+            let got_receipt = send_userop(
+                provider,
+                client.clone(),
+                wallet,
+                entrypoint,
+                chain_id,
+                account,
+                call_data,
+                init_code,
+                nonce,
+                tx_args,
+                explorer,
+                machine_mode,
+            )
+            .await?
+            .is_some();
+
+            if !got_receipt {
+                return Ok::<_, anyhow::Error>(None);
+            }
+
+            // Best-effort: look up subscription id after receipt. The RPC may not have caught up
+            // with the bundler's receipt yet, so a zero id is re-read a few times.
+            let sub_id = retry_until_nonzero(
+                post_subscribe_retries,
+                POST_SUBSCRIBE_RETRY_DELAY,
+                |attempt| {
+                    if attempt > 0 {
+                        tracing::info!(
+                            plan_id = %plan_id,
+                            attempt,
+                            retries = post_subscribe_retries,
+                            "activeSubscriptionOf still 0; re-reading"
+                        );
+                    }
+                    active_subscription_of(client.clone(), dep.open_sub, plan_id, account)
+                },
+            )
+            .await?;
+            let has_access = has_access(client.clone(), dep.open_sub, sub_id, None)
+                .await
+                .unwrap_or(false);
+            Ok(Some((sub_id, has_access)))
+        }
+    }))
+    .await;
+
+    let mut failed = 0usize;
+    for ((plan_id, _), res) in plan_prices.iter().zip(results) {
+        match res {
+            Ok(Some((sub_id, has_access))) => {
+                outln!(
+                    machine_mode,
+                    "\nactiveSubscriptionOf(planId={}, account={}) => {}",
+                    plan_id,
+                    account,
+                    sub_id
+                );
+                outln!(machine_mode, "hasAccess({}) => {}", sub_id, has_access);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                failed += 1;
+                outln!(
+                    machine_mode,
+                    "\nplan {}: subscribe failed: {:#}",
+                    plan_id,
+                    e
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} plan subscriptions failed",
+            failed,
+            plan_prices.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolve the plans targeted by `subscribe`: explicit `--plan-id` values, or the deployment's plan.
+fn resolve_plan_ids(explicit: &[u64], default_plan_id: U256) -> Result<Vec<U256>> {
+    if explicit.is_empty() {
+        return Ok(vec![default_plan_id]);
+    }
+
+    let mut seen = std::collections::BTreeSet::new();
+    for id in explicit {
+        if !seen.insert(*id) {
+            return Err(anyhow!("--plan-id {} specified more than once", id));
+        }
+    }
+
+    Ok(explicit.iter().map(|id| U256::from(*id)).collect())
+}
+
+async fn cmd_cancel(args: CancelArgs) -> Result<()> {
+    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+    let tx_args = TxArgs::from(&args).with_deployment(&dep)?;
+
+    let mode = stdout_mode(&args.common)?;
+    let machine_mode = mode != StdoutMode::Normal;
+
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != dep.chain_id {
+        return Err(anyhow!(
+            "chainId mismatch: deployment has {}, RPC returned {}",
+            dep.chain_id,
+            chain_id
+        ));
+    }
+
+    let entrypoint =
+        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
+    let factory_addr =
+        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+
+    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
+    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
+
+    if mode == StdoutMode::OwnerAddress {
+        println!("{}", owner);
+    }
+
+    if let Some(p) = owner_env_path.as_ref() {
+        report_generated_owner_key(mode, p);
+    }
+
+    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+
+    let salt = U256::from(args.common.salt);
+    let (account, deployed) =
+        compute_account_address(client.clone(), factory_addr, owner, salt).await?;
+
+    if mode == StdoutMode::SmartAccountAddress {
+        println!("{}", account);
+    }
+
+    if mode == StdoutMode::Json {
+        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
         let out = serde_json::json!({
             "owner": encoding::fmt_address(owner),
             "smartAccount": encoding::fmt_address(account),
@@ -519,30 +1692,248 @@ async fn cmd_account(args: AccountArgs) -> Result<()> {
         println!("{}", out);
     }
 
-    match mode {
-        StdoutMode::OwnerAddress => println!("{}", owner),
-        StdoutMode::SmartAccountAddress => println!("{}", account),
-        _ => {}
+    outln!(
+        machine_mode,
+        "smartAccount: {} (deployed={})",
+        account,
+        deployed
+    );
+
+    let sub_id = U256::from(args.subscription_id);
+
+    if let Some(periods) = args.after_periods {
+        let code = client
+            .get_code(dep.open_sub, None)
+            .await
+            .context("failed to fetch OpenSub code")?;
+        if !encoding::bytecode_has_selector(&code, cancel_after_selector()) {
+            eprintln!(
+                "warning: the OpenSub at {} has no cancelAfter(uint256,uint256); --after-periods is not supported by this deployment",
+                dep.open_sub
+            );
+            return Err(anyhow!(
+                "--after-periods {periods} needs cancelAfter(uint256,uint256), which the deployed OpenSub does not implement; use --at-period-end or an immediate cancel"
+            ));
+        }
+        outln!(
+            machine_mode,
+            "cancelling subscription {} after {} more period(s)",
+            sub_id,
+            periods
+        );
+    }
+
+    // With --revoke, the allowance to clear is on the subscription's plan token.
+    let revoke_token = if args.revoke {
+        let sub = read_subscription(client.clone(), dep.open_sub, sub_id, None).await?;
+        let token = read_plan(client.clone(), dep.open_sub, sub.plan_id)
+            .await?
+            .token;
+        outln!(
+            machine_mode,
+            "revoking OpenSub allowance on token {} in the same userOp",
+            token
+        );
+        Some(token)
+    } else {
+        None
+    };
+
+    let (call_data, init_code, nonce) = if let Some(token) = revoke_token {
+        let calls = cancel_and_revoke_calls(
+            dep.open_sub,
+            token,
+            sub_id,
+            args.at_period_end,
+            args.after_periods,
+            &args.approve_sig,
+        )?;
+        build_batch_call_payload(
+            client.clone(),
+            entrypoint,
+            factory_addr,
+            owner,
+            salt,
+            account,
+            deployed,
+            &calls,
+        )
+        .await?
+    } else {
+        let cancel_calldata = encode_cancel(sub_id, args.at_period_end, args.after_periods)?;
+
+        build_single_call_payload(
+            client.clone(),
+            entrypoint,
+            factory_addr,
+            owner,
+            salt,
+            account,
+            deployed,
+            dep.open_sub,
+            cancel_calldata,
+        )
+        .await?
+    };
+
+    let got_receipt = send_userop(
+        &provider,
+        client.clone(),
+        &wallet,
+        entrypoint,
+        chain_id,
+        account,
+        call_data,
+        init_code,
+        nonce,
+        &tx_args,
+        dep.explorer_base_url.as_deref(),
+        machine_mode,
+    )
+    .await?
+    .is_some();
+
+    if let (true, Some(token)) = (got_receipt, revoke_token) {
+        let sub = read_subscription(client.clone(), dep.open_sub, sub_id, None).await?;
+        let erc20_abi = AbiParser::default().parse(&[
+            "function allowance(address owner, address spender) view returns (uint256)",
+        ])?;
+        let erc20 = Contract::new(token, erc20_abi, client.clone());
+        let allowance: U256 = erc20
+            .method("allowance", (account, dep.open_sub))?
+            .call()
+            .await
+            .context("allowance() failed")?;
+
+        outln!(
+            machine_mode,
+            "subscription {} status after cancel: {} (paidThrough={})",
+            sub_id,
+            subscription_status_name(sub.status),
+            sub.paid_through
+        );
+        outln!(
+            machine_mode,
+            "allowance after revoke: {} (token {})",
+            allowance,
+            token
+        );
+    }
+
+    Ok(())
+}
+
+async fn cmd_resume(args: ResumeArgs) -> Result<()> {
+    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+    let tx_args = TxArgs::from(&args).with_deployment(&dep)?;
+
+    let mode = stdout_mode(&args.common)?;
+    let machine_mode = mode != StdoutMode::Normal;
+
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != dep.chain_id {
+        return Err(anyhow!(
+            "chainId mismatch: deployment has {}, RPC returned {}",
+            dep.chain_id,
+            chain_id
+        ));
+    }
+
+    let entrypoint =
+        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
+    let factory_addr =
+        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+
+    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
+    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
+
+    if mode == StdoutMode::OwnerAddress {
+        println!("{}", owner);
+    }
+
+    if let Some(p) = owner_env_path.as_ref() {
+        report_generated_owner_key(mode, p);
+    }
+
+    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+
+    let salt = U256::from(args.common.salt);
+    let (account, deployed) =
+        compute_account_address(client.clone(), factory_addr, owner, salt).await?;
+
+    if mode == StdoutMode::SmartAccountAddress {
+        println!("{}", account);
+    }
+
+    if mode == StdoutMode::Json {
+        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
+        let out = serde_json::json!({
+            "owner": encoding::fmt_address(owner),
+            "smartAccount": encoding::fmt_address(account),
+            "envPath": env_path,
+        });
+        println!("{}", out);
     }
 
-    outln!(machine_mode, "chainId:        {}", dep.chain_id);
-    outln!(machine_mode, "entryPoint:     {}", entrypoint);
-    outln!(machine_mode, "factory:        {}", factory_addr);
-    outln!(machine_mode, "owner:          {}", owner);
-    outln!(machine_mode, "smartAccount:   {}", account);
-    outln!(machine_mode, "isDeployed:     {}", deployed);
+    outln!(
+        machine_mode,
+        "smartAccount: {} (deployed={})",
+        account,
+        deployed
+    );
+
+    let sub_id = U256::from(args.subscription_id);
+    let open_sub_abi =
+        AbiParser::default().parse(&["function unscheduleCancel(uint256 subscriptionId)"])?;
+    let open_sub = Contract::new(dep.open_sub, open_sub_abi, client.clone());
+    let resume_calldata = open_sub
+        .method::<_, ()>("unscheduleCancel", (sub_id,))?
+        .calldata()
+        .ok_or_else(|| anyhow!("failed to build unscheduleCancel calldata"))?;
+
+    let (call_data, init_code, nonce) = build_single_call_payload(
+        client.clone(),
+        entrypoint,
+        factory_addr,
+        owner,
+        salt,
+        account,
+        deployed,
+        dep.open_sub,
+        resume_calldata,
+    )
+    .await?;
+
+    let _got_receipt = send_userop(
+        &provider,
+        client.clone(),
+        &wallet,
+        entrypoint,
+        chain_id,
+        account,
+        call_data,
+        init_code,
+        nonce,
+        &tx_args,
+        dep.explorer_base_url.as_deref(),
+        machine_mode,
+    )
+    .await?
+    .is_some();
 
     Ok(())
 }
 
-async fn cmd_subscribe(args: SubscribeArgs) -> Result<()> {
+async fn cmd_collect(args: CollectArgs) -> Result<()> {
     let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+    let tx_args = TxArgs::from(&args).with_deployment(&dep)?;
 
     let mode = stdout_mode(&args.common)?;
     let machine_mode = mode != StdoutMode::Normal;
 
-    let provider =
-        Provider::<Http>::try_from(dep.rpc_url.as_str())?.interval(Duration::from_millis(350));
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
 
     let chain_id = provider.get_chainid().await?.as_u64();
     if chain_id != dep.chain_id {
@@ -561,67 +1952,205 @@ async fn cmd_subscribe(args: SubscribeArgs) -> Result<()> {
     let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
     let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
 
-    // Machine mode: allow scripts to capture the owner address without parsing logs.
     if mode == StdoutMode::OwnerAddress {
         println!("{}", owner);
     }
 
     if let Some(p) = owner_env_path.as_ref() {
-        match mode {
-            StdoutMode::OwnerEnvPath => {
-                // stdout: single line for scripting
-                println!("{}", p.display());
-                // stderr: human logs
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            StdoutMode::Json => {
-                // JSON mode prints envPath inside the JSON object; keep logs on stderr.
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            _ => {
-                outln!(
-                    machine_mode,
-                    "generated new owner key; saved to {}",
-                    p.display()
-                );
-            }
-        }
+        report_generated_owner_key(mode, p);
+    }
 
-        if args.fund_eth.is_some() {
+    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+
+    let salt = U256::from(args.common.salt);
+    let (account, deployed) =
+        compute_account_address(client.clone(), factory_addr, owner, salt).await?;
+
+    if mode == StdoutMode::SmartAccountAddress {
+        println!("{}", account);
+    }
+
+    if mode == StdoutMode::Json {
+        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
+        let out = serde_json::json!({
+            "owner": encoding::fmt_address(owner),
+            "smartAccount": encoding::fmt_address(account),
+            "envPath": env_path,
+        });
+        println!("{}", out);
+    }
+
+    outln!(
+        machine_mode,
+        "smartAccount: {} (deployed={})",
+        account,
+        deployed
+    );
+
+    let calls = collect_calls(dep.open_sub, &args.subscription_ids)?;
+    let (call_data, init_code, nonce) = match calls.as_slice() {
+        [(target, collect_calldata)] => {
+            build_single_call_payload(
+                client.clone(),
+                entrypoint,
+                factory_addr,
+                owner,
+                salt,
+                account,
+                deployed,
+                *target,
+                collect_calldata.clone(),
+            )
+            .await?
+        }
+        _ => {
             outln!(
                 machine_mode,
-                "note: --fund-eth requires the NEW owner EOA ({}) to have ETH for gas.",
-                owner
+                "collecting {} subscriptions in one executeBatch",
+                calls.len()
             );
+            build_batch_call_payload(
+                client.clone(),
+                entrypoint,
+                factory_addr,
+                owner,
+                salt,
+                account,
+                deployed,
+                &calls,
+            )
+            .await?
+        }
+    };
+
+    let events = send_userop(
+        &provider,
+        client.clone(),
+        &wallet,
+        entrypoint,
+        chain_id,
+        account,
+        call_data,
+        init_code,
+        nonce,
+        &tx_args,
+        dep.explorer_base_url.as_deref(),
+        machine_mode,
+    )
+    .await?;
+
+    if let Some(events) = events {
+        let charged = events.charged_ids();
+        let results: Vec<(u64, bool)> = args
+            .subscription_ids
+            .iter()
+            .map(|id| (*id, charged.contains(&U256::from(*id))))
+            .collect();
+        if machine_mode {
+            // Like the userOp events: stdout belongs to the command's own output.
+            let results: Vec<_> = results
+                .iter()
+                .map(|(id, charged)| {
+                    serde_json::json!({ "subscriptionId": id.to_string(), "charged": charged })
+                })
+                .collect();
+            eprintln!("{}", serde_json::json!({ "collectResults": results }));
+        } else {
+            for (id, charged) in results {
+                let result = if charged { "charged" } else { "not charged" };
+                println!("subscription {id}: {result}");
+            }
         }
     }
 
-    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+    Ok(())
+}
 
-    // Load plan price/token from OpenSub.
-    let (plan_token, plan_price, plan_active) =
-        read_plan(client.clone(), dep.open_sub, dep.plan_id).await?;
-    if plan_token != dep.token {
+/// `collect(id)` on OpenSub for each id, in order. The list must be non-empty and free of
+/// duplicates: a second collect of the same id in one batch would revert the whole op.
+fn collect_calls(open_sub: Address, ids: &[u64]) -> Result<Vec<(Address, Bytes)>> {
+    if ids.is_empty() {
+        return Err(anyhow!("pass at least one --subscription-id"));
+    }
+    let mut seen = BTreeSet::new();
+    if let Some(dup) = ids.iter().find(|id| !seen.insert(**id)) {
+        return Err(anyhow!("--subscription-id {dup} is given more than once"));
+    }
+    let collect_fn = AbiParser::default().parse_function(
+        "function collect(uint256 subscriptionId) returns (uint256 merchantAmount, uint256 collectorFee)",
+    )?;
+    ids.iter()
+        .map(|id| {
+            let data = collect_fn.encode_input(&[Token::Uint(U256::from(*id))])?;
+            Ok((open_sub, Bytes::from(data)))
+        })
+        .collect()
+}
+
+async fn cmd_change_owner(args: ChangeOwnerArgs) -> Result<()> {
+    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+    let tx_args = TxArgs::from(&args).with_deployment(&dep)?;
+
+    let mode = stdout_mode(&args.common)?;
+    let machine_mode = mode != StdoutMode::Normal;
+
+    // Validate before touching the network.
+    let owner_update_calldata =
+        encode_owner_update(&args.owner_update_sig, args.new_owner_address)?;
+    if args.new_owner_address == Address::zero() {
         return Err(anyhow!(
-            "deployment token {} does not match OpenSub plan token {}",
-            dep.token,
-            plan_token
+            "--new-owner-address is the zero address; that would brick the account"
+        ));
+    }
+    if !args.yes && !args.dry_run {
+        return Err(anyhow!(
+            "change-owner is irreversible: the current owner key loses control of the account. Re-run with --yes (or --dry-run to only build + estimate)."
+        ));
+    }
+
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != dep.chain_id {
+        return Err(anyhow!(
+            "chainId mismatch: deployment has {}, RPC returned {}",
+            dep.chain_id,
+            chain_id
+        ));
+    }
+
+    let entrypoint =
+        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
+    let factory_addr =
+        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+
+    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
+    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
+
+    if owner == args.new_owner_address {
+        return Err(anyhow!(
+            "--new-owner-address is already the current owner ({owner})"
         ));
     }
-    if !plan_active {
-        return Err(anyhow!("plan {} is inactive on-chain", dep.plan_id));
+
+    if mode == StdoutMode::OwnerAddress {
+        println!("{}", owner);
+    }
+
+    if let Some(p) = owner_env_path.as_ref() {
+        report_generated_owner_key(mode, p);
     }
 
+    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+
     let salt = U256::from(args.common.salt);
     let (account, deployed) =
         compute_account_address(client.clone(), factory_addr, owner, salt).await?;
 
-    // Machine mode: allow scripts to capture the smart account address without parsing logs.
     if mode == StdoutMode::SmartAccountAddress {
         println!("{}", account);
     }
 
-    // Script-friendly JSON: print once to stdout early (before any long-running bundler calls).
     if mode == StdoutMode::Json {
         let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
         let out = serde_json::json!({
@@ -639,59 +2168,25 @@ async fn cmd_subscribe(args: SubscribeArgs) -> Result<()> {
         deployed
     );
 
-    // Optional funding for prefund.
-    if let Some(eth) = args.fund_eth.clone() {
-        let amount_wei = ethers::utils::parse_ether(eth.clone())
-            .with_context(|| format!("invalid --fund-eth value: {eth}"))?;
-        fund_account_eth(client.clone(), account, amount_wei).await?;
-    }
-
-    // Optional mint amount (demo-only token).
-    //
-    // Important: this is now executed *inside the UserOperation* (as part of the executeBatch call),
-    // so it can be sponsored by a paymaster in Milestone 6B.
-    //
-    // This only works for the repo's MockERC20, which has an unrestricted `mint(address,uint256)`.
-    let mint_amount: Option<U256> = if let Some(mint_amount) = args.mint.clone() {
-        let amt = U256::from_dec_str(&mint_amount)
-            .with_context(|| format!("invalid --mint amount (expected integer): {mint_amount}"))?;
-        if amt.is_zero() {
-            None
-        } else {
-            Some(amt)
-        }
-    } else {
-        None
-    };
-
-    // Compute allowance.
-    let allowance_amount = if let Some(a) = args.allowance_amount.clone() {
-        U256::from_dec_str(&a)
-            .with_context(|| format!("invalid --allowance-amount (expected integer): {a}"))?
-    } else {
-        plan_price
-            .checked_mul(U256::from(args.allowance_periods))
-            .ok_or_else(|| anyhow!("allowance overflow: price * periods"))?
-    };
+    eprintln!(
+        "WARNING: transferring ownership of {} from {} to {} is irreversible. The account address stays the same, but it is no longer derivable from the new owner + --salt; keep a record of it.",
+        account, owner, args.new_owner_address
+    );
 
-    // Build batched approve + subscribe calldata via account.executeBatch.
-    let (call_data, init_code, nonce) = build_userop_payload(
+    // The account calls the owner update on itself (`onlyOwner` accepts `address(this)`).
+    let (call_data, init_code, nonce) = build_single_call_payload(
         client.clone(),
         entrypoint,
         factory_addr,
-        dep.open_sub,
-        dep.token,
-        dep.plan_id,
         owner,
         salt,
         account,
         deployed,
-        mint_amount,
-        allowance_amount,
+        account,
+        owner_update_calldata,
     )
     .await?;
 
-    let tx_args: TxArgs = (&args).into();
     let got_receipt = send_userop(
         &provider,
         client.clone(),
@@ -703,40 +2198,186 @@ async fn cmd_subscribe(args: SubscribeArgs) -> Result<()> {
         init_code,
         nonce,
         &tx_args,
+        dep.explorer_base_url.as_deref(),
         machine_mode,
     )
+    .await?
+    .is_some();
+
+    if got_receipt {
+        let account_abi =
+            AbiParser::default().parse(&["function owner() view returns (address)"])?;
+        let account_c = Contract::new(account, account_abi, client.clone());
+        let new_owner: Address = account_c
+            .method("owner", ())?
+            .call()
+            .await
+            .context("owner() failed")?;
+        outln!(machine_mode, "owner after change: {}", new_owner);
+        if new_owner != args.new_owner_address {
+            return Err(anyhow!(
+                "userOp succeeded but owner() is {new_owner}, expected {}; check --owner-update-sig",
+                args.new_owner_address
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// `allowance-report`: find the account's Active subscriptions from OpenSub logs, sum what their
+/// next `--periods` renewals charge per token and compare it with OpenSub's allowance there.
+async fn cmd_allowance_report(args: AllowanceReportArgs) -> Result<()> {
+    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+
+    let mode = stdout_mode(&args.common)?;
+    let machine_mode = mode != StdoutMode::Normal;
+
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != dep.chain_id {
+        return Err(anyhow!(
+            "chainId mismatch: deployment has {}, RPC returned {}",
+            dep.chain_id,
+            chain_id
+        ));
+    }
+
+    let factory_addr =
+        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+    let (_wallet, owner, _) = load_or_generate_owner(&args.common, chain_id)?;
+    let client = Arc::new(provider.clone());
+
+    let (account, _deployed) = compute_account_address(
+        client.clone(),
+        factory_addr,
+        owner,
+        U256::from(args.common.salt),
+    )
     .await?;
 
-    if !got_receipt {
-        return Ok(());
+    match mode {
+        StdoutMode::OwnerAddress => println!("{}", owner),
+        StdoutMode::SmartAccountAddress => println!("{}", account),
+        _ => {}
     }
 
-    // Best-effort: print subscription id after receipt.
-    let sub_id = active_subscription_of(client.clone(), dep.open_sub, dep.plan_id, account).await?;
-    outln!(
-        machine_mode,
-        "\nactiveSubscriptionOf(planId={}, account={}) => {}",
-        dep.plan_id,
+    let subs = subscriptions_of(
+        client.clone(),
+        dep.open_sub,
         account,
-        sub_id
-    );
+        dep.start_block,
+        args.log_chunk,
+    )
+    .await?;
 
-    let has_access = has_access(client.clone(), dep.open_sub, sub_id)
-        .await
-        .unwrap_or(false);
-    outln!(machine_mode, "hasAccess({}) => {}", sub_id, has_access);
+    // Only Active subscriptions renew; NonRenewing/Cancelled ones will never be charged again.
+    let mut charges = Vec::new();
+    for sub in subs
+        .iter()
+        .filter(|s| s.status == SUBSCRIPTION_STATUS_ACTIVE)
+    {
+        let PlanInfo {
+            token,
+            price,
+            active,
+            ..
+        } = read_plan(client.clone(), dep.open_sub, sub.plan_id).await?;
+        if !active {
+            outln!(
+                machine_mode,
+                "skipping subscription {} (plan {} is inactive)",
+                sub.id,
+                sub.plan_id
+            );
+            continue;
+        }
+        charges.push(UpcomingCharge {
+            subscription_id: sub.id,
+            plan_id: sub.plan_id,
+            token,
+            price,
+            paid_through: sub.paid_through,
+        });
+    }
+
+    let erc20_abi = AbiParser::default()
+        .parse(&["function allowance(address owner, address spender) view returns (uint256)"])?;
+    let mut allowances = BTreeMap::new();
+    for token in charges.iter().map(|c| c.token).collect::<BTreeSet<_>>() {
+        let erc20 = Contract::new(token, erc20_abi.clone(), client.clone());
+        let allowance: U256 = erc20
+            .method("allowance", (account, dep.open_sub))?
+            .call()
+            .await
+            .with_context(|| format!("allowance() failed for token {token:?}"))?;
+        allowances.insert(token, allowance);
+    }
+
+    let rows = project_allowance(charges, &allowances, args.periods);
+
+    if mode == StdoutMode::Json {
+        let out: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|r| {
+                serde_json::json!({
+                    "subscriptionId": r.charge.subscription_id.to_string(),
+                    "planId": r.charge.plan_id.to_string(),
+                    "token": encoding::fmt_address(r.charge.token),
+                    "price": r.charge.price.to_string(),
+                    "paidThrough": r.charge.paid_through,
+                    "periods": args.periods,
+                    "required": r.required.to_string(),
+                    "allowance": r.allowance.to_string(),
+                    "shortfall": r.shortfall.to_string(),
+                    "suggestedApprove": r.suggested_approve.to_string(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(out));
+    }
+
+    outln!(machine_mode, "smartAccount:   {}", account);
+    outln!(machine_mode, "periods:        {}", args.periods);
+    if rows.is_empty() {
+        outln!(machine_mode, "no active subscriptions");
+    }
+    for r in &rows {
+        outln!(
+            machine_mode,
+            "subscription {} (plan {}): token={} required={} shortfall={}",
+            r.charge.subscription_id,
+            r.charge.plan_id,
+            r.charge.token,
+            r.required,
+            r.shortfall
+        );
+    }
+    let mut seen = BTreeSet::new();
+    for r in rows.iter().filter(|r| seen.insert(r.charge.token)) {
+        outln!(
+            machine_mode,
+            "token {}: allowance={} suggested approve={}",
+            r.charge.token,
+            r.allowance,
+            r.suggested_approve
+        );
+    }
 
     Ok(())
 }
 
-async fn cmd_cancel(args: CancelArgs) -> Result<()> {
+async fn cmd_bulk_approve(args: BulkApproveArgs) -> Result<()> {
+    let approvals = pair_approvals(&args.tokens, &args.amounts)?;
     let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+    let calls = bulk_approve_calls(dep.open_sub, &approvals, &args.approve_sig)?;
+    let tx_args = TxArgs::from(&args).with_deployment(&dep)?;
 
     let mode = stdout_mode(&args.common)?;
     let machine_mode = mode != StdoutMode::Normal;
 
-    let provider =
-        Provider::<Http>::try_from(dep.rpc_url.as_str())?.interval(Duration::from_millis(350));
+    let provider = connect_provider(&dep.rpc_url, &args.common.resolve)?;
 
     let chain_id = provider.get_chainid().await?.as_u64();
     if chain_id != dep.chain_id {
@@ -760,22 +2401,7 @@ async fn cmd_cancel(args: CancelArgs) -> Result<()> {
     }
 
     if let Some(p) = owner_env_path.as_ref() {
-        match mode {
-            StdoutMode::OwnerEnvPath => {
-                println!("{}", p.display());
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            StdoutMode::Json => {
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            _ => {
-                outln!(
-                    machine_mode,
-                    "generated new owner key; saved to {}",
-                    p.display()
-                );
-            }
-        }
+        report_generated_owner_key(mode, p);
     }
 
     let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
@@ -804,17 +2430,16 @@ async fn cmd_cancel(args: CancelArgs) -> Result<()> {
         account,
         deployed
     );
+    for (token, amount) in &approvals {
+        outln!(
+            machine_mode,
+            "approve OpenSub on token {}: {}",
+            token,
+            amount
+        );
+    }
 
-    let sub_id = U256::from(args.subscription_id);
-    let open_sub_abi = AbiParser::default()
-        .parse(&["function cancel(uint256 subscriptionId, bool atPeriodEnd)"])?;
-    let open_sub = Contract::new(dep.open_sub, open_sub_abi, client.clone());
-    let cancel_calldata = open_sub
-        .method::<_, ()>("cancel", (sub_id, args.at_period_end))?
-        .calldata()
-        .ok_or_else(|| anyhow!("failed to build cancel calldata"))?;
-
-    let (call_data, init_code, nonce) = build_single_call_payload(
+    let (call_data, init_code, nonce) = build_batch_call_payload(
         client.clone(),
         entrypoint,
         factory_addr,
@@ -822,13 +2447,11 @@ async fn cmd_cancel(args: CancelArgs) -> Result<()> {
         salt,
         account,
         deployed,
-        dep.open_sub,
-        cancel_calldata,
+        &calls,
     )
     .await?;
 
-    let tx_args: TxArgs = (&args).into();
-    let _got_receipt = send_userop(
+    let got_receipt = send_userop(
         &provider,
         client.clone(),
         &wallet,
@@ -839,21 +2462,81 @@ async fn cmd_cancel(args: CancelArgs) -> Result<()> {
         init_code,
         nonce,
         &tx_args,
+        dep.explorer_base_url.as_deref(),
         machine_mode,
     )
-    .await?;
+    .await?
+    .is_some();
+
+    if got_receipt {
+        let erc20_abi = AbiParser::default().parse(&[
+            "function allowance(address owner, address spender) view returns (uint256)",
+        ])?;
+        for (token, _) in &approvals {
+            let erc20 = Contract::new(*token, erc20_abi.clone(), client.clone());
+            let allowance: U256 = erc20
+                .method("allowance", (account, dep.open_sub))?
+                .call()
+                .await
+                .context("allowance() failed")?;
+            outln!(
+                machine_mode,
+                "allowance now: {} (token {})",
+                allowance,
+                token
+            );
+        }
+    }
 
     Ok(())
 }
 
-async fn cmd_resume(args: ResumeArgs) -> Result<()> {
-    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+/// Pair the n-th `--token` with the n-th `--amount` (decimal integers).
+fn pair_approvals(tokens: &[Address], amounts: &[String]) -> Result<Vec<(Address, U256)>> {
+    if tokens.len() != amounts.len() {
+        return Err(anyhow!(
+            "got {} --token but {} --amount values; pass one --amount per --token",
+            tokens.len(),
+            amounts.len()
+        ));
+    }
+    tokens
+        .iter()
+        .zip(amounts)
+        .map(|(token, amount)| {
+            let amount = U256::from_dec_str(amount).with_context(|| {
+                format!("invalid --amount for token {token} (expected integer): {amount}")
+            })?;
+            Ok((*token, amount))
+        })
+        .collect()
+}
 
-    let mode = stdout_mode(&args.common)?;
-    let machine_mode = mode != StdoutMode::Normal;
+/// One `approve(openSub, amount)` per token, in the order given, for `bulk-approve`.
+fn bulk_approve_calls(
+    open_sub: Address,
+    approvals: &[(Address, U256)],
+    approve_sig: &str,
+) -> Result<Vec<(Address, Bytes)>> {
+    if approvals.is_empty() {
+        return Err(anyhow!("bulk-approve needs at least one --token"));
+    }
+    let mut seen = BTreeSet::new();
+    if let Some((dup, _)) = approvals.iter().find(|(token, _)| !seen.insert(*token)) {
+        return Err(anyhow!(
+            "token {dup} is listed more than once; later approvals would overwrite earlier ones"
+        ));
+    }
+
+    approvals
+        .iter()
+        .map(|(token, amount)| Ok((*token, encode_approve(approve_sig, open_sub, *amount)?)))
+        .collect()
+}
 
-    let provider =
-        Provider::<Http>::try_from(dep.rpc_url.as_str())?.interval(Duration::from_millis(350));
+async fn cmd_access_at(args: AccessAtArgs) -> Result<()> {
+    let dep = load_deployment(&args.deployment, args.rpc.clone())?;
+    let provider = connect_provider(&dep.rpc_url, &args.resolve)?;
 
     let chain_id = provider.get_chainid().await?.as_u64();
     if chain_id != dep.chain_id {
@@ -864,113 +2547,415 @@ async fn cmd_resume(args: ResumeArgs) -> Result<()> {
         ));
     }
 
-    let entrypoint =
-        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
-    let factory_addr =
-        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+    let head = provider
+        .get_block_number()
+        .await
+        .context("eth_blockNumber failed")?
+        .as_u64();
+    if args.at_block > head {
+        return Err(anyhow!(
+            "--at-block {} is past the chain head ({head})",
+            args.at_block
+        ));
+    }
+    let block = provider
+        .get_block(args.at_block)
+        .await
+        .with_context(|| format!("eth_getBlockByNumber({}) failed", args.at_block))?
+        .ok_or_else(|| anyhow!("block {} not found", args.at_block))?;
 
-    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
-    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
+    let client = Arc::new(provider);
+    let at = Some(BlockNumber::Number(args.at_block.into()));
+    let id = U256::from(args.subscription_id);
+    let sub = read_subscription(client.clone(), dep.open_sub, id, at)
+        .await
+        .map_err(|e| historical_read_error(e, args.at_block))?;
+    let access = has_access(client, dep.open_sub, id, at)
+        .await
+        .map_err(|e| historical_read_error(e, args.at_block))?;
 
-    if mode == StdoutMode::OwnerAddress {
-        println!("{}", owner);
+    if args.json {
+        let out = serde_json::json!({
+            "block": args.at_block,
+            "blockTimestamp": block.timestamp.as_u64(),
+            "subscriptionId": args.subscription_id,
+            "planId": sub.plan_id.to_string(),
+            "status": subscription_status_name(sub.status),
+            "paidThrough": sub.paid_through,
+            "hasAccess": access,
+        });
+        println!("{}", out);
+    } else {
+        println!(
+            "at block {} (timestamp {}):",
+            args.at_block,
+            block.timestamp.as_u64()
+        );
+        println!(
+            "subscription {}: plan={} status={} paidThrough={}",
+            args.subscription_id,
+            sub.plan_id,
+            subscription_status_name(sub.status),
+            sub.paid_through
+        );
+        println!("hasAccess({}) => {}", args.subscription_id, access);
     }
 
-    if let Some(p) = owner_env_path.as_ref() {
-        match mode {
-            StdoutMode::OwnerEnvPath => {
-                println!("{}", p.display());
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            StdoutMode::Json => {
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            _ => {
-                outln!(
-                    machine_mode,
-                    "generated new owner key; saved to {}",
-                    p.display()
-                );
-            }
+    Ok(())
+}
+
+/// What `verify` read from the chain. `None` where the read failed.
+#[derive(Debug, Clone)]
+struct DeploymentFacts {
+    chain_id: Option<u64>,
+    open_sub_has_code: Option<bool>,
+    plan: Option<PlanInfo>,
+    token_decimals: Option<u8>,
+}
+
+/// One line of the `verify` report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VerifyCheck {
+    name: &'static str,
+    ok: bool,
+    detail: String,
+}
+
+impl VerifyCheck {
+    fn new(name: &'static str, ok: bool, detail: String) -> Self {
+        Self { name, ok, detail }
+    }
+}
+
+/// Compare the deployment with what the chain reports. A failed read fails its check.
+fn verify_checks(dep: &Deployment, facts: &DeploymentFacts) -> Vec<VerifyCheck> {
+    let unreadable = |what: &str| format!("could not read {what}");
+    let mut checks = Vec::new();
+
+    checks.push(match facts.chain_id {
+        Some(id) => VerifyCheck::new(
+            "chainId",
+            id == dep.chain_id,
+            format!("deployment {}, RPC {}", dep.chain_id, id),
+        ),
+        None => VerifyCheck::new("chainId", false, unreadable("eth_chainId")),
+    });
+
+    checks.push(match facts.open_sub_has_code {
+        Some(has_code) => VerifyCheck::new(
+            "openSub code",
+            has_code,
+            if has_code {
+                format!("{:?} has code", dep.open_sub)
+            } else {
+                format!("no code at {:?}", dep.open_sub)
+            },
+        ),
+        None => VerifyCheck::new("openSub code", false, unreadable("eth_getCode")),
+    });
+
+    match &facts.plan {
+        Some(plan) if plan.merchant.is_zero() => {
+            checks.push(VerifyCheck::new(
+                "plan",
+                false,
+                format!("plan {} does not exist", dep.plan_id),
+            ));
+        }
+        Some(plan) => {
+            checks.push(VerifyCheck::new(
+                "plan",
+                plan.active,
+                format!(
+                    "plan {} {}",
+                    dep.plan_id,
+                    if plan.active {
+                        "is active"
+                    } else {
+                        "is inactive"
+                    }
+                ),
+            ));
+            checks.push(VerifyCheck::new(
+                "plan token",
+                plan.token == dep.token,
+                format!("deployment {:?}, plan {:?}", dep.token, plan.token),
+            ));
         }
+        None => checks.push(VerifyCheck::new(
+            "plan",
+            false,
+            unreadable(&format!("plans({})", dep.plan_id)),
+        )),
     }
 
-    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+    checks.push(match facts.token_decimals {
+        Some(decimals) => VerifyCheck::new(
+            "decimals",
+            decimals == dep.decimals,
+            format!("deployment {}, token decimals() {}", dep.decimals, decimals),
+        ),
+        None => VerifyCheck::new("decimals", false, unreadable("decimals()")),
+    });
 
-    let salt = U256::from(args.common.salt);
-    let (account, deployed) =
-        compute_account_address(client.clone(), factory_addr, owner, salt).await?;
+    checks
+}
 
-    if mode == StdoutMode::SmartAccountAddress {
-        println!("{}", account);
+async fn cmd_verify(args: VerifyArgs) -> Result<()> {
+    let dep = load_deployment(&args.deployment, args.rpc.clone())?;
+    let provider = Arc::new(connect_provider(&dep.rpc_url, &args.resolve)?);
+
+    let chain_id = match provider.get_chainid().await {
+        Ok(id) => Some(id.as_u64()),
+        Err(err) => {
+            tracing::warn!("eth_chainId failed: {err}");
+            None
+        }
+    };
+    let open_sub_has_code = match provider.get_code(dep.open_sub, None).await {
+        Ok(code) => Some(!code.as_ref().is_empty()),
+        Err(err) => {
+            tracing::warn!("eth_getCode({:?}) failed: {err}", dep.open_sub);
+            None
+        }
+    };
+    let plan = match read_plan(provider.clone(), dep.open_sub, dep.plan_id).await {
+        Ok(plan) => Some(plan),
+        Err(err) => {
+            tracing::warn!("plans({}) failed: {err:#}", dep.plan_id);
+            None
+        }
+    };
+    let decimals_abi = AbiParser::default().parse(&["function decimals() view returns (uint8)"])?;
+    let token = Contract::new(dep.token, decimals_abi, provider);
+    let token_decimals = match token.method::<_, u8>("decimals", ())?.call().await {
+        Ok(d) => Some(d),
+        Err(err) => {
+            tracing::warn!("decimals() on {:?} failed: {err}", dep.token);
+            None
+        }
+    };
+
+    let checks = verify_checks(
+        &dep,
+        &DeploymentFacts {
+            chain_id,
+            open_sub_has_code,
+            plan,
+            token_decimals,
+        },
+    );
+    let failed = checks.iter().filter(|c| !c.ok).count();
+
+    if args.json {
+        let out = serde_json::json!({
+            "deployment": args.deployment.display().to_string(),
+            "ok": failed == 0,
+            "checks": checks
+                .iter()
+                .map(|c| serde_json::json!({ "check": c.name, "ok": c.ok, "detail": c.detail }))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", out);
+    } else {
+        println!("verify {}:", args.deployment.display());
+        for c in &checks {
+            println!(
+                "  [{}] {}: {}",
+                if c.ok { "pass" } else { "FAIL" },
+                c.name,
+                c.detail
+            );
+        }
     }
 
-    if mode == StdoutMode::Json {
-        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
+    if failed > 0 {
+        return Err(anyhow!(
+            "{failed} of {} deployment checks failed",
+            checks.len()
+        ));
+    }
+    Ok(())
+}
+
+fn cmd_decode_paymaster_data(args: DecodePaymasterDataArgs) -> Result<()> {
+    let hex_str = args.data.trim();
+    let blob = hex::decode(hex_str.strip_prefix("0x").unwrap_or(hex_str))
+        .context("invalid --data (expected 0x-prefixed hex)")?;
+    let decoded = paymaster::decode_paymaster_and_data(&blob, args.entrypoint_version)?;
+
+    if args.json {
         let out = serde_json::json!({
-            "owner": encoding::fmt_address(owner),
-            "smartAccount": encoding::fmt_address(account),
-            "envPath": env_path,
+            "entryPointVersion": args.entrypoint_version.to_string(),
+            "paymaster": encoding::fmt_address(decoded.paymaster),
+            "paymasterVerificationGasLimit": decoded.verification_gas_limit.map(|g| g.to_string()),
+            "paymasterPostOpGasLimit": decoded.post_op_gas_limit.map(|g| g.to_string()),
+            "paymasterData": encoding::fmt_bytes(&decoded.paymaster_data),
+            "validUntil": decoded.verifying.as_ref().map(|v| v.valid_until),
+            "validAfter": decoded.verifying.as_ref().map(|v| v.valid_after),
+            "signature": decoded.verifying.as_ref().map(|v| encoding::fmt_bytes(&v.signature)),
         });
         println!("{}", out);
+        return Ok(());
     }
 
-    outln!(
-        machine_mode,
-        "smartAccount: {} (deployed={})",
-        account,
-        deployed
+    println!(
+        "paymasterAndData ({} bytes, EntryPoint {}):",
+        blob.len(),
+        args.entrypoint_version
+    );
+    println!("  paymaster:                     {:?}", decoded.paymaster);
+    if let (Some(verification), Some(post_op)) =
+        (decoded.verification_gas_limit, decoded.post_op_gas_limit)
+    {
+        println!("  paymasterVerificationGasLimit: {verification}");
+        println!("  paymasterPostOpGasLimit:       {post_op}");
+    }
+    println!(
+        "  paymasterData:                 {} ({} bytes)",
+        encoding::fmt_bytes(&decoded.paymaster_data),
+        decoded.paymaster_data.len()
     );
+    match decoded.verifying {
+        Some(v) => {
+            println!("  as VerifyingPaymaster data:");
+            println!("    validUntil:                  {}", v.valid_until);
+            println!("    validAfter:                  {}", v.valid_after);
+            println!(
+                "    signature:                   {} ({} bytes)",
+                encoding::fmt_bytes(&v.signature),
+                v.signature.len()
+            );
+        }
+        None => println!(
+            "  (paymasterData is not in the VerifyingPaymaster validUntil/validAfter/signature layout)"
+        ),
+    }
+    Ok(())
+}
 
-    let sub_id = U256::from(args.subscription_id);
-    let open_sub_abi =
-        AbiParser::default().parse(&["function unscheduleCancel(uint256 subscriptionId)"])?;
-    let open_sub = Contract::new(dep.open_sub, open_sub_abi, client.clone());
-    let resume_calldata = open_sub
-        .method::<_, ()>("unscheduleCancel", (sub_id,))?
-        .calldata()
-        .ok_or_else(|| anyhow!("failed to build unscheduleCancel calldata"))?;
+async fn cmd_userop_hash(args: UseropHashArgs) -> Result<()> {
+    let raw = if args.op.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin()).context("failed to read the userOp from stdin")?
+    } else {
+        std::fs::read_to_string(&args.op)
+            .with_context(|| format!("failed to read {}", args.op.display()))?
+    };
+    let json: serde_json::Value = serde_json::from_str(&raw).context("invalid userOp JSON")?;
+    let op = encoding::user_op_from_json(&json)?;
+    let entrypoint = args
+        .entrypoint
+        .unwrap_or_else(|| args.entrypoint_version.canonical_address());
+
+    let (hash, chain_id) = if args.offline {
+        let chain_id = match args.chain_id {
+            Some(id) => id,
+            None => load_deployment(&args.deployment, args.rpc.clone())?.chain_id,
+        };
+        let hash =
+            userop::user_op_hash_offline(entrypoint, chain_id, args.entrypoint_version, &op)?;
+        (hash, chain_id)
+    } else {
+        let dep = load_deployment(&args.deployment, args.rpc.clone())?;
+        let provider = Arc::new(connect_provider(&dep.rpc_url, &args.resolve)?);
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let hash = userop::user_op_hash(provider, entrypoint, args.entrypoint_version, &op).await?;
+        (hash, chain_id)
+    };
 
-    let (call_data, init_code, nonce) = build_single_call_payload(
-        client.clone(),
-        entrypoint,
-        factory_addr,
-        owner,
-        salt,
-        account,
-        deployed,
-        dep.open_sub,
-        resume_calldata,
-    )
-    .await?;
+    if args.json {
+        let out = serde_json::json!({
+            "userOpHash": encoding::fmt_h256(hash),
+            "entryPoint": encoding::fmt_address(entrypoint),
+            "entryPointVersion": args.entrypoint_version.to_string(),
+            "chainId": chain_id,
+            "source": if args.offline { "offline" } else { "entryPoint" },
+        });
+        println!("{}", out);
+        return Ok(());
+    }
 
-    let tx_args: TxArgs = (&args).into();
-    let _got_receipt = send_userop(
-        &provider,
-        client.clone(),
-        &wallet,
+    println!("{}", encoding::fmt_h256(hash));
+    eprintln!(
+        "userOpHash for EntryPoint {} {:?} on chain {} ({})",
+        args.entrypoint_version,
         entrypoint,
         chain_id,
-        account,
-        call_data,
-        init_code,
-        nonce,
-        &tx_args,
-        machine_mode,
-    )
-    .await?;
-
+        if args.offline {
+            "computed offline"
+        } else {
+            "from getUserOpHash"
+        }
+    );
     Ok(())
 }
 
-async fn cmd_collect(args: CollectArgs) -> Result<()> {
-    let dep = load_deployment(&args.common.deployment, args.common.rpc.clone())?;
+async fn cmd_plan_info(args: PlanInfoArgs) -> Result<()> {
+    let dep = load_deployment(&args.deployment, args.rpc.clone())?;
+    let provider = connect_provider(&dep.rpc_url, &args.resolve)?;
 
-    let mode = stdout_mode(&args.common)?;
-    let machine_mode = mode != StdoutMode::Normal;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != dep.chain_id {
+        return Err(anyhow!(
+            "chainId mismatch: deployment has {}, RPC returned {}",
+            dep.chain_id,
+            chain_id
+        ));
+    }
+
+    let plan_id = args.plan_id.map(U256::from).unwrap_or(dep.plan_id);
+    let plan = read_plan(Arc::new(provider), dep.open_sub, plan_id).await?;
+    if plan.merchant.is_zero() {
+        return Err(anyhow!("plan {plan_id} does not exist"));
+    }
+    if plan.token != dep.token {
+        tracing::warn!(
+            "plan {} charges token {}, not the deployment token {}; price assumes {} decimals",
+            plan_id,
+            plan.token,
+            dep.token,
+            dep.decimals
+        );
+    }
+    let price = format_token_amount(plan.price, dep.decimals);
+
+    if args.json {
+        let out = serde_json::json!({
+            "planId": plan_id.to_string(),
+            "merchant": format!("{:?}", plan.merchant),
+            "token": format!("{:?}", plan.token),
+            "decimals": dep.decimals,
+            "price": plan.price.to_string(),
+            "priceFormatted": price,
+            "interval": plan.interval,
+            "collectorFeeBps": plan.collector_fee_bps,
+            "active": plan.active,
+            "createdAt": plan.created_at,
+        });
+        println!("{}", out);
+    } else {
+        println!("plan {}:", plan_id);
+        println!("  merchant: {:?}", plan.merchant);
+        println!("  token: {:?} ({} decimals)", plan.token, dep.decimals);
+        println!(
+            "  price: {} per {}s ({} base units)",
+            price, plan.interval, plan.price
+        );
+        println!(
+            "  collectorFeeBps: {} ({}%)",
+            plan.collector_fee_bps,
+            format_token_amount(U256::from(plan.collector_fee_bps), 2)
+        );
+        println!("  active: {}", plan.active);
+        println!("  createdAt: {}", plan.created_at);
+    }
+
+    Ok(())
+}
 
-    let provider =
-        Provider::<Http>::try_from(dep.rpc_url.as_str())?.interval(Duration::from_millis(350));
+async fn cmd_quote(args: QuoteArgs) -> Result<()> {
+    let dep = load_deployment(&args.deployment, args.rpc.clone())?;
+    let provider = connect_provider(&dep.rpc_url, &args.resolve)?;
 
     let chain_id = provider.get_chainid().await?.as_u64();
     if chain_id != dep.chain_id {
@@ -981,103 +2966,274 @@ async fn cmd_collect(args: CollectArgs) -> Result<()> {
         ));
     }
 
-    let entrypoint =
-        Address::from_str(&args.common.entrypoint).context("invalid --entrypoint address")?;
-    let factory_addr =
-        Address::from_str(&args.common.factory).context("invalid --factory address")?;
+    let plan_id = args.plan_id.map(U256::from).unwrap_or(dep.plan_id);
+    let plan = read_plan(Arc::new(provider), dep.open_sub, plan_id).await?;
+    if plan.merchant.is_zero() {
+        return Err(anyhow!("plan {plan_id} does not exist"));
+    }
+    if plan.token != dep.token {
+        tracing::warn!(
+            "plan {} charges token {}, not the deployment token {}; quote assumes {} decimals",
+            plan_id,
+            plan.token,
+            dep.token,
+            dep.decimals
+        );
+    }
+    let per_30_days = cost_per_30_days(plan.price, plan.interval)
+        .with_context(|| format!("cannot quote plan {plan_id}"))?;
+    let price = format_token_amount(plan.price, dep.decimals);
+    let per_30_days_formatted = format_token_amount(per_30_days, dep.decimals);
 
-    let (wallet, owner, owner_key_path) = load_or_generate_owner(&args.common, chain_id)?;
-    let owner_env_path = owner_key_path.map(|p| p.canonicalize().unwrap_or(p));
+    if args.json {
+        let out = serde_json::json!({
+            "planId": plan_id.to_string(),
+            "token": format!("{:?}", plan.token),
+            "decimals": dep.decimals,
+            "price": plan.price.to_string(),
+            "priceFormatted": price,
+            "interval": plan.interval,
+            "costPer30Days": per_30_days.to_string(),
+            "costPer30DaysFormatted": per_30_days_formatted,
+            "active": plan.active,
+        });
+        println!("{}", out);
+    } else {
+        println!(
+            "plan {} costs {} tokens every {} = {} per 30 days",
+            plan_id,
+            price,
+            format_interval(plan.interval),
+            per_30_days_formatted
+        );
+        if !plan.active {
+            println!("note: plan {} is inactive (no new subscriptions)", plan_id);
+        }
+    }
 
-    if mode == StdoutMode::OwnerAddress {
-        println!("{}", owner);
+    Ok(())
+}
+
+const THIRTY_DAYS: u64 = 30 * 86_400;
+
+/// `price` charged every `interval` seconds, scaled to 30 days (rounded down to a base unit).
+/// Intervals longer than 30 days are prorated the same way, e.g. a yearly price becomes 30/365 of it.
+fn cost_per_30_days(price: U256, interval: u64) -> Result<U256> {
+    if interval == 0 {
+        return Err(anyhow!("plan interval is zero"));
     }
+    let scaled = price.full_mul(U256::from(THIRTY_DAYS)) / U256::from(interval);
+    U256::try_from(scaled).map_err(|_| anyhow!("cost per 30 days overflows uint256"))
+}
 
-    if let Some(p) = owner_env_path.as_ref() {
-        match mode {
-            StdoutMode::OwnerEnvPath => {
-                println!("{}", p.display());
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            StdoutMode::Json => {
-                eprintln!("generated new owner key; saved to {}", p.display());
-            }
-            _ => {
-                outln!(
-                    machine_mode,
-                    "generated new owner key; saved to {}",
-                    p.display()
-                );
+/// `interval` seconds as whole days when it is a whole number of days (`30 days`), else seconds.
+fn format_interval(interval: u64) -> String {
+    match (interval / 86_400, interval % 86_400) {
+        (1, 0) => "1 day".to_string(),
+        (days, 0) => format!("{days} days"),
+        _ => format!("{interval}s"),
+    }
+}
+
+/// `amount` base units as a decimal string with `decimals` places, trailing zeros trimmed
+/// (`1500000` with 6 decimals is `"1.5"`).
+fn format_token_amount(amount: U256, decimals: u8) -> String {
+    let digits = amount.to_string();
+    let decimals = usize::from(decimals);
+    if decimals == 0 {
+        return digits;
+    }
+    let digits = format!("{digits:0>width$}", width = decimals + 1);
+    let (whole, frac) = digits.split_at(digits.len() - decimals);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{whole}.{frac}")
+    }
+}
+
+/// Whether an RPC error means the node no longer holds the state for the requested block.
+fn is_missing_historical_state(err: &str) -> bool {
+    const MARKERS: [&str; 6] = [
+        "missing trie node",
+        "header not found",
+        "historical state",
+        "state is not available",
+        "pruned",
+        "archive",
+    ];
+    let err = err.to_ascii_lowercase();
+    MARKERS.iter().any(|m| err.contains(m))
+}
+
+/// Turn a pruned-state error for a `--at-block` read into an actionable one.
+fn historical_read_error(err: anyhow::Error, block: u64) -> anyhow::Error {
+    if is_missing_historical_state(&format!("{err:#}")) {
+        anyhow!(
+            "the RPC cannot serve state at block {block} (pruned); use an archive node via --rpc: {err:#}"
+        )
+    } else {
+        err
+    }
+}
+
+/// `SubscriptionStatus.Active` in OpenSub.sol.
+const SUBSCRIPTION_STATUS_ACTIVE: u8 = 1;
+
+#[derive(Debug, Clone)]
+struct SubscriptionInfo {
+    id: U256,
+    plan_id: U256,
+    status: u8,
+    paid_through: u64,
+}
+
+/// Enumerate every subscription `subscriber` ever opened (via `Subscribed` logs) with its current
+/// on-chain state.
+async fn subscriptions_of<M: Middleware + 'static>(
+    client: Arc<M>,
+    open_sub: Address,
+    subscriber: Address,
+    from_block: u64,
+    log_chunk: u64,
+) -> Result<Vec<SubscriptionInfo>> {
+    let head = client
+        .get_block_number()
+        .await
+        .context("eth_blockNumber failed")?
+        .as_u64();
+
+    let mut ids = BTreeSet::new();
+    let mut start = from_block;
+    while start <= head {
+        let end = head.min(start.saturating_add(log_chunk.max(1) - 1));
+        let filter = Filter::new()
+            .address(open_sub)
+            .event("Subscribed(uint256,uint256,address,uint40,uint40)")
+            .topic3(H256::from(subscriber))
+            .from_block(start)
+            .to_block(end);
+        let logs = client
+            .get_logs(&filter)
+            .await
+            .with_context(|| format!("eth_getLogs failed for blocks {start}..={end}"))?;
+        for log in logs {
+            if let Some(id) = log.topics.get(1) {
+                ids.insert(U256::from_big_endian(id.as_bytes()));
             }
         }
+        start = end + 1;
     }
 
-    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+    let mut out = Vec::with_capacity(ids.len());
+    for id in ids {
+        out.push(read_subscription(client.clone(), open_sub, id, None).await?);
+    }
+    Ok(out)
+}
 
-    let salt = U256::from(args.common.salt);
-    let (account, deployed) =
-        compute_account_address(client.clone(), factory_addr, owner, salt).await?;
+/// `subscriptions(id)` at `block` (latest when `None`).
+async fn read_subscription<M: Middleware + 'static>(
+    client: Arc<M>,
+    open_sub: Address,
+    id: U256,
+    block: Option<BlockNumber>,
+) -> Result<SubscriptionInfo> {
+    let abi = AbiParser::default().parse(&[
+        "function subscriptions(uint256) view returns (uint256 planId,address subscriber,uint8 status,uint40 startTime,uint40 paidThrough,uint40 lastChargedAt)",
+    ])?;
+    let open_sub = Contract::new(open_sub, abi, client);
 
-    if mode == StdoutMode::SmartAccountAddress {
-        println!("{}", account);
+    let mut call = open_sub.method("subscriptions", id)?;
+    if let Some(block) = block {
+        call = call.block(block);
     }
+    let (plan_id, _subscriber, status, _start_time, paid_through, _last_charged_at): (
+        U256,
+        Address,
+        u8,
+        u64,
+        u64,
+        u64,
+    ) = call
+        .call()
+        .await
+        .with_context(|| format!("subscriptions({id}) failed"))?;
+    Ok(SubscriptionInfo {
+        id,
+        plan_id,
+        status,
+        paid_through,
+    })
+}
 
-    if mode == StdoutMode::Json {
-        let env_path = owner_env_path.as_ref().map(|p| p.display().to_string());
-        let out = serde_json::json!({
-            "owner": encoding::fmt_address(owner),
-            "smartAccount": encoding::fmt_address(account),
-            "envPath": env_path,
-        });
-        println!("{}", out);
+/// Name of an OpenSub `SubscriptionStatus` value.
+fn subscription_status_name(status: u8) -> &'static str {
+    match status {
+        0 => "None",
+        1 => "Active",
+        2 => "NonRenewing",
+        3 => "Cancelled",
+        _ => "Unknown",
     }
+}
 
-    outln!(
-        machine_mode,
-        "smartAccount: {} (deployed={})",
-        account,
-        deployed
-    );
+/// Max concurrent getAddress/getCode lookups for `account --salt-range`.
+const SALT_RANGE_CONCURRENCY: usize = 8;
 
-    let sub_id = U256::from(args.subscription_id);
-    let open_sub_abi = AbiParser::default()
-        .parse(&["function collect(uint256 subscriptionId) returns (uint256,uint256)"])?;
-    let open_sub = Contract::new(dep.open_sub, open_sub_abi, client.clone());
-    let collect_calldata = open_sub
-        .method::<_, (U256, U256)>("collect", (sub_id,))?
-        .calldata()
-        .ok_or_else(|| anyhow!("failed to build collect calldata"))?;
+/// Upper bound on `--salt-range` size, to avoid accidentally hammering the RPC.
+const MAX_SALT_RANGE: u64 = 10_000;
 
-    let (call_data, init_code, nonce) = build_single_call_payload(
-        client.clone(),
-        entrypoint,
-        factory_addr,
-        owner,
-        salt,
-        account,
-        deployed,
-        dep.open_sub,
-        collect_calldata,
-    )
-    .await?;
+/// Parse an `eth_feeHistory` reward percentile (0-100).
+fn parse_percentile(s: &str) -> std::result::Result<f64, String> {
+    let p: f64 = s
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid percentile '{s}': {e}"))?;
+    if !(0.0..=100.0).contains(&p) {
+        return Err(format!("percentile {p} is outside 0-100"));
+    }
+    Ok(p)
+}
 
-    let tx_args: TxArgs = (&args).into();
-    let _got_receipt = send_userop(
-        &provider,
-        client.clone(),
-        &wallet,
-        entrypoint,
-        chain_id,
-        account,
-        call_data,
-        init_code,
-        nonce,
-        &tx_args,
-        machine_mode,
-    )
-    .await?;
+/// Parse a half-open salt range `<start>..<end>` (e.g. `0..10`).
+fn parse_salt_range(s: &str) -> std::result::Result<Range<u64>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("invalid salt range '{s}' (expected <start>..<end>)"))?;
+    let start: u64 = start
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid salt range start '{start}': {e}"))?;
+    let end: u64 = end
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid salt range end '{end}': {e}"))?;
+    if start >= end {
+        return Err(format!(
+            "empty salt range {start}..{end} (end is exclusive)"
+        ));
+    }
+    if end - start > MAX_SALT_RANGE {
+        return Err(format!(
+            "salt range {start}..{end} is too large (max {MAX_SALT_RANGE} salts)"
+        ));
+    }
+    Ok(start..end)
+}
 
-    Ok(())
+/// Run `f` for each salt with bounded concurrency, returning results in salt order.
+async fn map_salts_ordered<T, F, Fut>(salts: Range<u64>, concurrency: usize, f: F) -> Result<Vec<T>>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    futures::stream::iter(salts)
+        .map(f)
+        .buffered(concurrency.max(1))
+        .try_collect()
+        .await
 }
 
 fn stdout_mode(common: &CommonArgs) -> Result<StdoutMode> {
@@ -1102,8 +3258,10 @@ fn stdout_mode(common: &CommonArgs) -> Result<StdoutMode> {
     }
 
     if common.print_owner_env_path {
-        if !common.new_owner {
-            return Err(anyhow!("--print-owner-env-path requires --new-owner"));
+        if !common.new_owner && common.owner_seed.is_none() {
+            return Err(anyhow!(
+                "--print-owner-env-path requires --new-owner or --owner-seed"
+            ));
         }
         return Ok(StdoutMode::OwnerEnvPath);
     }
@@ -1142,12 +3300,32 @@ fn choose_secrets_dir() -> Result<PathBuf> {
     Ok(cwd.join(".secrets"))
 }
 
-fn write_owner_env_file(path: &PathBuf, owner: Address, private_key_hex: &str) -> Result<()> {
-    let contents = format!(
-        "# Generated by opensub-aa --new-owner\n# DO NOT COMMIT THIS FILE.\nexport OPENSUB_AA_OWNER_PRIVATE_KEY={}\nexport OPENSUB_AA_OWNER_ADDRESS={}\n",
+fn owner_env_contents(
+    owner: Address,
+    private_key_hex: &str,
+    generated_by: &str,
+    format: OwnerEnvFormat,
+) -> String {
+    let export = match format {
+        OwnerEnvFormat::Shell => "export ",
+        OwnerEnvFormat::Dotenv => "",
+    };
+    format!(
+        "# Generated by opensub-aa {}\n# DO NOT COMMIT THIS FILE.\n{export}OPENSUB_AA_OWNER_PRIVATE_KEY={}\n{export}OPENSUB_AA_OWNER_ADDRESS={:?}\n",
+        generated_by,
         private_key_hex,
         owner
-    );
+    )
+}
+
+fn write_owner_env_file(
+    path: &PathBuf,
+    owner: Address,
+    private_key_hex: &str,
+    generated_by: &str,
+    format: OwnerEnvFormat,
+) -> Result<()> {
+    let contents = owner_env_contents(owner, private_key_hex, generated_by, format);
 
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).context("failed to create .secrets dir")?;
@@ -1187,17 +3365,89 @@ fn generate_random_wallet(chain_id: u64) -> Result<(LocalWallet, Address, String
     ))
 }
 
+/// Domain separator for `--owner-seed`, so the derived key is not just `keccak256(seed)`.
+const OWNER_SEED_DOMAIN: &[u8] = b"opensub-aa/owner-seed/v1";
+
+/// Deterministically derive an owner wallet from `seed_hex` (`--owner-seed`).
+///
+/// key = keccak256(domain || seed || counter), bumping the counter in the (negligible) case the
+/// hash is not a valid secp256k1 scalar.
+fn wallet_from_seed(seed_hex: &str, chain_id: u64) -> Result<(LocalWallet, Address, String)> {
+    let seed_hex = seed_hex.trim();
+    let seed = hex::decode(seed_hex.strip_prefix("0x").unwrap_or(seed_hex))
+        .context("invalid --owner-seed (expected hex)")?;
+    if seed.is_empty() {
+        return Err(anyhow!("--owner-seed must not be empty"));
+    }
+
+    for counter in 0u32..64 {
+        let mut preimage = Vec::with_capacity(OWNER_SEED_DOMAIN.len() + seed.len() + 4);
+        preimage.extend_from_slice(OWNER_SEED_DOMAIN);
+        preimage.extend_from_slice(&seed);
+        preimage.extend_from_slice(&counter.to_be_bytes());
+        let key = ethers::utils::keccak256(&preimage);
+
+        if let Ok(wallet) = LocalWallet::from_bytes(&key) {
+            let wallet = wallet.with_chain_id(chain_id);
+            let owner = wallet.address();
+            return Ok((wallet, owner, format!("0x{}", hex::encode(key))));
+        }
+    }
+    Err(anyhow!(
+        "failed to derive a valid private key from --owner-seed"
+    ))
+}
+
+/// Report an owner key generated by [`load_or_generate_owner`]. With `--stdout owner-env-path`
+/// its path is the single stdout line; in machine modes the note goes to stderr.
+fn report_generated_owner_key(mode: StdoutMode, path: &Path) {
+    if mode == StdoutMode::OwnerEnvPath {
+        println!("{}", path.display());
+    }
+    outln!(
+        mode != StdoutMode::Normal,
+        "generated new owner key; saved to {}",
+        path.display()
+    );
+}
+
 fn load_or_generate_owner(
     common: &CommonArgs,
     chain_id: u64,
 ) -> Result<(LocalWallet, Address, Option<PathBuf>)> {
+    if let Some(seed) = common.owner_seed.as_deref() {
+        let (wallet, owner, pk_hex) = wallet_from_seed(seed, chain_id)?;
+        tracing::warn!(
+            %owner,
+            "USING A SEED-DERIVED OWNER KEY: FOR TESTING ONLY. Anyone who knows --owner-seed controls this account; never fund it with real value."
+        );
+
+        let secrets_dir = choose_secrets_dir()?;
+        let fname = format!("aa_owner_{}.env", hex::encode(owner.as_bytes()));
+        let path = secrets_dir.join(fname);
+        write_owner_env_file(
+            &path,
+            owner,
+            &pk_hex,
+            "--owner-seed (TESTING ONLY)",
+            common.owner_env_format,
+        )?;
+        return Ok((wallet, owner, Some(path)));
+    }
+
     if common.new_owner {
         let (wallet, owner, pk_hex) = generate_random_wallet(chain_id)?;
 
         let secrets_dir = choose_secrets_dir()?;
         let fname = format!("aa_owner_{}.env", hex::encode(owner.as_bytes()));
         let path = secrets_dir.join(fname);
-        write_owner_env_file(&path, owner, &pk_hex)?;
+        write_owner_env_file(
+            &path,
+            owner,
+            &pk_hex,
+            "--new-owner",
+            common.owner_env_format,
+        )?;
         return Ok((wallet, owner, Some(path)));
     }
 
@@ -1236,17 +3486,28 @@ async fn compute_account_address<M: Middleware + 'static>(
     Ok((account, !code.as_ref().is_empty()))
 }
 
+#[derive(Debug, Clone)]
+struct PlanInfo {
+    merchant: Address,
+    token: Address,
+    price: U256,
+    interval: u64,
+    collector_fee_bps: u16,
+    active: bool,
+    created_at: u64,
+}
+
 async fn read_plan<M: Middleware + 'static>(
     client: Arc<M>,
     open_sub: Address,
     plan_id: U256,
-) -> Result<(Address, U256, bool)> {
+) -> Result<PlanInfo> {
     let open_sub_abi = AbiParser::default().parse(&[
         "function plans(uint256) view returns (address merchant,address token,uint256 price,uint40 interval,uint16 collectorFeeBps,bool active,uint40 createdAt)",
     ])?;
     let open_sub = Contract::new(open_sub, open_sub_abi, client);
 
-    let (_merchant, token, price, _interval, _fee_bps, active, _created_at): (
+    let (merchant, token, price, interval, collector_fee_bps, active, created_at): (
         Address,
         Address,
         U256,
@@ -1256,24 +3517,103 @@ async fn read_plan<M: Middleware + 'static>(
         u64,
     ) = open_sub.method("plans", plan_id)?.call().await?;
 
-    Ok((token, price, active))
+    Ok(PlanInfo {
+        merchant,
+        token,
+        price,
+        interval,
+        collector_fee_bps,
+        active,
+        created_at,
+    })
 }
 
-async fn fetch_entrypoint_nonce<M: Middleware + 'static>(
-    client: Arc<M>,
-    entrypoint: Address,
-    account: Address,
+/// Obvious plan misconfigurations worth flagging before committing funds: a zero price, an
+/// interval outside `[min_interval, max_interval]` seconds, or a collector fee above 100%.
+fn plan_sanity_issues(plan: &PlanInfo, min_interval: u64, max_interval: u64) -> Vec<String> {
+    let mut issues = Vec::new();
+    if plan.price.is_zero() {
+        issues.push("price is 0".to_string());
+    }
+    if plan.interval < min_interval {
+        issues.push(format!(
+            "interval {}s is below --min-interval {}s",
+            plan.interval, min_interval
+        ));
+    }
+    if plan.interval > max_interval {
+        issues.push(format!(
+            "interval {}s is above --max-interval {}s",
+            plan.interval, max_interval
+        ));
+    }
+    if plan.collector_fee_bps > 10_000 {
+        issues.push(format!(
+            "collectorFeeBps {} exceeds 10000 (100%)",
+            plan.collector_fee_bps
+        ));
+    }
+    issues
+}
+
+/// `--mint` / `--allowance-amount` / `--allowance-periods` values that cannot cover one charge of
+/// `price` (the combined price of the plans being subscribed to). Amounts are raw base units, so
+/// this usually means the token's decimals were left out (`10` instead of `10000000` for 10 USDC).
+fn funding_issues(
+    price: U256,
+    allowance_amount: Option<U256>,
+    allowance_periods: u64,
+    mint: Option<U256>,
+) -> Vec<String> {
+    let mut issues = Vec::new();
+    match allowance_amount {
+        Some(a) if a < price => issues.push(format!(
+            "--allowance-amount {a} is below the plan price {price} (raw base units; check the token's decimals)"
+        )),
+        None if allowance_periods == 0 => issues.push(
+            "--allowance-periods 0 approves less than one period of the plan price".to_string(),
+        ),
+        _ => {}
+    }
+    if let Some(m) = mint.filter(|m| *m < price) {
+        issues.push(format!(
+            "--mint {m} is below the plan price {price} (raw base units; check the token's decimals)"
+        ));
+    }
+    issues
+}
+
+/// `price * (periods + headroom_periods)` summed over the plans' prices.
+fn derived_allowance(
+    prices: impl IntoIterator<Item = U256>,
+    periods: u64,
+    headroom_periods: u64,
 ) -> Result<U256> {
-    let entrypoint_abi = AbiParser::default()
-        .parse(&["function getNonce(address sender, uint192 key) view returns (uint256)"])?;
-    let entrypoint_c = Contract::new(entrypoint, entrypoint_abi, client.clone());
+    let periods = periods
+        .checked_add(headroom_periods)
+        .ok_or_else(|| anyhow!("allowance overflow: periods + headroom"))?;
+    let mut total = U256::zero();
+    for price in prices {
+        let per_plan = price
+            .checked_mul(U256::from(periods))
+            .ok_or_else(|| anyhow!("allowance overflow: price * periods"))?;
+        total = total
+            .checked_add(per_plan)
+            .ok_or_else(|| anyhow!("allowance overflow: sum across plans"))?;
+    }
+    Ok(total)
+}
 
-    let nonce: U256 = entrypoint_c
-        .method("getNonce", (account, U256::zero()))?
-        .call()
-        .await
-        .context("entryPoint.getNonce failed")?;
-    Ok(nonce)
+/// A warning when `allowance` covers at least one period of `price` but fewer than two: the
+/// allowance then runs out exactly as a period is collected, so a late collect or a price rounding
+/// can leave it short. Below one period is a [`funding_issues`] problem instead.
+fn headroom_warning(price: U256, allowance: U256) -> Option<String> {
+    let two_periods = price.saturating_mul(U256::from(2));
+    (!price.is_zero() && allowance >= price && allowance < two_periods).then(|| {
+        format!(
+            "allowance {allowance} covers fewer than 2 periods of the plan price {price}; consider --allowance-headroom-periods 1"
+        )
+    })
 }
 
 async fn build_init_code<M: Middleware + 'static>(
@@ -1314,16 +3654,24 @@ async fn build_userop_payload<M: Middleware + 'static>(
     deployed: bool,
     mint_amount: Option<U256>,
     allowance_amount: U256,
+    native_value: Option<U256>,
+    nonce_key: U256,
+    subscribe_call: &SubscribeCall,
+    approve_sig: &str,
 ) -> Result<(Bytes, Bytes, U256)> {
-    let nonce = fetch_entrypoint_nonce(client.clone(), entrypoint, account).await?;
+    let nonce =
+        userop::fetch_entrypoint_nonce(client.clone(), entrypoint, account, nonce_key).await?;
     let init_code = build_init_code(client.clone(), factory, owner, salt, deployed).await?;
 
+    // Native-token plans: no approve, subscribe carries msg.value via execute().
+    if let Some(value) = native_value {
+        let call_data = encode_native_subscribe_call(open_sub, subscribe_call, plan_id, value)?;
+        return Ok((call_data, init_code, nonce));
+    }
+
     // Token call data (optionally mint, then approve).
     // NOTE: `mint` is demo-only; it will revert on real tokens.
-    let token_abi = AbiParser::default().parse(&[
-        "function mint(address to, uint256 amount)",
-        "function approve(address spender, uint256 amount) returns (bool)",
-    ])?;
+    let token_abi = AbiParser::default().parse(&["function mint(address to, uint256 amount)"])?;
     let token_c = Contract::new(token, token_abi, client.clone());
 
     let mint_calldata: Option<Bytes> = if let Some(amt) = mint_amount {
@@ -1337,46 +3685,252 @@ async fn build_userop_payload<M: Middleware + 'static>(
         None
     };
 
-    let approve_calldata = token_c
-        .method::<_, bool>("approve", (open_sub, allowance_amount))?
-        .calldata()
-        .ok_or_else(|| anyhow!("failed to build approve calldata"))?;
-
-    let open_sub_abi =
-        AbiParser::default().parse(&["function subscribe(uint256 planId) returns (uint256)"])?;
-    let open_sub = Contract::new(open_sub, open_sub_abi, client.clone());
-    let subscribe_calldata = open_sub
-        .method::<_, U256>("subscribe", plan_id)?
-        .calldata()
-        .ok_or_else(|| anyhow!("failed to build subscribe calldata"))?;
+    let approve_calldata = encode_approve(approve_sig, open_sub, allowance_amount)?;
 
-    // SimpleAccount.executeBatch(address[] dest, bytes[] func)
-    let account_abi =
-        AbiParser::default().parse(&["function executeBatch(address[] dest, bytes[] func)"])?;
-    let account_c = Contract::new(account, account_abi, client);
+    let subscribe_calldata = subscribe_call.encode(plan_id)?;
 
-    let mut dests: Vec<Address> = Vec::new();
-    let mut funcs: Vec<Bytes> = Vec::new();
+    let mut calls: Vec<(Address, Bytes)> = Vec::new();
 
     if let Some(m) = mint_calldata {
-        dests.push(token);
-        funcs.push(m);
+        calls.push((token, m));
     }
 
-    dests.push(token);
-    funcs.push(approve_calldata);
-
-    dests.push(open_sub.address());
-    funcs.push(subscribe_calldata);
+    calls.push((token, approve_calldata));
+    calls.push((open_sub, subscribe_calldata));
 
-    let call_data = account_c
-        .method::<_, ()>("executeBatch", (dests, funcs))?
-        .calldata()
-        .ok_or_else(|| anyhow!("failed to build executeBatch calldata"))?;
+    let call_data = encode_execute_batch(&calls)?;
 
     Ok((call_data, init_code, nonce))
 }
 
+/// Encode `SimpleAccount.executeBatch(address[] dest, bytes[] func)`; calls run in order.
+fn encode_execute_batch(calls: &[(Address, Bytes)]) -> Result<Bytes> {
+    let execute_batch_fn = AbiParser::default()
+        .parse_function("function executeBatch(address[] dest, bytes[] func)")?;
+    let (dests, funcs): (Vec<Token>, Vec<Token>) = calls
+        .iter()
+        .map(|(dest, func)| (Token::Address(*dest), Token::Bytes(func.to_vec())))
+        .unzip();
+    let call_data = execute_batch_fn.encode_input(&[Token::Array(dests), Token::Array(funcs)])?;
+    Ok(Bytes::from(call_data))
+}
+
+/// `cancel` followed by `approve(openSub, 0)` on the plan token, for `cancel --revoke`.
+fn cancel_and_revoke_calls(
+    open_sub: Address,
+    token: Address,
+    subscription_id: U256,
+    at_period_end: bool,
+    after_periods: Option<u64>,
+    approve_sig: &str,
+) -> Result<Vec<(Address, Bytes)>> {
+    let cancel = encode_cancel(subscription_id, at_period_end, after_periods)?;
+    let revoke = encode_approve(approve_sig, open_sub, U256::zero())?;
+
+    Ok(vec![(open_sub, cancel), (token, revoke)])
+}
+
+const CANCEL_SIG: &str = "function cancel(uint256 subscriptionId, bool atPeriodEnd)";
+
+/// Parameterized cancel of OpenSub versions with prepaid exits (`--after-periods`).
+const CANCEL_AFTER_SIG: &str = "function cancelAfter(uint256 subscriptionId, uint256 periods)";
+
+/// `cancelAfter(id, periods)` with `--after-periods`, else `cancel(id, atPeriodEnd)`.
+fn encode_cancel(
+    subscription_id: U256,
+    at_period_end: bool,
+    after_periods: Option<u64>,
+) -> Result<Bytes> {
+    let data = match after_periods {
+        Some(periods) => AbiParser::default()
+            .parse_function(CANCEL_AFTER_SIG)?
+            .encode_input(&[
+                Token::Uint(subscription_id),
+                Token::Uint(U256::from(periods)),
+            ])?,
+        None => AbiParser::default()
+            .parse_function(CANCEL_SIG)?
+            .encode_input(&[Token::Uint(subscription_id), Token::Bool(at_period_end)])?,
+    };
+    Ok(Bytes::from(data))
+}
+
+fn cancel_after_selector() -> [u8; 4] {
+    ethers::utils::id("cancelAfter(uint256,uint256)")
+}
+
+/// ERC-20 approve, declared without a return value: only the selector and arguments end up in the
+/// calldata, and the result is never decoded.
+const DEFAULT_APPROVE_SIG: &str = "function approve(address spender, uint256 amount)";
+
+/// Encode the token's approve call (`--approve-sig`, `(address spender, uint256 amount)`).
+///
+/// Any `returns (...)` clause in `sig` is accepted and ignored.
+fn encode_approve(sig: &str, spender: Address, amount: U256) -> Result<Bytes> {
+    let sig = sig.trim();
+    let sig = if sig.starts_with("function ") {
+        sig.to_string()
+    } else {
+        format!("function {sig}")
+    };
+    let function = AbiParser::default()
+        .parse_function(&sig)
+        .with_context(|| format!("invalid --approve-sig: {sig}"))?;
+    let kinds: Vec<_> = function.inputs.iter().map(|p| p.kind.clone()).collect();
+    if kinds
+        != [
+            ethers::abi::ParamType::Address,
+            ethers::abi::ParamType::Uint(256),
+        ]
+    {
+        return Err(anyhow!(
+            "--approve-sig must take (address spender, uint256 amount): {sig}"
+        ));
+    }
+    Ok(Bytes::from(function.encode_input(&[
+        Token::Address(spender),
+        Token::Uint(amount),
+    ])?))
+}
+
+/// Encode the account's owner-update call (`--owner-update-sig`, one `address` parameter).
+fn encode_owner_update(sig: &str, new_owner: Address) -> Result<Bytes> {
+    let sig = sig.trim();
+    let sig = if sig.starts_with("function ") {
+        sig.to_string()
+    } else {
+        format!("function {sig}")
+    };
+    let function = AbiParser::default()
+        .parse_function(&sig)
+        .with_context(|| format!("invalid --owner-update-sig: {sig}"))?;
+    if function.inputs.len() != 1 || function.inputs[0].kind != ethers::abi::ParamType::Address {
+        return Err(anyhow!(
+            "--owner-update-sig must take exactly one address parameter: {sig}"
+        ));
+    }
+    Ok(Bytes::from(
+        function.encode_input(&[Token::Address(new_owner)])?,
+    ))
+}
+
+const DEFAULT_SUBSCRIBE_SIG: &str = "function subscribe(uint256 planId) returns (uint256)";
+
+/// Placeholder in `--subscribe-args` replaced with the plan being subscribed to.
+const PLAN_ID_PLACEHOLDER: &str = "{planId}";
+
+/// The OpenSub subscribe call: `--subscribe-sig` / `--subscribe-args`, or the built-in default.
+#[derive(Debug, Clone)]
+struct SubscribeCall {
+    function: ethers::abi::Function,
+    /// Raw argument values; `None` means "all args are just the planId".
+    args: Option<Vec<String>>,
+}
+
+impl Default for SubscribeCall {
+    fn default() -> Self {
+        Self {
+            function: AbiParser::default()
+                .parse_function(DEFAULT_SUBSCRIBE_SIG)
+                .expect("default subscribe signature parses"),
+            args: None,
+        }
+    }
+}
+
+impl SubscribeCall {
+    /// Parse and validate the overrides up front so a typo fails before anything is built.
+    fn parse(sig: Option<&str>, args: &[String]) -> Result<Self> {
+        let Some(sig) = sig else {
+            return Ok(Self::default());
+        };
+        let sig = sig.trim();
+        let sig = if sig.starts_with("function ") {
+            sig.to_string()
+        } else {
+            format!("function {sig}")
+        };
+        let function = AbiParser::default()
+            .parse_function(&sig)
+            .with_context(|| format!("invalid --subscribe-sig: {sig}"))?;
+
+        let call = if args.is_empty() {
+            if function.inputs.len() != 1 {
+                return Err(anyhow!(
+                    "--subscribe-sig takes {} parameters; pass them with --subscribe-args",
+                    function.inputs.len()
+                ));
+            }
+            Self {
+                function,
+                args: None,
+            }
+        } else {
+            if args.len() != function.inputs.len() {
+                return Err(anyhow!(
+                    "--subscribe-args has {} values but --subscribe-sig takes {}",
+                    args.len(),
+                    function.inputs.len()
+                ));
+            }
+            Self {
+                function,
+                args: Some(args.to_vec()),
+            }
+        };
+        // Every value must tokenize; the placeholder is checked with a dummy plan id.
+        call.encode(U256::zero())?;
+        Ok(call)
+    }
+
+    fn encode(&self, plan_id: U256) -> Result<Bytes> {
+        use ethers::abi::token::{LenientTokenizer, Tokenizer};
+
+        let plan_id = plan_id.to_string();
+        let tokens = self
+            .function
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let raw = match &self.args {
+                    Some(args) => args[i].replace(PLAN_ID_PLACEHOLDER, &plan_id),
+                    None => plan_id.clone(),
+                };
+                LenientTokenizer::tokenize(&param.kind, &raw).with_context(|| {
+                    format!(
+                        "invalid subscribe argument {} ({}): {raw}",
+                        param.name, param.kind
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Bytes::from(self.function.encode_input(&tokens)?))
+    }
+}
+
+/// Encode `SimpleAccount.execute(openSub, value, subscribe(planId))` for native-token plans.
+fn encode_native_subscribe_call(
+    open_sub: Address,
+    subscribe_call: &SubscribeCall,
+    plan_id: U256,
+    value: U256,
+) -> Result<Bytes> {
+    let subscribe_calldata = subscribe_call.encode(plan_id)?.to_vec();
+
+    let execute_fn = AbiParser::default()
+        .parse_function("function execute(address dest, uint256 value, bytes func)")?;
+    let call_data = execute_fn.encode_input(&[
+        ethers::abi::Token::Address(open_sub),
+        ethers::abi::Token::Uint(value),
+        ethers::abi::Token::Bytes(subscribe_calldata),
+    ])?;
+
+    Ok(Bytes::from(call_data))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn build_single_call_payload<M: Middleware + 'static>(
     client: Arc<M>,
     entrypoint: Address,
@@ -1388,25 +3942,46 @@ async fn build_single_call_payload<M: Middleware + 'static>(
     target: Address,
     target_calldata: Bytes,
 ) -> Result<(Bytes, Bytes, U256)> {
-    let nonce = fetch_entrypoint_nonce(client.clone(), entrypoint, account).await?;
     let init_code = build_init_code(client.clone(), factory, owner, salt, deployed).await?;
+    let (call_data, nonce) = userop::build_single_call(
+        client,
+        entrypoint,
+        account,
+        U256::zero(),
+        target,
+        target_calldata,
+    )
+    .await?;
 
-    // SimpleAccount.execute(address dest, uint256 value, bytes func)
-    let account_abi = AbiParser::default()
-        .parse(&["function execute(address dest, uint256 value, bytes func)"])?;
-    let account_c = Contract::new(account, account_abi, client);
-    let call_data = account_c
-        .method::<_, ()>("execute", (target, U256::zero(), target_calldata))?
-        .calldata()
-        .ok_or_else(|| anyhow!("failed to build execute calldata"))?;
+    Ok((call_data, init_code, nonce))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn build_batch_call_payload<M: Middleware + 'static>(
+    client: Arc<M>,
+    entrypoint: Address,
+    factory: Address,
+    owner: Address,
+    salt: U256,
+    account: Address,
+    deployed: bool,
+    calls: &[(Address, Bytes)],
+) -> Result<(Bytes, Bytes, U256)> {
+    let nonce =
+        userop::fetch_entrypoint_nonce(client.clone(), entrypoint, account, U256::zero()).await?;
+    let init_code = build_init_code(client, factory, owner, salt, deployed).await?;
+    let call_data = encode_execute_batch(calls)?;
 
     Ok((call_data, init_code, nonce))
 }
 
+/// Build, sign and send a userOp. Returns the decoded receipt events, or `None` when nothing was
+/// sent or waited for (dry runs, `--no-wait`).
+#[allow(clippy::too_many_arguments)]
 async fn send_userop<M: Middleware + 'static>(
     provider: &Provider<Http>,
     client: Arc<M>,
-    wallet: &LocalWallet,
+    signer: &dyn UserOpSigner,
     entrypoint: Address,
     chain_id: u64,
     account: Address,
@@ -1414,25 +3989,97 @@ async fn send_userop<M: Middleware + 'static>(
     init_code: Bytes,
     nonce: U256,
     args: &TxArgs,
+    explorer: Option<&str>,
     machine_mode: bool,
-) -> Result<bool> {
+) -> Result<Option<ReceiptEvents>> {
+    let bundler_url = args
+        .bundler
+        .clone()
+        .ok_or_else(|| anyhow!("no bundler URL configured"))?;
+    let dump = args
+        .dump_rpc_dir
+        .clone()
+        .map(http::RpcDump::new)
+        .transpose()?;
+    if let Some(d) = dump.as_ref() {
+        outln!(
+            machine_mode,
+            "dumping bundler/paymaster JSON-RPC to {}",
+            d.dir().display()
+        );
+    }
+    let bundler = BundlerClient::with_fallbacks(bundler_url, args.bundler_fallbacks.clone())
+        .with_trace(args.http_trace)
+        .with_dump(dump.clone())
+        .with_client_options(&args.client_options)?;
+
     // Fee data (fallback to gas price for providers without EIP-1559 helpers).
     let gas_price = provider
         .get_gas_price()
         .await
         .context("failed to fetch gas price")?;
-    let bps = args.gas_multiplier_bps.max(1);
-    let max_priority_fee_per_gas = gas_price * U256::from(bps) / U256::from(10_000u64);
-    let max_fee_per_gas = max_priority_fee_per_gas;
-
-    if bps != 10_000 {
-        tracing::info!(
-            "gas multiplier applied: {} bps (maxFeePerGas={}, maxPriorityFeePerGas={})",
-            bps,
-            max_fee_per_gas,
-            max_priority_fee_per_gas
-        );
-    }
+
+    // Prefer the bundler's own fee suggestion when configured; it is more likely to be accepted.
+    let quote = match args.gas.bundler_gas_price_method.as_deref() {
+        Some(method) => match bundler.get_user_operation_gas_price(method).await {
+            Ok(q) => {
+                tracing::info!(
+                    method,
+                    max_fee_per_gas = ?q.max_fee_per_gas,
+                    max_priority_fee_per_gas = %q.max_priority_fee_per_gas,
+                    "using bundler gas price"
+                );
+                Some(q)
+            }
+            Err(err) => {
+                tracing::warn!(method, error = %err, "bundler gas price unavailable; using provider gas price");
+                None
+            }
+        },
+        None => None,
+    };
+    // `--adaptive-gas` replaces the provider gas price path, not a bundler quote.
+    let adaptive = if quote.is_none() && args.gas.adaptive_gas {
+        adaptive_fees(provider, &args.gas).await
+    } else {
+        None
+    };
+    let (base_max_fee, base_priority_fee) = match quote {
+        // A priority-only answer (e.g. rundler) still needs room for the base fee.
+        Some(q) => (
+            q.max_fee_per_gas
+                .unwrap_or(gas_price + q.max_priority_fee_per_gas),
+            q.max_priority_fee_per_gas,
+        ),
+        None => (gas_price, gas_price),
+    };
+
+    let (max_fee_per_gas, max_priority_fee_per_gas) = match adaptive {
+        Some(fees) => (fees.max_fee_per_gas, fees.max_priority_fee_per_gas),
+        None => {
+            let bps = args.gas_multiplier_bps.max(1);
+            let max_priority_fee_per_gas =
+                base_priority_fee * U256::from(bps) / U256::from(10_000u64);
+            let max_fee_per_gas = base_max_fee * U256::from(bps) / U256::from(10_000u64);
+
+            if bps != 10_000 {
+                tracing::info!(
+                    "gas multiplier applied: {} bps (maxFeePerGas={}, maxPriorityFeePerGas={})",
+                    bps,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas
+                );
+            }
+            (max_fee_per_gas, max_priority_fee_per_gas)
+        }
+    };
+
+    let validity = args
+        .valid_seconds
+        .map(|secs| ValidityWindow::from_now(now_unix(), secs))
+        .transpose()?;
+    // Sponsored ops carry the time range in paymasterAndData; otherwise it goes into the signature.
+    let signed_validity = if args.sponsor_gas { None } else { validity };
 
     // Initial gas guesses (will be overwritten by bundler estimate).
     let mut op = UserOperation {
@@ -1448,24 +4095,84 @@ async fn send_userop<M: Middleware + 'static>(
         max_fee_per_gas,
         max_priority_fee_per_gas,
         paymaster_and_data: Bytes::from(Vec::new()),
-        signature: Bytes::from(vec![0u8; 65]),
+        signature: dummy_signature(signed_validity),
     };
 
-    let bundler = BundlerClient::new(args.bundler.clone());
-
     // Optional paymaster (Milestone 6B: Alchemy Gas Manager via ERC-7677).
     let (paymaster, policy_id) = if args.sponsor_gas {
         let url = args.paymaster_url.clone().ok_or_else(|| {
-            anyhow!("--sponsor-gas requires --paymaster-url (or OPENSUB_AA_PAYMASTER_URL)")
+            anyhow!("--sponsor-gas requires --paymaster-url (or OPENSUB_AA_PAYMASTER_URL, or paymasterUrl in the deployment JSON)")
         })?;
         let policy_id = args.policy_id.clone().ok_or_else(|| {
-            anyhow!("--sponsor-gas requires --policy-id (or OPENSUB_AA_GAS_MANAGER_POLICY_ID)")
+            anyhow!("--sponsor-gas requires --policy-id (or OPENSUB_AA_GAS_MANAGER_POLICY_ID, or gasManagerPolicyId in the deployment JSON)")
         })?;
 
-        (Some(PaymasterClient::new(url)), Some(policy_id))
-    } else {
-        (None, None)
-    };
+        let mut pm = PaymasterClient::new(url)
+            .with_trace(args.http_trace)
+            .with_dump(dump.clone())
+            .with_client_options(&args.client_options)?;
+        if let Some(w) = validity {
+            pm = pm.with_time_range(w.valid_after, w.valid_until);
+        }
+        (Some(pm), Some(policy_id))
+    } else {
+        (None, None)
+    };
+
+    if let Some(w) = validity {
+        outln!(
+            machine_mode,
+            "userOp valid from {} until {} (via {})",
+            w.valid_after,
+            w.valid_until,
+            if args.sponsor_gas {
+                "paymaster context"
+            } else {
+                "signature time range"
+            }
+        );
+    }
+
+    if args.paymaster_dry_run {
+        let (Some(pm), Some(pid)) = (paymaster.as_ref(), policy_id.as_ref()) else {
+            return Err(anyhow!("--paymaster-dry-run requires --sponsor-gas"));
+        };
+        outln!(
+            machine_mode,
+            "checking sponsorship eligibility (pm_getPaymasterStubData)..."
+        );
+        let check = pm
+            .check_sponsorship(
+                encoding::user_op_to_paymaster_json(&op),
+                entrypoint,
+                chain_id,
+                pid,
+                args.webhook_data.as_deref(),
+            )
+            .await?;
+        return match check {
+            SponsorshipCheck::Sponsored(stub) => {
+                outln!(
+                    machine_mode,
+                    "sponsorship likely: paymaster returned stub data ({} bytes). Nothing was sent.",
+                    stub.len()
+                );
+                Ok(None)
+            }
+            SponsorshipCheck::Rejected(rejection) => Err(anyhow!(
+                "paymaster rejected sponsorship (code {}): {}{}",
+                rejection
+                    .code
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "n/a".to_string()),
+                rejection.message,
+                rejection
+                    .reason
+                    .map(|r| format!(" ({r})"))
+                    .unwrap_or_default()
+            )),
+        };
+    }
 
     // If using a paymaster, fetch stub paymasterAndData BEFORE gas estimation.
     if let (Some(pm), Some(pid)) = (paymaster.as_ref(), policy_id.as_ref()) {
@@ -1487,13 +4194,58 @@ async fn send_userop<M: Middleware + 'static>(
     }
 
     // Sign for estimation.
-    sign_userop(client.clone(), entrypoint, &mut op, wallet).await?;
+    sign_userop(client.clone(), entrypoint, &mut op, signer, signed_validity).await?;
 
-    // Estimate gas via bundler.
-    let est = bundler
-        .estimate_user_operation_gas(encoding::user_op_to_json(&op), entrypoint)
-        .await
-        .context("bundler gas estimate failed")?;
+    // Estimate gas via bundler (unless all three limits were given manually).
+    let est = match manual_gas_limits(&args.gas)? {
+        Some(manual) => {
+            outln!(
+                machine_mode,
+                "using manual gas limits; skipping eth_estimateUserOperationGas"
+            );
+            manual
+        }
+        None => {
+            // Sponsored ops are never cached: the paymaster data is part of what gets estimated.
+            let cache = args
+                .gas
+                .estimate_cache
+                .clone()
+                .filter(|_| !args.sponsor_gas)
+                .map(|path| {
+                    EstimateCache::new(path, Duration::from_secs(args.gas.estimate_cache_ttl))
+                });
+            let key = estimate_cache::cache_key(op.sender, &op.call_data, &op.init_code, chain_id);
+            let cached = match cache.as_ref() {
+                Some(c) => c.get(key, now_unix()).unwrap_or_else(|err| {
+                    tracing::warn!("ignoring estimate cache: {err:#}");
+                    None
+                }),
+                None => None,
+            };
+            match cached {
+                Some(est) => {
+                    outln!(
+                        machine_mode,
+                        "using cached gas estimate; skipping eth_estimateUserOperationGas"
+                    );
+                    est
+                }
+                None => {
+                    let est = bundler
+                        .estimate_user_operation_gas(encoding::user_op_to_json(&op), entrypoint)
+                        .await
+                        .context("bundler gas estimate failed")?;
+                    if let Some(c) = cache.as_ref() {
+                        if let Err(err) = c.put(key, &est, now_unix()) {
+                            tracing::warn!("failed to update estimate cache: {err:#}");
+                        }
+                    }
+                    est
+                }
+            }
+        }
+    };
 
     op.call_gas_limit = est.call_gas_limit;
     op.verification_gas_limit = est.verification_gas_limit;
@@ -1519,7 +4271,7 @@ async fn send_userop<M: Middleware + 'static>(
     }
 
     // Re-sign with final gas limits + final paymasterAndData.
-    sign_userop(client.clone(), entrypoint, &mut op, wallet).await?;
+    sign_userop(client.clone(), entrypoint, &mut op, signer, signed_validity).await?;
 
     outln!(
         machine_mode,
@@ -1529,7 +4281,7 @@ async fn send_userop<M: Middleware + 'static>(
 
     if args.dry_run {
         outln!(machine_mode, "\n--dry-run set: not sending user operation.");
-        return Ok(false);
+        return Ok(None);
     }
 
     // Send.
@@ -1543,10 +4295,21 @@ async fn send_userop<M: Middleware + 'static>(
         "\nuserOpHash: {}",
         encoding::fmt_h256(user_op_hash)
     );
+    if let Some(n) = bundler.active_endpoint().checked_sub(1) {
+        outln!(
+            machine_mode,
+            "accepted by --bundler-fallback #{} (the primary bundler was unavailable)",
+            n + 1
+        );
+    }
 
     if args.no_wait {
         outln!(machine_mode, "--no-wait set: not waiting for receipt.");
-        return Ok(false);
+        if let Some(path) = args.receipt_out.as_deref() {
+            write_receipt_out(path, &receipt_record(user_op_hash, account, None, None))?;
+            outln!(machine_mode, "userOpHash written to {}", path.display());
+        }
+        return Ok(None);
     }
 
     let receipt = bundler
@@ -1560,38 +4323,224 @@ async fn send_userop<M: Middleware + 'static>(
         serde_json::to_string_pretty(&receipt)?
     );
 
-    Ok(true)
+    let events = receipt_events::decode_receipt(&receipt);
+    if machine_mode {
+        // stdout is reserved for the command's own output; the structured summary goes to stderr
+        // as a single line.
+        eprintln!(
+            "{}",
+            serde_json::json!({ "userOpEvents": events.to_json() })
+        );
+    } else {
+        println!("\nEvents:");
+        for log in &events.logs {
+            println!("  {log}");
+        }
+        for id in events.subscribed_ids() {
+            println!("subscription id: {id}");
+        }
+        if let Some(cost) = events.actual_gas_cost {
+            println!(
+                "actual gas cost: {} wei ({} ETH)",
+                cost,
+                format_token_amount(cost, 18)
+            );
+        }
+        // The userOp itself has no explorer page; link the bundle tx that included it.
+        if let Some(url) = explorer.and_then(|base| receipt_tx_url(base, &receipt)) {
+            println!("transaction: {url}");
+        }
+    }
+
+    if let Some(path) = args.receipt_out.as_deref() {
+        write_receipt_out(
+            path,
+            &receipt_record(user_op_hash, account, Some(&receipt), Some(&events)),
+        )?;
+        outln!(machine_mode, "receipt written to {}", path.display());
+    }
+
+    Ok(Some(events))
+}
+
+/// Explorer link for the bundle tx of a `eth_getUserOperationReceipt` result.
+fn receipt_tx_url(explorer_base_url: &str, receipt: &serde_json::Value) -> Option<String> {
+    let tx_hash = receipt["receipt"]["transactionHash"].as_str()?;
+    Some(config::explorer_tx_url(explorer_base_url, tx_hash))
+}
+
+/// The `--receipt-out` document. `receipt` / `events` are `None` when the receipt was not waited
+/// for.
+fn receipt_record(
+    user_op_hash: H256,
+    sender: Address,
+    receipt: Option<&serde_json::Value>,
+    events: Option<&ReceiptEvents>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "userOpHash": encoding::fmt_h256(user_op_hash),
+        "sender": encoding::fmt_address(sender),
+        "receipt": receipt,
+        "summary": events.map(ReceiptEvents::to_json),
+    })
+}
+
+/// Write `record` to `path` via a temp file, so a reader never sees half a document.
+fn write_receipt_out(path: &Path, record: &serde_json::Value) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(record)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| {
+        format!(
+            "userOp was sent, but --receipt-out {} could not be written",
+            path.display()
+        )
+    })
+}
+
+/// `--adaptive-gas` fees, or `None` (logged) when `eth_feeHistory` fails or has nothing usable.
+async fn adaptive_fees(provider: &Provider<Http>, gas: &GasArgs) -> Option<AdaptiveFees> {
+    let history = match provider
+        .fee_history(
+            gas.fee_history_blocks,
+            BlockNumber::Latest,
+            &[gas.priority_fee_percentile],
+        )
+        .await
+    {
+        Ok(h) => h,
+        Err(err) => {
+            tracing::warn!(error = %err, "eth_feeHistory unavailable; using --gas-multiplier-bps");
+            return None;
+        }
+    };
+    match fee_history::adaptive_fees(&history) {
+        Some(fees) => {
+            tracing::info!(
+                blocks = gas.fee_history_blocks,
+                percentile = gas.priority_fee_percentile,
+                base_fee_ceiling = %fees.base_fee_ceiling,
+                max_fee_per_gas = %fees.max_fee_per_gas,
+                max_priority_fee_per_gas = %fees.max_priority_fee_per_gas,
+                "using adaptive gas from fee history"
+            );
+            Some(fees)
+        }
+        None => {
+            tracing::warn!("fee history has no base fees or rewards; using --gas-multiplier-bps");
+            None
+        }
+    }
+}
+
+/// `Some` when all three manual gas limits are set, `None` when none are; anything else is an error.
+fn manual_gas_limits(args: &GasArgs) -> Result<Option<GasEstimates>> {
+    match (
+        args.call_gas_limit,
+        args.verification_gas_limit,
+        args.pre_verification_gas,
+    ) {
+        (Some(call), Some(verification), Some(pre_verification)) => Ok(Some(GasEstimates {
+            call_gas_limit: U256::from(call),
+            verification_gas_limit: U256::from(verification),
+            pre_verification_gas: U256::from(pre_verification),
+        })),
+        (None, None, None) => Ok(None),
+        _ => Err(anyhow!(
+            "--call-gas-limit, --verification-gas-limit and --pre-verification-gas must be set together"
+        )),
+    }
 }
 
 async fn sign_userop<M: Middleware + 'static>(
     client: Arc<M>,
     entrypoint: Address,
     op: &mut UserOperation,
-    wallet: &LocalWallet,
+    signer: &dyn UserOpSigner,
+    validity: Option<ValidityWindow>,
 ) -> Result<()> {
-    // Use the on-chain EntryPoint.getUserOpHash for correctness.
-    let entrypoint_abi: Abi = serde_json::from_str(
-        r#"[{"inputs":[{"components":[{"internalType":"address","name":"sender","type":"address"},{"internalType":"uint256","name":"nonce","type":"uint256"},{"internalType":"bytes","name":"initCode","type":"bytes"},{"internalType":"bytes","name":"callData","type":"bytes"},{"internalType":"uint256","name":"callGasLimit","type":"uint256"},{"internalType":"uint256","name":"verificationGasLimit","type":"uint256"},{"internalType":"uint256","name":"preVerificationGas","type":"uint256"},{"internalType":"uint256","name":"maxFeePerGas","type":"uint256"},{"internalType":"uint256","name":"maxPriorityFeePerGas","type":"uint256"},{"internalType":"bytes","name":"paymasterAndData","type":"bytes"},{"internalType":"bytes","name":"signature","type":"bytes"}],"internalType":"struct UserOperation","name":"userOp","type":"tuple"}],"name":"getUserOpHash","outputs":[{"internalType":"bytes32","name":"","type":"bytes32"}],"stateMutability":"view","type":"function"}]"#,
-    )
-    .context("failed to parse EntryPoint ABI")?;
+    // The CLI only builds v0.6 ops.
+    let user_op_hash = userop::user_op_hash(client, entrypoint, EntryPointVersion::V06, op).await?;
+
+    // With a time range, the owner signs over (userOpHash, validUntil, validAfter) so the window
+    // cannot be altered without invalidating the signature.
+    let signed_hash = match validity {
+        Some(w) => w.digest(user_op_hash),
+        None => user_op_hash,
+    };
 
-    let entrypoint_c = Contract::new(entrypoint, entrypoint_abi, client);
+    let sig = signer.sign(signed_hash).await?;
+    op.signature = match validity {
+        Some(w) => w.encode_signature(&sig),
+        None => sig,
+    };
 
-    let user_op_tuple = op.as_abi_tuple();
-    let user_op_hash: H256 = entrypoint_c
-        .method("getUserOpHash", (user_op_tuple,))?
-        .call()
-        .await
-        .context("entryPoint.getUserOpHash failed")?;
+    Ok(())
+}
 
-    let sig = wallet
-        .sign_message(user_op_hash.as_bytes())
-        .await
-        .context("failed to sign userOpHash")?;
+/// Placeholder signature for gas estimation, as long as the real one: 65 bytes, plus the 12-byte
+/// time range prefix when the range is signed. A shorter one under-estimates verification gas and
+/// preVerificationGas.
+fn dummy_signature(signed_validity: Option<ValidityWindow>) -> Bytes {
+    let sig = [0u8; 65];
+    match signed_validity {
+        Some(w) => w.encode_signature(&sig),
+        None => Bytes::from(sig.to_vec()),
+    }
+}
 
-    op.signature = Bytes::from(sig.to_vec());
+/// Slack subtracted from `validAfter` so a slightly fast local clock does not produce an op that
+/// is not valid yet.
+const VALID_AFTER_SKEW_SECONDS: u64 = 60;
 
-    Ok(())
+/// ERC-4337 timestamps in `validationData` are uint48.
+const MAX_UINT48: u64 = (1 << 48) - 1;
+
+/// `--valid-seconds` time range (`validAfter <= block.timestamp <= validUntil`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ValidityWindow {
+    valid_after: u64,
+    valid_until: u64,
+}
+
+impl ValidityWindow {
+    fn from_now(now: u64, valid_seconds: u64) -> Result<Self> {
+        if valid_seconds == 0 {
+            return Err(anyhow!("--valid-seconds must be greater than 0"));
+        }
+        let valid_until = now
+            .checked_add(valid_seconds)
+            .filter(|t| *t <= MAX_UINT48)
+            .ok_or_else(|| {
+                anyhow!("--valid-seconds {valid_seconds} overflows uint48 validUntil")
+            })?;
+        Ok(Self {
+            valid_after: now.saturating_sub(VALID_AFTER_SKEW_SECONDS),
+            valid_until,
+        })
+    }
+
+    /// Hash the owner signs instead of the bare userOpHash:
+    /// `keccak256(abi.encode(userOpHash, uint48 validUntil, uint48 validAfter))`.
+    fn digest(&self, user_op_hash: H256) -> H256 {
+        H256::from(ethers::utils::keccak256(ethers::abi::encode(&[
+            Token::FixedBytes(user_op_hash.as_bytes().to_vec()),
+            Token::Uint(U256::from(self.valid_until)),
+            Token::Uint(U256::from(self.valid_after)),
+        ])))
+    }
+
+    /// `abi.encodePacked(uint48 validUntil, uint48 validAfter, signature)`.
+    fn encode_signature(&self, sig: &[u8]) -> Bytes {
+        let mut out = Vec::with_capacity(12 + sig.len());
+        out.extend_from_slice(&self.valid_until.to_be_bytes()[2..]);
+        out.extend_from_slice(&self.valid_after.to_be_bytes()[2..]);
+        out.extend_from_slice(sig);
+        Bytes::from(out)
+    }
 }
 
 async fn fund_account_eth<M: Middleware + 'static>(
@@ -1620,6 +4569,43 @@ async fn fund_account_eth<M: Middleware + 'static>(
     Ok(())
 }
 
+/// Chain RPC provider, with `--resolve` overrides applied to its HTTP client.
+fn connect_provider(rpc_url: &str, resolve: &[http::ResolveOverride]) -> Result<Provider<Http>> {
+    let url =
+        reqwest::Url::parse(rpc_url).with_context(|| format!("invalid RPC URL '{rpc_url}'"))?;
+    let client = http::ClientOptions {
+        resolve: resolve.to_vec(),
+        ..Default::default()
+    }
+    .client()?;
+    Ok(Provider::new(Http::new_with_client(url, client)).interval(Duration::from_millis(350)))
+}
+
+/// Delay between `--post-subscribe-retries` reads.
+const POST_SUBSCRIBE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Call `read` (with the 0-based attempt) until it returns a non-zero id, at most `retries + 1`
+/// times with `delay` in between. Read errors are retried like zeros; when every attempt is used
+/// up, the last result (zero, or the error) is returned.
+async fn retry_until_nonzero<F, Fut>(retries: u32, delay: Duration, mut read: F) -> Result<U256>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<U256>>,
+{
+    let mut attempt = 0;
+    loop {
+        let result = read(attempt).await;
+        if matches!(&result, Ok(id) if !id.is_zero()) || attempt >= retries {
+            return result;
+        }
+        if let Err(err) = &result {
+            tracing::debug!(attempt, error = %err, "read failed; retrying");
+        }
+        attempt += 1;
+        tokio::time::sleep(delay).await;
+    }
+}
+
 async fn active_subscription_of<M: Middleware + 'static>(
     client: Arc<M>,
     open_sub: Address,
@@ -1639,18 +4625,1119 @@ async fn active_subscription_of<M: Middleware + 'static>(
     Ok(sub_id)
 }
 
+/// `hasAccess(id)` at `block` (latest when `None`).
 async fn has_access<M: Middleware + 'static>(
     client: Arc<M>,
     open_sub: Address,
     subscription_id: U256,
+    block: Option<BlockNumber>,
 ) -> Result<bool> {
     let abi = AbiParser::default()
         .parse(&["function hasAccess(uint256 subscriptionId) view returns (bool)"])?;
     let open_sub = Contract::new(open_sub, abi, client);
 
-    let ok: bool = open_sub
-        .method("hasAccess", subscription_id)?
-        .call()
-        .await?;
+    let mut call = open_sub.method("hasAccess", subscription_id)?;
+    if let Some(block) = block {
+        call = call.block(block);
+    }
+    let ok: bool = call.call().await?;
     Ok(ok)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bulk_approve_calls, cancel_after_selector, cancel_and_revoke_calls, collect_calls,
+        cost_per_30_days, derived_allowance, dummy_signature, encode_approve, encode_cancel,
+        encode_execute_batch, encode_native_subscribe_call, encode_owner_update, format_interval,
+        format_token_amount, funding_issues, has_access, headroom_warning, historical_read_error,
+        manual_gas_limits, map_salts_ordered, pair_approvals, parse_salt_range, plan_sanity_issues,
+        read_subscription, receipt_events, receipt_record, receipt_tx_url, resolve_plan_ids,
+        retry_until_nonzero, sign_userop, verify_checks, wallet_from_seed, write_owner_env_file,
+        write_receipt_out, Cli, Deployment, DeploymentFacts, EntryPointVersion, GasArgs,
+        OwnerEnvFormat, PlanInfo, SubscribeCall, TxArgs, UserOpSigner, UserOperation,
+        ValidityWindow, VerifyCheck, CANCEL_AFTER_SIG, CANCEL_SIG, DEFAULT_APPROVE_SIG,
+    };
+    use clap::Parser;
+    use ethers::abi::{AbiParser, Token};
+    use ethers::contract::Contract;
+    use ethers::providers::Provider;
+    use ethers::types::{Address, BlockId, BlockNumber, Bytes, H256, U256};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn retry_until_nonzero_stops_at_the_first_id() {
+        let calls = std::cell::Cell::new(0u32);
+        // Stale zeros, an RPC error, then the id.
+        let id = retry_until_nonzero(5, Duration::ZERO, |attempt| {
+            calls.set(calls.get() + 1);
+            async move {
+                match attempt {
+                    0 | 1 => Ok(U256::zero()),
+                    2 => Err(anyhow::anyhow!("header not found")),
+                    _ => Ok(U256::from(42)),
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(id, U256::from(42));
+        assert_eq!(calls.get(), 4);
+
+        // Exhausted: one read plus `retries` re-reads, and the last zero comes back.
+        calls.set(0);
+        let id = retry_until_nonzero(2, Duration::ZERO, |_| {
+            calls.set(calls.get() + 1);
+            async { Ok(U256::zero()) }
+        })
+        .await
+        .unwrap();
+        assert!(id.is_zero());
+        assert_eq!(calls.get(), 3);
+
+        // 0 retries: a single read, errors included.
+        calls.set(0);
+        let err = retry_until_nonzero(0, Duration::ZERO, |_| {
+            calls.set(calls.get() + 1);
+            async { Err(anyhow::anyhow!("boom")) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "boom");
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn receipt_links_its_bundle_tx() {
+        let receipt = serde_json::json!({
+            "userOpHash": format!("{:?}", H256::repeat_byte(0xab)),
+            "receipt": { "transactionHash": format!("{:?}", H256::repeat_byte(0xcd)) },
+        });
+        assert_eq!(
+            receipt_tx_url("https://sepolia.basescan.org", &receipt).unwrap(),
+            format!(
+                "https://sepolia.basescan.org/tx/{:?}",
+                H256::repeat_byte(0xcd)
+            )
+        );
+        assert_eq!(
+            receipt_tx_url("https://sepolia.basescan.org", &serde_json::json!({})),
+            None
+        );
+    }
+
+    #[test]
+    fn receipt_out_file_has_hash_sender_receipt_and_summary() {
+        let dir = std::env::temp_dir().join(format!(
+            "opensub-aa-receipt-out-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let path = dir.join("out").join("receipt.json");
+        let hash = H256::repeat_byte(0xab);
+        let sender = Address::repeat_byte(0x5a);
+        let receipt = serde_json::json!({
+            "userOpHash": format!("{hash:?}"),
+            "success": true,
+            "actualGasCost": "0x2386f26fc10000",
+            "logs": [],
+        });
+        let events = receipt_events::decode_receipt(&receipt);
+
+        write_receipt_out(
+            &path,
+            &receipt_record(hash, sender, Some(&receipt), Some(&events)),
+        )
+        .unwrap();
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let mut keys: Vec<&str> = written
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, ["receipt", "sender", "summary", "userOpHash"]);
+        assert_eq!(written["userOpHash"], format!("0x{}", "ab".repeat(32)));
+        assert_eq!(written["sender"], format!("0x{}", "5a".repeat(20)));
+        assert_eq!(written["receipt"], receipt);
+        assert_eq!(written["summary"]["actualGasCost"], "10000000000000000");
+
+        // `--no-wait`: same keys, nothing waited for.
+        write_receipt_out(&path, &receipt_record(hash, sender, None, None)).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["userOpHash"], format!("0x{}", "ab".repeat(32)));
+        assert!(written["receipt"].is_null());
+        assert!(written["summary"].is_null());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_plan_ids_defaults_to_deployment_plan() {
+        let ids = resolve_plan_ids(&[], U256::from(7)).unwrap();
+        assert_eq!(ids, vec![U256::from(7)]);
+    }
+
+    #[test]
+    fn resolve_plan_ids_keeps_explicit_order() {
+        let ids = resolve_plan_ids(&[3, 1, 2], U256::from(7)).unwrap();
+        assert_eq!(ids, vec![U256::from(3), U256::from(1), U256::from(2)]);
+    }
+
+    #[test]
+    fn resolve_plan_ids_rejects_duplicates() {
+        assert!(resolve_plan_ids(&[1, 2, 1], U256::from(7)).is_err());
+    }
+
+    #[test]
+    fn tx_args_fall_back_to_deployment_endpoints() {
+        let dep = Deployment {
+            chain_id: 84532,
+            rpc_url: "http://127.0.0.1:8545".to_string(),
+            open_sub: Address::zero(),
+            token: Address::zero(),
+            decimals: 6,
+            plan_id: U256::one(),
+            start_block: 0,
+            bundler_url: Some("https://bundler.from-deployment".to_string()),
+            paymaster_url: Some("https://paymaster.from-deployment".to_string()),
+            gas_manager_policy_id: Some("deployment-policy".to_string()),
+            explorer_base_url: None,
+        };
+        let cli = TxArgs {
+            bundler: None,
+            bundler_fallbacks: Vec::new(),
+            sponsor_gas: true,
+            paymaster_url: None,
+            policy_id: None,
+            webhook_data: None,
+            gas_multiplier_bps: 10_000,
+            dry_run: true,
+            paymaster_dry_run: false,
+            no_wait: false,
+            max_wait_seconds: 180,
+            valid_seconds: None,
+            http_trace: false,
+            dump_rpc_dir: None,
+            receipt_out: None,
+            allowed_rpc_hosts: Vec::new(),
+            client_options: opensub_aa::http::ClientOptions::default(),
+            gas: GasArgs::default(),
+        };
+
+        let resolved = cli.clone().with_deployment(&dep).unwrap();
+        assert_eq!(
+            resolved.bundler.as_deref(),
+            Some("https://bundler.from-deployment")
+        );
+        assert_eq!(
+            resolved.paymaster_url.as_deref(),
+            Some("https://paymaster.from-deployment")
+        );
+        assert_eq!(resolved.policy_id.as_deref(), Some("deployment-policy"));
+
+        // CLI / env values win over the deployment.
+        let resolved = TxArgs {
+            bundler: Some("https://bundler.from-cli".to_string()),
+            policy_id: Some("cli-policy".to_string()),
+            ..cli.clone()
+        }
+        .with_deployment(&dep)
+        .unwrap();
+        assert_eq!(
+            resolved.bundler.as_deref(),
+            Some("https://bundler.from-cli")
+        );
+        assert_eq!(
+            resolved.paymaster_url.as_deref(),
+            Some("https://paymaster.from-deployment")
+        );
+        assert_eq!(resolved.policy_id.as_deref(), Some("cli-policy"));
+
+        // The deployment's endpoints are subject to --allowed-rpc-hosts too.
+        let guarded = TxArgs {
+            allowed_rpc_hosts: vec!["bundler.from-deployment".to_string()],
+            ..cli.clone()
+        };
+        let err = guarded.with_deployment(&dep).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("paymaster host 'paymaster.from-deployment'"),
+            "{err}"
+        );
+
+        // No bundler anywhere is an error up front.
+        let bare = Deployment {
+            bundler_url: None,
+            ..dep
+        };
+        assert!(cli.with_deployment(&bare).is_err());
+    }
+
+    #[test]
+    fn verify_flags_a_decimals_mismatch() {
+        let dep = Deployment {
+            chain_id: 84532,
+            rpc_url: "http://127.0.0.1:8545".to_string(),
+            open_sub: Address::repeat_byte(0x01),
+            token: Address::repeat_byte(0x70),
+            decimals: 6,
+            plan_id: U256::one(),
+            start_block: 0,
+            bundler_url: None,
+            paymaster_url: None,
+            gas_manager_policy_id: None,
+            explorer_base_url: None,
+        };
+        let facts = DeploymentFacts {
+            chain_id: Some(84532),
+            open_sub_has_code: Some(true),
+            plan: Some(PlanInfo {
+                merchant: Address::repeat_byte(0x71),
+                token: Address::repeat_byte(0x70),
+                price: U256::from(1_000_000),
+                interval: 30 * 86_400,
+                collector_fee_bps: 100,
+                active: true,
+                created_at: 1_700_000_000,
+            }),
+            token_decimals: Some(6),
+        };
+        assert!(verify_checks(&dep, &facts).iter().all(|c| c.ok));
+
+        // The token reports 18 decimals: only that check fails.
+        let failed: Vec<VerifyCheck> = verify_checks(
+            &dep,
+            &DeploymentFacts {
+                token_decimals: Some(18),
+                ..facts.clone()
+            },
+        )
+        .into_iter()
+        .filter(|c| !c.ok)
+        .collect();
+        assert_eq!(
+            failed,
+            vec![VerifyCheck::new(
+                "decimals",
+                false,
+                "deployment 6, token decimals() 18".to_string()
+            )]
+        );
+
+        // An unreadable decimals() is a failure too, not a pass.
+        let unreadable = verify_checks(
+            &dep,
+            &DeploymentFacts {
+                token_decimals: None,
+                ..facts
+            },
+        );
+        assert!(unreadable.iter().any(|c| c.name == "decimals" && !c.ok));
+    }
+
+    #[test]
+    fn plan_preflight_flags_each_misconfiguration() {
+        let healthy = PlanInfo {
+            merchant: Address::repeat_byte(0x71),
+            token: Address::repeat_byte(0x70),
+            price: U256::from(1_000_000),
+            interval: 30 * 86_400,
+            collector_fee_bps: 100,
+            active: true,
+            created_at: 1_700_000_000,
+        };
+        let (min, max) = (3_600, 366 * 86_400);
+        assert!(plan_sanity_issues(&healthy, min, max).is_empty());
+
+        let issues = |plan: PlanInfo| plan_sanity_issues(&plan, min, max);
+        assert_eq!(
+            issues(PlanInfo {
+                price: U256::zero(),
+                ..healthy.clone()
+            }),
+            vec!["price is 0"]
+        );
+        assert_eq!(
+            issues(PlanInfo {
+                interval: 60,
+                ..healthy.clone()
+            }),
+            vec!["interval 60s is below --min-interval 3600s"]
+        );
+        assert_eq!(
+            issues(PlanInfo {
+                interval: 10 * 365 * 86_400,
+                ..healthy.clone()
+            }),
+            vec!["interval 315360000s is above --max-interval 31622400s"]
+        );
+        assert_eq!(
+            issues(PlanInfo {
+                collector_fee_bps: 10_001,
+                ..healthy.clone()
+            }),
+            vec!["collectorFeeBps 10001 exceeds 10000 (100%)"]
+        );
+        // Bounds are inclusive; issues accumulate.
+        assert!(plan_sanity_issues(
+            &PlanInfo {
+                interval: min,
+                collector_fee_bps: 10_000,
+                ..healthy.clone()
+            },
+            min,
+            max
+        )
+        .is_empty());
+        assert_eq!(
+            issues(PlanInfo {
+                price: U256::zero(),
+                interval: 1,
+                ..healthy
+            })
+            .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn plan_price_is_formatted_in_token_units() {
+        let amount = |n: u64, decimals: u8| format_token_amount(U256::from(n), decimals);
+        assert_eq!(amount(1_500_000, 6), "1.5");
+        assert_eq!(amount(10_000_000, 6), "10");
+        assert_eq!(amount(1, 6), "0.000001");
+        assert_eq!(amount(123_456_789, 6), "123.456789");
+        assert_eq!(amount(0, 6), "0");
+        assert_eq!(amount(42, 0), "42");
+        // Collector fee bps as a percentage.
+        assert_eq!(amount(250, 2), "2.5");
+        assert_eq!(format_token_amount(U256::exp10(18) * 3 / 4, 18), "0.75");
+        assert_eq!(
+            format_token_amount(U256::MAX, 18),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+    }
+
+    #[test]
+    fn quote_prorates_the_price_to_30_days() {
+        const DAY: u64 = 86_400;
+        let quote = |price: u64, interval: u64| cost_per_30_days(U256::from(price), interval);
+
+        // Monthly: unchanged.
+        assert_eq!(quote(10_000_000, 30 * DAY).unwrap(), U256::from(10_000_000));
+        // Shorter intervals scale up.
+        assert_eq!(quote(1_000_000, 7 * DAY).unwrap(), U256::from(4_285_714));
+        assert_eq!(quote(250_000, DAY).unwrap(), U256::from(7_500_000));
+        assert_eq!(quote(1, 3_600).unwrap(), U256::from(720));
+        // Longer intervals are prorated down.
+        assert_eq!(quote(20_000_000, 60 * DAY).unwrap(), U256::from(10_000_000));
+        assert_eq!(
+            quote(120_000_000, 365 * DAY).unwrap(),
+            U256::from(9_863_013)
+        );
+        // Rounds down to a base unit.
+        assert_eq!(quote(1, 31 * DAY).unwrap(), U256::zero());
+        // No intermediate overflow for huge prices.
+        assert_eq!(
+            cost_per_30_days(U256::MAX, 60 * DAY).unwrap(),
+            U256::MAX / 2
+        );
+        assert!(cost_per_30_days(U256::MAX, DAY).is_err());
+        // A zero interval has no per-period cost.
+        assert!(quote(1_000_000, 0).is_err());
+
+        assert_eq!(format_interval(30 * DAY), "30 days");
+        assert_eq!(format_interval(DAY), "1 day");
+        assert_eq!(format_interval(3_600), "3600s");
+    }
+
+    #[test]
+    fn owner_env_file_matches_shell_and_dotenv_syntax() {
+        let dir = std::env::temp_dir().join(format!(
+            "opensub-aa-owner-env-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let owner = Address::repeat_byte(0x42);
+        let key = format!("0x{}", "11".repeat(32));
+
+        for (format, prefix) in [
+            (OwnerEnvFormat::Shell, "export "),
+            (OwnerEnvFormat::Dotenv, ""),
+        ] {
+            let path = dir.join(format!("{format:?}.env"));
+            write_owner_env_file(&path, owner, &key, "test", format).unwrap();
+            let raw = std::fs::read_to_string(&path).unwrap();
+            assert!(raw.contains("# DO NOT COMMIT THIS FILE."), "{raw}");
+
+            let vars: Vec<(&str, &str)> = raw
+                .lines()
+                .filter(|l| !l.starts_with('#') && !l.is_empty())
+                .map(|l| {
+                    // Shell: every assignment is exported. Dotenv: bare `KEY=VALUE`, no keyword.
+                    let assignment = l.strip_prefix(prefix).unwrap();
+                    assert!(!assignment.starts_with("export"), "{l}");
+                    let (k, v) = assignment.split_once('=').unwrap();
+                    assert!(k.chars().all(|c| c.is_ascii_uppercase() || c == '_'), "{l}");
+                    assert!(!v.contains(char::is_whitespace), "{l}");
+                    (k, v)
+                })
+                .collect();
+            assert_eq!(
+                vars,
+                vec![
+                    ("OPENSUB_AA_OWNER_PRIVATE_KEY", key.as_str()),
+                    ("OPENSUB_AA_OWNER_ADDRESS", format!("{owner:?}").as_str()),
+                ]
+            );
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn owner_update_calldata_transfers_to_new_owner() {
+        let new_owner = Address::repeat_byte(0x42);
+
+        let data =
+            encode_owner_update("function transferOwnership(address newOwner)", new_owner).unwrap();
+        // transferOwnership(address)
+        assert_eq!(&data[..4], &[0xf2, 0xfd, 0xe3, 0x8b]);
+        assert_eq!(data.len(), 4 + 32);
+        assert_eq!(&data[4 + 12..], new_owner.as_bytes());
+
+        // Custom signatures work with or without the `function` keyword.
+        let custom = encode_owner_update("setOwner(address owner)", new_owner).unwrap();
+        let set_owner = AbiParser::default()
+            .parse_function("function setOwner(address owner)")
+            .unwrap();
+        assert_eq!(&custom[..4], &set_owner.short_signature());
+        assert_eq!(
+            set_owner.decode_input(&custom[4..]).unwrap(),
+            vec![Token::Address(new_owner)]
+        );
+
+        assert!(encode_owner_update("function setOwner(uint256)", new_owner).is_err());
+        assert!(encode_owner_update("function rotate(address,address)", new_owner).is_err());
+        assert!(encode_owner_update("not a signature", new_owner).is_err());
+    }
+
+    #[test]
+    fn approve_calldata_ignores_the_declared_return_type() {
+        let spender = Address::repeat_byte(0x11);
+        let amount = U256::from(1_000_000u64);
+
+        let default = encode_approve(DEFAULT_APPROVE_SIG, spender, amount).unwrap();
+        for sig in [
+            "function approve(address spender, uint256 amount) returns (bool)",
+            "approve(address,uint256)",
+            "approve(address spender, uint256 amount) returns (uint256)",
+        ] {
+            assert_eq!(
+                encode_approve(sig, spender, amount).unwrap(),
+                default,
+                "{sig}"
+            );
+        }
+        // approve(address,uint256)
+        assert_eq!(&default[..4], &[0x09, 0x5e, 0xa7, 0xb3]);
+        assert_eq!(default.len(), 4 + 64);
+
+        // A different function name changes only the selector.
+        let custom = encode_approve("safeApprove(address,uint256)", spender, amount).unwrap();
+        assert_ne!(&custom[..4], &default[..4]);
+        assert_eq!(&custom[4..], &default[4..]);
+
+        assert!(encode_approve("approve(address)", spender, amount).is_err());
+        assert!(encode_approve("approve(uint256,address)", spender, amount).is_err());
+        assert!(encode_approve("not a signature", spender, amount).is_err());
+    }
+
+    #[test]
+    fn after_periods_selects_cancel_after() {
+        let cancel_fn = AbiParser::default().parse_function(CANCEL_SIG).unwrap();
+        let cancel_after_fn = AbiParser::default()
+            .parse_function(CANCEL_AFTER_SIG)
+            .unwrap();
+        assert_eq!(cancel_after_fn.short_signature(), cancel_after_selector());
+
+        let immediate = encode_cancel(U256::from(7), false, None).unwrap();
+        assert_eq!(&immediate[..4], &cancel_fn.short_signature());
+        assert_eq!(
+            cancel_fn.decode_input(&immediate[4..]).unwrap(),
+            vec![Token::Uint(U256::from(7)), Token::Bool(false)]
+        );
+
+        let after = encode_cancel(U256::from(7), false, Some(3)).unwrap();
+        assert_eq!(&after[..4], &cancel_after_fn.short_signature());
+        assert_eq!(
+            cancel_after_fn.decode_input(&after[4..]).unwrap(),
+            vec![Token::Uint(U256::from(7)), Token::Uint(U256::from(3))]
+        );
+
+        // --revoke batches the same cancel call.
+        let open_sub = Address::repeat_byte(0x11);
+        let calls = cancel_and_revoke_calls(
+            open_sub,
+            Address::repeat_byte(0x22),
+            U256::from(7),
+            false,
+            Some(3),
+            DEFAULT_APPROVE_SIG,
+        )
+        .unwrap();
+        assert_eq!(calls[0], (open_sub, after));
+    }
+
+    #[test]
+    fn cancel_revoke_batch_cancels_then_revokes() {
+        let open_sub = Address::repeat_byte(0x11);
+        let token = Address::repeat_byte(0x22);
+        let calls = cancel_and_revoke_calls(
+            open_sub,
+            token,
+            U256::from(7),
+            true,
+            None,
+            DEFAULT_APPROVE_SIG,
+        )
+        .unwrap();
+        let data = encode_execute_batch(&calls).unwrap();
+
+        let execute_batch_fn = AbiParser::default()
+            .parse_function("function executeBatch(address[] dest, bytes[] func)")
+            .unwrap();
+        assert_eq!(&data[..4], &execute_batch_fn.short_signature());
+        let decoded = execute_batch_fn.decode_input(&data[4..]).unwrap();
+        assert_eq!(
+            decoded[0],
+            Token::Array(vec![Token::Address(open_sub), Token::Address(token)])
+        );
+        let funcs = match &decoded[1] {
+            Token::Array(funcs) => funcs.clone(),
+            other => panic!("unexpected token {other:?}"),
+        };
+        assert_eq!(funcs.len(), 2);
+
+        let cancel_fn = AbiParser::default()
+            .parse_function("function cancel(uint256 subscriptionId, bool atPeriodEnd)")
+            .unwrap();
+        let approve_fn = AbiParser::default()
+            .parse_function("function approve(address spender, uint256 amount) returns (bool)")
+            .unwrap();
+        let (Token::Bytes(first), Token::Bytes(second)) = (&funcs[0], &funcs[1]) else {
+            panic!("expected bytes calls");
+        };
+        assert_eq!(&first[..4], &cancel_fn.short_signature());
+        assert_eq!(
+            cancel_fn.decode_input(&first[4..]).unwrap(),
+            vec![Token::Uint(U256::from(7)), Token::Bool(true)]
+        );
+        assert_eq!(&second[..4], &approve_fn.short_signature());
+        assert_eq!(
+            approve_fn.decode_input(&second[4..]).unwrap(),
+            vec![Token::Address(open_sub), Token::Uint(U256::zero())]
+        );
+    }
+
+    #[test]
+    fn same_owner_seed_yields_same_address() {
+        let (_, a, key_a) = wallet_from_seed("0xc0ffee", 84532).unwrap();
+        let (_, b, key_b) = wallet_from_seed("c0ffee", 84532).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(key_a, key_b);
+
+        let (_, other, _) = wallet_from_seed("0xc0ffef", 84532).unwrap();
+        assert_ne!(a, other);
+
+        assert!(wallet_from_seed("0x", 84532).is_err());
+        assert!(wallet_from_seed("not-hex", 84532).is_err());
+    }
+
+    #[test]
+    fn validity_window_time_range_encoding() {
+        let now = 1_700_000_000u64;
+        let w = ValidityWindow::from_now(now, 600).unwrap();
+        assert_eq!(w.valid_until, now + 600);
+        assert_eq!(w.valid_after, now - 60);
+
+        let sig = [0xabu8; 65];
+        let encoded = w.encode_signature(&sig);
+        assert_eq!(encoded.len(), 12 + 65);
+        // uint48 big-endian validUntil, then validAfter, then the raw signature.
+        let mut until = [0u8; 8];
+        until[2..].copy_from_slice(&encoded[..6]);
+        let mut after = [0u8; 8];
+        after[2..].copy_from_slice(&encoded[6..12]);
+        assert_eq!(u64::from_be_bytes(until), now + 600);
+        assert_eq!(u64::from_be_bytes(after), now - 60);
+        assert_eq!(&encoded[12..], &sig[..]);
+
+        // The signed digest commits to the window.
+        let hash = H256::repeat_byte(0x11);
+        let other = ValidityWindow::from_now(now, 601).unwrap();
+        assert_ne!(w.digest(hash), other.digest(hash));
+        assert_ne!(w.digest(hash), hash);
+
+        assert!(ValidityWindow::from_now(now, 0).is_err());
+        assert!(ValidityWindow::from_now(now, u64::MAX).is_err());
+
+        // Gas is estimated with a dummy as long as the signature that will be sent.
+        assert_eq!(dummy_signature(None).len(), 65);
+        assert_eq!(dummy_signature(Some(w)).len(), encoded.len());
+    }
+
+    #[test]
+    fn custom_subscribe_sig_produces_expected_selector() {
+        let beneficiary = Address::repeat_byte(0x22);
+        let call = SubscribeCall::parse(
+            Some("subscribe(uint256 planId, address beneficiary)"),
+            &["{planId}".to_string(), format!("{beneficiary:#x}")],
+        )
+        .unwrap();
+        let data = call.encode(U256::from(9)).unwrap();
+
+        let selector = ethers::utils::id("subscribe(uint256,address)");
+        assert_eq!(&data[..4], &selector[..4]);
+        let decoded = call.function.decode_input(&data[4..]).unwrap();
+        assert_eq!(
+            decoded,
+            vec![Token::Uint(U256::from(9)), Token::Address(beneficiary)]
+        );
+
+        // Without overrides we keep the original single-arg subscribe.
+        let default = SubscribeCall::parse(None, &[]).unwrap();
+        let data = default.encode(U256::from(9)).unwrap();
+        assert_eq!(&data[..4], &ethers::utils::id("subscribe(uint256)")[..4]);
+    }
+
+    #[test]
+    fn invalid_subscribe_overrides_are_rejected() {
+        assert!(SubscribeCall::parse(Some("subscribe(uint256 planId"), &[]).is_err());
+        // Two parameters need explicit args.
+        assert!(SubscribeCall::parse(Some("subscribe(uint256,address)"), &[]).is_err());
+        assert!(SubscribeCall::parse(
+            Some("subscribe(uint256,address)"),
+            &["{planId}".to_string(), "not-an-address".to_string()],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn native_subscribe_call_wraps_subscribe_in_execute_with_value() {
+        let open_sub = Address::repeat_byte(0x11);
+        let data = encode_native_subscribe_call(
+            open_sub,
+            &SubscribeCall::default(),
+            U256::from(5),
+            U256::from(1_000u64),
+        )
+        .unwrap();
+
+        let execute_fn = AbiParser::default()
+            .parse_function("function execute(address dest, uint256 value, bytes func)")
+            .unwrap();
+        assert_eq!(&data[..4], &execute_fn.short_signature());
+
+        let decoded = execute_fn.decode_input(&data[4..]).unwrap();
+        assert_eq!(decoded[0], Token::Address(open_sub));
+        assert_eq!(decoded[1], Token::Uint(U256::from(1_000u64)));
+
+        let subscribe_fn = AbiParser::default()
+            .parse_function("function subscribe(uint256 planId) returns (uint256)")
+            .unwrap();
+        let inner = match &decoded[2] {
+            Token::Bytes(b) => b.clone(),
+            other => panic!("unexpected token {other:?}"),
+        };
+        assert_eq!(&inner[..4], &subscribe_fn.short_signature());
+        let inner_args = subscribe_fn.decode_input(&inner[4..]).unwrap();
+        assert_eq!(inner_args, vec![Token::Uint(U256::from(5))]);
+    }
+
+    #[test]
+    fn parse_salt_range_accepts_half_open_ranges() {
+        assert_eq!(parse_salt_range("0..10").unwrap(), 0..10);
+        assert_eq!(parse_salt_range(" 5 .. 6 ").unwrap(), 5..6);
+    }
+
+    #[test]
+    fn parse_salt_range_rejects_bad_input() {
+        assert!(parse_salt_range("10").is_err());
+        assert!(parse_salt_range("a..3").is_err());
+        assert!(parse_salt_range("3..3").is_err());
+        assert!(parse_salt_range("4..3").is_err());
+        assert!(parse_salt_range("0..100000").is_err());
+    }
+
+    #[tokio::test]
+    async fn map_salts_ordered_returns_results_in_salt_order() {
+        // Later salts finish first; output must still be ordered by salt.
+        let out = map_salts_ordered(0..6, 4, |salt| async move {
+            tokio::time::sleep(Duration::from_millis(30 - salt * 5)).await;
+            Ok(salt * 100)
+        })
+        .await
+        .unwrap();
+        assert_eq!(out, vec![0, 100, 200, 300, 400, 500]);
+    }
+
+    /// Returns a fixed signature and records the hashes it was asked to sign.
+    struct FixedSigner {
+        signature: Bytes,
+        signed: std::sync::Mutex<Vec<H256>>,
+    }
+
+    #[async_trait::async_trait]
+    impl UserOpSigner for FixedSigner {
+        async fn sign(&self, user_op_hash: H256) -> anyhow::Result<Bytes> {
+            self.signed.lock().unwrap().push(user_op_hash);
+            Ok(self.signature.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_pluggable_signer_fills_the_op_signature() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let user_op_hash = H256::repeat_byte(0x42);
+        let signer = FixedSigner {
+            signature: Bytes::from(vec![0xab; 97]),
+            signed: Default::default(),
+        };
+        let mut op = UserOperation {
+            sender: Address::repeat_byte(0x0a),
+            nonce: U256::zero(),
+            init_code: Bytes::default(),
+            call_data: Bytes::default(),
+            call_gas_limit: U256::zero(),
+            verification_gas_limit: U256::zero(),
+            pre_verification_gas: U256::zero(),
+            max_fee_per_gas: U256::zero(),
+            max_priority_fee_per_gas: U256::zero(),
+            paymaster_and_data: Bytes::default(),
+            signature: Bytes::from(vec![0u8; 65]),
+        };
+        let entrypoint = EntryPointVersion::V06.canonical_address();
+
+        // Signed as is: the EntryPoint's userOpHash in, the signer's bytes out.
+        mock.push::<Bytes, _>(Bytes::from(user_op_hash.as_bytes().to_vec()))
+            .unwrap();
+        sign_userop(client.clone(), entrypoint, &mut op, &signer, None)
+            .await
+            .unwrap();
+        assert_eq!(op.signature, signer.signature);
+
+        // With a time range, the signer gets the window digest and the window prefixes its bytes.
+        let window = ValidityWindow::from_now(1_700_000_000, 600).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(user_op_hash.as_bytes().to_vec()))
+            .unwrap();
+        sign_userop(client, entrypoint, &mut op, &signer, Some(window))
+            .await
+            .unwrap();
+        assert_eq!(op.signature, window.encode_signature(&signer.signature));
+        assert_eq!(
+            *signer.signed.lock().unwrap(),
+            vec![user_op_hash, window.digest(user_op_hash)]
+        );
+    }
+
+    #[test]
+    fn funding_below_one_period_is_flagged() {
+        // 10 USDC (6 decimals).
+        let price = U256::from(10_000_000u64);
+
+        // Enough for a period: nothing to report.
+        assert!(funding_issues(price, None, 12, None).is_empty());
+        assert!(
+            funding_issues(price, Some(price), 12, Some(U256::from(120_000_000u64))).is_empty()
+        );
+
+        // Decimals forgotten: `10` instead of `10000000`.
+        let issues = funding_issues(price, Some(U256::from(10)), 12, Some(U256::from(10)));
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert!(issues[0].starts_with("--allowance-amount 10 is below the plan price 10000000"));
+        assert!(issues[1].starts_with("--mint 10 is below the plan price 10000000"));
+
+        // Zero periods only matter when the allowance is derived from them.
+        let issues = funding_issues(price, None, 0, None);
+        assert_eq!(
+            issues,
+            vec!["--allowance-periods 0 approves less than one period of the plan price"]
+        );
+        assert!(funding_issues(price, Some(price), 0, None).is_empty());
+    }
+
+    #[test]
+    fn headroom_adds_periods_to_the_derived_allowance() {
+        let usdc = |n: u64| U256::from(n * 1_000_000);
+
+        // One plan: price * (periods + headroom).
+        assert_eq!(derived_allowance([usdc(10)], 12, 0).unwrap(), usdc(120));
+        assert_eq!(derived_allowance([usdc(10)], 12, 2).unwrap(), usdc(140));
+        // Several plans: each gets the headroom.
+        assert_eq!(
+            derived_allowance([usdc(10), usdc(5)], 1, 1).unwrap(),
+            usdc(30)
+        );
+        assert_eq!(derived_allowance([usdc(10)], 0, 0).unwrap(), U256::zero());
+        assert!(derived_allowance([U256::MAX], 1, 1).is_err());
+        assert!(derived_allowance([usdc(1)], u64::MAX, 1).is_err());
+
+        // Exactly one period (or just under two) is flagged; two or more, or under one, is not.
+        let warning = headroom_warning(usdc(10), usdc(10)).unwrap();
+        assert!(warning.contains("fewer than 2 periods"), "{warning}");
+        assert!(headroom_warning(usdc(10), usdc(20) - 1).is_some());
+        assert!(headroom_warning(usdc(10), usdc(20)).is_none());
+        assert!(headroom_warning(usdc(10), usdc(9)).is_none());
+        assert!(headroom_warning(U256::zero(), U256::zero()).is_none());
+        // --allowance-periods 1 --allowance-headroom-periods 1 clears it.
+        let allowance = derived_allowance([usdc(10)], 1, 1).unwrap();
+        assert!(headroom_warning(usdc(10), allowance).is_none());
+    }
+
+    #[test]
+    fn manual_gas_limits_skip_estimation_only_when_complete() {
+        // Nothing set: estimate via the bundler.
+        assert!(manual_gas_limits(&GasArgs::default()).unwrap().is_none());
+
+        // All three: skip estimation and use them as-is.
+        let est = manual_gas_limits(&GasArgs {
+            call_gas_limit: Some(200_000),
+            verification_gas_limit: Some(500_000),
+            pre_verification_gas: Some(60_000),
+            ..Default::default()
+        })
+        .unwrap()
+        .expect("manual limits");
+        assert_eq!(est.call_gas_limit, U256::from(200_000));
+        assert_eq!(est.verification_gas_limit, U256::from(500_000));
+        assert_eq!(est.pre_verification_gas, U256::from(60_000));
+
+        // Partial: error.
+        assert!(manual_gas_limits(&GasArgs {
+            call_gas_limit: Some(200_000),
+            ..Default::default()
+        })
+        .is_err());
+
+        // ...which the command line already rejects, before any tx (e.g. `--fund-eth`) is sent.
+        let err = Cli::try_parse_from([
+            "opensub-aa",
+            "subscribe",
+            "--fund-eth",
+            "0.01",
+            "--call-gas-limit",
+            "200000",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(
+            err.to_string().contains("--verification-gas-limit"),
+            "{err}"
+        );
+        assert!(Cli::try_parse_from([
+            "opensub-aa",
+            "subscribe",
+            "--entrypoint",
+            "0x5FF137D4b0FDCD49DcA30c7CF57E578a026d2789",
+            "--factory",
+            "0x9406Cc6185a346906296840746125a0E44976454",
+            "--call-gas-limit",
+            "200000",
+            "--verification-gas-limit",
+            "500000",
+            "--pre-verification-gas",
+            "60000",
+        ])
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn access_at_reads_against_the_requested_block() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let open_sub = Address::repeat_byte(0x05);
+        let id = U256::from(9);
+        let at = Some(BlockNumber::Number(1_234u64.into()));
+
+        // Responses pop from the back: subscriptions() first, then hasAccess().
+        mock.push::<Bytes, _>(Bytes::from(ethers::abi::encode(&[Token::Bool(true)])))
+            .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(ethers::abi::encode(&[
+            Token::Uint(U256::from(2)),
+            Token::Address(Address::repeat_byte(0xaa)),
+            Token::Uint(U256::from(1)),
+            Token::Uint(U256::from(100)),
+            Token::Uint(U256::from(200)),
+            Token::Uint(U256::from(100)),
+        ])))
+        .unwrap();
+
+        let sub = read_subscription(client.clone(), open_sub, id, at)
+            .await
+            .unwrap();
+        assert_eq!(
+            (sub.plan_id, sub.status, sub.paid_through),
+            (U256::from(2), 1, 200)
+        );
+        assert!(has_access(client.clone(), open_sub, id, at).await.unwrap());
+
+        let expected = |sig: &str, name: &str| {
+            let abi = AbiParser::default().parse(&[sig]).unwrap();
+            Contract::new(open_sub, abi, client.clone())
+                .method::<_, Token>(name, id)
+                .unwrap()
+                .tx
+        };
+        let block = BlockId::from(BlockNumber::Number(1_234u64.into()));
+        mock.assert_request(
+            "eth_call",
+            (
+                expected(
+                    "function subscriptions(uint256) view returns (uint256 planId,address subscriber,uint8 status,uint40 startTime,uint40 paidThrough,uint40 lastChargedAt)",
+                    "subscriptions",
+                ),
+                block,
+            ),
+        )
+        .unwrap();
+        mock.assert_request(
+            "eth_call",
+            (
+                expected(
+                    "function hasAccess(uint256 subscriptionId) view returns (bool)",
+                    "hasAccess",
+                ),
+                block,
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn pruned_state_errors_point_at_an_archive_rpc() {
+        let err = historical_read_error(
+            anyhow::anyhow!("(code: -32000, message: missing trie node 0xabc, data: None)"),
+            1_234,
+        );
+        assert!(err.to_string().contains("archive node"), "{err}");
+        assert!(err.to_string().contains("block 1234"), "{err}");
+
+        let err = historical_read_error(anyhow::anyhow!("connection refused"), 1_234);
+        assert_eq!(err.to_string(), "connection refused");
+    }
+
+    #[test]
+    fn collect_batch_has_one_collect_per_id() {
+        let open_sub = Address::repeat_byte(0x05);
+        let calls = collect_calls(open_sub, &[7, 3, 12]).unwrap();
+        let collect = AbiParser::default()
+            .parse_function("function collect(uint256 subscriptionId)")
+            .unwrap();
+        let ids: Vec<Token> = calls
+            .iter()
+            .map(|(to, data)| {
+                assert_eq!(*to, open_sub);
+                assert_eq!(&data[..4], collect.short_signature().as_slice());
+                collect.decode_input(&data[4..]).unwrap().remove(0)
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                Token::Uint(U256::from(7)),
+                Token::Uint(U256::from(3)),
+                Token::Uint(U256::from(12)),
+            ]
+        );
+
+        let batch = encode_execute_batch(&calls).unwrap();
+        let execute_batch = AbiParser::default()
+            .parse_function("function executeBatch(address[] dest, bytes[] func)")
+            .unwrap();
+        let tokens = execute_batch.decode_input(&batch[4..]).unwrap();
+        assert_eq!(tokens[0], Token::Array(vec![Token::Address(open_sub); 3]));
+
+        assert!(collect_calls(open_sub, &[]).is_err());
+        let err = collect_calls(open_sub, &[7, 3, 7]).unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{err}");
+    }
+
+    #[test]
+    fn bulk_approve_batches_one_approve_per_token_in_order() {
+        let open_sub = Address::repeat_byte(0x05);
+        let usdc = Address::repeat_byte(0x0a);
+        let dai = Address::repeat_byte(0x0b);
+        let approvals =
+            pair_approvals(&[dai, usdc], &["1000".to_string(), "250".to_string()]).unwrap();
+        assert_eq!(
+            approvals,
+            vec![(dai, U256::from(1000)), (usdc, U256::from(250))]
+        );
+
+        let calls = bulk_approve_calls(open_sub, &approvals, DEFAULT_APPROVE_SIG).unwrap();
+        let approve = AbiParser::default()
+            .parse_function("function approve(address spender, uint256 amount) returns (bool)")
+            .unwrap();
+        let decoded: Vec<(Address, Vec<Token>)> = calls
+            .iter()
+            .map(|(to, data)| {
+                assert_eq!(&data[..4], approve.short_signature().as_slice());
+                (*to, approve.decode_input(&data[4..]).unwrap())
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (
+                    dai,
+                    vec![Token::Address(open_sub), Token::Uint(U256::from(1000))]
+                ),
+                (
+                    usdc,
+                    vec![Token::Address(open_sub), Token::Uint(U256::from(250))]
+                ),
+            ]
+        );
+
+        // The batch keeps the same order.
+        let batch = encode_execute_batch(&calls).unwrap();
+        let execute_batch = AbiParser::default()
+            .parse_function("function executeBatch(address[] dest, bytes[] func)")
+            .unwrap();
+        let tokens = execute_batch.decode_input(&batch[4..]).unwrap();
+        assert_eq!(
+            tokens[0],
+            Token::Array(vec![Token::Address(dai), Token::Address(usdc)])
+        );
+
+        // Rejected inputs.
+        assert!(bulk_approve_calls(open_sub, &[], DEFAULT_APPROVE_SIG).is_err());
+        let err = bulk_approve_calls(
+            open_sub,
+            &[
+                (dai, U256::one()),
+                (usdc, U256::one()),
+                (dai, U256::from(2)),
+            ],
+            DEFAULT_APPROVE_SIG,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{err}");
+        assert!(pair_approvals(&[dai, usdc], &["1".to_string()]).is_err());
+        assert!(pair_approvals(&[dai], &["0x10".to_string()]).is_err());
+    }
+}
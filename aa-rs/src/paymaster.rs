@@ -1,8 +1,113 @@
 use crate::encoding;
+use crate::http::{ClientOptions, JsonRpcHttp, RpcDump};
+use crate::types::EntryPointVersion;
 use anyhow::{anyhow, Context, Result};
 use ethers::types::{Address, Bytes, U256};
 use serde_json::Value;
 
+/// Outcome of `--paymaster-dry-run`: did the paymaster agree to sponsor the op?
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SponsorshipCheck {
+    /// Stub data was returned, so sponsorship is likely (the final data can still be refused).
+    Sponsored(Bytes),
+    /// The paymaster answered with a JSON-RPC error (policy limits, allowlist, ...).
+    Rejected(PolicyRejection),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRejection {
+    pub code: Option<i64>,
+    pub message: String,
+    /// Extra detail from the error's `data` field, when present.
+    pub reason: Option<String>,
+}
+
+/// A `paymasterAndData` blob split into its fields (`decode-paymaster-data`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedPaymasterData {
+    pub paymaster: Address,
+    /// v0.7 only: `paymasterVerificationGasLimit`.
+    pub verification_gas_limit: Option<u128>,
+    /// v0.7 only: `paymasterPostOpGasLimit`.
+    pub post_op_gas_limit: Option<u128>,
+    /// Everything after the fixed fields; its format is up to the paymaster.
+    pub paymaster_data: Bytes,
+    /// `paymaster_data` read as `abi.encode(uint48 validUntil, uint48 validAfter) || signature`,
+    /// the eth-infinitism `VerifyingPaymaster` layout most signing paymasters follow. `None` when
+    /// the data doesn't fit it.
+    pub verifying: Option<VerifyingPaymasterData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyingPaymasterData {
+    pub valid_until: u64,
+    pub valid_after: u64,
+    pub signature: Bytes,
+}
+
+/// Split `paymasterAndData`:
+///
+/// - v0.6: `paymaster (20) || paymasterData`
+/// - v0.7: `paymaster (20) || paymasterVerificationGasLimit (16) || paymasterPostOpGasLimit (16)
+///   || paymasterData`
+pub fn decode_paymaster_and_data(
+    blob: &[u8],
+    version: EntryPointVersion,
+) -> Result<DecodedPaymasterData> {
+    if blob.is_empty() {
+        return Err(anyhow!(
+            "paymasterAndData is empty (the op has no paymaster)"
+        ));
+    }
+    let fixed = match version {
+        EntryPointVersion::V06 => 20,
+        EntryPointVersion::V07 => 52,
+    };
+    if blob.len() < fixed {
+        return Err(anyhow!(
+            "paymasterAndData is {} bytes; EntryPoint {version} needs at least {fixed}",
+            blob.len()
+        ));
+    }
+
+    let gas_limit = |range: std::ops::Range<usize>| {
+        let mut word = [0u8; 16];
+        word.copy_from_slice(&blob[range]);
+        u128::from_be_bytes(word)
+    };
+    let (verification_gas_limit, post_op_gas_limit) = match version {
+        EntryPointVersion::V06 => (None, None),
+        EntryPointVersion::V07 => (Some(gas_limit(20..36)), Some(gas_limit(36..52))),
+    };
+    let paymaster_data = &blob[fixed..];
+
+    Ok(DecodedPaymasterData {
+        paymaster: Address::from_slice(&blob[..20]),
+        verification_gas_limit,
+        post_op_gas_limit,
+        paymaster_data: Bytes::from(paymaster_data.to_vec()),
+        verifying: verifying_layout(paymaster_data),
+    })
+}
+
+/// Two uint48 words followed by a 64- or 65-byte ECDSA signature.
+fn verifying_layout(data: &[u8]) -> Option<VerifyingPaymasterData> {
+    if !matches!(data.len().checked_sub(64)?, 64 | 65) {
+        return None;
+    }
+    let uint48 = |word: &[u8]| {
+        word[..26]
+            .iter()
+            .all(|b| *b == 0)
+            .then(|| U256::from_big_endian(word).as_u64())
+    };
+    Some(VerifyingPaymasterData {
+        valid_until: uint48(&data[..32])?,
+        valid_after: uint48(&data[32..64])?,
+        signature: Bytes::from(data[64..].to_vec()),
+    })
+}
+
 /// Minimal ERC-7677 paymaster web service client.
 ///
 /// Milestone 6B uses this with Alchemy Gas Manager on Base Sepolia.
@@ -11,18 +116,45 @@ use serde_json::Value;
 /// `pm_getPaymasterData`) so the CLI remains vendor-portable.
 #[derive(Debug, Clone)]
 pub struct PaymasterClient {
-    url: String,
-    http: reqwest::Client,
+    rpc: JsonRpcHttp,
+    /// `(validAfter, validUntil)` requested for the sponsorship, if any.
+    time_range: Option<(u64, u64)>,
 }
 
 impl PaymasterClient {
     pub fn new(url: String) -> Self {
         Self {
-            url,
-            http: reqwest::Client::new(),
+            rpc: JsonRpcHttp::new(url),
+            time_range: None,
         }
     }
 
+    /// Log request/response bodies at debug level (`--http-trace`).
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.rpc = self.rpc.with_trace(trace);
+        self
+    }
+
+    /// Write every request/response to `--dump-rpc-dir`.
+    pub fn with_dump(mut self, dump: Option<RpcDump>) -> Self {
+        self.rpc = self.rpc.with_dump(dump);
+        self
+    }
+
+    pub fn with_client_options(mut self, options: &ClientOptions) -> Result<Self> {
+        self.rpc = self.rpc.with_client_options(options)?;
+        Ok(self)
+    }
+
+    /// Ask the paymaster to bound its sponsorship to `validAfter..=validUntil`.
+    ///
+    /// Sent as `validAfter` / `validUntil` in the ERC-7677 context; verifying paymasters encode the
+    /// range into `paymasterAndData`. Services that do not support it ignore the fields.
+    pub fn with_time_range(mut self, valid_after: u64, valid_until: u64) -> Self {
+        self.time_range = Some((valid_after, valid_until));
+        self
+    }
+
     pub async fn get_paymaster_stub_data(
         &self,
         user_op: Value,
@@ -31,7 +163,14 @@ impl PaymasterClient {
         policy_id: &str,
         webhook_data: Option<&str>,
     ) -> Result<Bytes> {
-        let params = build_params(user_op, entrypoint, chain_id, policy_id, webhook_data);
+        let params = build_params(
+            user_op,
+            entrypoint,
+            chain_id,
+            policy_id,
+            webhook_data,
+            self.time_range,
+        );
         let res = self
             .rpc("pm_getPaymasterStubData", params)
             .await
@@ -39,6 +178,36 @@ impl PaymasterClient {
         parse_v06_paymaster_and_data(&res)
     }
 
+    /// Like `get_paymaster_stub_data`, but a JSON-RPC error is reported as a rejection instead of
+    /// failing (transport / HTTP errors still fail).
+    pub async fn check_sponsorship(
+        &self,
+        user_op: Value,
+        entrypoint: Address,
+        chain_id: u64,
+        policy_id: &str,
+        webhook_data: Option<&str>,
+    ) -> Result<SponsorshipCheck> {
+        let params = build_params(
+            user_op,
+            entrypoint,
+            chain_id,
+            policy_id,
+            webhook_data,
+            self.time_range,
+        );
+        match self
+            .rpc_raw("pm_getPaymasterStubData", params)
+            .await
+            .context("pm_getPaymasterStubData RPC failed")?
+        {
+            Ok(res) => Ok(SponsorshipCheck::Sponsored(parse_v06_paymaster_and_data(
+                &res,
+            )?)),
+            Err(err) => Ok(SponsorshipCheck::Rejected(parse_policy_rejection(&err))),
+        }
+    }
+
     pub async fn get_paymaster_data(
         &self,
         user_op: Value,
@@ -47,7 +216,14 @@ impl PaymasterClient {
         policy_id: &str,
         webhook_data: Option<&str>,
     ) -> Result<Bytes> {
-        let params = build_params(user_op, entrypoint, chain_id, policy_id, webhook_data);
+        let params = build_params(
+            user_op,
+            entrypoint,
+            chain_id,
+            policy_id,
+            webhook_data,
+            self.time_range,
+        );
         let res = self
             .rpc("pm_getPaymasterData", params)
             .await
@@ -56,35 +232,15 @@ impl PaymasterClient {
     }
 
     async fn rpc(&self, method: &str, params: Value) -> Result<Value> {
-        let req = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": method,
-            "params": params,
-        });
-
-        let resp = self
-            .http
-            .post(&self.url)
-            .json(&req)
-            .send()
-            .await
-            .with_context(|| format!("POST {} failed", self.url))?;
-
-        let status = resp.status();
-        let body: Value = resp.json().await.context("failed to decode JSON")?;
-
-        if !status.is_success() {
-            return Err(anyhow!("HTTP {}: {}", status, body));
-        }
-
-        if let Some(err) = body.get("error") {
-            return Err(anyhow!("RPC error: {}", err));
-        }
+        self.rpc.call(method, params).await
+    }
 
-        body.get("result")
-            .cloned()
-            .ok_or_else(|| anyhow!("missing result field"))
+    async fn rpc_raw(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<std::result::Result<Value, Value>> {
+        self.rpc.call_raw(method, params).await
     }
 }
 
@@ -94,6 +250,7 @@ fn build_params(
     chain_id: u64,
     policy_id: &str,
     webhook_data: Option<&str>,
+    time_range: Option<(u64, u64)>,
 ) -> Value {
     let mut ctx = serde_json::json!({
         "policyId": policy_id,
@@ -106,6 +263,11 @@ fn build_params(
         }
     }
 
+    if let (Some((valid_after, valid_until)), Some(obj)) = (time_range, ctx.as_object_mut()) {
+        obj.insert("validAfter".to_string(), Value::from(valid_after));
+        obj.insert("validUntil".to_string(), Value::from(valid_until));
+    }
+
     serde_json::json!([
         user_op,
         encoding::fmt_address(entrypoint),
@@ -114,6 +276,33 @@ fn build_params(
     ])
 }
 
+fn parse_policy_rejection(err: &Value) -> PolicyRejection {
+    let code = err.get("code").and_then(|c| c.as_i64());
+    let message = err
+        .get("message")
+        .and_then(|m| m.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| err.to_string());
+
+    // `data` is free-form: a plain string, or an object with a `reason` / `message`.
+    let reason = match err.get("data") {
+        Some(Value::String(s)) if !s.is_empty() => Some(s.clone()),
+        Some(Value::Object(obj)) => obj
+            .get("reason")
+            .or_else(|| obj.get("message"))
+            .and_then(|r| r.as_str())
+            .map(str::to_string)
+            .or_else(|| Some(Value::Object(obj.clone()).to_string())),
+        _ => None,
+    };
+
+    PolicyRejection {
+        code,
+        message,
+        reason,
+    }
+}
+
 fn parse_v06_paymaster_and_data(result: &Value) -> Result<Bytes> {
     // ERC-7677 examples return v0.6 data at the top level:
     //   { "paymasterAndData": "0x..." }
@@ -150,8 +339,12 @@ fn parse_v06_paymaster_and_data(result: &Value) -> Result<Bytes> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_v06_paymaster_and_data;
-    use ethers::types::Bytes;
+    use super::{
+        build_params, decode_paymaster_and_data, parse_policy_rejection,
+        parse_v06_paymaster_and_data, PolicyRejection, VerifyingPaymasterData,
+    };
+    use crate::types::EntryPointVersion;
+    use ethers::types::{Address, Bytes};
     use serde_json::json;
 
     const PM_DATA: &str = "0xdeadbeef";
@@ -186,4 +379,118 @@ mod tests {
         let res = json!({ "entrypointV07Response": { "paymasterAndData": PM_DATA } });
         assert!(parse_v06_paymaster_and_data(&res).is_err());
     }
+
+    #[test]
+    fn parse_policy_rejection_with_reason_object() {
+        let err = json!({
+            "code": -32602,
+            "message": "Policy rejected user operation",
+            "data": { "reason": "sender not in allowlist" }
+        });
+        assert_eq!(
+            parse_policy_rejection(&err),
+            PolicyRejection {
+                code: Some(-32602),
+                message: "Policy rejected user operation".to_string(),
+                reason: Some("sender not in allowlist".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_policy_rejection_with_string_data_or_none() {
+        let err = json!({ "code": -32500, "message": "max spend exceeded", "data": "daily limit" });
+        let out = parse_policy_rejection(&err);
+        assert_eq!(out.reason.as_deref(), Some("daily limit"));
+
+        let err = json!({ "message": "unsupported entrypoint" });
+        let out = parse_policy_rejection(&err);
+        assert_eq!(out.code, None);
+        assert_eq!(out.message, "unsupported entrypoint");
+        assert_eq!(out.reason, None);
+    }
+
+    #[test]
+    fn time_range_is_sent_in_paymaster_context() {
+        let params = build_params(
+            json!({}),
+            Address::zero(),
+            84532,
+            "policy",
+            None,
+            Some((100, 700)),
+        );
+        let ctx = &params[3];
+        assert_eq!(ctx["policyId"], "policy");
+        assert_eq!(ctx["validAfter"], 100);
+        assert_eq!(ctx["validUntil"], 700);
+
+        let params = build_params(json!({}), Address::zero(), 84532, "policy", None, None);
+        assert!(params[3].get("validUntil").is_none());
+    }
+
+    /// `abi.encode(uint48 validUntil, uint48 validAfter)` followed by a 65-byte signature.
+    fn verifying_paymaster_data() -> Vec<u8> {
+        let mut data = vec![0u8; 64];
+        data[26..32].copy_from_slice(&1_700_003_600u64.to_be_bytes()[2..]);
+        data[58..64].copy_from_slice(&1_700_000_000u64.to_be_bytes()[2..]);
+        data.extend_from_slice(&[0x5a; 65]);
+        data
+    }
+
+    #[test]
+    fn decodes_v06_paymaster_and_data() {
+        let paymaster = Address::repeat_byte(0x9a);
+        let mut blob = paymaster.as_bytes().to_vec();
+        blob.extend(verifying_paymaster_data());
+
+        let decoded = decode_paymaster_and_data(&blob, EntryPointVersion::V06).unwrap();
+        assert_eq!(decoded.paymaster, paymaster);
+        assert_eq!(decoded.verification_gas_limit, None);
+        assert_eq!(decoded.post_op_gas_limit, None);
+        assert_eq!(decoded.paymaster_data.len(), 129);
+        assert_eq!(
+            decoded.verifying,
+            Some(VerifyingPaymasterData {
+                valid_until: 1_700_003_600,
+                valid_after: 1_700_000_000,
+                signature: Bytes::from(vec![0x5a; 65]),
+            })
+        );
+
+        // Opaque data (e.g. a vendor format) is kept as is, without a validity window.
+        let mut blob = paymaster.as_bytes().to_vec();
+        blob.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let decoded = decode_paymaster_and_data(&blob, EntryPointVersion::V06).unwrap();
+        assert_eq!(decoded.paymaster_data, expected_bytes());
+        assert_eq!(decoded.verifying, None);
+
+        assert!(decode_paymaster_and_data(&[], EntryPointVersion::V06).is_err());
+        assert!(decode_paymaster_and_data(&[0x9a; 19], EntryPointVersion::V06).is_err());
+    }
+
+    #[test]
+    fn decodes_v07_packed_paymaster_fields() {
+        let paymaster = Address::repeat_byte(0x9b);
+        let mut blob = paymaster.as_bytes().to_vec();
+        blob.extend_from_slice(&100_000u128.to_be_bytes());
+        blob.extend_from_slice(&50_000u128.to_be_bytes());
+        blob.extend(verifying_paymaster_data());
+
+        let decoded = decode_paymaster_and_data(&blob, EntryPointVersion::V07).unwrap();
+        assert_eq!(decoded.paymaster, paymaster);
+        assert_eq!(decoded.verification_gas_limit, Some(100_000));
+        assert_eq!(decoded.post_op_gas_limit, Some(50_000));
+        assert_eq!(
+            decoded.verifying.map(|v| (v.valid_until, v.valid_after)),
+            Some((1_700_003_600, 1_700_000_000))
+        );
+
+        // Read as v0.6, the gas limits would be taken for paymaster data; too short for v0.7 is
+        // an error rather than a misread.
+        let as_v06 = decode_paymaster_and_data(&blob, EntryPointVersion::V06).unwrap();
+        assert_eq!(as_v06.paymaster_data.len(), 32 + 129);
+        assert_eq!(as_v06.verifying, None);
+        assert!(decode_paymaster_and_data(&blob[..40], EntryPointVersion::V07).is_err());
+    }
 }
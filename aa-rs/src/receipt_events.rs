@@ -0,0 +1,357 @@
+use crate::encoding::{self, parse_h256, parse_u256_quantity};
+use ethers::abi::{AbiParser, Event, RawLog, Token};
+use ethers::types::{Address, Bytes, H256, U256};
+use serde_json::Value;
+use std::fmt;
+
+/// Events worth decoding in a userOp receipt: OpenSub's, ERC-20's and the EntryPoint's.
+const KNOWN_EVENTS: [&str; 8] = [
+    "event Subscribed(uint256 indexed subscriptionId, uint256 indexed planId, address indexed subscriber, uint40 startTime, uint40 paidThrough)",
+    "event Charged(uint256 indexed subscriptionId, uint256 indexed planId, address indexed subscriber, address token, uint256 amount, uint256 collectorFee, address collector, uint40 chargedAt, uint40 paidThrough)",
+    "event CancelScheduled(uint256 indexed subscriptionId, uint40 accessUntil)",
+    "event CancelUnscheduled(uint256 indexed subscriptionId)",
+    "event Cancelled(uint256 indexed subscriptionId, uint40 cancelledAt)",
+    "event Transfer(address indexed from, address indexed to, uint256 value)",
+    "event Approval(address indexed owner, address indexed spender, uint256 value)",
+    USER_OPERATION_EVENT,
+];
+
+const USER_OPERATION_EVENT: &str = "event UserOperationEvent(bytes32 indexed userOpHash, address indexed sender, address indexed paymaster, uint256 nonce, bool success, uint256 actualGasCost, uint256 actualGasUsed)";
+
+/// One log from a userOp receipt. `event` is `None` when no known ABI matched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedLog {
+    pub address: Address,
+    pub event: Option<String>,
+    pub args: Vec<(String, Token)>,
+    /// Raw topics, kept for logs that could not be decoded.
+    pub topics: Vec<H256>,
+}
+
+impl DecodedLog {
+    pub fn arg(&self, name: &str) -> Option<&Token> {
+        self.args.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+
+    pub fn to_json(&self) -> Value {
+        match &self.event {
+            Some(event) => serde_json::json!({
+                "address": encoding::fmt_address(self.address),
+                "event": event,
+                "args": self
+                    .args
+                    .iter()
+                    .map(|(name, token)| (name.clone(), Value::String(fmt_token(token))))
+                    .collect::<serde_json::Map<_, _>>(),
+            }),
+            None => serde_json::json!({
+                "address": encoding::fmt_address(self.address),
+                "event": Value::Null,
+                "topics": self.topics.iter().map(|t| encoding::fmt_h256(*t)).collect::<Vec<_>>(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for DecodedLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(event) = &self.event else {
+            let topic0 = self
+                .topics
+                .first()
+                .map(|t| encoding::fmt_h256(*t))
+                .unwrap_or_else(|| "none".to_string());
+            return write!(
+                f,
+                "unknown log from {} (topic0 {topic0})",
+                encoding::fmt_address(self.address)
+            );
+        };
+        let args = self
+            .args
+            .iter()
+            .map(|(name, token)| format!("{name}={}", fmt_token(token)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "{event}({args}) from {}",
+            encoding::fmt_address(self.address)
+        )
+    }
+}
+
+/// Decoded events of a userOp receipt (`eth_getUserOperationReceipt`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptEvents {
+    pub logs: Vec<DecodedLog>,
+    /// From `UserOperationEvent`, falling back to the receipt's `actualGasCost`.
+    pub actual_gas_cost: Option<U256>,
+}
+
+impl ReceiptEvents {
+    /// Subscription ids opened by this op (`Subscribed`).
+    pub fn subscribed_ids(&self) -> Vec<U256> {
+        self.logs
+            .iter()
+            .filter(|l| l.event.as_deref() == Some("Subscribed"))
+            .filter_map(|l| l.arg("subscriptionId").and_then(|t| t.clone().into_uint()))
+            .collect()
+    }
+
+    /// Subscription ids charged by this op (`Charged`).
+    pub fn charged_ids(&self) -> Vec<U256> {
+        self.logs
+            .iter()
+            .filter(|l| l.event.as_deref() == Some("Charged"))
+            .filter_map(|l| l.arg("subscriptionId").and_then(|t| t.clone().into_uint()))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "subscribedIds": self
+                .subscribed_ids()
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>(),
+            "actualGasCost": self.actual_gas_cost.map(|c| c.to_string()),
+            "events": self.logs.iter().map(DecodedLog::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Decode the op's `logs`, plus its `UserOperationEvent` from the bundle receipt when the bundler
+/// leaves that out of `logs`. Logs that match no known event (or are malformed) are kept as
+/// unknown rather than failing.
+pub fn decode_receipt(receipt: &Value) -> ReceiptEvents {
+    let events: Vec<Event> = KNOWN_EVENTS
+        .iter()
+        .map(|sig| {
+            AbiParser::default()
+                .parse_event(sig)
+                .expect("known event signature parses")
+        })
+        .collect();
+
+    let mut raw: Vec<&Value> = receipt
+        .get("logs")
+        .and_then(Value::as_array)
+        .map(|logs| logs.iter().collect())
+        .unwrap_or_default();
+
+    let op_event = events
+        .iter()
+        .find(|e| e.name == "UserOperationEvent")
+        .map(|e| e.signature())
+        .expect("UserOperationEvent is a known event");
+    let has_op_event = raw.iter().any(|l| log_topics(l).first() == Some(&op_event));
+    let user_op_hash = receipt
+        .get("userOpHash")
+        .and_then(Value::as_str)
+        .and_then(|h| parse_h256(h).ok());
+    if let (false, Some(hash)) = (has_op_event, user_op_hash) {
+        let bundle_logs = receipt
+            .get("receipt")
+            .and_then(|r| r.get("logs"))
+            .and_then(Value::as_array);
+        raw.extend(bundle_logs.into_iter().flatten().filter(|l| {
+            let topics = log_topics(l);
+            topics.first() == Some(&op_event) && topics.get(1) == Some(&hash)
+        }));
+    }
+
+    let logs: Vec<DecodedLog> = raw.into_iter().map(|l| decode_log(&events, l)).collect();
+    let actual_gas_cost = logs
+        .iter()
+        .find(|l| l.event.as_deref() == Some("UserOperationEvent"))
+        .and_then(|l| l.arg("actualGasCost"))
+        .and_then(|t| t.clone().into_uint())
+        .or_else(|| {
+            receipt
+                .get("actualGasCost")
+                .and_then(Value::as_str)
+                .and_then(|c| parse_u256_quantity(c).ok())
+        });
+
+    ReceiptEvents {
+        logs,
+        actual_gas_cost,
+    }
+}
+
+fn decode_log(events: &[Event], log: &Value) -> DecodedLog {
+    let address = log
+        .get("address")
+        .and_then(Value::as_str)
+        .and_then(|a| a.parse::<Address>().ok())
+        .unwrap_or_default();
+    let topics = log_topics(log);
+    let data = log
+        .get("data")
+        .and_then(Value::as_str)
+        .and_then(|d| d.parse::<Bytes>().ok())
+        .unwrap_or_default();
+
+    let decoded = events
+        .iter()
+        .filter(|e| topics.first() == Some(&e.signature()))
+        // Same topic0, different indexing (ERC-721 `Transfer`): the parse fails and we move on.
+        .find_map(|e| {
+            e.parse_log(RawLog {
+                topics: topics.clone(),
+                data: data.to_vec(),
+            })
+            .ok()
+            .map(|parsed| (e.name.clone(), parsed))
+        });
+
+    match decoded {
+        Some((name, parsed)) => DecodedLog {
+            address,
+            event: Some(name),
+            args: parsed
+                .params
+                .into_iter()
+                .map(|p| (p.name, p.value))
+                .collect(),
+            topics,
+        },
+        None => DecodedLog {
+            address,
+            event: None,
+            args: Vec::new(),
+            topics,
+        },
+    }
+}
+
+fn log_topics(log: &Value) -> Vec<H256> {
+    log.get("topics")
+        .and_then(Value::as_array)
+        .map(|topics| {
+            topics
+                .iter()
+                .filter_map(|t| t.as_str().and_then(|t| parse_h256(t).ok()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn fmt_token(token: &Token) -> String {
+    match token {
+        Token::Address(a) => encoding::fmt_address(*a),
+        Token::Uint(v) | Token::Int(v) => v.to_string(),
+        Token::Bool(b) => b.to_string(),
+        Token::FixedBytes(b) | Token::Bytes(b) => format!("0x{}", hex::encode(b)),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_receipt, USER_OPERATION_EVENT};
+    use ethers::abi::{AbiParser, Token};
+    use ethers::types::{Address, H256, U256};
+
+    fn topic_u256(v: u64) -> String {
+        format!("{:?}", H256::from_low_u64_be(v))
+    }
+
+    #[test]
+    fn decodes_subscribed_from_a_userop_receipt() {
+        let open_sub = Address::repeat_byte(0x05);
+        let account = Address::repeat_byte(0xaa);
+        let user_op_hash = H256::repeat_byte(0x77);
+        let subscribed =
+            ethers::utils::keccak256("Subscribed(uint256,uint256,address,uint40,uint40)");
+        let op_event = AbiParser::default()
+            .parse_event(USER_OPERATION_EVENT)
+            .unwrap()
+            .signature();
+
+        let receipt = serde_json::json!({
+            "userOpHash": format!("{user_op_hash:?}"),
+            "success": true,
+            "actualGasCost": "0x1",
+            "logs": [
+                {
+                    "address": format!("{open_sub:?}"),
+                    "topics": [
+                        format!("0x{}", hex::encode(subscribed)),
+                        topic_u256(42),
+                        topic_u256(3),
+                        format!("{:?}", H256::from(account)),
+                    ],
+                    "data": format!(
+                        "0x{}",
+                        hex::encode(ethers::abi::encode(&[
+                            Token::Uint(U256::from(1_700_000_000u64)),
+                            Token::Uint(U256::from(1_702_592_000u64)),
+                        ]))
+                    ),
+                },
+                // Unknown event: kept, not an error.
+                {
+                    "address": format!("{:?}", Address::repeat_byte(0x09)),
+                    "topics": [format!("{:?}", H256::repeat_byte(0x01))],
+                    "data": "0x",
+                },
+            ],
+            "receipt": {
+                "logs": [
+                    {
+                        "address": format!("{:?}", Address::repeat_byte(0xee)),
+                        "topics": [
+                            format!("{op_event:?}"),
+                            format!("{user_op_hash:?}"),
+                            format!("{:?}", H256::from(account)),
+                            format!("{:?}", H256::zero()),
+                        ],
+                        "data": format!(
+                            "0x{}",
+                            hex::encode(ethers::abi::encode(&[
+                                Token::Uint(U256::zero()),
+                                Token::Bool(true),
+                                Token::Uint(U256::from(123_456u64)),
+                                Token::Uint(U256::from(100_000u64)),
+                            ]))
+                        ),
+                    },
+                ],
+            },
+        });
+
+        let events = decode_receipt(&receipt);
+        assert_eq!(events.logs.len(), 3);
+        assert_eq!(events.subscribed_ids(), vec![U256::from(42)]);
+
+        let sub = &events.logs[0];
+        assert_eq!(sub.event.as_deref(), Some("Subscribed"));
+        assert_eq!(sub.address, open_sub);
+        assert_eq!(sub.arg("planId"), Some(&Token::Uint(U256::from(3))));
+        assert_eq!(sub.arg("subscriber"), Some(&Token::Address(account)));
+        assert_eq!(
+            sub.arg("paidThrough"),
+            Some(&Token::Uint(U256::from(1_702_592_000u64)))
+        );
+        assert!(sub
+            .to_string()
+            .starts_with("Subscribed(subscriptionId=42, planId=3, "));
+
+        assert_eq!(events.logs[1].event, None);
+        assert!(events.logs[1]
+            .to_string()
+            .starts_with("unknown log from 0x0909"));
+
+        // The bundle receipt's UserOperationEvent wins over the top-level actualGasCost.
+        assert_eq!(events.logs[2].event.as_deref(), Some("UserOperationEvent"));
+        assert_eq!(events.actual_gas_cost, Some(U256::from(123_456u64)));
+
+        let json = events.to_json();
+        assert_eq!(json["subscribedIds"], serde_json::json!(["42"]));
+        assert_eq!(json["actualGasCost"], "123456");
+        assert_eq!(json["events"][0]["args"]["subscriptionId"], "42");
+        assert_eq!(json["events"][1]["event"], serde_json::Value::Null);
+    }
+}
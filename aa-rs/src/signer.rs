@@ -0,0 +1,27 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Bytes, H256};
+
+/// Produces `UserOperation.signature` for a smart account.
+///
+/// `user_op_hash` is the hash the account validates: the EntryPoint's `getUserOpHash`, or the
+/// time-range digest over it when the op carries a validity window. Implementations return the
+/// signature bytes the account's `validateUserOp` expects (an owner ECDSA signature, a multisig
+/// bundle, an ERC-1271 payload, ...).
+#[async_trait]
+pub trait UserOpSigner: Send + Sync {
+    async fn sign(&self, user_op_hash: H256) -> Result<Bytes>;
+}
+
+/// The default owner key: an EIP-191 `eth_sign` over the hash, as SimpleAccount verifies it.
+#[async_trait]
+impl UserOpSigner for LocalWallet {
+    async fn sign(&self, user_op_hash: H256) -> Result<Bytes> {
+        let sig = self
+            .sign_message(user_op_hash.as_bytes())
+            .await
+            .context("failed to sign userOpHash")?;
+        Ok(Bytes::from(sig.to_vec()))
+    }
+}
@@ -1,4 +1,52 @@
 use ethers::types::{Address, Bytes, U256};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// EntryPoint release a userOp targets. v0.6 and v0.7 differ in op layout and in how
+/// `getUserOpHash` is computed, so the version has to be known before building an op.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum EntryPointVersion {
+    #[default]
+    #[serde(rename = "0.6", alias = "v0.6")]
+    V06,
+    #[serde(rename = "0.7", alias = "v0.7")]
+    V07,
+}
+
+impl EntryPointVersion {
+    /// Canonical EntryPoint address for this version (the same on every chain).
+    pub fn canonical_address(self) -> Address {
+        let addr = match self {
+            Self::V06 => "0x5FF137D4b0FDCD49DcA30c7CF57E578a026d2789",
+            Self::V07 => "0x0000000071727De22E5E9d8BAf0edAc6f37da032",
+        };
+        addr.parse().expect("canonical EntryPoint address parses")
+    }
+}
+
+impl fmt::Display for EntryPointVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V06 => f.write_str("v0.6"),
+            Self::V07 => f.write_str("v0.7"),
+        }
+    }
+}
+
+impl FromStr for EntryPointVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().trim_start_matches('v') {
+            "0.6" => Ok(Self::V06),
+            "0.7" => Ok(Self::V07),
+            other => Err(format!(
+                "unknown EntryPoint version {other:?} (expected 0.6 or 0.7)"
+            )),
+        }
+    }
+}
 
 /// ERC-4337 UserOperation (EntryPoint v0.6 layout).
 ///
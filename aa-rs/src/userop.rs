@@ -0,0 +1,360 @@
+use crate::types::{EntryPointVersion, UserOperation};
+use anyhow::{anyhow, bail, Context, Result};
+use ethers::abi::{encode, Abi, AbiParser, Token};
+use ethers::contract::{Contract, ContractCall};
+use ethers::providers::Middleware;
+use ethers::types::{Address, Bytes, H256, U256};
+use ethers::utils::keccak256;
+use std::sync::Arc;
+
+/// Encode `SimpleAccount.execute(address dest, uint256 value, bytes func)`.
+pub fn encode_execute(dest: Address, value: U256, func: Bytes) -> Result<Bytes> {
+    let execute_fn = AbiParser::default()
+        .parse_function("function execute(address dest, uint256 value, bytes func)")?;
+    let call_data = execute_fn.encode_input(&[
+        Token::Address(dest),
+        Token::Uint(value),
+        Token::Bytes(func.to_vec()),
+    ])?;
+    Ok(Bytes::from(call_data))
+}
+
+/// Read the EntryPoint nonce for `account` under the given 2D nonce `key` (uint192).
+pub async fn fetch_entrypoint_nonce<M: Middleware + 'static>(
+    client: Arc<M>,
+    entrypoint: Address,
+    account: Address,
+    key: U256,
+) -> Result<U256> {
+    let entrypoint_abi = AbiParser::default()
+        .parse(&["function getNonce(address sender, uint192 key) view returns (uint256)"])?;
+    let entrypoint_c = Contract::new(entrypoint, entrypoint_abi, client.clone());
+
+    let nonce: U256 = entrypoint_c
+        .method("getNonce", (account, key))?
+        .call()
+        .await
+        .context("entryPoint.getNonce failed")?;
+    Ok(nonce)
+}
+
+/// `callData` and nonce for an op whose only action is `account.execute(target, 0, calldata)`.
+pub async fn build_single_call<M: Middleware + 'static>(
+    client: Arc<M>,
+    entrypoint: Address,
+    account: Address,
+    nonce_key: U256,
+    target: Address,
+    target_calldata: Bytes,
+) -> Result<(Bytes, U256)> {
+    let nonce = fetch_entrypoint_nonce(client, entrypoint, account, nonce_key).await?;
+    let call_data = encode_execute(target, U256::zero(), target_calldata)?;
+    Ok((call_data, nonce))
+}
+
+/// Fail unless userOps for `version` can be built here. Only the v0.6 layout is implemented;
+/// v0.7 packs gas limits and paymaster fields differently.
+pub fn ensure_supported(version: EntryPointVersion) -> Result<()> {
+    match version {
+        EntryPointVersion::V06 => Ok(()),
+        EntryPointVersion::V07 => bail!("EntryPoint {version} userOps are not supported yet"),
+    }
+}
+
+/// `EntryPoint.getUserOpHash(op)`, computed on-chain for correctness.
+///
+/// For v0.7, `op.init_code` and `op.paymaster_and_data` hold the packed v0.7 forms (see
+/// [`user_op_hash_offline`]).
+pub async fn user_op_hash<M: Middleware + 'static>(
+    client: Arc<M>,
+    entrypoint: Address,
+    version: EntryPointVersion,
+    op: &UserOperation,
+) -> Result<H256> {
+    get_user_op_hash_call(client, entrypoint, version, op)?
+        .call()
+        .await
+        .context("entryPoint.getUserOpHash failed")
+}
+
+fn get_user_op_hash_call<M: Middleware + 'static>(
+    client: Arc<M>,
+    entrypoint: Address,
+    version: EntryPointVersion,
+    op: &UserOperation,
+) -> Result<ContractCall<M, H256>> {
+    if version == EntryPointVersion::V07 {
+        let entrypoint_abi = AbiParser::default().parse(&[
+            "struct PackedUserOperation { address sender; uint256 nonce; bytes initCode; bytes callData; bytes32 accountGasLimits; uint256 preVerificationGas; bytes32 gasFees; bytes paymasterAndData; bytes signature; }",
+            "function getUserOpHash(PackedUserOperation userOp) view returns (bytes32)",
+        ])?;
+        let packed = Token::Tuple(vec![
+            Token::Address(op.sender),
+            Token::Uint(op.nonce),
+            Token::Bytes(op.init_code.to_vec()),
+            Token::Bytes(op.call_data.to_vec()),
+            pack_u128_pair(op.verification_gas_limit, op.call_gas_limit, "gas limits")?,
+            Token::Uint(op.pre_verification_gas),
+            pack_u128_pair(op.max_priority_fee_per_gas, op.max_fee_per_gas, "gas fees")?,
+            Token::Bytes(op.paymaster_and_data.to_vec()),
+            Token::Bytes(op.signature.to_vec()),
+        ]);
+        return Ok(
+            Contract::new(entrypoint, entrypoint_abi, client).method("getUserOpHash", (packed,))?
+        );
+    }
+
+    let entrypoint_abi: Abi = serde_json::from_str(
+        r#"[{"inputs":[{"components":[{"internalType":"address","name":"sender","type":"address"},{"internalType":"uint256","name":"nonce","type":"uint256"},{"internalType":"bytes","name":"initCode","type":"bytes"},{"internalType":"bytes","name":"callData","type":"bytes"},{"internalType":"uint256","name":"callGasLimit","type":"uint256"},{"internalType":"uint256","name":"verificationGasLimit","type":"uint256"},{"internalType":"uint256","name":"preVerificationGas","type":"uint256"},{"internalType":"uint256","name":"maxFeePerGas","type":"uint256"},{"internalType":"uint256","name":"maxPriorityFeePerGas","type":"uint256"},{"internalType":"bytes","name":"paymasterAndData","type":"bytes"},{"internalType":"bytes","name":"signature","type":"bytes"}],"internalType":"struct UserOperation","name":"userOp","type":"tuple"}],"name":"getUserOpHash","outputs":[{"internalType":"bytes32","name":"","type":"bytes32"}],"stateMutability":"view","type":"function"}]"#,
+    )
+    .context("failed to parse EntryPoint ABI")?;
+
+    let entrypoint_c = Contract::new(entrypoint, entrypoint_abi, client);
+    Ok(entrypoint_c.method("getUserOpHash", (op.as_abi_tuple(),))?)
+}
+
+/// `EntryPoint.getUserOpHash(op)` computed locally, without an RPC: the op's fields hashed as
+/// the EntryPoint `version` packs them, bound to `entrypoint` and `chain_id`.
+///
+/// For v0.7, `op.init_code` is `factory ++ factoryData` and `op.paymaster_and_data` is
+/// `paymaster ++ paymasterVerificationGasLimit ++ paymasterPostOpGasLimit ++ paymasterData`, as
+/// in the on-chain `PackedUserOperation`. Its gas limits and fees must fit in 128 bits.
+pub fn user_op_hash_offline(
+    entrypoint: Address,
+    chain_id: u64,
+    version: EntryPointVersion,
+    op: &UserOperation,
+) -> Result<H256> {
+    let hashed = |b: &Bytes| Token::FixedBytes(keccak256(b).to_vec());
+    let fields = match version {
+        EntryPointVersion::V06 => vec![
+            Token::Address(op.sender),
+            Token::Uint(op.nonce),
+            hashed(&op.init_code),
+            hashed(&op.call_data),
+            Token::Uint(op.call_gas_limit),
+            Token::Uint(op.verification_gas_limit),
+            Token::Uint(op.pre_verification_gas),
+            Token::Uint(op.max_fee_per_gas),
+            Token::Uint(op.max_priority_fee_per_gas),
+            hashed(&op.paymaster_and_data),
+        ],
+        EntryPointVersion::V07 => vec![
+            Token::Address(op.sender),
+            Token::Uint(op.nonce),
+            hashed(&op.init_code),
+            hashed(&op.call_data),
+            pack_u128_pair(op.verification_gas_limit, op.call_gas_limit, "gas limits")?,
+            Token::Uint(op.pre_verification_gas),
+            pack_u128_pair(op.max_priority_fee_per_gas, op.max_fee_per_gas, "gas fees")?,
+            hashed(&op.paymaster_and_data),
+        ],
+    };
+    let op_hash = keccak256(encode(&fields));
+    Ok(H256(keccak256(encode(&[
+        Token::FixedBytes(op_hash.to_vec()),
+        Token::Address(entrypoint),
+        Token::Uint(U256::from(chain_id)),
+    ]))))
+}
+
+/// v0.7 `bytes32` pair of 128-bit values: `high << 128 | low` (`accountGasLimits`, `gasFees`).
+fn pack_u128_pair(high: U256, low: U256, what: &str) -> Result<Token> {
+    let limit = U256::one() << 128;
+    if high >= limit || low >= limit {
+        return Err(anyhow!(
+            "{what} do not fit the v0.7 128-bit packing ({high}, {low})"
+        ));
+    }
+    let mut packed = [0u8; 32];
+    ((high << 128) | low).to_big_endian(&mut packed);
+    Ok(Token::FixedBytes(packed.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_execute, ensure_supported, get_user_op_hash_call, user_op_hash, user_op_hash_offline,
+    };
+    use crate::encoding::user_op_from_json;
+    use crate::types::{EntryPointVersion, UserOperation};
+    use ethers::abi::{AbiParser, Token};
+    use ethers::providers::Provider;
+    use ethers::types::{Address, BlockId, BlockNumber, Bytes, H256, U256};
+    use std::sync::Arc;
+
+    /// A v0.7 op in its RPC shape, with a factory and a paymaster to pack.
+    fn rpc_op() -> serde_json::Value {
+        serde_json::json!({
+            "sender": "0x0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a",
+            "nonce": "0x2a",
+            "factory": "0x0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f",
+            "factoryData": "0xabcd",
+            "callData": "0xdeadbeef",
+            "callGasLimit": "0x186a0",
+            "verificationGasLimit": "0x30d40",
+            "preVerificationGas": "0xc350",
+            "maxFeePerGas": "0x3b9aca00",
+            "maxPriorityFeePerGas": "0x5f5e100",
+            "paymaster": "0x0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b",
+            "paymasterVerificationGasLimit": "0x7530",
+            "paymasterPostOpGasLimit": "0x2710",
+            "paymasterData": "0x01",
+            "signature": "0x"
+        })
+    }
+
+    #[test]
+    fn execute_calldata_wraps_target_call() {
+        let dest = Address::repeat_byte(0x05);
+        let func = Bytes::from(vec![0xde, 0xad, 0xbe, 0xef]);
+        let data = encode_execute(dest, U256::from(7), func.clone()).unwrap();
+
+        let execute = AbiParser::default()
+            .parse_function("function execute(address dest, uint256 value, bytes func)")
+            .unwrap();
+        assert_eq!(&data[..4], execute.short_signature().as_slice());
+        let decoded = execute.decode_input(&data[4..]).unwrap();
+        assert_eq!(
+            decoded,
+            vec![
+                Token::Address(dest),
+                Token::Uint(U256::from(7)),
+                Token::Bytes(func.to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn entrypoint_version_parses_and_gates_op_building() {
+        assert_eq!("0.6".parse(), Ok(EntryPointVersion::V06));
+        assert_eq!("v0.7".parse(), Ok(EntryPointVersion::V07));
+        assert!("0.8".parse::<EntryPointVersion>().is_err());
+        assert_eq!(EntryPointVersion::default(), EntryPointVersion::V06);
+        assert_ne!(
+            EntryPointVersion::V06.canonical_address(),
+            EntryPointVersion::V07.canonical_address()
+        );
+
+        assert!(ensure_supported(EntryPointVersion::V06).is_ok());
+        let err = ensure_supported(EntryPointVersion::V07).unwrap_err();
+        assert!(err.to_string().contains("v0.7"), "{err}");
+    }
+
+    #[test]
+    fn rpc_json_packs_v07_factory_and_paymaster_fields() {
+        let op = user_op_from_json(&rpc_op()).unwrap();
+        assert_eq!(op.nonce, U256::from(42));
+        assert_eq!(
+            op.init_code,
+            Bytes::from([vec![0x0f; 20], vec![0xab, 0xcd]].concat())
+        );
+        let mut paymaster_and_data = vec![0x0b; 20];
+        paymaster_and_data.extend_from_slice(&30_000u128.to_be_bytes());
+        paymaster_and_data.extend_from_slice(&10_000u128.to_be_bytes());
+        paymaster_and_data.push(0x01);
+        assert_eq!(op.paymaster_and_data, Bytes::from(paymaster_and_data));
+        assert!(op.signature.is_empty());
+
+        // The v0.6 shape passes the packed fields through as is.
+        let mut v06 = crate::encoding::user_op_to_json(&op);
+        let round_trip = user_op_from_json(&v06).unwrap();
+        assert_eq!(round_trip.init_code, op.init_code);
+        assert_eq!(round_trip.paymaster_and_data, op.paymaster_and_data);
+        v06.as_object_mut().unwrap().remove("sender");
+        let err = user_op_from_json(&v06).unwrap_err();
+        assert!(err.to_string().contains("sender"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn offline_hash_matches_the_entrypoint_get_user_op_hash_call() {
+        let op = user_op_from_json(&rpc_op()).unwrap();
+        let chain_id = 84_532;
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+
+        // getUserOpHash of the canonical v0.6 / v0.7 EntryPoint for this op on chain 84532:
+        // keccak256(abi.encode(UserOperationLib.hash(op), entryPoint, chainid)), with each
+        // version's own packing of the op.
+        for (version, expected) in [
+            (
+                EntryPointVersion::V06,
+                "0x147bee4147bec5f60cf35b3181d6cef013c0d7d9ea15b97f8ed317953f7a522b",
+            ),
+            (
+                EntryPointVersion::V07,
+                "0xa61e98049465a87855dedbea60ab2a7c857523d38241f0582ff591bf9c0e0995",
+            ),
+        ] {
+            let expected: H256 = expected.parse().unwrap();
+            let entrypoint = version.canonical_address();
+            let offline = user_op_hash_offline(entrypoint, chain_id, version, &op).unwrap();
+            assert_eq!(offline, expected, "{version:?}");
+
+            // The EntryPoint is called with the op in the version's own layout...
+            let call = get_user_op_hash_call(client.clone(), entrypoint, version, &op).unwrap();
+            // Selectors of the deployed EntryPoints' getUserOpHash.
+            let (selector, tuple) = match version {
+                EntryPointVersion::V06 => ("a6193531", "struct Op { address a; uint256 b; bytes c; bytes d; uint256 e; uint256 f; uint256 g; uint256 h; uint256 i; bytes j; bytes k; }"),
+                EntryPointVersion::V07 => ("22cdde4c", "struct Op { address a; uint256 b; bytes c; bytes d; bytes32 e; uint256 f; bytes32 g; bytes h; bytes i; }"),
+            };
+            let abi = AbiParser::default()
+                .parse(&[
+                    tuple,
+                    "function getUserOpHash(Op op) view returns (bytes32)",
+                ])
+                .unwrap();
+            let function = abi.function("getUserOpHash").unwrap();
+            let data = call.tx.data().unwrap();
+            assert_eq!(hex::encode(&data[..4]), selector);
+            assert_eq!(&data[..4], function.short_signature().as_slice());
+            let Token::Tuple(fields) = function.decode_input(&data[4..]).unwrap().remove(0) else {
+                panic!("getUserOpHash takes a tuple");
+            };
+            if version == EntryPointVersion::V07 {
+                // accountGasLimits = verificationGasLimit << 128 | callGasLimit.
+                let mut account_gas_limits = [0u8; 32];
+                account_gas_limits[..16].copy_from_slice(&200_000u128.to_be_bytes());
+                account_gas_limits[16..].copy_from_slice(&100_000u128.to_be_bytes());
+                assert_eq!(fields[4], Token::FixedBytes(account_gas_limits.to_vec()));
+            }
+            assert_eq!(call.tx.to_addr(), Some(&entrypoint));
+
+            // ...and what it returns is decoded as the hash.
+            mock.push::<Bytes, _>(Bytes::from(expected.as_bytes().to_vec()))
+                .unwrap();
+            let on_chain = user_op_hash(client.clone(), entrypoint, version, &op)
+                .await
+                .unwrap();
+            mock.assert_request("eth_call", (call.tx, BlockId::from(BlockNumber::Latest)))
+                .unwrap();
+            assert_eq!(on_chain, expected);
+        }
+    }
+
+    #[test]
+    fn offline_hash_binds_version_entrypoint_and_chain() {
+        let op = user_op_from_json(&rpc_op()).unwrap();
+        let entrypoint = EntryPointVersion::V06.canonical_address();
+        let hash = |entrypoint, chain_id, version| {
+            user_op_hash_offline(entrypoint, chain_id, version, &op).unwrap()
+        };
+
+        let base = hash(entrypoint, 1, EntryPointVersion::V06);
+        let others: Vec<H256> = vec![
+            hash(entrypoint, 8_453, EntryPointVersion::V06),
+            hash(Address::repeat_byte(0x01), 1, EntryPointVersion::V06),
+            hash(entrypoint, 1, EntryPointVersion::V07),
+        ];
+        assert!(others.iter().all(|h| *h != base), "{others:?}");
+        assert_eq!(base, hash(entrypoint, 1, EntryPointVersion::V06));
+
+        // v0.7 packs gas into 128-bit halves; larger values cannot be hashed.
+        let oversized = UserOperation {
+            call_gas_limit: U256::one() << 128,
+            ..op.clone()
+        };
+        assert!(user_op_hash_offline(entrypoint, 1, EntryPointVersion::V06, &oversized).is_ok());
+        assert!(user_op_hash_offline(entrypoint, 1, EntryPointVersion::V07, &oversized).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use ethers::types::{H256, U256};
+use eyre::{eyre, Result};
+use opensub_aa::now_unix;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditAction {
+    Send,
+    Success,
+    Revert,
+    PrecheckFail,
+    /// The collect tx could not be filled (nonce, fees, gas estimate).
+    FillFail,
+    /// The node or bundler refused the collect tx / userOp.
+    SendFail,
+    Throttle,
+}
+
+/// One line of `--audit-log`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub ts: u64,
+    pub action: AuditAction,
+    pub subscription_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collector_fee: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, subscription_id: u64) -> Self {
+        Self {
+            ts: now_unix(),
+            action,
+            subscription_id,
+            tx_hash: None,
+            collector_fee: None,
+            reason: None,
+        }
+    }
+
+    pub fn tx(mut self, tx_hash: H256) -> Self {
+        self.tx_hash = Some(tx_hash);
+        self
+    }
+
+    pub fn fee(mut self, collector_fee: Option<U256>) -> Self {
+        self.collector_fee = collector_fee;
+        self
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// Append-only JSON-lines audit trail.
+///
+/// Writes happen on a dedicated thread so the collect loop never waits on disk. When the file
+/// grows past `max_bytes` it is renamed to `<path>.1` (replacing any previous backup) and a fresh
+/// file is started. Dropping the log flushes everything queued so far.
+pub struct AuditLog {
+    tx: Option<mpsc::Sender<AuditEvent>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl AuditLog {
+    pub fn open(path: PathBuf, max_bytes: u64) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| {
+                    eyre!(
+                        "failed to create audit log directory {}: {e}",
+                        parent.display()
+                    )
+                })?;
+            }
+        }
+        let file = open_append(&path)?;
+
+        let (tx, rx) = mpsc::channel::<AuditEvent>();
+        let writer = std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || write_loop(rx, path, file, max_bytes))
+            .map_err(|e| eyre!("failed to spawn audit log writer: {e}"))?;
+
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn record(&self, event: AuditEvent) {
+        if let Some(tx) = self.tx.as_ref() {
+            // The writer only goes away if it panicked; auditing is best-effort.
+            let _ = tx.send(event);
+        }
+    }
+
+    pub fn record_all(&self, events: impl IntoIterator<Item = AuditEvent>) {
+        for event in events {
+            self.record(event);
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer drain the queue and exit.
+        self.tx.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn write_loop(rx: mpsc::Receiver<AuditEvent>, path: PathBuf, mut file: File, max_bytes: u64) {
+    let mut size = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+    for event in rx {
+        let mut line = match serde_json::to_string(&event) {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to serialize audit event");
+                continue;
+            }
+        };
+        line.push('\n');
+
+        if max_bytes > 0 && size > 0 && size + line.len() as u64 > max_bytes {
+            match rotate(&path) {
+                Ok(fresh) => {
+                    file = fresh;
+                    size = 0;
+                }
+                Err(err) => tracing::warn!(error = %err, "failed to rotate audit log"),
+            }
+        }
+
+        match file.write_all(line.as_bytes()) {
+            Ok(()) => size += line.len() as u64,
+            Err(err) => {
+                tracing::warn!(path = %path.display(), error = %err, "failed to write audit log")
+            }
+        }
+    }
+}
+
+fn rotate(path: &Path) -> Result<File> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".1");
+    fs::rename(path, &backup)
+        .map_err(|e| eyre!("failed to rotate audit log {}: {e}", path.display()))?;
+    open_append(path)
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| eyre!("failed to open audit log {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditAction, AuditEvent, AuditLog};
+    use ethers::types::{H256, U256};
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "opensub-keeper-audit-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("audit.jsonl")
+    }
+
+    #[test]
+    fn successful_collect_produces_audit_entry() {
+        let path = temp_path("success");
+        {
+            let log = AuditLog::open(path.clone(), 0).unwrap();
+            log.record(
+                AuditEvent::new(AuditAction::Success, 7)
+                    .tx(H256::repeat_byte(0x42))
+                    .fee(Some(U256::from(10))),
+            );
+        }
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = raw.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let v: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(v["action"], "success");
+        assert_eq!(v["subscriptionId"], 7);
+        assert_eq!(v["txHash"], format!("{:#x}", H256::repeat_byte(0x42)));
+        assert!(v.get("reason").is_none());
+        assert!(v["ts"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn audit_log_rotates_by_size() {
+        let path = temp_path("rotate");
+        {
+            let log = AuditLog::open(path.clone(), 120).unwrap();
+            for id in 0..4 {
+                log.record(
+                    AuditEvent::new(AuditAction::Throttle, id).reason("tx budget exhausted"),
+                );
+            }
+        }
+
+        let backup = path.with_extension("jsonl.1");
+        assert!(backup.exists());
+        let current = std::fs::read_to_string(&path).unwrap();
+        let rotated = std::fs::read_to_string(&backup).unwrap();
+        assert!(current.len() as u64 <= 120);
+        // Only one backup is kept; the newest entry always lands in the live file.
+        assert!(!rotated.is_empty());
+        assert!(current
+            .lines()
+            .last()
+            .unwrap()
+            .contains("\"subscriptionId\":3"));
+    }
+}
@@ -0,0 +1,209 @@
+use serde::Serialize;
+use std::time::Duration;
+
+/// `--breaker-*` settings. `trip_cycles == 0` disables the breaker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakerConfig {
+    /// Failure ratio (`failed / checked`) at or above which a cycle counts as bad.
+    pub threshold: f64,
+    /// Consecutive bad cycles that trip the breaker.
+    pub trip_cycles: u32,
+    /// How long the breaker stays open once tripped.
+    pub cooldown: Duration,
+}
+
+impl BreakerConfig {
+    pub fn enabled(&self) -> bool {
+        self.trip_cycles > 0
+    }
+}
+
+/// What a recorded cycle did to the breaker, for logging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerTransition {
+    /// No change worth logging.
+    None,
+    /// This cycle was bad; `bad_cycles` in a row so far.
+    Bad { bad_cycles: u32 },
+    /// Too many bad cycles: open until the given unix time.
+    Tripped { open_until: u64 },
+    /// A healthy cycle after bad ones (or after a cooldown) closed the breaker.
+    Reset,
+}
+
+/// Breaker state as reported in the `--once --json` summary.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakerState {
+    pub open: bool,
+    /// Unix time the cooldown ends, while open.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_until: Option<u64>,
+    pub bad_cycles: u32,
+    /// Times the breaker has tripped since startup.
+    pub trips: u32,
+}
+
+/// Cycle-wide circuit breaker.
+///
+/// Per-subscription backoff can't help when *everything* fails (RPC returning garbage, OpenSub
+/// upgraded under us): every id fails, backs off briefly and fails again. After `trip_cycles`
+/// consecutive cycles whose failure ratio reaches `threshold`, the breaker opens and the keeper
+/// only runs light health checks for `cooldown`. It then half-opens: one more bad cycle trips it
+/// again straight away, a healthy one closes it.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    bad_cycles: u32,
+    open_until: Option<u64>,
+    /// Cooldown elapsed; the next cycle decides between closing and re-tripping.
+    half_open: bool,
+    trips: u32,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            bad_cycles: 0,
+            open_until: None,
+            half_open: false,
+            trips: 0,
+        }
+    }
+
+    /// Whether the keeper should skip work at `now`. Moves an expired breaker to half-open.
+    pub fn is_open(&mut self, now: u64) -> bool {
+        match self.open_until {
+            Some(until) if now < until => true,
+            Some(_) => {
+                self.open_until = None;
+                self.half_open = true;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Record a collect pass that checked `checked` subscriptions, `failed` of which failed.
+    /// Passes that checked nothing say nothing about health and are ignored.
+    pub fn record_cycle(&mut self, checked: usize, failed: usize, now: u64) -> BreakerTransition {
+        if !self.config.enabled() || checked == 0 {
+            return BreakerTransition::None;
+        }
+
+        let ratio = failed as f64 / checked as f64;
+        if ratio < self.config.threshold {
+            let recovered = self.bad_cycles > 0 || self.half_open;
+            self.bad_cycles = 0;
+            self.half_open = false;
+            return if recovered {
+                BreakerTransition::Reset
+            } else {
+                BreakerTransition::None
+            };
+        }
+
+        self.bad_cycles = self.bad_cycles.saturating_add(1);
+        if self.half_open || self.bad_cycles >= self.config.trip_cycles {
+            let open_until = now.saturating_add(self.config.cooldown.as_secs());
+            self.open_until = Some(open_until);
+            self.half_open = false;
+            self.trips = self.trips.saturating_add(1);
+            BreakerTransition::Tripped { open_until }
+        } else {
+            BreakerTransition::Bad {
+                bad_cycles: self.bad_cycles,
+            }
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        BreakerState {
+            open: self.open_until.is_some(),
+            open_until: self.open_until,
+            bad_cycles: self.bad_cycles,
+            trips: self.trips,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BreakerConfig, BreakerTransition, CircuitBreaker};
+    use std::time::Duration;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig {
+            threshold: 0.9,
+            trip_cycles: 3,
+            cooldown: Duration::from_secs(600),
+        })
+    }
+
+    #[test]
+    fn consecutive_bad_cycles_trip_the_breaker() {
+        let mut b = breaker();
+        assert_eq!(
+            b.record_cycle(10, 10, 1_000),
+            BreakerTransition::Bad { bad_cycles: 1 }
+        );
+        assert_eq!(
+            b.record_cycle(10, 9, 1_030),
+            BreakerTransition::Bad { bad_cycles: 2 }
+        );
+        assert!(!b.is_open(1_050));
+        assert_eq!(
+            b.record_cycle(10, 10, 1_060),
+            BreakerTransition::Tripped { open_until: 1_660 }
+        );
+        assert!(b.is_open(1_061));
+        assert!(b.is_open(1_659));
+        assert!(b.state().open);
+        assert_eq!(b.state().trips, 1);
+
+        // Cooldown over: half-open, and a single bad cycle trips it again.
+        assert!(!b.is_open(1_660));
+        assert!(!b.state().open);
+        assert_eq!(
+            b.record_cycle(10, 10, 1_700),
+            BreakerTransition::Tripped { open_until: 2_300 }
+        );
+        assert_eq!(b.state().trips, 2);
+
+        // After the next cooldown, a healthy cycle closes it.
+        assert!(!b.is_open(2_300));
+        assert_eq!(b.record_cycle(10, 1, 2_310), BreakerTransition::Reset);
+        assert_eq!(b.state().bad_cycles, 0);
+        assert_eq!(b.record_cycle(10, 0, 2_340), BreakerTransition::None);
+    }
+
+    #[test]
+    fn a_healthy_cycle_resets_the_streak() {
+        let mut b = breaker();
+        b.record_cycle(4, 4, 0);
+        b.record_cycle(4, 4, 30);
+        assert_eq!(b.record_cycle(4, 1, 60), BreakerTransition::Reset);
+        // Empty passes are ignored rather than counted as healthy or bad.
+        b.record_cycle(4, 4, 90);
+        assert_eq!(b.record_cycle(0, 0, 120), BreakerTransition::None);
+        b.record_cycle(4, 4, 150);
+        assert_eq!(
+            b.record_cycle(4, 4, 180),
+            BreakerTransition::Tripped { open_until: 780 }
+        );
+    }
+
+    #[test]
+    fn disabled_breaker_never_trips() {
+        let mut b = CircuitBreaker::new(BreakerConfig {
+            threshold: 0.5,
+            trip_cycles: 0,
+            cooldown: Duration::from_secs(600),
+        });
+        for i in 0..10 {
+            assert_eq!(b.record_cycle(5, 5, i), BreakerTransition::None);
+        }
+        assert!(!b.is_open(10));
+    }
+}
@@ -0,0 +1,179 @@
+use eyre::{eyre, Result};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// `--collect-window`: the time of day collects may be sent, e.g. `22:00-06:00`. A window whose
+/// end is before its start crosses midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectWindow {
+    /// Seconds after local midnight the window opens (inclusive).
+    start: i64,
+    /// Seconds after local midnight the window closes (exclusive).
+    end: i64,
+    /// `--timezone`: local time minus UTC, in seconds.
+    utc_offset: i64,
+}
+
+impl CollectWindow {
+    /// Parse `HH:MM-HH:MM` in `timezone` (`UTC` or a fixed offset such as `+02:00`).
+    pub fn parse(window: &str, timezone: &str) -> Result<Self> {
+        let invalid = || eyre!("invalid --collect-window '{window}': expected HH:MM-HH:MM");
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let start = parse_time_of_day(start).ok_or_else(invalid)?;
+        let end = parse_time_of_day(end).ok_or_else(invalid)?;
+        if start == end {
+            return Err(eyre!(
+                "--collect-window '{window}' opens and closes at the same time; drop the flag to collect at any time"
+            ));
+        }
+        Ok(Self {
+            start,
+            end,
+            utc_offset: parse_utc_offset(timezone)?,
+        })
+    }
+
+    /// Seconds after local midnight at `now_unix`.
+    fn time_of_day(&self, now_unix: u64) -> i64 {
+        (now_unix as i64 + self.utc_offset).rem_euclid(SECS_PER_DAY)
+    }
+
+    /// Whether collects may be sent at `now_unix`.
+    pub fn contains(&self, now_unix: u64) -> bool {
+        let t = self.time_of_day(now_unix);
+        if self.start < self.end {
+            self.start <= t && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+
+    /// Seconds until the window next opens; 0 while it is open.
+    pub fn seconds_until_open(&self, now_unix: u64) -> u64 {
+        if self.contains(now_unix) {
+            return 0;
+        }
+        (self.start - self.time_of_day(now_unix)).rem_euclid(SECS_PER_DAY) as u64
+    }
+}
+
+/// `HH:MM` (24h) as seconds after midnight. `24:00` is accepted as the end of the day.
+fn parse_time_of_day(raw: &str) -> Option<i64> {
+    let (h, m) = raw.trim().split_once(':')?;
+    let (h, m): (i64, i64) = (h.parse().ok()?, m.parse().ok()?);
+    match (h, m) {
+        (0..=23, 0..=59) => Some(h * 3_600 + m * 60),
+        (24, 0) => Some(SECS_PER_DAY),
+        _ => None,
+    }
+    .map(|t| t % SECS_PER_DAY)
+}
+
+/// `UTC` or `±HH:MM`. Zone names are not supported: there is no tz database to resolve them (or
+/// their DST changes) against.
+fn parse_utc_offset(raw: &str) -> Result<i64> {
+    let raw = raw.trim();
+    let offset = raw
+        .strip_prefix("UTC")
+        .or_else(|| raw.strip_prefix("utc"))
+        .unwrap_or(raw);
+    if offset.is_empty() {
+        return Ok(0);
+    }
+    let invalid = || eyre!("invalid --timezone '{raw}': expected UTC or an offset like +02:00");
+    let (sign, rest) = match offset.as_bytes()[0] {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return Err(invalid()),
+    };
+    let (h, m) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (h, m): (i64, i64) = (
+        h.parse().map_err(|_| invalid())?,
+        m.parse().map_err(|_| invalid())?,
+    );
+    if h > 14 || m > 59 {
+        return Err(invalid());
+    }
+    Ok(sign * (h * 3_600 + m * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CollectWindow;
+
+    /// 2026-01-01T00:00:00Z.
+    const MIDNIGHT: u64 = 1_767_225_600;
+
+    fn at(hour: u64, minute: u64) -> u64 {
+        MIDNIGHT + hour * 3_600 + minute * 60
+    }
+
+    #[test]
+    fn window_contains_times_of_day() {
+        let day = CollectWindow::parse("09:30-17:00", "UTC").unwrap();
+        assert!(!day.contains(at(9, 29)));
+        assert!(day.contains(at(9, 30)));
+        assert!(day.contains(at(16, 59)));
+        assert!(!day.contains(at(17, 0)));
+        assert!(!day.contains(at(0, 0)));
+        assert_eq!(day.seconds_until_open(at(9, 0)), 1_800);
+        assert_eq!(day.seconds_until_open(at(17, 0)), 16 * 3_600 + 1_800);
+        assert_eq!(day.seconds_until_open(at(12, 0)), 0);
+
+        // Crossing midnight: 22:00 to 06:00 the next day.
+        let night = CollectWindow::parse("22:00-06:00", "UTC").unwrap();
+        assert!(night.contains(at(22, 0)));
+        assert!(night.contains(at(23, 59)));
+        assert!(night.contains(at(0, 0)));
+        assert!(night.contains(at(5, 59)));
+        assert!(!night.contains(at(6, 0)));
+        assert!(!night.contains(at(12, 0)));
+        assert!(!night.contains(at(21, 59)));
+        assert_eq!(night.seconds_until_open(at(21, 0)), 3_600);
+        assert_eq!(night.seconds_until_open(at(6, 0)), 16 * 3_600);
+
+        // `24:00` ends the window at midnight.
+        let evening = CollectWindow::parse("18:00-24:00", "UTC").unwrap();
+        assert!(evening.contains(at(23, 59)));
+        assert!(!evening.contains(at(0, 0)));
+    }
+
+    #[test]
+    fn timezone_offset_shifts_the_window() {
+        // 22:00-06:00 at UTC+02:00 is 20:00-04:00 UTC.
+        let night = CollectWindow::parse("22:00-06:00", "+02:00").unwrap();
+        assert!(night.contains(at(20, 0)));
+        assert!(night.contains(at(3, 59)));
+        assert!(!night.contains(at(4, 0)));
+        assert!(!night.contains(at(19, 59)));
+
+        let west = CollectWindow::parse("01:00-02:00", "UTC-05:30").unwrap();
+        assert!(west.contains(at(6, 30)));
+        assert!(!west.contains(at(1, 0)));
+        assert_eq!(
+            CollectWindow::parse("01:00-02:00", "utc+0")
+                .unwrap()
+                .utc_offset,
+            0
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_windows() {
+        for window in [
+            "22:00",
+            "25:00-06:00",
+            "22:60-06:00",
+            "10:00-10:00",
+            "ten-six",
+        ] {
+            assert!(CollectWindow::parse(window, "UTC").is_err(), "{window}");
+        }
+        for timezone in ["Europe/Berlin", "+15:00", "02:00"] {
+            assert!(
+                CollectWindow::parse("22:00-06:00", timezone).is_err(),
+                "{timezone}"
+            );
+        }
+    }
+}
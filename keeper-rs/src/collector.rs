@@ -1,21 +1,45 @@
-use crate::erc20::Erc20;
-use crate::opensub::OpenSub;
-use crate::state::FailureKind;
-use ethers::providers::Middleware;
-use ethers::types::{Address, U256, U64};
-use eyre::Result;
+use crate::audit::{AuditAction, AuditEvent};
+use crate::eip7702;
+use crate::erc20::{Erc20, Erc20Slots};
+use crate::gas_limits::GasLimitOverrides;
+use crate::multicall::{self, Multicall3, PlanRow, Prefetched, SubscriptionRow};
+use crate::opensub::{
+    cancel_scheduled, collect_call, decode_revert, OpenSub, STATUS_ACTIVE, STATUS_CANCELLED,
+    STATUS_NON_RENEWING,
+};
+use crate::relayer::Relayer;
+use crate::send_guard::SendGuard;
+use crate::state::{receipt_gas_cost, receipt_succeeded, FailureKind};
+use ethers::abi::Detokenize;
+use ethers::contract::{ContractError, FunctionCall};
+use ethers::providers::call_raw::{spoof, RawCall};
+use ethers::providers::{Middleware, RpcError};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, BlockNumber, Bytes, Eip1559TransactionRequest, H256, U256};
+use eyre::{eyre, Result};
 use futures::stream;
 use futures::StreamExt;
+use opensub_aa::now_unix;
+use serde::Serialize;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
 };
 use std::time::Duration;
+use tokio::time::Instant;
 
 #[derive(Debug, Clone)]
 pub struct PendingTx {
     pub subscription_id: u64,
     pub tx_hash: ethers::types::H256,
+    /// Nonce assigned to the tx (filled before sending).
+    pub nonce: Option<U256>,
+    /// Sender of the tx.
+    pub from: Option<Address>,
+    /// Block the tx was mined in (set for `CollectOutcome::confirming`).
+    pub mined_block: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +49,17 @@ pub struct FailureRecord {
     pub reason: Option<String>,
 }
 
+/// Result of simulating `collect()` for one subscription in `--simulate-only` mode.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationResult {
+    pub subscription_id: u64,
+    pub would_succeed: bool,
+    pub merchant_amount: Option<U256>,
+    pub collector_fee: Option<U256>,
+    pub reason: Option<String>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct CollectOutcome {
     pub stats: CollectStats,
@@ -35,14 +70,71 @@ pub struct CollectOutcome {
     /// Subscriptions that were successfully collected this cycle.
     pub successes: Vec<u64>,
 
+    /// Successful collects still waiting for `--collect-confirmations`.
+    pub confirming: Vec<PendingTx>,
+
     /// Failures that should be backoff-tracked by the caller.
     pub failures: Vec<FailureRecord>,
+
+    /// `--simulate-only`: per-subscription simulation results (empty otherwise).
+    pub simulations: Vec<SimulationResult>,
+
+    /// Per-action records for `--audit-log` (sends, successes, reverts, precheck failures, throttles).
+    pub audit: Vec<AuditEvent>,
+
+    /// Updates to the cached `paidThrough` per subscription: a fresh on-chain value, or `None`
+    /// when the cache proved stale (locally due, but `isDue()` said no).
+    pub paid_through: Vec<(u64, Option<u64>)>,
+
+    /// `successes` with the details `--post-collect-cmd` substitutes.
+    pub collected: Vec<Collected>,
+
+    /// `--prune-inactive`: ids observed Cancelled (or NonRenewing past their paid period) that
+    /// the caller should drop from `subscription_ids`.
+    pub pruned: Vec<u64>,
+
+    /// `--dry-run`: due ids that passed every check and would have been sent.
+    pub would_collect: Vec<u64>,
 }
 
-#[derive(Debug, Default, Clone)]
+/// A recorded success. Plan and fee are unknown for txs that finalized after the cycle that sent
+/// them (in-flight / confirming), and the fee is only known when simulating.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collected {
+    pub subscription_id: u64,
+    pub plan_id: Option<U256>,
+    pub tx_hash: Option<H256>,
+    pub collector_fee: Option<U256>,
+}
+
+/// Local `isDue` pre-filter: ids whose cached `paidThrough` is still more than `margin` seconds
+/// away are skipped without any RPC. The on-chain `isDue()` still decides for the rest.
+#[derive(Debug, Clone, Default)]
+pub struct LocalDueFilter {
+    pub margin: u64,
+    pub paid_through: BTreeMap<u64, u64>,
+}
+
+/// `--collect-order`: which due subscriptions a cycle reaches first. Matters once
+/// `--max-txs-per-cycle` or a slow cycle means not every due subscription gets a collect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CollectOrder {
+    /// The order the ids were discovered in.
+    #[default]
+    Fifo,
+    /// Oldest `paidThrough` first.
+    MostOverdue,
+    /// Largest expected collector fee (`price * collectorFeeBps / 10000`) first.
+    HighestFee,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct CollectStats {
     pub checked: usize,
+    /// Skipped by the local due pre-filter (cached `paidThrough` still in the future).
+    pub not_due_locally: usize,
     pub due: usize,
     pub sent: usize,
     pub succeeded: usize,
@@ -50,22 +142,132 @@ pub struct CollectStats {
     pub precheck_failed: usize,
     pub throttled: usize,
     pub pending: usize,
+    /// Mined successfully but still short of `--collect-confirmations`.
+    pub confirming: usize,
+    /// Sum of `merchantAmount` for collects that succeeded this cycle.
+    ///
+    /// Taken from the `collect()` eth_call simulation (the real values are only observable via
+    /// the `Charged` event), so this stays zero when simulation is disabled.
+    pub merchant_amount_total: U256,
+    /// Sum of `collectorFee` earned by this keeper for collects that succeeded this cycle.
+    ///
+    /// Simulated, like `merchant_amount_total`.
+    pub collector_fee_total: U256,
+    /// Sum of the expected collector fee (`price * collectorFeeBps / 10000`, from the plan) over
+    /// due subscriptions that passed the plan checks. Read from state, so it is known without
+    /// simulation.
+    pub expected_fee_total: U256,
+    /// Gas paid (`gasUsed * effectiveGasPrice`) by collect() receipts seen this cycle, reverts
+    /// included.
+    pub gas_spent_wei: U256,
+}
+
+/// Everything [`collect_due`] needs besides the contract, the client and the ids: the cycle's
+/// settings from the config plus what the caller knows from the state file.
+#[derive(Clone)]
+pub struct CollectOptions {
+    pub max_concurrency: usize,
+    pub gas_limit: Option<u64>,
+    pub gas_limit_overrides: GasLimitOverrides,
+    pub max_txs_per_cycle: usize,
+    /// What is left of `--daily-gas-budget-wei` in the rolling window; `None` when unset.
+    pub gas_budget: Option<U256>,
+    pub tx_timeout: Duration,
+    pub cycle_deadline: Option<Duration>,
+    pub force_pending: bool,
+    pub simulate: bool,
+    pub simulate_only: bool,
+    pub dry_run: bool,
+    pub min_seconds_between_collects: u64,
+    /// Last successful collect per id (unix seconds).
+    pub last_success: BTreeMap<u64, u64>,
+    pub collector_address: Option<Address>,
+    pub collect_confirmations: u64,
+    pub due_confirmations: u64,
+    pub fee_recipient: Option<Address>,
+    pub simulate_overrides: Option<Erc20Slots>,
+    pub relayer: Option<Arc<Relayer>>,
+    pub local_due: Option<LocalDueFilter>,
+    pub send_guard: Option<Arc<SendGuard>>,
+    pub prune_inactive: bool,
+    /// `--simulate-at-block` / `--pin-block`.
+    pub pin_block: Option<u64>,
+    pub multicall_address: Option<Address>,
+    pub collect_order: CollectOrder,
+    /// `--eip7702`: queue the collects for one batch tx instead of sending each.
+    pub eip7702_batch: bool,
+}
+
+#[cfg(test)]
+impl CollectOptions {
+    /// One collect at a time with simulation on and nothing else enabled, for unit tests.
+    pub fn test_default() -> Self {
+        Self {
+            max_concurrency: 1,
+            gas_limit: None,
+            gas_limit_overrides: GasLimitOverrides::default(),
+            max_txs_per_cycle: 25,
+            gas_budget: None,
+            tx_timeout: Duration::from_secs(5),
+            cycle_deadline: None,
+            force_pending: false,
+            simulate: true,
+            simulate_only: false,
+            dry_run: false,
+            min_seconds_between_collects: 0,
+            last_success: BTreeMap::new(),
+            collector_address: None,
+            collect_confirmations: 1,
+            due_confirmations: 0,
+            fee_recipient: None,
+            simulate_overrides: None,
+            relayer: None,
+            local_due: None,
+            send_guard: None,
+            prune_inactive: false,
+            pin_block: None,
+            multicall_address: None,
+            collect_order: CollectOrder::Fifo,
+            eip7702_batch: false,
+        }
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
 pub async fn collect_due<M: Middleware + 'static>(
     opensub: OpenSub<M>,
     opensub_address: Address,
     client: Arc<M>,
     subscription_ids: Vec<u64>,
-    max_concurrency: usize,
-    gas_limit: Option<u64>,
-    max_txs_per_cycle: usize,
-    tx_timeout: Duration,
-    force_pending: bool,
-    simulate: bool,
-    dry_run: bool,
+    opts: CollectOptions,
 ) -> Result<CollectOutcome> {
+    let CollectOptions {
+        max_concurrency,
+        gas_limit,
+        gas_limit_overrides,
+        max_txs_per_cycle,
+        gas_budget,
+        tx_timeout,
+        cycle_deadline,
+        force_pending,
+        simulate,
+        simulate_only,
+        dry_run,
+        min_seconds_between_collects,
+        last_success,
+        collector_address,
+        collect_confirmations,
+        due_confirmations,
+        fee_recipient,
+        simulate_overrides,
+        relayer,
+        local_due,
+        send_guard,
+        prune_inactive,
+        pin_block,
+        multicall_address,
+        collect_order,
+        eip7702_batch,
+    } = opts;
     let stats = Arc::new(AtomicStats::default());
 
     // Safety valve: cap tx submissions per cycle.
@@ -74,15 +276,111 @@ pub async fn collect_due<M: Middleware + 'static>(
     // We intentionally do not "release" budget after a tx completes.
     let remaining_budget = Arc::new(AtomicUsize::new(max_txs_per_cycle));
 
+    // `--daily-gas-budget-wei`: whatever is left of it is checked again before every send.
+    let gas_budget = gas_budget.map(|left| Arc::new(GasBudget::new(left)));
+
+    // `--cycle-deadline-seconds`: no receipt wait runs past this, whatever is left of
+    // `tx_timeout`.
+    let receipt_deadline = cycle_deadline.map(|d| Instant::now() + d);
+
     // Collect pending txs for persistence.
     let pending_out = Arc::new(tokio::sync::Mutex::new(Vec::<PendingTx>::new()));
 
     // Collect successes/failures for backoff accounting.
     let successes_out = Arc::new(tokio::sync::Mutex::new(Vec::<u64>::new()));
+    let confirming_out = Arc::new(tokio::sync::Mutex::new(Vec::<PendingTx>::new()));
     let failures_out = Arc::new(tokio::sync::Mutex::new(Vec::<FailureRecord>::new()));
+    let simulations_out = Arc::new(tokio::sync::Mutex::new(Vec::<SimulationResult>::new()));
+    let audit_out = Arc::new(tokio::sync::Mutex::new(Vec::<AuditEvent>::new()));
+    let paid_through_out = Arc::new(tokio::sync::Mutex::new(Vec::<(u64, Option<u64>)>::new()));
+    let collected_out = Arc::new(tokio::sync::Mutex::new(Vec::<Collected>::new()));
+    let pruned_out = Arc::new(tokio::sync::Mutex::new(Vec::<u64>::new()));
+    let would_collect_out = Arc::new(tokio::sync::Mutex::new(Vec::<u64>::new()));
+    // `--eip7702`: (id, collect calldata) queued for the cycle's single batch tx.
+    let batch_out = Arc::new(tokio::sync::Mutex::new(Vec::<(u64, Bytes)>::new()));
+
+    // `--simulate-at-block` / `--pin-block`: every read and the simulation run against that
+    // block's state. Sends still go to the live chain.
+    let read_block = pin_block.map(|n| BlockId::from(BlockNumber::Number(n.into())));
+
+    // `--due-confirmations`: one reference block per cycle for every isDue() check.
+    let due_block = match pin_block {
+        Some(n) => Some(BlockNumber::Number(n.into())),
+        None => match due_check_block(client.as_ref(), due_confirmations).await {
+            Ok(b) => b,
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to fetch head for --due-confirmations; skipping collect this cycle");
+                return Ok(CollectOutcome::default());
+            }
+        },
+    };
+
+    // Ids the local due filter lets through; only these are worth reading up front.
+    let candidates: Vec<u64> = subscription_ids
+        .iter()
+        .copied()
+        .filter(|id| match local_due.as_ref() {
+            Some(filter) => filter
+                .paid_through
+                .get(id)
+                .is_none_or(|pt| locally_due(*pt, now_unix(), filter.margin)),
+            None => true,
+        })
+        .collect();
+
+    // `--multicall-address`: read subscriptions()/plans() for the whole set in a few aggregate
+    // calls. Anything the pre-pass misses is read per call below.
+    let mut prefetched = match multicall_address {
+        Some(address) => {
+            let multicall = Multicall3::new(address, client.clone());
+            match multicall::prefetch(&multicall, &opensub, &candidates, read_block).await {
+                Ok(p) => {
+                    tracing::debug!(
+                        subscriptions = p.subscriptions.len(),
+                        plans = p.plans.len(),
+                        "multicall pre-pass"
+                    );
+                    p
+                }
+                Err(err) => {
+                    tracing::warn!(error = %err, "multicall pre-pass failed; reading per call");
+                    Prefetched::default()
+                }
+            }
+        }
+        None => Prefetched::default(),
+    };
+
+    // `--collect-order`: the ordering needs every candidate's row before the first collect, so
+    // read whatever the multicall pre-pass didn't. The loop below reuses these rows.
+    let mut subscription_ids = subscription_ids;
+    if collect_order != CollectOrder::Fifo {
+        let missing: Vec<u64> = candidates
+            .into_iter()
+            .filter(|id| !prefetched.subscriptions.contains_key(id))
+            .collect();
+        read_rows(
+            &opensub,
+            &missing,
+            read_block,
+            max_concurrency,
+            &mut prefetched,
+        )
+        .await;
+        order_ids(&mut subscription_ids, collect_order, &prefetched);
+    }
 
     let opensub = Arc::new(opensub);
     let client = client;
+    let prefetched = Arc::new(prefetched);
+    let last_success = Arc::new(last_success);
+    let local_due = Arc::new(local_due);
+    let gas_limit_overrides = Arc::new(gas_limit_overrides);
+    // Relayed collects reach OpenSub from the smart account, so that is the fee-earning sender.
+    let signer = match relayer.as_ref() {
+        Some(r) => Some(r.account()),
+        None => client.default_sender(),
+    };
 
     stream::iter(subscription_ids)
         .for_each_concurrent(max_concurrency, |id| {
@@ -90,16 +388,73 @@ pub async fn collect_due<M: Middleware + 'static>(
             let client = client.clone();
             let stats = stats.clone();
             let remaining_budget = remaining_budget.clone();
+            let gas_budget = gas_budget.clone();
             let pending_out = pending_out.clone();
             let successes_out = successes_out.clone();
+            let confirming_out = confirming_out.clone();
             let failures_out = failures_out.clone();
+            let simulations_out = simulations_out.clone();
+            let audit_out = audit_out.clone();
+            let paid_through_out = paid_through_out.clone();
+            let collected_out = collected_out.clone();
+            let pruned_out = pruned_out.clone();
+            let would_collect_out = would_collect_out.clone();
+            let batch_out = batch_out.clone();
+            let local_due = local_due.clone();
+            let last_success = last_success.clone();
+            let gas_limit_overrides = gas_limit_overrides.clone();
+            let relayer = relayer.clone();
+            let send_guard = send_guard.clone();
+            let prefetched = prefetched.clone();
             async move {
                 stats.checked.fetch_add(1, Ordering::Relaxed);
 
                 let id_u256 = U256::from(id);
 
+                // Local pre-filter: skip the isDue() round trip while the cached paidThrough is
+                // clearly in the future. Unknown ids are read once to fill the cache.
+                let mut cached_paid_through = None;
+                let mut status_read = false;
+                if let Some(filter) = local_due.as_ref() {
+                    let paid_through = match filter.paid_through.get(&id) {
+                        Some(pt) => {
+                            cached_paid_through = Some(*pt);
+                            Some(*pt)
+                        }
+                        None => match read_subscription(&opensub, id, read_block, &prefetched).await {
+                            Ok((_, _, status, _, pt, _)) => {
+                                let pt = pt.as_u64();
+                                status_read = true;
+                                if prune_inactive && is_terminal(status, pt, now_unix()) {
+                                    tracing::info!(subscription_id = id, status, "subscription ended; pruning");
+                                    pruned_out.lock().await.push(id);
+                                    return;
+                                }
+                                paid_through_out.lock().await.push((id, Some(pt)));
+                                Some(pt)
+                            }
+                            Err(err) => {
+                                // Not fatal: fall back to asking isDue().
+                                tracing::debug!(subscription_id = id, error = %err, "subscriptions() read for local due check failed");
+                                None
+                            }
+                        },
+                    };
+                    if let Some(pt) = paid_through {
+                        if !locally_due(pt, now_unix(), filter.margin) {
+                            stats.not_due_locally.fetch_add(1, Ordering::Relaxed);
+                            tracing::debug!(subscription_id = id, paid_through = pt, "not due yet (local check); skipping isDue()");
+                            return;
+                        }
+                    }
+                }
+
                 // Cheap pre-check to avoid revert/gas waste.
-                let due = match opensub.is_due(id_u256).call().await {
+                let mut is_due = opensub.is_due(id_u256);
+                if let Some(block) = due_block {
+                    is_due = is_due.block(block);
+                }
+                let due = match is_due.call().await {
                     Ok(v) => v,
                     Err(err) => {
                         stats.failed.fetch_add(1, Ordering::Relaxed);
@@ -117,6 +472,23 @@ pub async fn collect_due<M: Middleware + 'static>(
                 };
 
                 if !due {
+                    // Past the cached paidThrough but not due on-chain: the cache is stale
+                    // (collected by someone else, or cancelled); re-read it next time.
+                    if cached_paid_through.is_some_and(|pt| now_unix() >= pt) {
+                        paid_through_out.lock().await.push((id, None));
+                    }
+                    // isDue() is false for anything not Active, so ending is only visible in
+                    // subscriptions(). Ids read above for the local filter were checked there.
+                    if prune_inactive && !status_read {
+                        if let Ok((_, _, status, _, pt, _)) =
+                            read_subscription(&opensub, id, read_block, &prefetched).await
+                        {
+                            if is_terminal(status, pt.as_u64(), now_unix()) {
+                                tracing::info!(subscription_id = id, status, "subscription ended; pruning");
+                                pruned_out.lock().await.push(id);
+                            }
+                        }
+                    }
                     return;
                 }
 
@@ -125,11 +497,8 @@ pub async fn collect_due<M: Middleware + 'static>(
                 // Prechecks (Milestone 5.1): avoid spending gas on collect() that will revert.
                 //
                 // 1) Read subscription -> get planId/subscriber.
-                let (plan_id, subscriber, status, _start, _paid_through, _last) = match opensub
-                    .subscriptions(id_u256)
-                    .call()
-                    .await
-                {
+                let (plan_id, subscriber, status, _start, paid_through, _last) =
+                    match read_subscription(&opensub, id, read_block, &prefetched).await {
                     Ok(v) => v,
                     Err(err) => {
                         stats.failed.fetch_add(1, Ordering::Relaxed);
@@ -146,16 +515,55 @@ pub async fn collect_due<M: Middleware + 'static>(
                     }
                 };
 
+                if local_due.is_some() {
+                    paid_through_out
+                        .lock()
+                        .await
+                        .push((id, Some(paid_through.as_u64())));
+                }
+
                 // Status enum: 1 == Active.
                 // If it changed between isDue() and now, skip (another actor may have cancelled).
-                if status != 1u8 {
-                    tracing::info!(subscription_id = id, status, "subscription no longer Active; skipping");
+                if status != STATUS_ACTIVE {
+                    if prune_inactive && is_terminal(status, paid_through.as_u64(), now_unix()) {
+                        tracing::info!(subscription_id = id, status, "subscription ended; pruning");
+                        pruned_out.lock().await.push(id);
+                    } else if cancel_scheduled(status) {
+                        // isDue() at a `--due-confirmations` block can predate the cancel; the
+                        // final period is paid, so there is nothing left to collect.
+                        tracing::info!(
+                            subscription_id = id,
+                            paid_through = %paid_through,
+                            "cancel scheduled at period end; not renewing"
+                        );
+                    } else {
+                        tracing::info!(subscription_id = id, status, "subscription no longer Active; skipping");
+                    }
                     return;
                 }
 
+                match collector_decision(collector_address, signer, subscriber) {
+                    CollectorDecision::Collect => {}
+                    CollectorDecision::SignerMismatch => {
+                        tracing::debug!(
+                            subscription_id = id,
+                            signer = ?signer,
+                            collector_address = ?collector_address,
+                            "collector fee will be paid to the signer, not --collector-address"
+                        );
+                    }
+                    CollectorDecision::FeeWaived => {
+                        tracing::info!(
+                            subscription_id = id,
+                            subscriber = ?subscriber,
+                            "signer is the subscriber; collecting with the collector fee waived"
+                        );
+                    }
+                }
+
                 // 2) Read plan -> active/token/price.
-                let (_merchant, token, price, _interval, _fee_bps, plan_active, _created_at) =
-                    match opensub.plans(plan_id).call().await {
+                let (_merchant, token, price, interval, fee_bps, plan_active, _created_at) =
+                    match read_plan(&opensub, plan_id, read_block, &prefetched).await {
                         Ok(v) => v,
                         Err(err) => {
                             stats.failed.fetch_add(1, Ordering::Relaxed);
@@ -186,13 +594,45 @@ pub async fn collect_due<M: Middleware + 'static>(
                     return;
                 }
 
+                // OpenSub caps collectorFeeBps at 10000 when a plan is created; anything above
+                // means the plan row (or the contract at this address) is not what we expect.
+                let Some(expected_fee) = expected_collector_fee(price, fee_bps) else {
+                    stats.precheck_failed.fetch_add(1, Ordering::Relaxed);
+                    let reason = format!("collectorFeeBps {fee_bps} exceeds {MAX_BPS}");
+                    tracing::warn!(subscription_id = id, plan_id = ?plan_id, collector_fee_bps = %fee_bps, "malformed plan; backing off");
+                    failures_out
+                        .lock()
+                        .await
+                        .push(FailureRecord {
+                            subscription_id: id,
+                            kind: FailureKind::MalformedPlan,
+                            reason: Some(reason),
+                        });
+                    return;
+                };
+                stats.add_expected_fee(expected_fee);
+
+                // A long-overdue subscription still needs only one collect: OpenSub charges a
+                // single period and restarts the schedule from now (missed periods are not billed).
+                let overdue = overdue_periods(paid_through, interval, U256::from(now_unix()));
+                if overdue > 1 {
+                    tracing::info!(
+                        subscription_id = id,
+                        overdue_periods = overdue,
+                        "subscription several periods overdue; one collect charges one period and resets the schedule"
+                    );
+                }
+
                 // 3) Check allowance/balance for the total price.
                 // Note: OpenSub performs two transferFrom calls, but the same spender (OpenSub).
                 // Total allowance needed is at least `price`.
                 let erc20 = Erc20::new(token, client.clone());
                 let spender = opensub_address;
 
-                let allowance = match erc20.allowance(subscriber, spender).call().await {
+                let allowance = match at_block(erc20.allowance(subscriber, spender), read_block)
+                    .call()
+                    .await
+                {
                     Ok(v) => v,
                     Err(err) => {
                         stats.failed.fetch_add(1, Ordering::Relaxed);
@@ -209,21 +649,40 @@ pub async fn collect_due<M: Middleware + 'static>(
                     }
                 };
 
+                // `--simulate-with-overrides`: the same collect() eth_call, with the subscriber's
+                // balance/allowance overridden to cover `price`.
+                let funds_check = |reason: String| {
+                    let client = client.clone();
+                    let opensub = opensub.clone();
+                    async move {
+                        let Some(slots) = simulate_overrides else {
+                            return reason;
+                        };
+                        let state = slots.funds_override(token, subscriber, spender, price);
+                        let mut tx = collect_call(&opensub, id_u256, fee_recipient).tx;
+                        if let Some(from) = signer {
+                            tx.set_from(from);
+                        }
+                        format!("{reason}; {}", simulate_with_state(client.as_ref(), &tx, &state, read_block).await)
+                    }
+                };
+
                 if allowance < price {
                     stats.precheck_failed.fetch_add(1, Ordering::Relaxed);
+                    let reason = funds_check(format!("allowance {} < price {}", allowance, price)).await;
                     failures_out
                         .lock()
                         .await
                         .push(FailureRecord {
                             subscription_id: id,
                             kind: FailureKind::InsufficientAllowance,
-                            reason: Some(format!("allowance {} < price {}", allowance, price)),
+                            reason: Some(reason),
                         });
                     tracing::info!(subscription_id = id, allowance = %allowance, price = %price, "insufficient allowance; backing off");
                     return;
                 }
 
-                let balance = match erc20.balance_of(subscriber).call().await {
+                let balance = match at_block(erc20.balance_of(subscriber), read_block).call().await {
                     Ok(v) => v,
                     Err(err) => {
                         stats.failed.fetch_add(1, Ordering::Relaxed);
@@ -242,28 +701,108 @@ pub async fn collect_due<M: Middleware + 'static>(
 
                 if balance < price {
                     stats.precheck_failed.fetch_add(1, Ordering::Relaxed);
+                    let reason = funds_check(format!("balance {} < price {}", balance, price)).await;
                     failures_out
                         .lock()
                         .await
                         .push(FailureRecord {
                             subscription_id: id,
                             kind: FailureKind::InsufficientBalance,
-                            reason: Some(format!("balance {} < price {}", balance, price)),
+                            reason: Some(reason),
                         });
                     tracing::info!(subscription_id = id, balance = %balance, price = %price, "insufficient balance; backing off");
                     return;
                 }
 
+                if simulate_only {
+                    // Report-only: simulate every eligible id, never touch the tx budget or send.
+                    let result = match at_block(collect_call(&opensub, id_u256, fee_recipient), read_block)
+                        .call()
+                        .await
+                    {
+                        Ok((merchant_amount, collector_fee)) => {
+                            tracing::info!(
+                                subscription_id = id,
+                                merchant_amount = %merchant_amount,
+                                collector_fee = %collector_fee,
+                                "SIMULATE ONLY: collect() would succeed"
+                            );
+                            SimulationResult {
+                                subscription_id: id,
+                                would_succeed: true,
+                                merchant_amount: Some(merchant_amount),
+                                collector_fee: Some(collector_fee),
+                                reason: None,
+                            }
+                        }
+                        Err(err) => {
+                            let reason = funds_check(revert_reason(&err)).await;
+                            tracing::info!(subscription_id = id, reason = %reason, "SIMULATE ONLY: collect() would revert");
+                            SimulationResult {
+                                subscription_id: id,
+                                would_succeed: false,
+                                merchant_amount: None,
+                                collector_fee: None,
+                                reason: Some(reason),
+                            }
+                        }
+                    };
+                    simulations_out.lock().await.push(result);
+                    return;
+                }
+
                 if dry_run {
                     tracing::info!(subscription_id = id, "DRY RUN: would call collect()");
+                    would_collect_out.lock().await.push(id);
+                    return;
+                }
+
+
+                // Safety rail: refuse to collect the same subscription again too soon after a
+                // previous success, even if isDue() says otherwise.
+                if collected_too_recently(
+                    last_success.get(&id).copied(),
+                    now_unix(),
+                    min_seconds_between_collects,
+                ) {
+                    stats.throttled.fetch_add(1, Ordering::Relaxed);
+                    audit_out.lock().await.push(
+                        AuditEvent::new(AuditAction::Throttle, id).reason("collected too recently"),
+                    );
+                    tracing::warn!(
+                        subscription_id = id,
+                        last_success = ?last_success.get(&id),
+                        min_seconds_between_collects,
+                        "collected too recently; skipping collect this cycle"
+                    );
                     return;
                 }
 
+                // `--min-resend-seconds`: a send shortly before a crash/restart may not have made it
+                // into the state file yet, so check the send guard as well.
+                if let Some(sent_at) = send_guard
+                    .as_ref()
+                    .and_then(|g| g.recently_sent(id, now_unix()))
+                {
+                    stats.throttled.fetch_add(1, Ordering::Relaxed);
+                    audit_out.lock().await.push(
+                        AuditEvent::new(AuditAction::Throttle, id).reason("sent too recently"),
+                    );
+                    tracing::warn!(
+                        subscription_id = id,
+                        sent_at,
+                        "collect sent too recently; skipping resend"
+                    );
+                    return;
+                }
 
                 // Quick check: if the per-cycle tx budget is already exhausted, skip early.
                 // (We still enforce the budget atomically right before sending.)
                 if remaining_budget.load(Ordering::Relaxed) == 0 {
                     stats.throttled.fetch_add(1, Ordering::Relaxed);
+                    audit_out.lock().await.push(
+                        AuditEvent::new(AuditAction::Throttle, id).reason("tx budget exhausted"),
+                    );
                     tracing::warn!(
                         subscription_id = id,
                         "tx budget exhausted; skipping collect this cycle"
@@ -271,24 +810,31 @@ pub async fn collect_due<M: Middleware + 'static>(
                     return;
                 }
 
+                // Simulated (merchantAmount, collectorFee); only known when simulating.
+                let mut simulated: Option<(U256, U256)> = None;
+
                 if simulate {
                     // Final guardrail: simulate collect() via eth_call.
                     // This avoids spending gas on transactions that would revert.
-                    match opensub.collect(id_u256).call().await {
-                        Ok((_merchant_amount, _collector_fee)) => {
-                            // ok
+                    match at_block(collect_call(&opensub, id_u256, fee_recipient), read_block)
+                        .call()
+                        .await
+                    {
+                        Ok((merchant_amount, collector_fee)) => {
+                            simulated = Some((merchant_amount, collector_fee));
                         }
                         Err(err) => {
+                            let reason = funds_check(revert_reason(&err)).await;
                             stats.precheck_failed.fetch_add(1, Ordering::Relaxed);
+                            tracing::warn!(subscription_id = id, reason = %reason, "collect() simulation reverted; backing off");
                             failures_out
                                 .lock()
                                 .await
                                 .push(FailureRecord {
                                     subscription_id: id,
                                     kind: FailureKind::SimulationRevert,
-                                    reason: Some(err.to_string()),
+                                    reason: Some(reason),
                                 });
-                            tracing::warn!(subscription_id = id, error = %err, "collect() simulation reverted; backing off");
                             return;
                         }
                     }
@@ -304,6 +850,9 @@ pub async fn collect_due<M: Middleware + 'static>(
 
                 if !budget_ok {
                     stats.throttled.fetch_add(1, Ordering::Relaxed);
+                    audit_out.lock().await.push(
+                        AuditEvent::new(AuditAction::Throttle, id).reason("tx budget exhausted"),
+                    );
                     tracing::warn!(
                         subscription_id = id,
                         "tx budget exhausted; skipping collect this cycle"
@@ -312,18 +861,222 @@ pub async fn collect_due<M: Middleware + 'static>(
                 }
 
 
+                if let Some(guard) = send_guard.as_ref() {
+                    if let Err(err) = guard.record(id, now_unix()) {
+                        // Sending without the record would defeat the guard after a restart.
+                        stats.throttled.fetch_add(1, Ordering::Relaxed);
+                        audit_out.lock().await.push(
+                            AuditEvent::new(AuditAction::Throttle, id)
+                                .reason("send guard not persisted"),
+                        );
+                        tracing::warn!(subscription_id = id, error = %err, "failed to persist send guard; skipping collect");
+                        return;
+                    }
+                }
+
+                if eip7702_batch {
+                    let calldata = collect_call(&opensub, id_u256, fee_recipient)
+                        .calldata()
+                        .expect("collect() calldata");
+                    batch_out.lock().await.push((id, calldata));
+                    return;
+                }
+
+                if let Some(relayer) = relayer {
+                    // The op's cost is only known once it lands, so this can only stop the next one.
+                    if !relayer.sponsored() && gas_budget.as_ref().is_some_and(|b| b.exhausted()) {
+                        gas_budget_throttle(id, &stats, &audit_out).await;
+                        return;
+                    }
+                    let calldata = collect_call(&opensub, id_u256, fee_recipient)
+                        .calldata()
+                        .expect("collect() calldata");
+                    let user_op_hash = match relayer
+                        .submit_collect(
+                            client.clone(),
+                            id,
+                            opensub_address,
+                            calldata,
+                            gas_limit_overrides.resolve(id, plan_id, gas_limit),
+                        )
+                        .await
+                    {
+                        Ok(h) => h,
+                        Err(err) => {
+                            stats.failed.fetch_add(1, Ordering::Relaxed);
+                            tracing::warn!(subscription_id = id, error = %err, "relayed collect submit failed");
+                            audit_out.lock().await.push(
+                                AuditEvent::new(AuditAction::SendFail, id).reason(err.to_string()),
+                            );
+                            failures_out
+                                .lock()
+                                .await
+                                .push(FailureRecord {
+                                    subscription_id: id,
+                                    kind: FailureKind::RpcError,
+                                    reason: Some(err.to_string()),
+                                });
+                            return;
+                        }
+                    };
+
+                    stats.sent.fetch_add(1, Ordering::Relaxed);
+                    audit_out.lock().await.push(
+                        AuditEvent::new(AuditAction::Send, id)
+                            .reason(format!("userOp {user_op_hash:?}")),
+                    );
+
+                    // No tx hash to track until the op is bundled, so a timeout is a retryable
+                    // failure; a duplicate op for the same subscription reuses its nonce key and
+                    // cannot land twice.
+                    // Past the cycle deadline there is nothing left to wait (and a zero timeout
+                    // would mean "no timeout" to the bundler client).
+                    let wait = receipt_wait(tx_timeout, receipt_deadline);
+                    let relayed = if wait.is_zero() {
+                        Err(eyre!("cycle deadline reached before the userOp receipt"))
+                    } else {
+                        relayer.wait(user_op_hash, wait).await
+                    };
+                    let relayed = match relayed {
+                        Ok(r) => r,
+                        Err(err) => {
+                            stats.failed.fetch_add(1, Ordering::Relaxed);
+                            tracing::warn!(subscription_id = id, user_op = ?user_op_hash, error = %err, "relayed collect not confirmed");
+                            failures_out
+                                .lock()
+                                .await
+                                .push(FailureRecord {
+                                    subscription_id: id,
+                                    kind: FailureKind::RpcError,
+                                    reason: Some(err.to_string()),
+                                });
+                            return;
+                        }
+                    };
+
+                    if !relayer.sponsored() {
+                        stats.add_gas_spent(relayed.actual_gas_cost);
+                        if let Some(budget) = gas_budget.as_ref() {
+                            budget.spend(relayed.actual_gas_cost);
+                        }
+                    }
+                    if let (true, Some(tx_hash), true) =
+                        (relayed.success, relayed.tx_hash, collect_confirmations > 1)
+                    {
+                        // Track the bundle tx like a direct collect until it is deep enough.
+                        stats.confirming.fetch_add(1, Ordering::Relaxed);
+                        tracing::info!(
+                            subscription_id = id,
+                            user_op = ?user_op_hash,
+                            tx = ?tx_hash,
+                            collect_confirmations,
+                            "relayed collect included; waiting for confirmations"
+                        );
+                        confirming_out.lock().await.push(PendingTx {
+                            subscription_id: id,
+                            tx_hash,
+                            nonce: None,
+                            from: None,
+                            mined_block: relayed.block_number,
+                        });
+                    } else if relayed.success {
+                        stats.succeeded.fetch_add(1, Ordering::Relaxed);
+                        if let Some((merchant_amount, collector_fee)) = simulated {
+                            stats.add_collected(merchant_amount, collector_fee);
+                        }
+                        tracing::info!(
+                            subscription_id = id,
+                            user_op = ?user_op_hash,
+                            tx = ?relayed.tx_hash,
+                            collector_fee = ?simulated.map(|s| s.1),
+                            "relayed collect succeeded"
+                        );
+                        successes_out.lock().await.push(id);
+                        collected_out.lock().await.push(Collected {
+                            subscription_id: id,
+                            plan_id: Some(plan_id),
+                            tx_hash: relayed.tx_hash,
+                            collector_fee: simulated.map(|s| s.1),
+                        });
+                        let mut event = AuditEvent::new(AuditAction::Success, id)
+                            .fee(simulated.map(|s| s.1));
+                        if let Some(tx_hash) = relayed.tx_hash {
+                            event = event.tx(tx_hash);
+                        }
+                        audit_out.lock().await.push(event);
+                    } else {
+                        stats.failed.fetch_add(1, Ordering::Relaxed);
+                        tracing::warn!(subscription_id = id, user_op = ?user_op_hash, tx = ?relayed.tx_hash, "relayed collect included but reverted");
+                        let mut event = AuditEvent::new(AuditAction::Revert, id)
+                            .reason("userOp included but reverted");
+                        if let Some(tx_hash) = relayed.tx_hash {
+                            event = event.tx(tx_hash);
+                        }
+                        audit_out.lock().await.push(event);
+                        failures_out
+                            .lock()
+                            .await
+                            .push(FailureRecord {
+                                subscription_id: id,
+                                kind: FailureKind::MinedRevert,
+                                reason: Some("userOp included but reverted".to_string()),
+                            });
+                    }
+                    return;
+                }
+
                 // Build collect tx.
-                let mut call = opensub.collect(id_u256);
-                if let Some(gl) = gas_limit {
+                let mut call = collect_call(&opensub, id_u256, fee_recipient);
+                if let Some(gl) = gas_limit_overrides.resolve(id, plan_id, gas_limit) {
                     call = call.gas(U256::from(gl));
                 }
 
+                // Fill before sending so we know the nonce/sender to record for in-flight
+                // reconciliation. Fees and gas are estimated first: the nonce manager hands out
+                // the nonce in `fill_transaction`, and an estimate failing after that would leave
+                // a nonce gap that stalls every later send.
+                let mut tx = call.tx.clone();
+                let filled = match estimate_tx(client.as_ref(), &mut tx).await {
+                    // Reserved at the worst case (`gas * maxFeePerGas`); nothing is sent unless it
+                    // fits in what is left of the budget.
+                    Ok(()) if gas_budget
+                        .as_ref()
+                        .is_some_and(|b| !b.reserve(tx.max_cost().unwrap_or_default())) =>
+                    {
+                        gas_budget_throttle(id, &stats, &audit_out).await;
+                        return;
+                    }
+                    Ok(()) => client.fill_transaction(&mut tx, None).await,
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = filled {
+                    stats.failed.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(subscription_id = id, error = %err, "collect tx fill (nonce/gas) failed");
+                    audit_out.lock().await.push(
+                        AuditEvent::new(AuditAction::FillFail, id).reason(err.to_string()),
+                    );
+                    failures_out
+                        .lock()
+                        .await
+                        .push(FailureRecord {
+                            subscription_id: id,
+                            kind: FailureKind::RpcError,
+                            reason: Some(err.to_string()),
+                        });
+                    return;
+                }
+                let nonce = tx.nonce().copied();
+                let from = tx.from().copied();
+
                 // Send.
-                let pending = match call.send().await {
+                let pending = match client.send_transaction(tx, None).await {
                     Ok(p) => p,
                     Err(err) => {
                         stats.failed.fetch_add(1, Ordering::Relaxed);
                         tracing::warn!(subscription_id = id, error = %err, "collect send failed");
+                        audit_out.lock().await.push(
+                            AuditEvent::new(AuditAction::SendFail, id).reason(err.to_string()),
+                        );
                         failures_out
                             .lock()
                             .await
@@ -339,6 +1092,11 @@ pub async fn collect_due<M: Middleware + 'static>(
                 stats.sent.fetch_add(1, Ordering::Relaxed);
 
                 let tx_hash = pending.tx_hash();
+                let nonce = sent_nonce(client.as_ref(), tx_hash, nonce).await;
+                audit_out
+                    .lock()
+                    .await
+                    .push(AuditEvent::new(AuditAction::Send, id).tx(tx_hash));
 
                 if force_pending {
                     stats.pending.fetch_add(1, Ordering::Relaxed);
@@ -350,23 +1108,67 @@ pub async fn collect_due<M: Middleware + 'static>(
                     pending_out
                         .lock()
                         .await
-                        .push(PendingTx { subscription_id: id, tx_hash });
+                        .push(PendingTx { subscription_id: id, tx_hash, nonce, from, mined_block: None });
                     return;
                 }
 
                 // Wait for receipt.
-                let receipt_res = tokio::time::timeout(tx_timeout, pending).await;
+                let receipt_res =
+                    tokio::time::timeout(receipt_wait(tx_timeout, receipt_deadline), pending).await;
 
                 match receipt_res {
                     Ok(Ok(Some(rcpt))) => {
-                        let ok = rcpt.status == Some(U64::from(1));
-                        if ok {
+                        stats.add_gas_spent(receipt_gas_cost(&rcpt));
+                        let ok = receipt_succeeded(client.as_ref(), &rcpt).await;
+                        if ok && collect_confirmations > 1 {
+                            // Don't count it yet: a shallow reorg could still drop it.
+                            stats.confirming.fetch_add(1, Ordering::Relaxed);
+                            tracing::info!(
+                                subscription_id = id,
+                                tx = ?tx_hash,
+                                block = rcpt.block_number.map(|b| b.as_u64()),
+                                collect_confirmations,
+                                "collect mined; waiting for confirmations"
+                            );
+                            confirming_out.lock().await.push(PendingTx {
+                                subscription_id: id,
+                                tx_hash,
+                                nonce,
+                                from,
+                                mined_block: rcpt.block_number.map(|b| b.as_u64()),
+                            });
+                        } else if ok {
                             stats.succeeded.fetch_add(1, Ordering::Relaxed);
-                            tracing::info!(subscription_id = id, tx = ?tx_hash, "collect succeeded");
+                            if let Some((merchant_amount, collector_fee)) = simulated {
+                                stats.add_collected(merchant_amount, collector_fee);
+                            }
+                            tracing::info!(
+                                subscription_id = id,
+                                tx = ?tx_hash,
+                                merchant_amount = ?simulated.map(|s| s.0),
+                                collector_fee = ?simulated.map(|s| s.1),
+                                "collect succeeded"
+                            );
                             successes_out.lock().await.push(id);
+                            collected_out.lock().await.push(Collected {
+                                subscription_id: id,
+                                plan_id: Some(plan_id),
+                                tx_hash: Some(tx_hash),
+                                collector_fee: simulated.map(|s| s.1),
+                            });
+                            audit_out.lock().await.push(
+                                AuditEvent::new(AuditAction::Success, id)
+                                    .tx(tx_hash)
+                                    .fee(simulated.map(|s| s.1)),
+                            );
                         } else {
                             stats.failed.fetch_add(1, Ordering::Relaxed);
                             tracing::warn!(subscription_id = id, tx = ?tx_hash, "collect mined but reverted");
+                            audit_out.lock().await.push(
+                                AuditEvent::new(AuditAction::Revert, id)
+                                    .tx(tx_hash)
+                                    .reason("mined but reverted"),
+                            );
                             failures_out
                                 .lock()
                                 .await
@@ -384,7 +1186,7 @@ pub async fn collect_due<M: Middleware + 'static>(
                         pending_out
                             .lock()
                             .await
-                            .push(PendingTx { subscription_id: id, tx_hash });
+                            .push(PendingTx { subscription_id: id, tx_hash, nonce, from, mined_block: None });
                     }
                     Ok(Err(err)) => {
                         // We successfully submitted the tx, but failed while waiting for the receipt.
@@ -394,36 +1196,573 @@ pub async fn collect_due<M: Middleware + 'static>(
                         pending_out
                             .lock()
                             .await
-                            .push(PendingTx { subscription_id: id, tx_hash });
+                            .push(PendingTx { subscription_id: id, tx_hash, nonce, from, mined_block: None });
                     }
                     Err(_) => {
                         // Timed out waiting for receipt; treat as pending.
                         stats.pending.fetch_add(1, Ordering::Relaxed);
-                        tracing::warn!(subscription_id = id, tx = ?tx_hash, timeout_s = tx_timeout.as_secs(), "collect still pending after timeout; tracking as in-flight");
+                        if receipt_deadline.is_some_and(|d| Instant::now() >= d) {
+                            tracing::warn!(subscription_id = id, tx = ?tx_hash, "cycle deadline reached before the receipt; tracking as in-flight");
+                        } else {
+                            tracing::warn!(subscription_id = id, tx = ?tx_hash, timeout_s = tx_timeout.as_secs(), "collect still pending after timeout; tracking as in-flight");
+                        }
                         pending_out
                             .lock()
                             .await
-                            .push(PendingTx { subscription_id: id, tx_hash });
+                            .push(PendingTx { subscription_id: id, tx_hash, nonce, from, mined_block: None });
                     }
                 }
             }
         })
         .await;
 
+    let batch = std::mem::take(&mut *batch_out.lock().await);
+    if !batch.is_empty() {
+        send_batch(
+            client.as_ref(),
+            opensub_address,
+            batch,
+            gas_budget.as_deref(),
+            &stats,
+            &pending_out,
+            &failures_out,
+            &audit_out,
+        )
+        .await;
+    }
+
     let pending = pending_out.lock().await.clone();
     let successes = successes_out.lock().await.clone();
+    let confirming = confirming_out.lock().await.clone();
     let failures = failures_out.lock().await.clone();
+    let mut simulations = simulations_out.lock().await.clone();
+    simulations.sort_by_key(|s| s.subscription_id);
+    let mut audit = audit_out.lock().await.clone();
+    let paid_through = paid_through_out.lock().await.clone();
+    let collected = collected_out.lock().await.clone();
+    let pruned = pruned_out.lock().await.clone();
+    let would_collect = would_collect_out.lock().await.clone();
+    // Mined reverts and fill/send failures are recorded above; everything else failed a precheck.
+    let recorded: BTreeSet<u64> = audit
+        .iter()
+        .filter(|e| matches!(e.action, AuditAction::FillFail | AuditAction::SendFail))
+        .map(|e| e.subscription_id)
+        .collect();
+    audit.extend(
+        failures
+            .iter()
+            .filter(|f| {
+                f.kind != FailureKind::MinedRevert && !recorded.contains(&f.subscription_id)
+            })
+            .map(|f| {
+                let reason = match f.reason.as_deref() {
+                    Some(r) => format!("{:?}: {r}", f.kind),
+                    None => format!("{:?}", f.kind),
+                };
+                AuditEvent::new(AuditAction::PrecheckFail, f.subscription_id).reason(reason)
+            }),
+    );
     Ok(CollectOutcome {
         stats: stats.into_collect_stats(),
         pending,
         successes,
+        confirming,
         failures,
+        simulations,
+        audit,
+        paid_through,
+        collected,
+        pruned,
+        would_collect,
     })
 }
 
+/// `--eip7702`: send the cycle's queued collects as one `executeBatch` tx from the delegated
+/// keeper EOA to itself. Every id is tracked in flight with the batch tx (no receipt wait); the
+/// next cycle's reconciliation resolves each one from its `Charged` log.
+#[allow(clippy::too_many_arguments)]
+async fn send_batch<M: Middleware>(
+    client: &M,
+    opensub: Address,
+    batch: Vec<(u64, Bytes)>,
+    gas_budget: Option<&GasBudget>,
+    stats: &AtomicStats,
+    pending_out: &tokio::sync::Mutex<Vec<PendingTx>>,
+    failures_out: &tokio::sync::Mutex<Vec<FailureRecord>>,
+    audit_out: &tokio::sync::Mutex<Vec<AuditEvent>>,
+) {
+    let ids: Vec<u64> = batch.iter().map(|(id, _)| *id).collect();
+    let calls: Vec<Bytes> = batch.into_iter().map(|(_, data)| data).collect();
+
+    let sent = async {
+        let eoa = client.default_sender().ok_or_else(|| {
+            (
+                AuditAction::FillFail,
+                eyre!("no signer to send the batch from"),
+            )
+        })?;
+        let data = eip7702::batch_collect_calldata(opensub, &calls)
+            .map_err(|e| (AuditAction::FillFail, e))?;
+        let mut tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .from(eoa)
+            .to(eoa)
+            .data(data)
+            .into();
+        // Same order as a single collect: no nonce is taken until fees and gas are known.
+        let filled = match estimate_tx(client, &mut tx).await {
+            Ok(()) if gas_budget.is_some_and(|b| !b.reserve(tx.max_cost().unwrap_or_default())) => {
+                return Err((AuditAction::Throttle, eyre!("daily gas budget exhausted")));
+            }
+            Ok(()) => client.fill_transaction(&mut tx, None).await,
+            Err(err) => Err(err),
+        };
+        filled.map_err(|e| {
+            (
+                AuditAction::FillFail,
+                eyre!("batch tx fill (nonce/gas) failed: {e}"),
+            )
+        })?;
+        let nonce = tx.nonce().copied();
+        let pending = client
+            .send_transaction(tx, None)
+            .await
+            .map_err(|e| (AuditAction::SendFail, eyre!("batch send failed: {e}")))?;
+        let tx_hash = pending.tx_hash();
+        let nonce = sent_nonce(client, tx_hash, nonce).await;
+        Ok::<_, (AuditAction, eyre::Report)>((tx_hash, nonce, eoa))
+    }
+    .await;
+
+    match sent {
+        Ok((tx_hash, nonce, from)) => {
+            stats.sent.fetch_add(1, Ordering::Relaxed);
+            stats.pending.fetch_add(ids.len(), Ordering::Relaxed);
+            tracing::info!(tx = ?tx_hash, collects = ids.len(), "EIP-7702 batch sent; tracking each collect as in-flight");
+            let mut pending = pending_out.lock().await;
+            let mut audit = audit_out.lock().await;
+            for id in ids {
+                pending.push(PendingTx {
+                    subscription_id: id,
+                    tx_hash,
+                    nonce,
+                    from: Some(from),
+                    mined_block: None,
+                });
+                audit.push(AuditEvent::new(AuditAction::Send, id).tx(tx_hash));
+            }
+        }
+        Err((AuditAction::Throttle, err)) => {
+            stats.throttled.fetch_add(ids.len(), Ordering::Relaxed);
+            tracing::warn!(
+                collects = ids.len(),
+                "daily gas budget exhausted; EIP-7702 batch not sent this cycle"
+            );
+            let mut audit = audit_out.lock().await;
+            for id in ids {
+                audit.push(AuditEvent::new(AuditAction::Throttle, id).reason(err.to_string()));
+            }
+        }
+        Err((action, err)) => {
+            stats.failed.fetch_add(ids.len(), Ordering::Relaxed);
+            tracing::warn!(collects = ids.len(), error = %err, "EIP-7702 batch not sent");
+            let mut failures = failures_out.lock().await;
+            let mut audit = audit_out.lock().await;
+            for id in ids {
+                audit.push(AuditEvent::new(action, id).reason(err.to_string()));
+                failures.push(FailureRecord {
+                    subscription_id: id,
+                    kind: FailureKind::RpcError,
+                    reason: Some(err.to_string()),
+                });
+            }
+        }
+    }
+}
+
+/// What is left of `--daily-gas-budget-wei` for the rest of a cycle.
+struct GasBudget {
+    left: std::sync::Mutex<U256>,
+}
+
+impl GasBudget {
+    fn new(left: U256) -> Self {
+        Self {
+            left: std::sync::Mutex::new(left),
+        }
+    }
+
+    /// Take `cost` out of the budget; false (and nothing taken) when it does not fit.
+    fn reserve(&self, cost: U256) -> bool {
+        let mut left = self.left.lock().unwrap_or_else(|e| e.into_inner());
+        if left.is_zero() || cost > *left {
+            return false;
+        }
+        *left -= cost;
+        true
+    }
+
+    fn spend(&self, cost: U256) {
+        let mut left = self.left.lock().unwrap_or_else(|e| e.into_inner());
+        *left = left.saturating_sub(cost);
+    }
+
+    fn exhausted(&self) -> bool {
+        self.left
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_zero()
+    }
+}
+
+async fn gas_budget_throttle(
+    id: u64,
+    stats: &AtomicStats,
+    audit_out: &tokio::sync::Mutex<Vec<AuditEvent>>,
+) {
+    stats.throttled.fetch_add(1, Ordering::Relaxed);
+    audit_out
+        .lock()
+        .await
+        .push(AuditEvent::new(AuditAction::Throttle, id).reason("daily gas budget exhausted"));
+    tracing::warn!(
+        subscription_id = id,
+        "daily gas budget exhausted; skipping collect this cycle"
+    );
+}
+
+/// Fill in `from`, fees and gas, leaving the nonce to `fill_transaction`.
+async fn estimate_tx<M: Middleware>(client: &M, tx: &mut TypedTransaction) -> Result<(), M::Error> {
+    if tx.from().is_none() {
+        if let Some(from) = client.default_sender() {
+            tx.set_from(from);
+        }
+    }
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            if inner.max_fee_per_gas.is_none() || inner.max_priority_fee_per_gas.is_none() {
+                let (max_fee, tip) = client.estimate_eip1559_fees(None).await?;
+                let max_fee = *inner.max_fee_per_gas.get_or_insert(max_fee);
+                inner.max_priority_fee_per_gas =
+                    Some(inner.max_priority_fee_per_gas.unwrap_or(tip).min(max_fee));
+            }
+        }
+        _ => {
+            if tx.gas_price().is_none() {
+                tx.set_gas_price(client.get_gas_price().await?);
+            }
+        }
+    }
+    if tx.gas().is_none() {
+        let gas = client.estimate_gas(tx, None).await?;
+        tx.set_gas(gas);
+    }
+    Ok(())
+}
+
+/// Nonce the node has for a tx just sent, else the one it was filled with. A nonce manager
+/// re-sends with a resynced nonce when the first attempt is rejected, so the two can differ.
+async fn sent_nonce<M: Middleware>(
+    client: &M,
+    tx_hash: H256,
+    filled: Option<U256>,
+) -> Option<U256> {
+    match client.get_transaction(tx_hash).await {
+        Ok(Some(tx)) => Some(tx.nonce),
+        _ => filled,
+    }
+}
+
+/// How long to wait for one receipt: `tx_timeout`, cut short by the cycle's receipt deadline.
+fn receipt_wait(tx_timeout: Duration, deadline: Option<Instant>) -> Duration {
+    match deadline {
+        Some(deadline) => tx_timeout.min(deadline.saturating_duration_since(Instant::now())),
+        None => tx_timeout,
+    }
+}
+
+/// Block `isDue` is evaluated at: `latest - confirmations`, or `None` (latest) when 0.
+async fn due_check_block<M: Middleware>(
+    client: &M,
+    confirmations: u64,
+) -> Result<Option<BlockNumber>, M::Error> {
+    if confirmations == 0 {
+        return Ok(None);
+    }
+    let head = client.get_block_number().await?.as_u64();
+    Ok(Some(BlockNumber::Number(
+        head.saturating_sub(confirmations).into(),
+    )))
+}
+
+/// Pin a contract read to `block` (latest when `None`).
+fn at_block<B, M, D>(call: FunctionCall<B, M, D>, block: Option<BlockId>) -> FunctionCall<B, M, D>
+where
+    B: Borrow<M>,
+    M: Middleware,
+    D: Detokenize,
+{
+    match block {
+        Some(block) => call.block(block),
+        None => call,
+    }
+}
+
+/// `subscriptions(id)` from the multicall pre-pass, or read on its own.
+async fn read_subscription<M: Middleware>(
+    opensub: &OpenSub<M>,
+    id: u64,
+    block: Option<BlockId>,
+    prefetched: &Prefetched,
+) -> std::result::Result<SubscriptionRow, ContractError<M>> {
+    match prefetched.subscriptions.get(&id) {
+        Some(row) => Ok(*row),
+        None => {
+            at_block(opensub.subscriptions(U256::from(id)), block)
+                .call()
+                .await
+        }
+    }
+}
+
+/// `plans(planId)` from the multicall pre-pass, or read on its own.
+async fn read_plan<M: Middleware>(
+    opensub: &OpenSub<M>,
+    plan_id: U256,
+    block: Option<BlockId>,
+    prefetched: &Prefetched,
+) -> std::result::Result<PlanRow, ContractError<M>> {
+    match prefetched.plans.get(&plan_id) {
+        Some(row) => Ok(*row),
+        None => at_block(opensub.plans(plan_id), block).call().await,
+    }
+}
+
+/// Fill `prefetched` with `subscriptions(id)` for `ids` and `plans(planId)` for the plans of the
+/// Active ones, one call each. Failed reads are left out and retried by the collect loop.
+async fn read_rows<M: Middleware>(
+    opensub: &OpenSub<M>,
+    ids: &[u64],
+    block: Option<BlockId>,
+    max_concurrency: usize,
+    prefetched: &mut Prefetched,
+) {
+    let rows: Vec<(u64, SubscriptionRow)> = stream::iter(ids.iter().copied())
+        .map(|id| async move {
+            at_block(opensub.subscriptions(U256::from(id)), block)
+                .call()
+                .await
+                .ok()
+                .map(|row| (id, row))
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .filter_map(|row| async move { row })
+        .collect()
+        .await;
+    prefetched.subscriptions.extend(rows);
+
+    let plan_ids: BTreeSet<U256> = prefetched
+        .subscriptions
+        .values()
+        .filter(|s| s.2 == STATUS_ACTIVE && !prefetched.plans.contains_key(&s.0))
+        .map(|s| s.0)
+        .collect();
+    let plans: Vec<(U256, PlanRow)> = stream::iter(plan_ids)
+        .map(|plan_id| async move {
+            at_block(opensub.plans(plan_id), block)
+                .call()
+                .await
+                .ok()
+                .map(|row| (plan_id, row))
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .filter_map(|row| async move { row })
+        .collect()
+        .await;
+    prefetched.plans.extend(plans);
+}
+
+/// Reorder `ids` for `--collect-order`. Sorting is stable, so ties (and `fifo`) keep discovery
+/// order; ids without a row in `prefetched` go last.
+pub fn order_ids(ids: &mut [u64], order: CollectOrder, prefetched: &Prefetched) {
+    match order {
+        CollectOrder::Fifo => {}
+        CollectOrder::MostOverdue => {
+            ids.sort_by_key(|id| prefetched.subscriptions.get(id).map_or(U256::MAX, |s| s.4))
+        }
+        CollectOrder::HighestFee => {
+            ids.sort_by_key(|id| std::cmp::Reverse(expected_fee(prefetched, *id)))
+        }
+    }
+}
+
+/// The expected collector fee of an Active subscription's plan; `None` otherwise (or if the plan
+/// is malformed).
+fn expected_fee(prefetched: &Prefetched, id: u64) -> Option<U256> {
+    let subscription = prefetched.subscriptions.get(&id)?;
+    if subscription.2 != STATUS_ACTIVE {
+        return None;
+    }
+    let plan = prefetched.plans.get(&subscription.0)?;
+    expected_collector_fee(plan.2, plan.4)
+}
+
+/// Basis points in 100%; the largest valid `collectorFeeBps`.
+const MAX_BPS: u64 = 10_000;
+
+/// `price * collectorFeeBps / 10000`, OpenSub's `computeCollectorFee`. `None` when
+/// `collectorFeeBps` is above 10000, which no valid plan has.
+pub fn expected_collector_fee(price: U256, collector_fee_bps: U256) -> Option<U256> {
+    if collector_fee_bps > U256::from(MAX_BPS) {
+        return None;
+    }
+    Some(price.saturating_mul(collector_fee_bps) / U256::from(MAX_BPS))
+}
+
+/// `--simulate-at-block` / `--pin-block` (named by `flag`): fail up front, with a clear error,
+/// when the RPC can't serve state at `block` (pruned, non-archival nodes) or OpenSub did not exist
+/// yet at that block.
+pub async fn check_state_at_block<M: Middleware>(
+    client: &M,
+    opensub: Address,
+    block: u64,
+    flag: &str,
+) -> Result<()> {
+    let head = client
+        .get_block_number()
+        .await
+        .map_err(|e| eyre!("failed to fetch head block: {e}"))?
+        .as_u64();
+    if block > head {
+        return Err(eyre!("{flag} {block} is ahead of the chain head ({head})"));
+    }
+    let code = client
+        .get_code(opensub, Some(BlockNumber::Number(block.into()).into()))
+        .await
+        .map_err(|e| {
+            eyre!("{flag} {block}: the RPC cannot serve state at that block ({e}); an archive node is required")
+        })?;
+    if code.is_empty() {
+        return Err(eyre!(
+            "{flag} {block}: OpenSub {opensub:?} has no code at that block (deployed later?)"
+        ));
+    }
+    Ok(())
+}
+
+/// Reason for a reverted `collect()` simulation, with OpenSub custom errors decoded
+/// (e.g. `NotDue(paidThrough=...)`); falls back to the raw error.
+fn revert_reason<M: Middleware>(err: &ContractError<M>) -> String {
+    err.as_revert()
+        .and_then(|data| decode_revert(data))
+        .unwrap_or_else(|| err.to_string())
+}
+
+/// Run `tx` as an `eth_call` with a state override set and describe the outcome, to tell a revert
+/// caused by the subscriber's funds from one caused by contract logic.
+async fn simulate_with_state<M: Middleware>(
+    client: &M,
+    tx: &TypedTransaction,
+    state: &spoof::State,
+    block: Option<BlockId>,
+) -> String {
+    let mut call = client.provider().call_raw(tx).state(state);
+    if let Some(block) = block {
+        call = call.block(block);
+    }
+    match call.await {
+        Ok(_) => "with balance/allowance overridden collect() succeeds (funding issue)".to_string(),
+        Err(err) => {
+            let reason = err
+                .as_error_response()
+                .and_then(|e| e.as_revert_data())
+                .and_then(|data| decode_revert(&data))
+                .unwrap_or_else(|| err.to_string());
+            format!("with balance/allowance overridden collect() still reverts: {reason}")
+        }
+    }
+}
+
+/// How `--collector-address` applies to one subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectorDecision {
+    Collect,
+    /// OpenSub pays the fee to `msg.sender`, so it goes to the signer rather than the configured
+    /// collector. Collected anyway; the mismatch is logged.
+    SignerMismatch,
+    /// The signer is the subscriber, so OpenSub waives the collector fee. Collected anyway (the
+    /// merchant is still paid); the waived fee is logged.
+    FeeWaived,
+}
+
+/// OpenSub has no per-subscription collector: whoever calls `collect()` is paid (see `Charged.collector`).
+/// Nothing on-chain designates another collector, so every decision still collects; the variants
+/// only pick what gets logged.
+pub fn collector_decision(
+    collector_address: Option<Address>,
+    signer: Option<Address>,
+    subscriber: Address,
+) -> CollectorDecision {
+    let (Some(expected), Some(signer)) = (collector_address, signer) else {
+        return CollectorDecision::Collect;
+    };
+    if signer == subscriber {
+        CollectorDecision::FeeWaived
+    } else if signer != expected {
+        CollectorDecision::SignerMismatch
+    } else {
+        CollectorDecision::Collect
+    }
+}
+
+/// Number of whole billing periods elapsed since `paid_through` (the due period counts as one).
+///
+/// 0 when not yet due or when `interval` is zero.
+fn overdue_periods(paid_through: U256, interval: U256, now: U256) -> u64 {
+    if interval.is_zero() || now < paid_through {
+        return 0;
+    }
+    let periods = (now - paid_through) / interval + 1;
+    if periods > U256::from(u64::MAX) {
+        u64::MAX
+    } else {
+        periods.as_u64()
+    }
+}
+
+/// Local stand-in for OpenSub's `isDue` (`block.timestamp >= paidThrough`), from a cached
+/// `paidThrough`. `margin` makes it fire early so clock skew or a stale cache can only cost an
+/// extra `isDue()` call, never a missed collect.
+pub fn locally_due(paid_through: u64, now: u64, margin: u64) -> bool {
+    now.saturating_add(margin) >= paid_through
+}
+
+/// How long past `paidThrough` a NonRenewing subscription is kept before pruning, so a local clock
+/// running ahead of chain time can't drop one that `unscheduleCancel()` could still resume.
+const NON_RENEWING_PRUNE_GRACE_SECS: u64 = 3_600;
+
+/// Whether a subscription can never be collected again (`--prune-inactive`): Cancelled, or
+/// NonRenewing with its paid period over (`unscheduleCancel()` reverts from then on). `None`
+/// (unknown id, e.g. a reorged-out `Subscribed`) is not terminal.
+pub fn is_terminal(status: u8, paid_through: u64, now: u64) -> bool {
+    match status {
+        STATUS_CANCELLED => true,
+        STATUS_NON_RENEWING => now >= paid_through.saturating_add(NON_RENEWING_PRUNE_GRACE_SECS),
+        _ => false,
+    }
+}
+
+/// Whether a collect now would violate `--min-seconds-between-collects` (0 disables the guard).
+fn collected_too_recently(last_success: Option<u64>, now: u64, min_seconds: u64) -> bool {
+    match last_success {
+        Some(at) if min_seconds > 0 => now.saturating_sub(at) < min_seconds,
+        _ => false,
+    }
+}
+
 #[derive(Debug, Default)]
 struct AtomicStats {
     checked: AtomicUsize,
+    not_due_locally: AtomicUsize,
     due: AtomicUsize,
     sent: AtomicUsize,
     succeeded: AtomicUsize,
@@ -431,12 +1770,44 @@ struct AtomicStats {
     precheck_failed: AtomicUsize,
     throttled: AtomicUsize,
     pending: AtomicUsize,
+    confirming: AtomicUsize,
+    merchant_amount_total: std::sync::Mutex<U256>,
+    collector_fee_total: std::sync::Mutex<U256>,
+    expected_fee_total: std::sync::Mutex<U256>,
+    gas_spent_wei: std::sync::Mutex<U256>,
 }
 
 impl AtomicStats {
+    fn add_collected(&self, merchant_amount: U256, collector_fee: U256) {
+        let mut m = self
+            .merchant_amount_total
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *m = m.saturating_add(merchant_amount);
+        let mut f = self
+            .collector_fee_total
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *f = f.saturating_add(collector_fee);
+    }
+
+    fn add_expected_fee(&self, fee: U256) {
+        let mut f = self
+            .expected_fee_total
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *f = f.saturating_add(fee);
+    }
+
+    fn add_gas_spent(&self, wei: U256) {
+        let mut g = self.gas_spent_wei.lock().unwrap_or_else(|e| e.into_inner());
+        *g = g.saturating_add(wei);
+    }
+
     fn into_collect_stats(self: Arc<Self>) -> CollectStats {
         CollectStats {
             checked: self.checked.load(Ordering::Relaxed),
+            not_due_locally: self.not_due_locally.load(Ordering::Relaxed),
             due: self.due.load(Ordering::Relaxed),
             sent: self.sent.load(Ordering::Relaxed),
             succeeded: self.succeeded.load(Ordering::Relaxed),
@@ -444,6 +1815,1127 @@ impl AtomicStats {
             precheck_failed: self.precheck_failed.load(Ordering::Relaxed),
             throttled: self.throttled.load(Ordering::Relaxed),
             pending: self.pending.load(Ordering::Relaxed),
+            confirming: self.confirming.load(Ordering::Relaxed),
+            merchant_amount_total: *self
+                .merchant_amount_total
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+            collector_fee_total: *self
+                .collector_fee_total
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+            expected_fee_total: *self
+                .expected_fee_total
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+            gas_spent_wei: *self.gas_spent_wei.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        collect_due, collected_too_recently, collector_decision, expected_collector_fee,
+        is_terminal, locally_due, now_unix, order_ids, overdue_periods, receipt_wait, send_batch,
+        simulate_with_state, AtomicStats, CollectOptions, CollectOrder, CollectorDecision,
+        LocalDueFilter,
+    };
+    use crate::audit::AuditAction;
+    use crate::erc20::{Erc20, Erc20Slots};
+    use crate::multicall::Prefetched;
+    use crate::opensub::{cancel_scheduled, OpenSub};
+    use crate::send_guard::{send_guard_path, SendGuard};
+    use crate::state::FailureKind;
+    use ethers::abi::{encode, Token};
+    use ethers::middleware::{NonceManagerMiddleware, SignerMiddleware};
+    use ethers::providers::{JsonRpcError, Middleware, MockProvider, MockResponse, Provider};
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers::types::transaction::eip2718::TypedTransaction;
+    use ethers::types::{
+        Address, Block, BlockId, BlockNumber, Bytes, FeeHistory, Transaction, TransactionRequest,
+        H256, U256, U64,
+    };
+    use ethers::utils::eip1559_default_estimator;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    const PRICE: u64 = 1_000;
+
+    fn ret(tokens: &[Token]) -> Bytes {
+        Bytes::from(encode(tokens))
+    }
+
+    /// Queue eth_call responses for one due, fully funded subscription, in call order:
+    /// isDue, subscriptions, plans, allowance, balanceOf, then collect() simulation.
+    ///
+    /// MockProvider pops responses from the back, so they are pushed in reverse.
+    fn push_due_subscription(mock: &MockProvider) {
+        push_due_subscription_of(mock, Address::repeat_byte(0xaa));
+    }
+
+    /// `push_due_subscription` for a subscription owned by `subscriber`.
+    fn push_due_subscription_of(mock: &MockProvider, subscriber: Address) {
+        let calls = vec![
+            ret(&[Token::Bool(true)]),
+            ret(&[
+                Token::Uint(U256::from(1)),
+                Token::Address(subscriber),
+                Token::Uint(U256::from(1)),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+            ]),
+            ret(&[
+                Token::Address(Address::repeat_byte(0xbb)),
+                Token::Address(Address::repeat_byte(0xcc)),
+                Token::Uint(U256::from(PRICE)),
+                Token::Uint(U256::from(30 * 86_400u64)),
+                Token::Uint(U256::from(100)),
+                Token::Bool(true),
+                Token::Uint(U256::zero()),
+            ]),
+            ret(&[Token::Uint(U256::from(PRICE))]),
+            ret(&[Token::Uint(U256::from(PRICE))]),
+            ret(&[Token::Uint(U256::from(990)), Token::Uint(U256::from(10))]),
+        ];
+        for r in calls.into_iter().rev() {
+            mock.push::<Bytes, _>(r).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn simulate_only_reports_without_sending() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let probe = client.clone();
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        push_due_subscription(&mock);
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions {
+                simulate_only: true,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.sent, 0);
+        assert!(outcome.pending.is_empty());
+        assert!(outcome.successes.is_empty());
+        assert!(outcome.failures.is_empty());
+        assert_eq!(outcome.simulations.len(), 1);
+        let sim = &outcome.simulations[0];
+        assert_eq!(sim.subscription_id, 7);
+        assert!(sim.would_succeed);
+        assert_eq!(sim.collector_fee, Some(U256::from(10)));
+        assert_eq!(outcome.stats.expected_fee_total, U256::from(10));
+
+        // Every queued response was consumed by the reads + simulation. A send would have found no
+        // queued response and surfaced as a failure above.
+        assert!(probe.get_block_number().await.is_err());
+    }
+
+    #[test]
+    fn expected_fee_is_price_times_bps() {
+        let price = U256::from(1_000_000u64);
+        assert_eq!(
+            expected_collector_fee(price, U256::from(250)),
+            Some(U256::from(25_000))
+        );
+        assert_eq!(
+            expected_collector_fee(price, U256::zero()),
+            Some(U256::zero())
+        );
+        assert_eq!(
+            expected_collector_fee(price, U256::from(10_000)),
+            Some(price)
+        );
+        // Rounds down, like OpenSub.
+        assert_eq!(
+            expected_collector_fee(U256::from(999), U256::from(1)),
+            Some(U256::zero())
+        );
+        assert_eq!(expected_collector_fee(price, U256::from(10_001)), None);
+        assert_eq!(expected_collector_fee(price, U256::MAX), None);
+    }
+
+    #[tokio::test]
+    async fn malformed_fee_bps_skips_the_plan() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let probe = client.clone();
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        // isDue, subscriptions, then a plan whose collectorFeeBps is above 100%.
+        let calls = vec![
+            ret(&[Token::Bool(true)]),
+            ret(&[
+                Token::Uint(U256::from(1)),
+                Token::Address(Address::repeat_byte(0xaa)),
+                Token::Uint(U256::from(1)),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+            ]),
+            ret(&[
+                Token::Address(Address::repeat_byte(0xbb)),
+                Token::Address(Address::repeat_byte(0xcc)),
+                Token::Uint(U256::from(PRICE)),
+                Token::Uint(U256::from(30 * 86_400u64)),
+                Token::Uint(U256::from(10_001)),
+                Token::Bool(true),
+                Token::Uint(U256::zero()),
+            ]),
+        ];
+        for r in calls.into_iter().rev() {
+            mock.push::<Bytes, _>(r).unwrap();
+        }
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions::test_default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.precheck_failed, 1);
+        assert_eq!(outcome.stats.sent, 0);
+        assert_eq!(outcome.stats.expected_fee_total, U256::zero());
+        assert_eq!(outcome.failures.len(), 1);
+        assert_eq!(outcome.failures[0].subscription_id, 7);
+        assert_eq!(outcome.failures[0].kind, FailureKind::MalformedPlan);
+        // No allowance/balance reads or simulation followed.
+        assert!(probe.get_block_number().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn due_confirmations_check_is_due_at_a_confirmed_block() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        // Responses pop from the back: head 100 first, then isDue() == false.
+        mock.push::<Bytes, _>(ret(&[Token::Bool(false)])).unwrap();
+        mock.push(U64::from(100)).unwrap();
+
+        let outcome = collect_due(
+            opensub.clone(),
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions {
+                due_confirmations: 3,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.stats.checked, 1);
+        assert_eq!(outcome.stats.due, 0);
+
+        mock.assert_request("eth_blockNumber", ()).unwrap();
+        let is_due = opensub.is_due(U256::from(7)).tx;
+        mock.assert_request(
+            "eth_call",
+            (is_due, BlockId::from(BlockNumber::Number(97u64.into()))),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancel_scheduled_at_the_boundary_is_not_collected() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        // isDue() at the confirmed block predates `cancel(id, true)`; the latest subscriptions()
+        // row is NonRenewing with its final period ending now. Pushed in reverse (LIFO).
+        mock.push::<Bytes, _>(ret(&[
+            Token::Uint(U256::from(1)),
+            Token::Address(Address::repeat_byte(0xaa)),
+            Token::Uint(U256::from(2)),
+            Token::Uint(U256::zero()),
+            Token::Uint(U256::from(now_unix())),
+            Token::Uint(U256::zero()),
+        ]))
+        .unwrap();
+        mock.push::<Bytes, _>(ret(&[Token::Bool(true)])).unwrap();
+        mock.push(U64::from(100)).unwrap();
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions {
+                due_confirmations: 3,
+                prune_inactive: true,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // Due at the confirmed block, but neither collected, failed nor pruned: unscheduleCancel()
+        // is still possible within the prune grace period.
+        assert_eq!(outcome.stats.due, 1);
+        assert!(outcome.successes.is_empty());
+        assert!(outcome.pending.is_empty());
+        assert!(outcome.failures.is_empty());
+        assert!(outcome.pruned.is_empty());
+        assert!(cancel_scheduled(2));
+        assert!(!cancel_scheduled(1));
+        assert!(!cancel_scheduled(3));
+    }
+
+    #[tokio::test]
+    async fn simulate_at_block_pins_every_read_to_that_block() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        push_due_subscription(&mock);
+
+        let outcome = collect_due(
+            opensub.clone(),
+            opensub_address,
+            client.clone(),
+            vec![7],
+            CollectOptions {
+                simulate_only: true,
+                pin_block: Some(1_234),
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.simulations.len(), 1);
+        assert!(outcome.simulations[0].would_succeed);
+
+        // No head lookup: isDue, the prechecks and collect() all read block 1234.
+        let at = BlockId::from(BlockNumber::Number(1_234u64.into()));
+        let id = U256::from(7);
+        let erc20 = Erc20::new(Address::repeat_byte(0xcc), client);
+        let subscriber = Address::repeat_byte(0xaa);
+        for tx in [
+            opensub.is_due(id).tx,
+            opensub.subscriptions(id).tx,
+            opensub.plans(U256::from(1)).tx,
+            erc20.allowance(subscriber, opensub_address).tx,
+            erc20.balance_of(subscriber).tx,
+            opensub.collect(id).tx,
+        ] {
+            mock.assert_request("eth_call", (tx, at)).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn pin_block_pins_reads_and_the_pre_send_simulation() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        push_due_subscription(&mock);
+
+        // Sending path (not --simulate-only): the collect is queued for an --eip7702 batch, which
+        // the mocked provider has no signer for.
+        let outcome = collect_due(
+            opensub.clone(),
+            opensub_address,
+            client.clone(),
+            vec![7],
+            CollectOptions {
+                pin_block: Some(1_234),
+                eip7702_batch: true,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(outcome.simulations.is_empty());
+        assert_eq!(outcome.stats.precheck_failed, 0);
+        assert_eq!(outcome.failures.len(), 1);
+        assert_eq!(outcome.failures[0].kind, FailureKind::RpcError);
+        assert!(outcome.failures[0]
+            .reason
+            .as_deref()
+            .unwrap()
+            .contains("no signer"));
+
+        let at = BlockId::from(BlockNumber::Number(1_234u64.into()));
+        let id = U256::from(7);
+        let erc20 = Erc20::new(Address::repeat_byte(0xcc), client);
+        let subscriber = Address::repeat_byte(0xaa);
+        for tx in [
+            opensub.is_due(id).tx,
+            opensub.subscriptions(id).tx,
+            opensub.plans(U256::from(1)).tx,
+            erc20.allowance(subscriber, opensub_address).tx,
+            erc20.balance_of(subscriber).tx,
+            opensub.collect(id).tx,
+        ] {
+            mock.assert_request("eth_call", (tx, at)).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn cycle_deadline_cuts_receipt_waits_and_tracks_txs_in_flight() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+
+        // Two due subscriptions, sent one after the other; neither receipt ever arrives. Per
+        // subscription, after the reads: the EIP-1559 fee estimate (latest block, fee history),
+        // eth_sendTransaction and the sent-nonce lookup (not found). Pushed in reverse, so the
+        // second subscription goes first.
+        for tx_hash in [H256::repeat_byte(0x02), H256::repeat_byte(0x01)] {
+            mock.push::<Option<Transaction>, _>(None).unwrap();
+            mock.push(tx_hash).unwrap();
+            mock.push(FeeHistory {
+                base_fee_per_gas: vec![U256::from(1_000_000_000u64)],
+                gas_used_ratio: vec![0.5],
+                oldest_block: U256::from(99),
+                reward: vec![vec![U256::from(1_000_000_000u64)]],
+            })
+            .unwrap();
+            mock.push(Block::<H256> {
+                number: Some(U64::from(100)),
+                base_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+                ..Default::default()
+            })
+            .unwrap();
+            push_due_subscription(&mock);
+        }
+
+        let started = std::time::Instant::now();
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7, 8],
+            CollectOptions {
+                gas_limit: Some(200_000),
+                tx_timeout: Duration::from_secs(30),
+                cycle_deadline: Some(Duration::from_millis(300)),
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // Well before the 30s --tx-timeout-seconds.
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "{:?}",
+            started.elapsed()
+        );
+        assert_eq!(outcome.stats.sent, 2);
+        assert_eq!(outcome.stats.pending, 2);
+        assert!(outcome.failures.is_empty());
+        assert!(outcome.successes.is_empty());
+        let pending: Vec<(u64, H256)> = outcome
+            .pending
+            .iter()
+            .map(|p| (p.subscription_id, p.tx_hash))
+            .collect();
+        assert_eq!(
+            pending,
+            vec![(7, H256::repeat_byte(0x01)), (8, H256::repeat_byte(0x02))]
+        );
+    }
+
+    #[tokio::test]
+    async fn fill_and_send_failures_are_audited_as_such() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+
+        // Subscription 8: the fee estimate works but eth_sendTransaction is rejected.
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "insufficient funds for gas * price + value".to_string(),
+            data: None,
+        }));
+        mock.push(FeeHistory {
+            base_fee_per_gas: vec![U256::from(1_000_000_000u64)],
+            gas_used_ratio: vec![0.5],
+            oldest_block: U256::from(99),
+            reward: vec![vec![U256::from(1_000_000_000u64)]],
+        })
+        .unwrap();
+        mock.push(Block::<H256> {
+            number: Some(U64::from(100)),
+            base_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+            ..Default::default()
+        })
+        .unwrap();
+        push_due_subscription(&mock);
+        // Subscription 7: the fee estimate (latest block) fails, so the tx is never filled.
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "header not found".to_string(),
+            data: None,
+        }));
+        push_due_subscription(&mock);
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7, 8],
+            CollectOptions {
+                gas_limit: Some(200_000),
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.sent, 0);
+        assert_eq!(outcome.stats.failed, 2);
+        let actions: Vec<(u64, AuditAction)> = outcome
+            .audit
+            .iter()
+            .map(|e| (e.subscription_id, e.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![(7, AuditAction::FillFail), (8, AuditAction::SendFail)]
+        );
+    }
+
+    #[tokio::test]
+    async fn gas_budget_is_checked_before_each_send() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+
+        let base_fee = U256::from(1_000_000_000u64);
+        let rewards = vec![vec![U256::from(1_000_000_000u64)]];
+        let (max_fee, _) = eip1559_default_estimator(base_fee, rewards.clone());
+        let tx_cost = U256::from(200_000) * max_fee;
+
+        // Both subscriptions reach the fee estimate; only the first one is sent (with the
+        // sent-nonce lookup after it). Pushed in reverse, so the second subscription goes first.
+        for sent in [false, true] {
+            if sent {
+                mock.push::<Option<Transaction>, _>(None).unwrap();
+                mock.push(H256::repeat_byte(0x07)).unwrap();
+            }
+            mock.push(FeeHistory {
+                base_fee_per_gas: vec![base_fee],
+                gas_used_ratio: vec![0.5],
+                oldest_block: U256::from(99),
+                reward: rewards.clone(),
+            })
+            .unwrap();
+            mock.push(Block::<H256> {
+                number: Some(U64::from(100)),
+                base_fee_per_gas: Some(base_fee),
+                ..Default::default()
+            })
+            .unwrap();
+            push_due_subscription(&mock);
+        }
+
+        // Room for one tx at its worst-case cost, not two.
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7, 8],
+            CollectOptions {
+                gas_limit: Some(200_000),
+                gas_budget: Some(tx_cost * 3 / 2),
+                force_pending: true,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.sent, 1);
+        assert_eq!(outcome.stats.throttled, 1);
+        assert_eq!(outcome.pending.len(), 1);
+        assert_eq!(outcome.pending[0].subscription_id, 7);
+        let throttled: Vec<u64> = outcome
+            .audit
+            .iter()
+            .filter(|e| e.action == AuditAction::Throttle)
+            .map(|e| e.subscription_id)
+            .collect();
+        assert_eq!(throttled, vec![8]);
+    }
+
+    #[tokio::test]
+    async fn failed_fill_does_not_take_a_nonce() {
+        let (provider, mock) = Provider::mocked();
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let client = Arc::new(NonceManagerMiddleware::new(
+            SignerMiddleware::new(provider, wallet.clone()),
+            wallet.address(),
+        ));
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+
+        // Subscription 8: fee estimate, the nonce manager's first eth_getTransactionCount (5),
+        // eth_sendRawTransaction, then the sent-nonce lookup (not found yet).
+        mock.push::<Option<Transaction>, _>(None).unwrap();
+        mock.push(H256::repeat_byte(0x08)).unwrap();
+        mock.push(U256::from(5)).unwrap();
+        mock.push(FeeHistory {
+            base_fee_per_gas: vec![U256::from(1_000_000_000u64)],
+            gas_used_ratio: vec![0.5],
+            oldest_block: U256::from(99),
+            reward: vec![vec![U256::from(1_000_000_000u64)]],
+        })
+        .unwrap();
+        mock.push(Block::<H256> {
+            number: Some(U64::from(100)),
+            base_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+            ..Default::default()
+        })
+        .unwrap();
+        push_due_subscription(&mock);
+        // Subscription 7: the fee estimate fails before any nonce is handed out.
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "header not found".to_string(),
+            data: None,
+        }));
+        push_due_subscription(&mock);
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client.clone(),
+            vec![7, 8],
+            CollectOptions {
+                gas_limit: Some(200_000),
+                force_pending: true,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.failures.len(), 1);
+        assert_eq!(outcome.failures[0].subscription_id, 7);
+        let pending: Vec<(u64, Option<U256>)> = outcome
+            .pending
+            .iter()
+            .map(|p| (p.subscription_id, p.nonce))
+            .collect();
+        assert_eq!(pending, vec![(8, Some(U256::from(5)))]);
+        // No gap: the next tx gets 6.
+        assert_eq!(client.next(), U256::from(6));
+    }
+
+    #[tokio::test]
+    async fn failed_batch_fill_does_not_take_a_nonce() {
+        let (provider, mock) = Provider::mocked();
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let client = NonceManagerMiddleware::new(
+            SignerMiddleware::new(provider, wallet.clone()),
+            wallet.address(),
+        );
+        let opensub = Address::repeat_byte(0x01);
+        let stats = AtomicStats::default();
+        let pending_out = tokio::sync::Mutex::new(Vec::new());
+        let failures_out = tokio::sync::Mutex::new(Vec::new());
+        let audit_out = tokio::sync::Mutex::new(Vec::new());
+
+        // Second batch: fee estimate, gas estimate, the nonce manager's first
+        // eth_getTransactionCount (5), eth_sendRawTransaction and the sent-nonce lookup. First
+        // batch: the gas estimate fails. Responses pop from the back.
+        mock.push::<Option<Transaction>, _>(None).unwrap();
+        mock.push(H256::repeat_byte(0x08)).unwrap();
+        mock.push(U256::from(5)).unwrap();
+        mock.push(U256::from(300_000)).unwrap();
+        for estimate_gas in [None, Some("execution reverted")] {
+            if let Some(message) = estimate_gas {
+                mock.push_response(MockResponse::Error(JsonRpcError {
+                    code: 3,
+                    message: message.to_string(),
+                    data: None,
+                }));
+            }
+            mock.push(FeeHistory {
+                base_fee_per_gas: vec![U256::from(1_000_000_000u64)],
+                gas_used_ratio: vec![0.5],
+                oldest_block: U256::from(99),
+                reward: vec![vec![U256::from(1_000_000_000u64)]],
+            })
+            .unwrap();
+            mock.push(Block::<H256> {
+                number: Some(U64::from(100)),
+                base_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+                ..Default::default()
+            })
+            .unwrap();
+        }
+
+        for id in [7, 8] {
+            send_batch(
+                &client,
+                opensub,
+                vec![(id, Bytes::from(vec![0xaa]))],
+                None,
+                &stats,
+                &pending_out,
+                &failures_out,
+                &audit_out,
+            )
+            .await;
+        }
+
+        let failures = failures_out.into_inner();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].subscription_id, 7);
+        let pending: Vec<(u64, Option<U256>)> = pending_out
+            .into_inner()
+            .iter()
+            .map(|p| (p.subscription_id, p.nonce))
+            .collect();
+        assert_eq!(pending, vec![(8, Some(U256::from(5)))]);
+        assert_eq!(client.next(), U256::from(6));
+    }
+
+    #[test]
+    fn receipt_wait_is_cut_to_what_is_left_of_the_deadline() {
+        let timeout = Duration::from_secs(30);
+        assert_eq!(receipt_wait(timeout, None), timeout);
+        assert_eq!(
+            receipt_wait(timeout, Some(Instant::now() + Duration::from_secs(60))),
+            timeout
+        );
+
+        // Less than a second left: a sub-second wait, not zero (which would mean no timeout).
+        let wait = receipt_wait(timeout, Some(Instant::now() + Duration::from_millis(400)));
+        assert!(
+            !wait.is_zero() && wait <= Duration::from_millis(400),
+            "{wait:?}"
+        );
+
+        // Past the deadline: nothing left to wait.
+        let passed = Instant::now() - Duration::from_millis(1);
+        assert!(receipt_wait(timeout, Some(passed)).is_zero());
+    }
+
+    #[test]
+    fn collected_amounts_accumulate_across_subscriptions() {
+        let stats = Arc::new(AtomicStats::default());
+        stats.add_collected(U256::from(990u64), U256::from(10u64));
+        stats.add_collected(U256::from(1_980u64), U256::from(20u64));
+
+        let out = stats.into_collect_stats();
+        assert_eq!(out.merchant_amount_total, U256::from(2_970u64));
+        assert_eq!(out.collector_fee_total, U256::from(30u64));
+    }
+
+    #[test]
+    fn collected_amounts_saturate_instead_of_overflowing() {
+        let stats = Arc::new(AtomicStats::default());
+        stats.add_collected(U256::MAX, U256::MAX);
+        stats.add_collected(U256::one(), U256::one());
+
+        let out = stats.into_collect_stats();
+        assert_eq!(out.merchant_amount_total, U256::MAX);
+        assert_eq!(out.collector_fee_total, U256::MAX);
+    }
+
+    #[test]
+    fn min_collect_interval_guard() {
+        // Disabled by default.
+        assert!(!collected_too_recently(Some(1_000), 1_001, 0));
+        // Never collected before.
+        assert!(!collected_too_recently(None, 1_001, 60));
+        // Too soon after the previous success.
+        assert!(collected_too_recently(Some(1_000), 1_059, 60));
+        // Exactly at / past the minimum gap.
+        assert!(!collected_too_recently(Some(1_000), 1_060, 60));
+    }
+
+    #[test]
+    fn local_due_predicate() {
+        const MONTH: u64 = 30 * 86_400;
+        let start = 1_700_000_000;
+        // Subscribed at `start`: paid through one interval later.
+        let paid_through = start + MONTH;
+
+        // Mid-period: not due, with or without margin.
+        assert!(!locally_due(paid_through, start + MONTH / 2, 0));
+        assert!(!locally_due(paid_through, start + MONTH / 2, 120));
+        // Just before paidThrough: only due within the margin.
+        assert!(!locally_due(paid_through, paid_through - 121, 120));
+        assert!(locally_due(paid_through, paid_through - 120, 120));
+        assert!(!locally_due(paid_through, paid_through - 1, 0));
+        // At and after paidThrough (same boundary as OpenSub's isDue), including long overdue.
+        assert!(locally_due(paid_through, paid_through, 0));
+        assert!(locally_due(paid_through, paid_through + 3 * MONTH, 0));
+        // A huge margin saturates rather than overflowing.
+        assert!(locally_due(u64::MAX, u64::MAX - 10, u64::MAX));
+    }
+
+    /// Ids 1-4 in discovery order: 1 is barely overdue on a cheap plan, 2 is long overdue on a
+    /// mid plan, 3 is an expensive plan, 4 was never read.
+    fn sample_rows() -> Prefetched {
+        let subscriber = Address::repeat_byte(0x55);
+        let plan = |price: u64, fee_bps: u64| {
+            (
+                Address::repeat_byte(0x4d),
+                Address::repeat_byte(0x70),
+                U256::from(price),
+                U256::from(2_592_000u64),
+                U256::from(fee_bps),
+                true,
+                U256::from(900u64),
+            )
+        };
+        let subscription = |plan_id: u64, paid_through: u64| {
+            (
+                U256::from(plan_id),
+                subscriber,
+                1,
+                U256::from(1_000u64),
+                U256::from(paid_through),
+                U256::from(1_000u64),
+            )
+        };
+        let mut rows = Prefetched::default();
+        rows.subscriptions.insert(1, subscription(10, 5_000));
+        rows.subscriptions.insert(2, subscription(11, 1_000));
+        rows.subscriptions.insert(3, subscription(12, 3_000));
+        rows.plans.insert(U256::from(10), plan(1_000_000, 100));
+        rows.plans.insert(U256::from(11), plan(5_000_000, 100));
+        rows.plans.insert(U256::from(12), plan(20_000_000, 50));
+        rows
+    }
+
+    #[test]
+    fn fifo_order_keeps_discovery_order() {
+        let mut ids = vec![4, 1, 3, 2];
+        order_ids(&mut ids, CollectOrder::Fifo, &sample_rows());
+        assert_eq!(ids, vec![4, 1, 3, 2]);
+    }
+
+    #[test]
+    fn most_overdue_order_sorts_by_paid_through() {
+        let mut ids = vec![4, 1, 3, 2];
+        order_ids(&mut ids, CollectOrder::MostOverdue, &sample_rows());
+        // paidThrough 1000, 3000, 5000; the unread id last.
+        assert_eq!(ids, vec![2, 3, 1, 4]);
+    }
+
+    #[test]
+    fn highest_fee_order_sorts_by_expected_fee() {
+        let mut rows = sample_rows();
+        let mut ids = vec![4, 1, 3, 2];
+        order_ids(&mut ids, CollectOrder::HighestFee, &rows);
+        // Fees 100000 (3), 50000 (2), 10000 (1); the unread id last.
+        assert_eq!(ids, vec![3, 2, 1, 4]);
+
+        // A cancelled subscription earns nothing, whatever its plan pays.
+        rows.subscriptions.get_mut(&3).unwrap().2 = 3;
+        let mut ids = vec![4, 1, 3, 2];
+        order_ids(&mut ids, CollectOrder::HighestFee, &rows);
+        assert_eq!(ids, vec![2, 1, 4, 3]);
+    }
+
+    #[tokio::test]
+    async fn cached_paid_through_skips_is_due_until_close() {
+        let (provider, _mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let probe = client.clone();
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+
+        let mut paid_through = BTreeMap::new();
+        paid_through.insert(7u64, now_unix() + 86_400);
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions {
+                local_due: Some(LocalDueFilter {
+                    margin: 120,
+                    paid_through,
+                }),
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.checked, 1);
+        assert_eq!(outcome.stats.not_due_locally, 1);
+        assert!(outcome.failures.is_empty());
+        assert!(outcome.paid_through.is_empty());
+        // No request was made: the mock had nothing queued, so isDue() would have failed above.
+        assert!(probe.get_block_number().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn cancelled_subscription_is_pruned() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        let subscription = |status: u64| {
+            ret(&[
+                Token::Uint(U256::from(1)),
+                Token::Address(Address::repeat_byte(0xaa)),
+                Token::Uint(U256::from(status)),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::from(now_unix() + 86_400)),
+                Token::Uint(U256::zero()),
+            ])
+        };
+        // 7: isDue() false, subscriptions() says Cancelled.
+        // 8: isDue() false, subscriptions() says NonRenewing with access left (resumable).
+        // Pushed in reverse (LIFO).
+        for r in [
+            subscription(2),
+            ret(&[Token::Bool(false)]),
+            subscription(3),
+            ret(&[Token::Bool(false)]),
+        ] {
+            mock.push::<Bytes, _>(r).unwrap();
         }
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7, 8],
+            CollectOptions {
+                prune_inactive: true,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.checked, 2);
+        assert_eq!(outcome.stats.due, 0);
+        assert!(outcome.failures.is_empty());
+        assert_eq!(outcome.pruned, vec![7]);
+
+        let now = 1_000_000;
+        assert!(is_terminal(3, now + 86_400, now));
+        assert!(!is_terminal(2, now, now));
+        assert!(is_terminal(2, now - 3_600, now));
+        assert!(!is_terminal(1, 0, now));
+        assert!(!is_terminal(0, 0, now));
+    }
+
+    #[tokio::test]
+    async fn too_soon_second_collect_is_throttled() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        push_due_subscription(&mock);
+
+        let mut last_success = BTreeMap::new();
+        last_success.insert(7u64, now_unix());
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions {
+                simulate: false,
+                min_seconds_between_collects: 3_600,
+                last_success,
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.throttled, 1);
+        assert_eq!(outcome.stats.sent, 0);
+        assert!(outcome.failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resend_within_window_is_skipped_after_restart() {
+        let dir =
+            std::env::temp_dir().join(format!("opensub-keeper-resend-{}", std::process::id()));
+        let path = send_guard_path(&dir.join("state.json"));
+        // The previous process sent a collect for 7, then crashed before saving the outcome.
+        SendGuard::load(path.clone(), 600, now_unix())
+            .unwrap()
+            .record(7, now_unix())
+            .unwrap();
+
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        push_due_subscription(&mock);
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions {
+                simulate: false,
+                send_guard: Some(Arc::new(SendGuard::load(path, 600, now_unix()).unwrap())),
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.stats.due, 1);
+        assert_eq!(outcome.stats.throttled, 1);
+        assert_eq!(outcome.stats.sent, 0);
+        assert!(outcome.failures.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn collector_address_filtering_decision() {
+        let signer = Address::repeat_byte(0x11);
+        let other = Address::repeat_byte(0x22);
+        let subscriber = Address::repeat_byte(0xaa);
+
+        // No filter configured (or signer unknown): always collect.
+        assert_eq!(
+            collector_decision(None, Some(signer), subscriber),
+            CollectorDecision::Collect
+        );
+        assert_eq!(
+            collector_decision(Some(signer), None, subscriber),
+            CollectorDecision::Collect
+        );
+        // Fee goes to the configured collector.
+        assert_eq!(
+            collector_decision(Some(signer), Some(signer), subscriber),
+            CollectorDecision::Collect
+        );
+        // Fee goes to the signer instead.
+        assert_eq!(
+            collector_decision(Some(other), Some(signer), subscriber),
+            CollectorDecision::SignerMismatch
+        );
+        // Self-collection: OpenSub waives the fee, but the merchant must still be paid.
+        assert_eq!(
+            collector_decision(Some(signer), Some(signer), signer),
+            CollectorDecision::FeeWaived
+        );
+    }
+
+    #[tokio::test]
+    async fn self_owned_subscription_is_still_collected() {
+        let (provider, mock) = Provider::mocked();
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let client = Arc::new(SignerMiddleware::new(provider, wallet.clone()));
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, client.clone());
+        // The signer owns the subscription, so OpenSub waives the fee; the merchant is still due.
+        push_due_subscription_of(&mock, wallet.address());
+
+        let outcome = collect_due(
+            opensub,
+            opensub_address,
+            client,
+            vec![7],
+            CollectOptions {
+                simulate_only: true,
+                collector_address: Some(wallet.address()),
+                ..CollectOptions::test_default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.simulations.len(), 1);
+        assert!(outcome.simulations[0].would_succeed);
+        assert_eq!(outcome.stats.throttled, 0);
+    }
+
+    #[test]
+    fn overdue_period_computation() {
+        let day = 86_400u64;
+        let interval = U256::from(30 * day);
+        let paid_through = U256::from(1_000_000u64);
+
+        // Not due yet.
+        assert_eq!(overdue_periods(paid_through, interval, paid_through - 1), 0);
+        // Due exactly now / within the first period.
+        assert_eq!(overdue_periods(paid_through, interval, paid_through), 1);
+        assert_eq!(
+            overdue_periods(paid_through, interval, paid_through + U256::from(29 * day)),
+            1
+        );
+        // Keeper was down for ~3 months.
+        assert_eq!(
+            overdue_periods(paid_through, interval, paid_through + U256::from(95 * day)),
+            4
+        );
+        // Degenerate plan.
+        assert_eq!(
+            overdue_periods(paid_through, U256::zero(), paid_through + 1),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn override_simulation_sends_state_set_and_reports_outcome() {
+        let (provider, mock) = Provider::mocked();
+        let opensub = Address::repeat_byte(0x05);
+        let token = Address::repeat_byte(0x70);
+        let state = Erc20Slots {
+            balance: U256::from(3),
+            allowance: U256::from(4),
+        }
+        .funds_override(
+            token,
+            Address::repeat_byte(0xaa),
+            opensub,
+            U256::from(PRICE),
+        );
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(opensub)
+            .data(vec![0x01, 0x02])
+            .into();
+
+        // Second call still reverts, first succeeds. Responses pop from the back.
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: None,
+        }));
+        mock.push::<Bytes, _>(Bytes::new()).unwrap();
+
+        let ok = simulate_with_state(&provider, &tx, &state, None).await;
+        assert!(ok.contains("succeeds"), "{ok}");
+        mock.assert_request("eth_call", (&tx, "latest", &state))
+            .unwrap();
+
+        let still = simulate_with_state(&provider, &tx, &state, None).await;
+        assert!(still.contains("still reverts"), "{still}");
     }
 }
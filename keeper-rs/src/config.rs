@@ -1,32 +1,192 @@
+use crate::breaker::BreakerConfig;
+use crate::collect_window::CollectWindow;
+use crate::collector::CollectOrder;
 use crate::deployments::DeploymentArtifact;
-use ethers::types::Address;
+use crate::erc20::Erc20Slots;
+use crate::gas_limits::GasLimitOverrides;
+use crate::relayer::RelayerConfig;
+use crate::state::{EvictionPolicy, FailureKind};
+use ethers::types::{Address, U256};
 use eyre::{eyre, Result};
+use opensub_aa::http::ClientOptions;
 use std::{path::PathBuf, str::FromStr, time::Duration};
 
+/// Optional per-`FailureKind` backoff bases (seconds) from the CLI.
+///
+/// Unset kinds fall back to `backoff_base`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KindBackoffOverrides {
+    pub insufficient_allowance: Option<u64>,
+    pub insufficient_balance: Option<u64>,
+    pub simulation_revert: Option<u64>,
+    pub mined_revert: Option<u64>,
+    pub unknown: Option<u64>,
+}
+
+/// CLI values for [`KeeperConfig::from_cli_and_deployment`], before clamping and validation.
+#[derive(Debug, Clone)]
+pub struct CliOptions {
+    pub rpc_override: Option<String>,
+    pub rpc_client: ClientOptions,
+    pub json_rpc_batch: bool,
+    pub private_key_env: String,
+    pub keystore: Option<PathBuf>,
+    pub keystore_password_env: String,
+    pub poll_seconds: u64,
+    pub startup_splay_seconds: u64,
+    pub poll_splay_seconds: u64,
+    pub log_chunk: u64,
+    pub scan_confirmations: u64,
+    pub collect_confirmations: u64,
+    pub due_confirmations: u64,
+    pub local_due_margin: Option<u64>,
+    pub prune_inactive: bool,
+    pub multicall_address: Option<Address>,
+    pub collect_order: CollectOrder,
+    pub max_tracked_subscriptions: Option<usize>,
+    pub eviction_policy: EvictionPolicy,
+    pub state_file: PathBuf,
+    pub max_concurrency: usize,
+    pub gas_limit: Option<u64>,
+    pub gas_limit_overrides: GasLimitOverrides,
+    pub max_txs_per_cycle: usize,
+    pub daily_gas_budget_wei: Option<u128>,
+    pub tx_timeout_seconds: u64,
+    pub cycle_deadline_seconds: Option<u64>,
+    pub pending_ttl_seconds: u64,
+    pub backoff_base_seconds: u64,
+    pub backoff_max_seconds: u64,
+    pub plan_inactive_backoff_seconds: u64,
+    pub rpc_error_backoff_seconds: u64,
+    pub kind_backoff: KindBackoffOverrides,
+    pub jitter_seconds: u64,
+    pub random_jitter: bool,
+    pub breaker: BreakerConfig,
+    pub min_seconds_between_collects: u64,
+    pub min_resend_seconds: u64,
+    pub collector_address: Option<Address>,
+    pub fee_recipient: Option<Address>,
+    pub startup_retries: u32,
+    pub startup_retry_delay_seconds: u64,
+    pub force_pending: bool,
+    pub simulate: bool,
+    pub simulate_overrides: Option<Erc20Slots>,
+    pub once: bool,
+    pub catch_up: bool,
+    pub reconcile_only: bool,
+    pub dry_run: bool,
+    pub explain: bool,
+    pub simulate_only: bool,
+    pub simulate_report: Option<PathBuf>,
+    pub simulate_at_block: Option<u64>,
+    pub pin_block: Option<u64>,
+    pub control_file: Option<PathBuf>,
+    pub collect_window: Option<CollectWindow>,
+    pub only_tag: Option<String>,
+    pub audit_log: Option<PathBuf>,
+    pub audit_log_max_bytes: u64,
+    pub new_subscription_webhook_url: Option<String>,
+    pub post_collect_cmd: Option<String>,
+    pub relayer: Option<RelayerConfig>,
+    pub eip7702: Option<Address>,
+    pub from_block: Option<u64>,
+    pub reset_scan: bool,
+    pub local_dev: bool,
+    pub monitor_only: bool,
+    pub nonce_manager: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct KeeperConfig {
     pub chain_id: u64,
     pub rpc_url: String,
+
+    /// `--rpc-pool-*`: connection pool of the provider's HTTP client.
+    pub rpc_client: ClientOptions,
+    /// `--json-rpc-batch`: coalesce concurrent `eth_call`s into JSON-RPC batch requests.
+    pub json_rpc_batch: bool,
     pub opensub: Address,
+    /// Scan floor: the deployment start block, unless overridden with `--from-block`.
     pub start_block: u64,
 
+    /// `--from-block`, when given (already folded into `start_block`).
+    pub from_block: Option<u64>,
+
+    /// `--reset-scan`: rewind `last_scanned_block` to `start_block - 1` at startup.
+    pub reset_scan: bool,
+
     pub poll_interval: Duration,
+
+    /// Upper bound of the random delay before the first cycle (zero = disabled).
+    pub startup_splay: Duration,
+
+    /// Upper bound of the random delay added to each `poll_interval` sleep (zero = disabled).
+    pub poll_splay: Duration,
+
+    /// How often ethers polls for pending tx receipts (800ms; 100ms with `--local-dev`).
+    pub provider_poll_interval: Duration,
     pub log_chunk_size: u64,
-    pub confirmations: u64,
+
+    /// How far behind head `Subscribed` logs are scanned. Independent of `collect_confirmations`,
+    /// so discovery can wait for deeper blocks than tx finality.
+    pub scan_confirmations: u64,
+
+    /// Confirmations a successful collect() receipt needs before it counts as a success (<= 1:
+    /// the receipt alone is enough).
+    pub collect_confirmations: u64,
+
+    /// `isDue` must hold at `latest - due_confirmations` before collecting (0: at latest).
+    pub due_confirmations: u64,
+
+    /// Seconds before a cached `paidThrough` at which a subscription is treated as locally due and
+    /// checked with `isDue()`. `None` disables the local pre-filter (every id is checked on-chain).
+    pub local_due_margin: Option<u64>,
+
+    /// `--prune-inactive`: drop ids from `subscription_ids` once they are Cancelled (or
+    /// NonRenewing past their paid period) instead of re-checking them every cycle.
+    pub prune_inactive: bool,
+
+    /// `--multicall-address`: Multicall3 used to batch the per-cycle `subscriptions()` / `plans()`
+    /// reads. `None` reads them one call per id.
+    pub multicall_address: Option<Address>,
+
+    /// `--collect-order`: which due subscriptions each cycle reaches first.
+    pub collect_order: CollectOrder,
+
+    /// `--max-tracked-subscriptions`: cap on `subscription_ids`, enforced after each scan by
+    /// evicting per `eviction_policy`. `None` tracks every discovered id.
+    pub max_tracked_subscriptions: Option<usize>,
+    pub eviction_policy: EvictionPolicy,
 
     pub state_file: PathBuf,
     pub max_concurrency: usize,
 
     pub private_key_env: String,
 
+    /// `--keystore`: encrypted JSON keystore to sign with instead of `private_key_env`.
+    pub keystore: Option<PathBuf>,
+
+    /// Environment variable holding the `--keystore` password.
+    pub keystore_password_env: String,
+
     pub gas_limit: Option<u64>,
 
+    /// `--gas-limits-file`: per-plan / per-subscription limits that take precedence over
+    /// `gas_limit`.
+    pub gas_limit_overrides: GasLimitOverrides,
+
     /// Max number of collect() txs to submit per cycle.
     pub max_txs_per_cycle: usize,
 
+    /// `--daily-gas-budget-wei`: stop sending once the rolling 24h gas spend reaches this.
+    pub daily_gas_budget_wei: Option<U256>,
+
     /// How long to wait for a transaction receipt before considering it "still pending".
     pub tx_timeout: Duration,
 
+    /// `--cycle-deadline-seconds`: cap on the total time a cycle waits for receipts.
+    pub cycle_deadline: Option<Duration>,
+
     /// How long to keep an in-flight tx in the state file before dropping it and allowing a retry.
     pub pending_ttl: Duration,
 
@@ -36,15 +196,51 @@ pub struct KeeperConfig {
     /// Milestone 5.1: maximum backoff duration.
     pub backoff_max: Duration,
 
-    /// Milestone 5.1: backoff base duration for PlanInactive.
+    /// Milestone 5.1: backoff base duration for PlanInactive (and MalformedPlan).
     pub plan_inactive_backoff: Duration,
 
     /// Milestone 5.1: backoff base duration for transient RPC errors.
     pub rpc_error_backoff: Duration,
 
+    /// Per-kind backoff bases; `None` means "use `backoff_base`".
+    pub insufficient_allowance_backoff: Option<Duration>,
+    pub insufficient_balance_backoff: Option<Duration>,
+    pub simulation_revert_backoff: Option<Duration>,
+    pub mined_revert_backoff: Option<Duration>,
+    pub unknown_backoff: Option<Duration>,
+
     /// Milestone 5.1: deterministic jitter window to avoid thundering herd.
     pub jitter: Duration,
 
+    /// Draw jitter from a PRNG instead of deriving it from the subscription id.
+    pub random_jitter: bool,
+
+    /// Cycle-wide circuit breaker (`--breaker-*`).
+    pub breaker: BreakerConfig,
+
+    /// Minimum gap between successful collects of the same subscription (0 = disabled).
+    pub min_seconds_between_collects: u64,
+
+    /// Minimum gap between collect() sends for the same subscription, persisted across restarts
+    /// (0 = disabled).
+    pub min_resend_seconds: u64,
+
+    /// Address this keeper expects collector fees to be paid to (`--collector-address`).
+    ///
+    /// OpenSub pays the fee to `msg.sender`, so this only has an effect as a sanity check against
+    /// the signer, plus skipping subscriptions whose fee would be waived (signer == subscriber).
+    pub collector_address: Option<Address>,
+
+    /// `--fee-recipient`: use `collect(id, feeRecipient)`. Cleared at startup if the deployed
+    /// contract has no such overload.
+    pub fee_recipient: Option<Address>,
+
+    /// Extra attempts for the startup RPC checks before giving up.
+    pub startup_retries: u32,
+
+    /// Initial delay between startup retries (doubles per attempt).
+    pub startup_retry_delay: Duration,
+
     /// Test hook: mark sent txs as pending immediately (skip receipt wait).
     pub force_pending: bool,
 
@@ -53,35 +249,147 @@ pub struct KeeperConfig {
     /// This avoids wasting gas on transactions that would revert.
     pub simulate: bool,
 
+    /// `--simulate-with-overrides`: token slots used to re-simulate failing collects with the
+    /// subscriber's funds overridden.
+    pub simulate_overrides: Option<Erc20Slots>,
+
     pub once: bool,
+
+    /// Scan until caught up with the confirmed head, do one collect pass, then exit.
+    pub catch_up: bool,
+
+    /// Reconcile in-flight / confirming txs, report, and exit (no scan, no collect). Implies
+    /// `once`.
+    pub reconcile_only: bool,
     pub dry_run: bool,
+
+    /// `--explain`: report each subscription's decision and resulting backoff every cycle.
+    pub explain: bool,
+
+    /// No signer: scan + precheck + report only. Implies `dry_run`.
+    pub monitor_only: bool,
+
+    /// Wrap the signer in `NonceManagerMiddleware` (cleared by `--no-nonce-manager`, which leaves
+    /// nonces to the provider's pending count).
+    pub nonce_manager: bool,
+
+    /// Report-only mode: simulate collect() for every eligible id, never send or persist outcomes.
+    pub simulate_only: bool,
+
+    /// Optional JSON report path for `--simulate-only`.
+    pub simulate_report: Option<PathBuf>,
+
+    /// `--simulate-at-block`: run `--simulate-only` against this (historical) block's state.
+    pub simulate_at_block: Option<u64>,
+
+    /// `--pin-block`: read isDue, the prechecks and the simulation at this block; sends stay live.
+    pub pin_block: Option<u64>,
+
+    /// `--control-file`: `pause` in this file skips the collect step each cycle.
+    pub control_file: Option<PathBuf>,
+
+    /// `--collect-window` / `--timezone`: collects are only sent inside this time of day.
+    pub collect_window: Option<CollectWindow>,
+
+    /// `--only-tag`: only subscriptions with this tag are checked each cycle.
+    pub only_tag: Option<String>,
+
+    /// Optional JSON-lines audit trail of individual keeper actions.
+    pub audit_log: Option<PathBuf>,
+
+    /// Size (bytes) after which the audit log is rotated to `<path>.1` (0 = never).
+    pub audit_log_max_bytes: u64,
+
+    /// `--new-subscription-webhook-url`: POST each newly discovered subscription here.
+    pub new_subscription_webhook_url: Option<String>,
+
+    /// Shell command template run after each recorded success (`--post-collect-cmd`).
+    pub post_collect_cmd: Option<String>,
+
+    /// `--relayer-mode`: send collect() as a userOp from a smart account via a bundler.
+    pub relayer: Option<RelayerConfig>,
+
+    /// `--eip7702`: delegate implementation the keeper EOA batches collects through. Cleared at
+    /// startup unless the EOA is delegated to it.
+    pub eip7702: Option<Address>,
 }
 
 impl KeeperConfig {
-    #[allow(clippy::too_many_arguments)]
     pub fn from_cli_and_deployment(
         deployment: &DeploymentArtifact,
-        rpc_override: Option<String>,
-        private_key_env: String,
-        poll_seconds: u64,
-        log_chunk: u64,
-        confirmations: u64,
-        state_file: PathBuf,
-        max_concurrency: usize,
-        gas_limit: Option<u64>,
-        max_txs_per_cycle: usize,
-        tx_timeout_seconds: u64,
-        pending_ttl_seconds: u64,
-        backoff_base_seconds: u64,
-        backoff_max_seconds: u64,
-        plan_inactive_backoff_seconds: u64,
-        rpc_error_backoff_seconds: u64,
-        jitter_seconds: u64,
-        force_pending: bool,
-        simulate: bool,
-        once: bool,
-        dry_run: bool,
+        cli: CliOptions,
     ) -> Result<Self> {
+        let CliOptions {
+            rpc_override,
+            rpc_client,
+            json_rpc_batch,
+            private_key_env,
+            keystore,
+            keystore_password_env,
+            poll_seconds,
+            startup_splay_seconds,
+            poll_splay_seconds,
+            log_chunk,
+            scan_confirmations,
+            collect_confirmations,
+            due_confirmations,
+            local_due_margin,
+            prune_inactive,
+            multicall_address,
+            collect_order,
+            max_tracked_subscriptions,
+            eviction_policy,
+            state_file,
+            max_concurrency,
+            gas_limit,
+            gas_limit_overrides,
+            max_txs_per_cycle,
+            daily_gas_budget_wei,
+            tx_timeout_seconds,
+            cycle_deadline_seconds,
+            pending_ttl_seconds,
+            backoff_base_seconds,
+            backoff_max_seconds,
+            plan_inactive_backoff_seconds,
+            rpc_error_backoff_seconds,
+            kind_backoff,
+            jitter_seconds,
+            random_jitter,
+            breaker,
+            min_seconds_between_collects,
+            min_resend_seconds,
+            collector_address,
+            fee_recipient,
+            startup_retries,
+            startup_retry_delay_seconds,
+            force_pending,
+            simulate,
+            simulate_overrides,
+            once,
+            catch_up,
+            reconcile_only,
+            dry_run,
+            explain,
+            simulate_only,
+            simulate_report,
+            simulate_at_block,
+            pin_block,
+            control_file,
+            collect_window,
+            only_tag,
+            audit_log,
+            audit_log_max_bytes,
+            new_subscription_webhook_url,
+            post_collect_cmd,
+            relayer,
+            eip7702,
+            from_block,
+            reset_scan,
+            local_dev,
+            monitor_only,
+            nonce_manager,
+        } = cli;
+
         let rpc_url = rpc_override
             .or_else(|| std::env::var("OPENSUB_KEEPER_RPC_URL").ok())
             .or_else(|| {
@@ -111,7 +419,15 @@ impl KeeperConfig {
             return Err(eyre!("max txs per cycle must be > 0"));
         }
 
-        if rpc_url.contains("alchemy.com/v2/") || rpc_url.contains("infura.io/v3/") {
+        if !(breaker.threshold > 0.0 && breaker.threshold <= 1.0) {
+            return Err(eyre!(
+                "breaker threshold must be in (0, 1], got {}",
+                breaker.threshold
+            ));
+        }
+
+        if !local_dev && (rpc_url.contains("alchemy.com/v2/") || rpc_url.contains("infura.io/v3/"))
+        {
             tracing::warn!("RPC URL looks like it may contain an API key; consider using OPENSUB_KEEPER_RPC_URL env instead of committing it.");
         }
 
@@ -135,17 +451,37 @@ impl KeeperConfig {
         Ok(Self {
             chain_id: deployment.chain_id,
             rpc_url,
+            rpc_client,
+            json_rpc_batch,
             opensub,
-            start_block: deployment.start_block,
+            start_block: from_block.unwrap_or(deployment.start_block),
+            from_block,
+            reset_scan,
             poll_interval: Duration::from_secs(poll_seconds.max(1)),
+            startup_splay: Duration::from_secs(startup_splay_seconds),
+            poll_splay: Duration::from_secs(poll_splay_seconds),
+            provider_poll_interval: Duration::from_millis(if local_dev { 100 } else { 800 }),
             log_chunk_size: log_chunk,
-            confirmations,
+            scan_confirmations,
+            collect_confirmations,
+            due_confirmations,
+            local_due_margin,
+            prune_inactive,
+            multicall_address,
+            collect_order,
+            max_tracked_subscriptions,
+            eviction_policy,
             state_file,
             max_concurrency,
             private_key_env,
+            keystore,
+            keystore_password_env,
             gas_limit,
+            gas_limit_overrides,
             max_txs_per_cycle,
+            daily_gas_budget_wei: daily_gas_budget_wei.map(U256::from),
             tx_timeout: Duration::from_secs(tx_timeout_seconds.max(5)),
+            cycle_deadline: cycle_deadline_seconds.map(Duration::from_secs),
             pending_ttl: Duration::from_secs(pending_ttl_seconds.max(30)),
             backoff_max: Duration::from_secs(backoff_max_seconds.max(1)),
             backoff_base: Duration::from_secs(
@@ -157,11 +493,166 @@ impl KeeperConfig {
                     .min(backoff_max_seconds.max(1)),
             ),
             rpc_error_backoff: Duration::from_secs(rpc_error_backoff_seconds.max(1)),
+            insufficient_allowance_backoff: kind_backoff
+                .insufficient_allowance
+                .map(|s| Duration::from_secs(s.max(1))),
+            insufficient_balance_backoff: kind_backoff
+                .insufficient_balance
+                .map(|s| Duration::from_secs(s.max(1))),
+            simulation_revert_backoff: kind_backoff
+                .simulation_revert
+                .map(|s| Duration::from_secs(s.max(1))),
+            mined_revert_backoff: kind_backoff
+                .mined_revert
+                .map(|s| Duration::from_secs(s.max(1))),
+            unknown_backoff: kind_backoff.unknown.map(|s| Duration::from_secs(s.max(1))),
             jitter: Duration::from_secs(jitter_seconds),
+            random_jitter,
+            breaker,
+            min_seconds_between_collects,
+            min_resend_seconds,
+            collector_address,
+            fee_recipient,
+            startup_retries,
+            startup_retry_delay: Duration::from_secs(startup_retry_delay_seconds),
             force_pending,
             simulate,
-            once,
-            dry_run,
+            simulate_overrides,
+            once: once || reconcile_only,
+            catch_up,
+            reconcile_only,
+            dry_run: dry_run || monitor_only,
+            explain,
+            monitor_only,
+            nonce_manager,
+            simulate_only,
+            simulate_report,
+            simulate_at_block,
+            pin_block,
+            control_file,
+            collect_window,
+            only_tag,
+            audit_log,
+            audit_log_max_bytes,
+            new_subscription_webhook_url,
+            post_collect_cmd,
+            relayer,
+            eip7702,
         })
     }
+
+    /// Block collect reads are pinned to (`--simulate-at-block` or `--pin-block`); `None` reads
+    /// the chain head.
+    pub fn read_block(&self) -> Option<u64> {
+        self.simulate_at_block.or(self.pin_block)
+    }
+
+    /// Backoff base for a failure kind (before exponential growth and the `backoff_max` cap).
+    pub fn backoff_base_for(&self, kind: FailureKind) -> Duration {
+        match kind {
+            FailureKind::PlanInactive | FailureKind::MalformedPlan => {
+                Some(self.plan_inactive_backoff)
+            }
+            FailureKind::RpcError => Some(self.rpc_error_backoff),
+            FailureKind::InsufficientAllowance => self.insufficient_allowance_backoff,
+            FailureKind::InsufficientBalance => self.insufficient_balance_backoff,
+            FailureKind::SimulationRevert => self.simulation_revert_backoff,
+            FailureKind::MinedRevert => self.mined_revert_backoff,
+            FailureKind::Unknown => self.unknown_backoff,
+        }
+        .unwrap_or(self.backoff_base)
+    }
+
+    /// Whether collect outcomes (in-flight txs, backoff) should be written to the state file.
+    pub fn persists_outcomes(&self) -> bool {
+        !self.dry_run && !self.simulate_only && !self.monitor_only
+    }
+}
+
+#[cfg(test)]
+impl KeeperConfig {
+    /// A config with the CLI defaults, for unit tests that don't go through clap.
+    pub fn test_default() -> Self {
+        Self {
+            chain_id: 31337,
+            rpc_url: "http://127.0.0.1:8545".to_string(),
+            rpc_client: ClientOptions::default(),
+            json_rpc_batch: false,
+            opensub: Address::zero(),
+            start_block: 1,
+            from_block: None,
+            reset_scan: false,
+            poll_interval: Duration::from_secs(30),
+            startup_splay: Duration::ZERO,
+            poll_splay: Duration::ZERO,
+            provider_poll_interval: Duration::from_millis(800),
+            log_chunk_size: 2000,
+            scan_confirmations: 2,
+            collect_confirmations: 1,
+            due_confirmations: 0,
+            local_due_margin: None,
+            prune_inactive: false,
+            multicall_address: None,
+            collect_order: CollectOrder::Fifo,
+            max_tracked_subscriptions: None,
+            eviction_policy: EvictionPolicy::FurthestFromDue,
+            state_file: PathBuf::from("state.json"),
+            max_concurrency: 10,
+            private_key_env: "KEEPER_PRIVATE_KEY".to_string(),
+            keystore: None,
+            keystore_password_env: "KEEPER_KEYSTORE_PASSWORD".to_string(),
+            gas_limit: None,
+            gas_limit_overrides: GasLimitOverrides::default(),
+            max_txs_per_cycle: 25,
+            daily_gas_budget_wei: None,
+            tx_timeout: Duration::from_secs(120),
+            cycle_deadline: None,
+            pending_ttl: Duration::from_secs(900),
+            backoff_base: Duration::from_secs(300),
+            backoff_max: Duration::from_secs(21600),
+            plan_inactive_backoff: Duration::from_secs(1800),
+            rpc_error_backoff: Duration::from_secs(30),
+            insufficient_allowance_backoff: None,
+            insufficient_balance_backoff: None,
+            simulation_revert_backoff: None,
+            mined_revert_backoff: None,
+            unknown_backoff: None,
+            jitter: Duration::from_secs(30),
+            random_jitter: false,
+            breaker: BreakerConfig {
+                threshold: 0.9,
+                trip_cycles: 0,
+                cooldown: Duration::from_secs(600),
+            },
+            min_seconds_between_collects: 0,
+            min_resend_seconds: 0,
+            collector_address: None,
+            fee_recipient: None,
+            startup_retries: 5,
+            startup_retry_delay: Duration::from_secs(2),
+            force_pending: false,
+            simulate: true,
+            simulate_overrides: None,
+            once: false,
+            catch_up: false,
+            reconcile_only: false,
+            dry_run: false,
+            explain: false,
+            monitor_only: false,
+            nonce_manager: true,
+            simulate_only: false,
+            simulate_report: None,
+            simulate_at_block: None,
+            pin_block: None,
+            control_file: None,
+            collect_window: None,
+            only_tag: None,
+            audit_log: None,
+            audit_log_max_bytes: 10 * 1024 * 1024,
+            new_subscription_webhook_url: None,
+            post_collect_cmd: None,
+            relayer: None,
+            eip7702: None,
+        }
+    }
 }
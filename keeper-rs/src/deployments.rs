@@ -1,4 +1,5 @@
 use eyre::{eyre, Result};
+use opensub_aa::types::EntryPointVersion;
 use serde::Deserialize;
 use std::{fs, path::Path};
 
@@ -18,6 +19,10 @@ pub struct DeploymentArtifact {
     pub open_sub: String,
     pub start_block: u64,
 
+    /// EntryPoint version (`"0.6"` / `"0.7"`) for `--relayer-mode`; unset means v0.6.
+    #[serde(default)]
+    pub entry_point_version: Option<EntryPointVersion>,
+
     // Optional conveniences (not required by the keeper)
     #[allow(dead_code)]
     #[serde(default)]
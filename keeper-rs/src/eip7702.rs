@@ -0,0 +1,207 @@
+use ethers::abi::{AbiParser, Token};
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockNumber, Bytes, U256};
+use eyre::{eyre, Result};
+
+/// Code of an EIP-7702 delegated EOA: `0xef0100 || delegate`.
+const DELEGATION_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+/// Batch entry point of the delegate, as in eth-infinitism's `Simple7702Account`. It accepts
+/// calls from the account itself, so a tx from the EOA to its own address runs every call in one
+/// go (and reverts them all if one fails).
+const EXECUTE_BATCH_ABI: [&str; 2] = [
+    "struct Call { address target; uint256 value; bytes data; }",
+    "function executeBatch(Call[] calls)",
+];
+
+/// `--eip7702`: what the keeper EOA's code and the chain say about batching through it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelegationStatus {
+    /// Delegated to the expected implementation; batches can be sent.
+    Delegated,
+    /// Delegated, but to some other implementation whose batch ABI is unknown.
+    DelegatedElsewhere(Address),
+    /// The chain has EIP-7702 (Prague) but the EOA carries no delegation yet.
+    NotDelegated,
+    /// No delegation and no sign of Prague in the latest header.
+    Unsupported,
+}
+
+/// The delegate an EOA's code designates, if it is an EIP-7702 delegation.
+pub fn delegation_target(code: &[u8]) -> Option<Address> {
+    let rest = code.strip_prefix(&DELEGATION_PREFIX[..])?;
+    (rest.len() == 20).then(|| Address::from_slice(rest))
+}
+
+/// `executeBatch` calldata running `collect_calls` (each an OpenSub `collect` calldata) in order.
+pub fn batch_collect_calldata(opensub: Address, collect_calls: &[Bytes]) -> Result<Bytes> {
+    let abi = AbiParser::default()
+        .parse(&EXECUTE_BATCH_ABI)
+        .map_err(|e| eyre!("invalid executeBatch ABI: {e}"))?;
+    let function = abi
+        .function("executeBatch")
+        .map_err(|e| eyre!("invalid executeBatch ABI: {e}"))?;
+    let calls = collect_calls
+        .iter()
+        .map(|data| {
+            Token::Tuple(vec![
+                Token::Address(opensub),
+                Token::Uint(U256::zero()),
+                Token::Bytes(data.to_vec()),
+            ])
+        })
+        .collect();
+    function
+        .encode_input(&[Token::Array(calls)])
+        .map(Bytes::from)
+        .map_err(|e| eyre!("failed to encode executeBatch: {e}"))
+}
+
+/// Check whether `eoa` can batch through `delegate`.
+///
+/// Setting the delegation needs a type-0x04 (set-code) transaction, which ethers cannot build;
+/// [`authorization_unsupported`] is what a run without one reports. Prague is detected from the
+/// latest header's `requestsHash` (EIP-7685, activated in the same fork).
+pub async fn check_delegation<M: Middleware>(
+    client: &M,
+    eoa: Address,
+    delegate: Address,
+) -> Result<DelegationStatus> {
+    let code = client
+        .get_code(eoa, None)
+        .await
+        .map_err(|e| eyre!("eth_getCode for the keeper EOA failed: {e}"))?;
+    match delegation_target(&code) {
+        Some(target) if target == delegate => return Ok(DelegationStatus::Delegated),
+        Some(target) => return Ok(DelegationStatus::DelegatedElsewhere(target)),
+        None => {}
+    }
+
+    let block = client
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(|e| eyre!("failed to fetch the latest block: {e}"))?;
+    Ok(match block {
+        Some(b) if b.other.contains_key("requestsHash") => DelegationStatus::NotDelegated,
+        _ => DelegationStatus::Unsupported,
+    })
+}
+
+/// Why the keeper cannot delegate its EOA itself.
+pub fn authorization_unsupported(delegate: Address) -> String {
+    format!(
+        "this build cannot send type-0x04 authorizations; delegate the keeper EOA to {delegate:?} with an external tool (e.g. `cast send --auth`) and restart"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batch_collect_calldata, check_delegation, delegation_target, DelegationStatus};
+    use crate::opensub::{collect_call, OpenSub};
+    use ethers::abi::{AbiParser, Token};
+    use ethers::providers::Provider;
+    use ethers::types::{Address, Bytes, U256};
+    use std::sync::Arc;
+
+    #[test]
+    fn batches_collects_into_one_execute_batch_call() {
+        let (provider, _mock) = Provider::mocked();
+        let opensub_address = Address::repeat_byte(0x05);
+        let opensub = OpenSub::new(opensub_address, Arc::new(provider));
+        let calls: Vec<Bytes> = [3u64, 7, 11]
+            .iter()
+            .map(|id| {
+                collect_call(&opensub, U256::from(*id), None)
+                    .calldata()
+                    .unwrap()
+            })
+            .collect();
+
+        let data = batch_collect_calldata(opensub_address, &calls).unwrap();
+        let abi = AbiParser::default()
+            .parse(&[
+                "struct Call { address target; uint256 value; bytes data; }",
+                "function executeBatch(Call[] calls)",
+            ])
+            .unwrap();
+        let function = abi.function("executeBatch").unwrap();
+        // executeBatch((address,uint256,bytes)[])
+        assert_eq!(
+            ethers::utils::hex::encode(function.short_signature()),
+            "34fcd5be"
+        );
+        assert_eq!(data[..4], function.short_signature());
+
+        let decoded = function.decode_input(&data[4..]).unwrap();
+        let Token::Array(entries) = &decoded[0] else {
+            panic!("expected an array of calls: {decoded:?}");
+        };
+        assert_eq!(entries.len(), 3);
+        for (entry, call) in entries.iter().zip(&calls) {
+            assert_eq!(
+                entry,
+                &Token::Tuple(vec![
+                    Token::Address(opensub_address),
+                    Token::Uint(U256::zero()),
+                    Token::Bytes(call.to_vec()),
+                ])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn delegation_is_read_from_the_eoa_code() {
+        let delegate = Address::repeat_byte(0x77);
+        let mut designator = vec![0xef, 0x01, 0x00];
+        designator.extend_from_slice(delegate.as_bytes());
+        assert_eq!(delegation_target(&designator), Some(delegate));
+        assert_eq!(delegation_target(&[]), None);
+        assert_eq!(delegation_target(&designator[..10]), None);
+
+        let eoa = Address::repeat_byte(0x0e);
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Bytes, _>(Bytes::from(designator)).unwrap();
+        assert_eq!(
+            check_delegation(&provider, eoa, delegate).await.unwrap(),
+            DelegationStatus::Delegated
+        );
+        let mut elsewhere = vec![0xef, 0x01, 0x00];
+        elsewhere.extend_from_slice(&[0x66; 20]);
+        mock.push::<Bytes, _>(Bytes::from(elsewhere)).unwrap();
+        assert_eq!(
+            check_delegation(&provider, eoa, delegate).await.unwrap(),
+            DelegationStatus::DelegatedElsewhere(Address::repeat_byte(0x66))
+        );
+
+        // No delegation: the latest header tells a Prague chain from an older one. Responses
+        // pop from the back, so the block is pushed before the code.
+        let header = |requests_hash: bool| {
+            let mut block = serde_json::json!({
+                "hash": format!("{:#x}", ethers::types::H256::repeat_byte(0x01)),
+                "parentHash": format!("{:#x}", ethers::types::H256::zero()),
+                "number": "0x10",
+                "timestamp": "0x1",
+                "gasLimit": "0x1c9c380",
+                "gasUsed": "0x0",
+                "transactions": [],
+            });
+            if requests_hash {
+                block["requestsHash"] =
+                    serde_json::json!(format!("{:#x}", ethers::types::H256::repeat_byte(0xe3)));
+            }
+            block
+        };
+        mock.push(header(true)).unwrap();
+        mock.push::<Bytes, _>(Bytes::default()).unwrap();
+        assert_eq!(
+            check_delegation(&provider, eoa, delegate).await.unwrap(),
+            DelegationStatus::NotDelegated
+        );
+        mock.push(header(false)).unwrap();
+        mock.push::<Bytes, _>(Bytes::default()).unwrap();
+        assert_eq!(
+            check_delegation(&provider, eoa, delegate).await.unwrap(),
+            DelegationStatus::Unsupported
+        );
+    }
+}
@@ -1,4 +1,7 @@
 use ethers::contract::abigen;
+use ethers::providers::call_raw::spoof;
+use ethers::types::{Address, H256, U256};
+use ethers::utils::keccak256;
 
 // Minimal ERC20 ABI for keeper-side prechecks.
 //
@@ -11,3 +14,87 @@ abigen!(
         function balanceOf(address owner) view returns (uint256)
     ]"#
 );
+
+/// Storage slots of a token's `balanceOf` / `allowance` mappings, for `eth_call` state overrides.
+///
+/// Solidity puts `mapping[key]` at `keccak256(pad32(key) ++ pad32(slot))`, so these are the
+/// declaration slots (e.g. 3 / 4 for this repo's `MockERC20`, 0 / 1 for OpenZeppelin's `ERC20`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Erc20Slots {
+    pub balance: U256,
+    pub allowance: U256,
+}
+
+impl Erc20Slots {
+    /// Override set giving `owner` a balance of `amount` and an allowance of `amount` for `spender`.
+    pub fn funds_override(
+        &self,
+        token: Address,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+    ) -> spoof::State {
+        let balance_key = mapping_slot(H256::from(owner), self.balance);
+        let owner_allowances = mapping_slot(H256::from(owner), self.allowance);
+        let allowance_key = mapping_slot(
+            H256::from(spender),
+            U256::from_big_endian(owner_allowances.as_bytes()),
+        );
+
+        let mut value = [0u8; 32];
+        amount.to_big_endian(&mut value);
+
+        let mut state = spoof::state();
+        state
+            .account(token)
+            .store(balance_key, H256(value))
+            .store(allowance_key, H256(value));
+        state
+    }
+}
+
+fn mapping_slot(key: H256, slot: U256) -> H256 {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(key.as_bytes());
+    slot.to_big_endian(&mut buf[32..]);
+    H256(keccak256(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Erc20Slots;
+    use ethers::abi::{encode, Token};
+    use ethers::types::{Address, H256, U256};
+    use ethers::utils::keccak256;
+
+    #[test]
+    fn funds_override_targets_balance_and_allowance_slots() {
+        let token = Address::repeat_byte(0x70);
+        let owner = Address::repeat_byte(0xaa);
+        let spender = Address::repeat_byte(0x05);
+        let slots = Erc20Slots {
+            balance: U256::from(3),
+            allowance: U256::from(4),
+        };
+
+        let state = slots.funds_override(token, owner, spender, U256::from(1_000_000));
+
+        // balanceOf[owner] and allowance[owner][spender], computed the way solc lays them out.
+        let balance_key = H256(keccak256(encode(&[
+            Token::Address(owner),
+            Token::Uint(U256::from(3)),
+        ])));
+        let inner = keccak256(encode(&[Token::Address(owner), Token::Uint(U256::from(4))]));
+        let allowance_key = H256(keccak256(encode(&[
+            Token::Address(spender),
+            Token::FixedBytes(inner.to_vec()),
+        ])));
+        let amount = format!("{:#x}", H256::from_low_u64_be(1_000_000));
+
+        let v = serde_json::to_value(&state).unwrap();
+        let diff = &v[format!("{token:#x}")]["stateDiff"];
+        assert_eq!(diff.as_object().unwrap().len(), 2);
+        assert_eq!(diff[format!("{balance_key:#x}")], amount);
+        assert_eq!(diff[format!("{allowance_key:#x}")], amount);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::audit::AuditAction;
+use crate::collector::{CollectOutcome, FailureRecord};
+use crate::state::{FailureKind, KeeperState};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// What a cycle did (or, under `--dry-run`, would do) with one subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decision {
+    /// Sent (or would send) collect().
+    Collect,
+    NotDue,
+    SkipInFlight,
+    SkipBackoff,
+    /// Lacks the `--only-tag` tag.
+    SkipUntagged,
+    /// `--min-seconds-between-collects` / `--min-resend-seconds`.
+    SkipThrottled,
+    PrecheckFail,
+    /// Sent, mined and reverted.
+    Reverted,
+    Pruned,
+}
+
+/// `--explain`: one line per subscription. For failures, the backoff the cycle records (or would
+/// record, under `--dry-run`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Explanation {
+    pub subscription_id: u64,
+    pub decision: Decision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_kind: Option<FailureKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consecutive_failures: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_retry_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Explanation {
+    fn new(subscription_id: u64, decision: Decision) -> Self {
+        Self {
+            subscription_id,
+            decision,
+            failure_kind: None,
+            consecutive_failures: None,
+            next_retry_at: None,
+            reason: None,
+        }
+    }
+
+    /// An id left out of the cycle because it lacks the `--only-tag` tag.
+    pub fn untagged(subscription_id: u64) -> Self {
+        Self::new(subscription_id, Decision::SkipUntagged)
+    }
+
+    /// An id left out of the cycle because it has a tracked tx or is still backing off.
+    pub fn skipped(state: &KeeperState, subscription_id: u64) -> Self {
+        if state.is_tracked(subscription_id) {
+            return Self::new(subscription_id, Decision::SkipInFlight);
+        }
+        let retry = state.retries.get(&subscription_id);
+        Self {
+            failure_kind: retry.map(|r| r.last_failure_kind),
+            consecutive_failures: retry.map(|r| r.consecutive_failures),
+            next_retry_at: retry.map(|r| r.next_retry_at),
+            ..Self::new(subscription_id, Decision::SkipBackoff)
+        }
+    }
+}
+
+/// Explain every id handed to `collect_due`. `next_retry` gives `(consecutive, next_retry_at)` for
+/// a failure, computed exactly as when it is persisted.
+pub fn explain_checked(
+    checked: &[u64],
+    outcome: &CollectOutcome,
+    mut next_retry: impl FnMut(&FailureRecord) -> (u32, u64),
+) -> Vec<Explanation> {
+    let collected: BTreeSet<u64> = outcome
+        .successes
+        .iter()
+        .copied()
+        .chain(outcome.confirming.iter().map(|p| p.subscription_id))
+        .chain(outcome.pending.iter().map(|p| p.subscription_id))
+        .chain(outcome.would_collect.iter().copied())
+        .collect();
+    let throttled: BTreeSet<u64> = outcome
+        .audit
+        .iter()
+        .filter(|e| e.action == AuditAction::Throttle)
+        .map(|e| e.subscription_id)
+        .collect();
+    let pruned: BTreeSet<u64> = outcome.pruned.iter().copied().collect();
+
+    checked
+        .iter()
+        .map(|&id| {
+            if let Some(failure) = outcome.failures.iter().find(|f| f.subscription_id == id) {
+                let (consecutive, next_retry_at) = next_retry(failure);
+                let decision = match failure.kind {
+                    FailureKind::MinedRevert => Decision::Reverted,
+                    _ => Decision::PrecheckFail,
+                };
+                return Explanation {
+                    failure_kind: Some(failure.kind),
+                    consecutive_failures: Some(consecutive),
+                    next_retry_at: Some(next_retry_at),
+                    reason: failure.reason.clone(),
+                    ..Explanation::new(id, decision)
+                };
+            }
+            let decision = if collected.contains(&id) {
+                Decision::Collect
+            } else if throttled.contains(&id) {
+                Decision::SkipThrottled
+            } else if pruned.contains(&id) {
+                Decision::Pruned
+            } else {
+                Decision::NotDue
+            };
+            Explanation::new(id, decision)
+        })
+        .collect()
+}
@@ -0,0 +1,107 @@
+use ethers::types::U256;
+use eyre::{eyre, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::{fs, path::Path};
+
+/// Per-plan / per-subscription collect() gas limits from `--gas-limits-file`.
+///
+/// ```json
+/// { "plans": { "2": 350000 }, "subscriptions": { "17": 500000 } }
+/// ```
+///
+/// A subscription entry wins over its plan's entry, which wins over `--gas-limit`. With none of
+/// them set the gas is estimated as usual.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GasLimitOverrides {
+    #[serde(default)]
+    pub plans: BTreeMap<u64, u64>,
+    #[serde(default)]
+    pub subscriptions: BTreeMap<u64, u64>,
+}
+
+impl GasLimitOverrides {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = fs::read_to_string(path)
+            .map_err(|e| eyre!("failed to read gas limits file {}: {e}", path.display()))?;
+        let limits: GasLimitOverrides = serde_json::from_str(&raw)
+            .map_err(|e| eyre!("failed to parse gas limits file {}: {e}", path.display()))?;
+
+        if let Some((id, _)) = limits.subscriptions.iter().find(|(_, gas)| **gas == 0) {
+            return Err(eyre!(
+                "gas limits file: subscription {id} has a zero gas limit"
+            ));
+        }
+        if let Some((id, _)) = limits.plans.iter().find(|(_, gas)| **gas == 0) {
+            return Err(eyre!("gas limits file: plan {id} has a zero gas limit"));
+        }
+
+        Ok(limits)
+    }
+
+    /// Gas limit for collecting `subscription_id` (on `plan_id`), falling back to `default`.
+    pub fn resolve(
+        &self,
+        subscription_id: u64,
+        plan_id: U256,
+        default: Option<u64>,
+    ) -> Option<u64> {
+        let plan = (plan_id <= U256::from(u64::MAX))
+            .then(|| self.plans.get(&plan_id.as_u64()))
+            .flatten();
+        self.subscriptions
+            .get(&subscription_id)
+            .or(plan)
+            .copied()
+            .or(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GasLimitOverrides;
+    use ethers::types::U256;
+
+    #[test]
+    fn plan_override_applies_to_matching_subscriptions_only() {
+        let limits: GasLimitOverrides = serde_json::from_str(
+            r#"{ "plans": { "2": 350000 }, "subscriptions": { "9": 500000 } }"#,
+        )
+        .unwrap();
+
+        // Subscriptions on plan 2 get the plan's limit, others keep the global one.
+        assert_eq!(
+            limits.resolve(7, U256::from(2), Some(200_000)),
+            Some(350_000)
+        );
+        assert_eq!(
+            limits.resolve(8, U256::from(1), Some(200_000)),
+            Some(200_000)
+        );
+        assert_eq!(limits.resolve(8, U256::from(1), None), None);
+        // A subscription entry beats its plan's.
+        assert_eq!(
+            limits.resolve(9, U256::from(2), Some(200_000)),
+            Some(500_000)
+        );
+        // Plan ids past u64 can't be listed; fall through to the global limit.
+        assert_eq!(limits.resolve(7, U256::MAX, None), None);
+    }
+
+    #[test]
+    fn zero_gas_limit_is_rejected() {
+        let path = std::env::temp_dir().join(format!(
+            "opensub-keeper-gas-limits-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, r#"{ "plans": { "2": 0 } }"#).unwrap();
+        let err = GasLimitOverrides::load(&path).unwrap_err();
+        assert!(err.to_string().contains("plan 2"), "{err}");
+
+        std::fs::write(&path, r#"{ "plan": { "2": 100000 } }"#).unwrap();
+        assert!(GasLimitOverrides::load(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,402 @@
+use crate::opensub::OpenSub;
+use crate::state::{FailureKind, InFlightTx, KeeperState, GAS_SPEND_WINDOW_SECS};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// `--inspect`: a derived, read-only view of the keeper state file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InspectReport {
+    pub now: u64,
+    pub last_scanned_block: u64,
+    pub total_ids: usize,
+    pub in_flight: Vec<TrackedTxView>,
+    pub confirming: Vec<TrackedTxView>,
+    pub retries: Vec<RetryView>,
+    pub gas_spent_24h_wei: U256,
+    /// `--tag-id` tags per subscription.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<u64, Vec<String>>,
+    /// Ids whose `isDue` is true right now (`--inspect-due` only). Tracked ids are not queried.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<Vec<u64>>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedTxView {
+    pub subscription_id: u64,
+    pub tx_hash: String,
+    pub age_secs: u64,
+    pub nonce: Option<u64>,
+    pub mined_block: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryView {
+    pub subscription_id: u64,
+    pub consecutive_failures: u32,
+    pub kind: FailureKind,
+    pub next_retry_at: u64,
+    /// 0 once the backoff has elapsed (the id is eligible next cycle).
+    pub retry_in_secs: u64,
+    pub reason: Option<String>,
+}
+
+/// Seconds left before a backed-off subscription is retried, as seen at `now`.
+pub fn retry_countdown(next_retry_at: u64, now: u64) -> u64 {
+    next_retry_at.saturating_sub(now)
+}
+
+pub fn build_report(state: &KeeperState, now: u64, due: Option<Vec<u64>>) -> InspectReport {
+    let tracked = |txs: &BTreeMap<u64, InFlightTx>| {
+        txs.iter()
+            .map(|(id, tx)| TrackedTxView {
+                subscription_id: *id,
+                tx_hash: tx.tx_hash.clone(),
+                age_secs: now.saturating_sub(tx.sent_at),
+                nonce: tx.nonce,
+                mined_block: tx.mined_block,
+            })
+            .collect()
+    };
+
+    let mut retries: Vec<RetryView> = state
+        .retries
+        .iter()
+        .map(|(id, r)| RetryView {
+            subscription_id: *id,
+            consecutive_failures: r.consecutive_failures,
+            kind: r.last_failure_kind,
+            next_retry_at: r.next_retry_at,
+            retry_in_secs: retry_countdown(r.next_retry_at, now),
+            reason: r.last_failure_reason.clone(),
+        })
+        .collect();
+    // Soonest retry first.
+    retries.sort_by_key(|r| (r.retry_in_secs, r.subscription_id));
+
+    // Same window as `rolling_gas_spend`, without pruning the (borrowed) state.
+    let window_start = now.saturating_sub(GAS_SPEND_WINDOW_SECS);
+    let gas_spent_24h_wei = state
+        .gas_spend
+        .iter()
+        .filter(|g| g.at > window_start)
+        .fold(U256::zero(), |acc, g| acc.saturating_add(g.wei));
+
+    InspectReport {
+        now,
+        last_scanned_block: state.last_scanned_block,
+        total_ids: state.subscription_ids.len(),
+        in_flight: tracked(&state.in_flight),
+        confirming: tracked(&state.confirming),
+        retries,
+        gas_spent_24h_wei,
+        tags: state.tags.clone(),
+        due,
+    }
+}
+
+/// `isDue` for every known id without a tracked tx. Ids whose call fails are left out (and logged).
+pub async fn due_now<M: Middleware>(
+    opensub: &OpenSub<M>,
+    state: &KeeperState,
+    max_concurrency: usize,
+) -> Vec<u64> {
+    let ids = state
+        .subscription_ids
+        .iter()
+        .copied()
+        .filter(|id| !state.is_tracked(*id));
+
+    let mut due: Vec<u64> = stream::iter(ids)
+        .map(|id| async move {
+            match opensub.is_due(U256::from(id)).call().await {
+                Ok(due) => due.then_some(id),
+                Err(err) => {
+                    tracing::warn!(subscription_id = id, error = %err, "isDue call failed");
+                    None
+                }
+            }
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .filter_map(|id| async move { id })
+        .collect()
+        .await;
+    due.sort_unstable();
+    due
+}
+
+/// `--list-due`: a subscription whose `isDue` is true, with its `subscriptions()` row.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DueSubscription {
+    pub subscription_id: u64,
+    pub plan_id: U256,
+    pub subscriber: Address,
+    pub paid_through: u64,
+}
+
+/// `isDue` for each of `ids` (at most `max_concurrency` at a time), then `subscriptions()` for the
+/// due ones. Ids whose calls fail are left out (and logged). Sorted by id.
+pub async fn list_due<M: Middleware>(
+    opensub: &OpenSub<M>,
+    ids: &[u64],
+    max_concurrency: usize,
+) -> Vec<DueSubscription> {
+    let mut due: Vec<DueSubscription> = stream::iter(ids.iter().copied())
+        .map(|id| async move {
+            match opensub.is_due(U256::from(id)).call().await {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => {
+                    tracing::warn!(subscription_id = id, error = %err, "isDue call failed");
+                    return None;
+                }
+            }
+            match opensub.subscriptions(U256::from(id)).call().await {
+                Ok((plan_id, subscriber, _, _, paid_through, _)) => Some(DueSubscription {
+                    subscription_id: id,
+                    plan_id,
+                    subscriber,
+                    paid_through: paid_through.low_u64(),
+                }),
+                Err(err) => {
+                    tracing::warn!(subscription_id = id, error = %err, "subscriptions() call failed");
+                    None
+                }
+            }
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .filter_map(|due| async move { due })
+        .collect()
+        .await;
+    due.sort_by_key(|d| d.subscription_id);
+    due
+}
+
+/// `--offset` / `--limit`: the page of `items` (already in id order) starting at `offset`, and
+/// the offset of the next page if anything is left after it.
+pub fn page<T>(items: &[T], offset: usize, limit: Option<usize>) -> (&[T], Option<usize>) {
+    let start = offset.min(items.len());
+    let end = limit.map_or(items.len(), |limit| {
+        start.saturating_add(limit).min(items.len())
+    });
+    (&items[start..end], (end < items.len()).then_some(end))
+}
+
+pub fn print_due(due: &[DueSubscription], total: usize, now: u64) {
+    println!("due now ({total}):");
+    for d in due {
+        println!(
+            "  #{} plan {} subscriber {:?} paidThrough {} (overdue {}s)",
+            d.subscription_id,
+            d.plan_id,
+            d.subscriber,
+            d.paid_through,
+            now.saturating_sub(d.paid_through)
+        );
+    }
+}
+
+pub fn print_report(report: &InspectReport) {
+    println!("last scanned block: {}", report.last_scanned_block);
+    println!("subscriptions: {}", report.total_ids);
+    println!("gas spent (24h): {} wei", report.gas_spent_24h_wei);
+
+    for (label, txs) in [
+        ("in-flight", &report.in_flight),
+        ("confirming", &report.confirming),
+    ] {
+        println!("{label} ({}):", txs.len());
+        for tx in txs {
+            let mut line = format!(
+                "  #{} {} age {}s",
+                tx.subscription_id, tx.tx_hash, tx.age_secs
+            );
+            if let Some(nonce) = tx.nonce {
+                line.push_str(&format!(" nonce {nonce}"));
+            }
+            if let Some(block) = tx.mined_block {
+                line.push_str(&format!(" mined in {block}"));
+            }
+            println!("{line}");
+        }
+    }
+
+    println!("retries ({}):", report.retries.len());
+    for r in &report.retries {
+        let when = if r.retry_in_secs == 0 {
+            "retry due".to_string()
+        } else {
+            format!("retry in {}s", r.retry_in_secs)
+        };
+        println!(
+            "  #{} {:?} x{} {when}{}",
+            r.subscription_id,
+            r.kind,
+            r.consecutive_failures,
+            r.reason
+                .as_deref()
+                .map(|reason| format!(" ({reason})"))
+                .unwrap_or_default()
+        );
+    }
+
+    if !report.tags.is_empty() {
+        println!("tags ({}):", report.tags.len());
+        for (id, tags) in &report.tags {
+            println!("  #{id} {}", tags.join(", "));
+        }
+    }
+
+    if let Some(due) = &report.due {
+        let ids: Vec<String> = due.iter().map(u64::to_string).collect();
+        println!("due now ({}): {}", due.len(), ids.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_report, list_due, page, retry_countdown, DueSubscription};
+    use crate::opensub::OpenSub;
+    use crate::state::{FailureKind, InFlightTx, KeeperState};
+    use ethers::abi::{encode, Token};
+    use ethers::providers::{JsonRpcError, MockResponse, Provider};
+    use ethers::types::{Address, Bytes, U256};
+    use std::sync::Arc;
+
+    #[test]
+    fn retry_countdown_counts_down_to_zero() {
+        assert_eq!(retry_countdown(1_300, 1_000), 300);
+        assert_eq!(retry_countdown(1_300, 1_299), 1);
+        // Elapsed backoffs read as "retry now", never as a negative/wrapped countdown.
+        assert_eq!(retry_countdown(1_300, 1_300), 0);
+        assert_eq!(retry_countdown(1_300, 5_000), 0);
+        assert_eq!(retry_countdown(0, 1_000), 0);
+    }
+
+    #[test]
+    fn report_derives_ages_and_countdowns_without_touching_state() {
+        let now = 1_700_000_000;
+        let mut state = KeeperState {
+            last_scanned_block: 42,
+            subscription_ids: vec![1, 2, 3],
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+        state.in_flight.insert(
+            1,
+            InFlightTx {
+                tx_hash: "0xabc".to_string(),
+                sent_at: now - 90,
+                nonce: Some(4),
+                from: None,
+                sent_block: None,
+                mined_block: None,
+            },
+        );
+        state.note_failure(2, FailureKind::InsufficientBalance, now + 600, None);
+        state.note_failure(
+            3,
+            FailureKind::PlanInactive,
+            now - 5,
+            Some("plan inactive".into()),
+        );
+        state.record_gas_spend(now - 2 * 24 * 60 * 60, U256::from(7));
+        state.record_gas_spend(now - 60, U256::from(5));
+        let before = serde_json::to_string(&state).unwrap();
+
+        let report = build_report(&state, now, Some(vec![3]));
+
+        assert_eq!(report.total_ids, 3);
+        assert_eq!(report.in_flight[0].age_secs, 90);
+        let countdowns: Vec<(u64, u64)> = report
+            .retries
+            .iter()
+            .map(|r| (r.subscription_id, r.retry_in_secs))
+            .collect();
+        assert_eq!(countdowns, vec![(3, 0), (2, 600)]);
+        assert_eq!(report.gas_spent_24h_wei, U256::from(5));
+        assert_eq!(report.due, Some(vec![3]));
+        assert_eq!(serde_json::to_string(&state).unwrap(), before);
+    }
+
+    #[tokio::test]
+    async fn list_due_reports_only_due_subscriptions() {
+        let (provider, mock) = Provider::mocked();
+        let opensub = OpenSub::new(Address::repeat_byte(0x01), Arc::new(provider));
+        let subscriber = Address::repeat_byte(0x55);
+        let ret = |tokens: &[Token]| Bytes::from(encode(tokens));
+
+        // max_concurrency 1 keeps the calls in order: isDue(1) false, isDue(2) true,
+        // subscriptions(2), isDue(3) reverts, isDue(4) true, subscriptions(4). Pushed in reverse.
+        let row = |plan_id: u64, paid_through: u64| {
+            ret(&[
+                Token::Uint(U256::from(plan_id)),
+                Token::Address(subscriber),
+                Token::Uint(U256::from(1u64)),
+                Token::Uint(U256::from(1_000u64)),
+                Token::Uint(U256::from(paid_through)),
+                Token::Uint(U256::from(1_000u64)),
+            ])
+        };
+        mock.push::<Bytes, _>(row(9, 5_000)).unwrap();
+        mock.push::<Bytes, _>(ret(&[Token::Bool(true)])).unwrap();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: None,
+        }));
+        mock.push::<Bytes, _>(row(7, 4_000)).unwrap();
+        mock.push::<Bytes, _>(ret(&[Token::Bool(true)])).unwrap();
+        mock.push::<Bytes, _>(ret(&[Token::Bool(false)])).unwrap();
+
+        let due = list_due(&opensub, &[1, 2, 3, 4], 1).await;
+        assert_eq!(
+            due,
+            vec![
+                DueSubscription {
+                    subscription_id: 2,
+                    plan_id: U256::from(7),
+                    subscriber,
+                    paid_through: 4_000,
+                },
+                DueSubscription {
+                    subscription_id: 4,
+                    plan_id: U256::from(9),
+                    subscriber,
+                    paid_through: 5_000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_through_sorted_results() {
+        let ids = [1, 2, 3, 4, 5];
+        assert_eq!(page(&ids, 0, None), (&ids[..], None));
+        assert_eq!(page(&ids, 0, Some(2)), (&ids[..2], Some(2)));
+        assert_eq!(page(&ids, 2, Some(2)), (&ids[2..4], Some(4)));
+        // The last page, exactly full or short: no next page.
+        assert_eq!(page(&ids, 3, Some(2)), (&ids[3..], None));
+        assert_eq!(page(&ids, 4, Some(2)), (&ids[4..], None));
+        // A limit larger than the total returns everything.
+        assert_eq!(page(&ids, 0, Some(100)), (&ids[..], None));
+        assert_eq!(page(&ids, 1, Some(usize::MAX)), (&ids[1..], None));
+        // Offsets at or past the end give an empty page.
+        assert_eq!(page(&ids, 5, Some(2)), (&ids[5..], None));
+        assert_eq!(page(&ids, 9, None), (&ids[5..], None));
+        assert_eq!(page::<u64>(&[], 0, Some(2)), (&[][..], None));
+    }
+}
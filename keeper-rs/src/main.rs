@@ -1,34 +1,109 @@
+mod audit;
+mod breaker;
+mod collect_window;
 mod collector;
 mod config;
 mod deployments;
+mod eip7702;
 mod erc20;
+mod explain;
+mod gas_limits;
+mod inspect;
+mod multicall;
 mod opensub;
+mod post_collect;
+mod relayer;
 mod scanner;
+mod send_guard;
 mod state;
+mod tags;
+mod transport;
+mod webhook;
 
+use audit::{AuditAction, AuditEvent, AuditLog};
+use breaker::{BreakerConfig, BreakerState, BreakerTransition, CircuitBreaker};
 use clap::Parser;
-use collector::collect_due;
+use collect_window::CollectWindow;
+use collector::{
+    collect_due, CollectOptions, CollectOrder, CollectStats, Collected, FailureRecord,
+    SimulationResult,
+};
 use config::KeeperConfig;
 use deployments::DeploymentArtifact;
+use erc20::Erc20Slots;
 use ethers::middleware::NonceManagerMiddleware;
-use ethers::prelude::{Http, LocalWallet, Provider, SignerMiddleware};
+use ethers::prelude::{LocalWallet, Provider, SignerMiddleware};
 use ethers::providers::Middleware;
 use ethers::signers::Signer;
+use ethers::types::{H256, U256};
+use explain::{explain_checked, Explanation};
 use eyre::{eyre, Result};
+use gas_limits::GasLimitOverrides;
 use opensub::OpenSub;
-use state::{FailureKind, KeeperState, ReconcileOutcome};
+use opensub_aa::http::ClientOptions;
+use opensub_aa::now_unix;
+use opensub_aa::types::EntryPointVersion;
+use post_collect::PostCollectHook;
+use relayer::{Relayer, RelayerConfig};
+use scanner::ScanStats;
+use send_guard::{send_guard_path, SendGuard};
+use state::{EvictionPolicy, FailureKind, InFlightTx, KeeperState, ReconcileOutcome};
+use std::collections::BTreeMap;
 use std::fs::OpenOptions;
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
+use transport::RpcTransport;
+use webhook::NewSubscriptionWebhook;
 
 use fs2::FileExt;
+use rand::Rng;
+use serde::Serialize;
 
-fn now_unix() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_else(|_| Duration::from_secs(0))
-        .as_secs()
+/// Backoff for one more failure of `subscription_id` at `now`:
+/// `(consecutive, backoff_s, next_retry_at)`.
+fn next_retry(
+    cfg: &KeeperConfig,
+    state: &KeeperState,
+    kind: FailureKind,
+    subscription_id: u64,
+    now: u64,
+) -> (u32, u64, u64) {
+    let prev = state
+        .retries
+        .get(&subscription_id)
+        .map(|r| r.consecutive_failures)
+        .unwrap_or(0);
+    let consecutive = prev.saturating_add(1);
+    let backoff_s = compute_backoff_seconds(cfg, kind, consecutive, subscription_id);
+    (consecutive, backoff_s, now.saturating_add(backoff_s))
+}
+
+/// Record this cycle's failures in the backoff state.
+fn record_failures(
+    cfg: &KeeperConfig,
+    state: &mut KeeperState,
+    failures: Vec<FailureRecord>,
+    now: u64,
+) {
+    for f in failures {
+        let (consecutive, backoff_s, next_retry_at) =
+            next_retry(cfg, state, f.kind, f.subscription_id, now);
+
+        tracing::warn!(
+            subscription_id = f.subscription_id,
+            kind = ?f.kind,
+            consecutive,
+            backoff_s,
+            next_retry_at,
+            reason = f.reason.as_deref().unwrap_or(""),
+            "collect failed; backing off"
+        );
+
+        state.note_failure(f.subscription_id, f.kind, next_retry_at, f.reason);
+    }
 }
 
 fn compute_backoff_seconds(
@@ -40,15 +115,7 @@ fn compute_backoff_seconds(
     // Exponential backoff with deterministic jitter.
     //
     // Important: this must remain fast even if `consecutive_failures` grows large over time.
-    let base = match kind {
-        FailureKind::PlanInactive => cfg.plan_inactive_backoff.as_secs().max(1),
-        FailureKind::RpcError => cfg.rpc_error_backoff.as_secs().max(1),
-        FailureKind::InsufficientAllowance
-        | FailureKind::InsufficientBalance
-        | FailureKind::SimulationRevert
-        | FailureKind::MinedRevert
-        | FailureKind::Unknown => cfg.backoff_base.as_secs().max(1),
-    };
+    let base = cfg.backoff_base_for(kind).as_secs().max(1);
 
     let max = cfg.backoff_max.as_secs().max(1);
 
@@ -59,25 +126,266 @@ fn compute_backoff_seconds(
     let exp = consecutive_failures.saturating_sub(1).min(63);
     let mut backoff = base.saturating_mul(1u64 << exp).min(max);
 
-    // Deterministic jitter in [0, jitter_max) to reduce thundering herd,
-    // clamped so backoff_max remains a hard cap.
+    // Jitter in [0, jitter_max) to reduce thundering herd, clamped so backoff_max remains a hard
+    // cap. Deterministic by default (reproducible); `--random-jitter` draws it per call so that
+    // redundant keepers watching the same subscriptions don't retry in lockstep.
     let jitter_max = cfg.jitter.as_secs();
     if jitter_max > 0 {
-        backoff = backoff
-            .saturating_add(subscription_id % jitter_max)
-            .min(max);
+        let jitter = if cfg.random_jitter {
+            rand::thread_rng().gen_range(0..jitter_max)
+        } else {
+            subscription_id % jitter_max
+        };
+        backoff = backoff.saturating_add(jitter).min(max);
     }
 
     backoff
 }
 
+/// Run a startup check, retrying up to `retries` extra times with exponential backoff.
+///
+/// Process supervisors restart a crashed keeper immediately, so a momentarily unreachable RPC at
+/// boot would otherwise turn into a crash loop. Only the final error is returned.
+async fn retry_startup<T, F, Fut>(what: &str, retries: u32, delay: Duration, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut delay = delay;
+    let mut attempt = 0u32;
+    loop {
+        attempt += 1;
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(err) => {
+                if attempt > retries {
+                    return Err(err.wrap_err(format!("{what} failed after {attempt} attempt(s)")));
+                }
+                tracing::warn!(
+                    attempt,
+                    retries,
+                    sleep_s = delay.as_secs(),
+                    error = %err,
+                    "{what} failed at startup; retrying"
+                );
+                tokio::time::sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+        }
+    }
+}
+
+/// Random delay in `[0, max)` (millisecond resolution); zero when `max` is zero.
+///
+/// Used for `--startup-splay-seconds` / `--poll-splay-seconds` so keepers started together by the
+/// same orchestrator don't poll the RPC in lockstep.
+fn splay(max: Duration) -> Duration {
+    let max_ms = max.as_millis() as u64;
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..max_ms))
+}
+
+/// Whether `--control-file` currently asks the keeper to pause collections.
+///
+/// The file is re-read every cycle: `pause` pauses, `resume` (or no file) runs normally. Anything
+/// else is logged and ignored, so a half-written file never stops collections by accident.
+fn control_paused(path: Option<&Path>) -> bool {
+    let Some(path) = path else {
+        return false;
+    };
+    match std::fs::read_to_string(path) {
+        Ok(raw) => match raw.trim().to_ascii_lowercase().as_str() {
+            "pause" => true,
+            "resume" | "" => false,
+            other => {
+                tracing::warn!(path = %path.display(), content = other, "unrecognized control file content; expected 'pause' or 'resume'");
+                false
+            }
+        },
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+        Err(err) => {
+            tracing::warn!(path = %path.display(), error = %err, "failed to read control file");
+            false
+        }
+    }
+}
+
+/// Merge simulation results with precheck failures so the `--simulate-only` report covers every
+/// eligible subscription, sorted by id.
+fn simulation_report(
+    mut simulations: Vec<SimulationResult>,
+    failures: &[FailureRecord],
+) -> Vec<SimulationResult> {
+    simulations.extend(failures.iter().map(|f| SimulationResult {
+        subscription_id: f.subscription_id,
+        would_succeed: false,
+        merchant_amount: None,
+        collector_fee: None,
+        reason: Some(format!(
+            "{:?}: {}",
+            f.kind,
+            f.reason.as_deref().unwrap_or("precheck failed")
+        )),
+    }));
+    simulations.sort_by_key(|s| s.subscription_id);
+    simulations
+}
+
+fn write_simulation_report(path: &Path, report: &[SimulationResult]) -> Result<()> {
+    let json = serde_json::to_string_pretty(report)
+        .map_err(|e| eyre!("failed to serialize simulation report: {e}"))?;
+    std::fs::write(path, json)
+        .map_err(|e| eyre!("failed to write simulation report {}: {e}", path.display()))
+}
+
+/// What `--reconcile-only` did with the txs tracked in the state file.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReconcileSummary {
+    /// Mined successfully (or matched by a `Charged` log) and finalized.
+    succeeded: Vec<u64>,
+    /// Mined but reverted; backed off like any mined revert.
+    reverted: Vec<u64>,
+    /// Cleared without a receipt: expired, replaced, or an unreadable hash. Eligible again.
+    dropped: Vec<u64>,
+    /// Still waiting for a receipt.
+    pending: Vec<u64>,
+    /// Mined, waiting for `--collect-confirmations`.
+    confirming: Vec<u64>,
+}
+
+impl ReconcileSummary {
+    fn new(
+        tracked_before: &BTreeMap<u64, InFlightTx>,
+        state: &KeeperState,
+        succeeded: &[u64],
+        reverted: &[u64],
+    ) -> Self {
+        let pending: Vec<u64> = state.in_flight.keys().copied().collect();
+        let confirming: Vec<u64> = state.confirming.keys().copied().collect();
+        let dropped = tracked_before
+            .keys()
+            .copied()
+            .filter(|id| {
+                ![succeeded, reverted, &pending, &confirming]
+                    .iter()
+                    .any(|ids| ids.contains(id))
+            })
+            .collect();
+        Self {
+            succeeded: succeeded.to_vec(),
+            reverted: reverted.to_vec(),
+            dropped,
+            pending,
+            confirming,
+        }
+    }
+
+    fn print(&self) {
+        println!("reconcile-only:");
+        println!("  succeeded:  {:?}", self.succeeded);
+        println!("  reverted:   {:?}", self.reverted);
+        println!("  dropped:    {:?}", self.dropped);
+        println!("  pending:    {:?}", self.pending);
+        println!("  confirming: {:?}", self.confirming);
+    }
+}
+
+/// Machine-readable result of a single `--once --json` cycle.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycleSummary {
+    stats: CollectStats,
+    scan: ScanStats,
+    discovered: usize,
+    last_scanned_block: u64,
+    succeeded: Vec<u64>,
+    failed: Vec<u64>,
+    pending: Vec<u64>,
+    paused: bool,
+    /// Outside `--collect-window`: ids whose cached paidThrough has passed, left for the window.
+    deferred: usize,
+    /// `--only-tag`: known ids left out for lacking the tag.
+    skipped_untagged: usize,
+    /// Gas spent by collect() txs over the rolling 24h window (before this cycle's sends).
+    gas_spent_24h_wei: U256,
+    /// `--daily-gas-budget-wei` reached: nothing is sent until the window rolls over.
+    gas_budget_exhausted: bool,
+    breaker: BreakerState,
+    /// `--explain`: one entry per known subscription.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    explain: Vec<Explanation>,
+}
+
 #[derive(Parser, Debug)]
 #[command(
     name = "opensub-keeper",
     version,
-    about = "OpenSub Milestone 5 keeper bot (Rust)"
+    about = "OpenSub Milestone 5 keeper bot (Rust)",
+    group(clap::ArgGroup::new("json_source").args(["once", "inspect", "reconcile_only", "list_due"]).multiple(true)),
+    group(clap::ArgGroup::new("pinned_run").args(["once", "dry_run"]).multiple(true))
 )]
 struct Args {
+    /// Print a read-only report of the state file (known ids, in-flight txs and their ages, retry
+    /// countdowns) and exit. No key, no lock, nothing is written.
+    #[arg(long)]
+    inspect: bool,
+
+    /// With --inspect: also call `isDue` for every id without a tracked tx and list the due ones.
+    #[arg(long, requires = "inspect")]
+    inspect_due: bool,
+
+    /// Print the subscriptions that are due right now (`isDue` for every known id, with subscriber,
+    /// planId and paidThrough) and exit. Implies --once; no key, no lock, nothing is sent or
+    /// written.
+    #[arg(long, conflicts_with_all = ["inspect", "reconcile_only", "catch_up"])]
+    list_due: bool,
+
+    /// Tag a known subscription in the state file, as `<id>=<tag>` (repeatable), and exit. Tags
+    /// show up in --inspect and select ids for --only-tag. Takes the state lock, so stop the
+    /// keeper first.
+    #[arg(long, value_name = "ID=TAG", value_parser = tags::parse_tag, conflicts_with_all = ["inspect", "list_due", "reconcile_only", "once"])]
+    tag_id: Vec<(u64, String)>,
+
+    /// Remove a tag, as `<id>=<tag>`, or all of a subscription's tags, as `<id>` (repeatable),
+    /// and exit. Applied before --tag-id.
+    #[arg(long, value_name = "ID[=TAG]", value_parser = tags::parse_untag, conflicts_with_all = ["inspect", "list_due", "reconcile_only", "once"])]
+    untag_id: Vec<(u64, Option<String>)>,
+
+    /// Only check subscriptions tagged with this (see --tag-id) each cycle; the rest are skipped.
+    #[arg(long)]
+    only_tag: Option<String>,
+
+    /// With --list-due: skip this many due subscriptions (in subscription id order).
+    #[arg(long, requires = "list_due", default_value_t = 0)]
+    offset: u64,
+
+    /// With --list-due: print at most this many due subscriptions. The `--json` output's
+    /// `nextOffset` is the --offset of the next page (null on the last one).
+    #[arg(long, requires = "list_due", value_parser = clap::value_parser!(u64).range(1..))]
+    limit: Option<u64>,
+
+    /// Monitoring only: no private key needed. Scans, prechecks and reports who is due, but never
+    /// sends (implies --dry-run).
+    #[arg(long, conflicts_with = "force_pending")]
+    monitor_only: bool,
+
+    /// Don't allocate nonces locally; let the provider fill each tx from its pending nonce. For
+    /// managed RPCs that sequence nonces per key themselves, where a local nonce manager causes
+    /// "nonce too low" storms. Concurrent sends may then race for the same nonce, so pair it with
+    /// `--max-concurrency 1` unless the provider handles that.
+    #[arg(long, conflicts_with = "monitor_only")]
+    no_nonce_manager: bool,
+
+    /// Route the collector fee to this address (e.g. a cold treasury) instead of the signer.
+    ///
+    /// Needs an OpenSub that exposes `collect(uint256,address)`; on contracts without that
+    /// overload the keeper warns and collects normally.
+    #[arg(long)]
+    fee_recipient: Option<ethers::types::Address>,
+
     /// Path to a deployment artifact JSON (e.g., deployments/base-sepolia.json)
     #[arg(long, default_value = "deployments/base-sepolia.json")]
     deployment: PathBuf,
@@ -86,17 +394,105 @@ struct Args {
     #[arg(long)]
     rpc_url: Option<String>,
 
+    /// Idle keep-alive connections kept per host by the RPC (and relayer bundler/paymaster) HTTP
+    /// clients. Unset: no cap. 0 opens a new connection for every request.
+    #[arg(long)]
+    rpc_pool_max_idle: Option<usize>,
+
+    /// Seconds an idle pooled RPC connection is kept open (default 90). Set it below the
+    /// provider's / load balancer's own idle timeout to avoid reusing connections it has closed.
+    #[arg(long)]
+    rpc_pool_idle_timeout: Option<u64>,
+
+    /// Send concurrent `eth_call`s (isDue, subscriptions, plans, allowance, balanceOf) as
+    /// JSON-RPC batch requests instead of one HTTP request each. Only for providers that accept
+    /// batches; a rejected batch is retried call by call.
+    #[arg(long)]
+    json_rpc_batch: bool,
+
     /// Environment variable name that contains the keeper's private key.
     #[arg(long, default_value = "KEEPER_PRIVATE_KEY")]
     private_key_env: String,
 
-    /// Polling interval in seconds.
-    #[arg(long, default_value_t = 30)]
-    poll_seconds: u64,
+    /// Sign with this encrypted JSON keystore (geth / `cast wallet` format) instead of a raw key
+    /// from `--private-key-env`.
+    #[arg(long, conflicts_with = "private_key_env")]
+    keystore: Option<PathBuf>,
 
-    /// Block confirmations to wait before scanning logs.
-    #[arg(long, default_value_t = 2)]
-    confirmations: u64,
+    /// Environment variable that contains the `--keystore` password.
+    #[arg(long, default_value = "KEEPER_KEYSTORE_PASSWORD")]
+    keystore_password_env: String,
+
+    /// Polling interval in seconds. [default: 30, or 2 with --local-dev]
+    #[arg(long)]
+    poll_seconds: Option<u64>,
+
+    /// Sleep a random delay in [0, N) seconds before the first cycle, so keepers started together
+    /// don't poll in lockstep. 0 disables.
+    #[arg(long, default_value_t = 0)]
+    startup_splay_seconds: u64,
+
+    /// Add a random delay in [0, N) seconds to every poll-interval sleep. 0 disables.
+    #[arg(long, default_value_t = 0)]
+    poll_splay_seconds: u64,
+
+    /// Blocks behind head to scan `Subscribed` logs up to, so events near the tip that a reorg may
+    /// drop are not picked up yet. Separate from --collect-confirmations (tx finality).
+    /// `--confirmations` is accepted as an alias.
+    #[arg(long, alias = "confirmations", default_value_t = 2)]
+    scan_confirmations: u64,
+
+    /// Confirmations a successful collect() receipt needs before it counts as a success. Until
+    /// then the subscription stays in a "confirming" set (not recollected); a receipt that
+    /// disappears (reorg) moves it back to in-flight.
+    #[arg(long, default_value_t = 1)]
+    collect_confirmations: u64,
+
+    /// Only collect when `isDue` already holds at `latest - N`, a block N deep, instead of at the
+    /// chain tip. Subscriptions that only look due on an unstable tip wait for a later cycle.
+    /// 0 checks at latest.
+    #[arg(long, default_value_t = 0)]
+    due_confirmations: u64,
+
+    /// Treat a subscription as due locally once `now + N >= paidThrough` (cached from `Subscribed`
+    /// logs and `subscriptions()` reads), and skip the on-chain `isDue()` call before that.
+    #[arg(long, default_value_t = 120)]
+    local_due_margin_seconds: u64,
+
+    /// Disable the local `paidThrough` pre-filter and call `isDue()` for every id each cycle.
+    #[arg(long)]
+    no_local_due_filter: bool,
+
+    /// Remove subscriptions from the state file once they have ended (Cancelled, or NonRenewing
+    /// past their paid period) instead of re-checking them every cycle. Costs a `subscriptions()`
+    /// read for ids `isDue()` reports as not due.
+    #[arg(long)]
+    prune_inactive: bool,
+
+    /// Multicall3 contract used to batch the `subscriptions()` / `plans()` reads of each cycle
+    /// into a few `aggregate3` calls (commonly 0xcA11bde05977b3631167028862bE2a173976CA11).
+    /// Unset: one read per id.
+    #[arg(long)]
+    multicall_address: Option<ethers::types::Address>,
+
+    /// Which due subscriptions each cycle collects first: `fifo` (discovery order),
+    /// `most-overdue` (oldest paidThrough) or `highest-fee` (largest expected collector fee).
+    /// Non-fifo orders read every candidate's subscription and plan before the first collect.
+    #[arg(long, value_enum, default_value_t = CollectOrder::Fifo)]
+    collect_order: CollectOrder,
+
+    /// Cap on tracked subscription ids, for resource-constrained monitors. Above it, ids are
+    /// evicted by --eviction-policy after each scan. Evicted ids come back when a `Charged` or
+    /// `CancelUnscheduled` log shows them alive (one more log query per scan) or when `isDue`,
+    /// checked for up to 100 evicted ids per scan, turns true.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_tracked_subscriptions: Option<u64>,
+
+    /// Which ids --max-tracked-subscriptions evicts first: `furthest-from-due` (long-failing
+    /// ids, then the latest cached paidThrough / most recent collect; overdue ids last) or
+    /// `oldest` (lowest ids).
+    #[arg(long, value_enum, default_value_t = EvictionPolicy::FurthestFromDue, requires = "max_tracked_subscriptions")]
+    eviction_policy: EvictionPolicy,
 
     /// Log scan chunk size (blocks per eth_getLogs request).
     #[arg(long, default_value_t = 2000)]
@@ -110,6 +506,17 @@ struct Args {
     #[arg(long)]
     gas_limit: Option<u64>,
 
+    /// JSON file of per-plan / per-subscription collect() gas limits
+    /// (`{"plans": {"<planId>": gas}, "subscriptions": {"<id>": gas}}`); these take precedence
+    /// over `--gas-limit`.
+    #[arg(long)]
+    gas_limits_file: Option<PathBuf>,
+
+    /// Stop sending collect() txs once their gas cost (gasUsed * effectiveGasPrice) over the last
+    /// 24h reaches this many wei; resumes as the window rolls over. Tracked in the state file.
+    #[arg(long)]
+    daily_gas_budget_wei: Option<u128>,
+
     /// Max number of collect() transactions to submit per cycle.
     ///
     /// This is a safety valve to avoid draining the keeper wallet if something goes wrong.
@@ -117,8 +524,20 @@ struct Args {
     max_txs_per_cycle: usize,
 
     /// How many seconds to wait for a transaction receipt before treating it as "still pending".
-    #[arg(long, default_value_t = 120)]
-    tx_timeout_seconds: u64,
+    /// [default: 120, or 10 with --local-dev]
+    #[arg(long)]
+    tx_timeout_seconds: Option<u64>,
+
+    /// Cap on the total time a cycle waits for collect receipts, however many txs it sent. Txs
+    /// still unconfirmed at the deadline are tracked as in-flight (as after --tx-timeout-seconds)
+    /// and the cycle moves on.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    cycle_deadline_seconds: Option<u64>,
+
+    /// Defaults for a local node with instant mining (Anvil): fast provider polling, short poll
+    /// and receipt timeouts, no API-key warning. Only defaults change; explicit flags still win.
+    #[arg(long)]
+    local_dev: bool,
 
     /// How many seconds to keep an in-flight tx recorded before dropping it and allowing a retry.
     #[arg(long, default_value_t = 900)]
@@ -144,14 +563,87 @@ struct Args {
     #[arg(long, default_value_t = 30)]
     rpc_error_backoff_seconds: u64,
 
+    /// Base backoff (seconds) for InsufficientAllowance. Defaults to --backoff-base-seconds.
+    #[arg(long)]
+    insufficient_allowance_backoff_seconds: Option<u64>,
+
+    /// Base backoff (seconds) for InsufficientBalance. Defaults to --backoff-base-seconds.
+    #[arg(long)]
+    insufficient_balance_backoff_seconds: Option<u64>,
+
+    /// Base backoff (seconds) for SimulationRevert. Defaults to --backoff-base-seconds.
+    #[arg(long)]
+    simulation_revert_backoff_seconds: Option<u64>,
+
+    /// Base backoff (seconds) for MinedRevert. Defaults to --backoff-base-seconds.
+    #[arg(long)]
+    mined_revert_backoff_seconds: Option<u64>,
+
+    /// Base backoff (seconds) for Unknown failures. Defaults to --backoff-base-seconds.
+    #[arg(long)]
+    unknown_backoff_seconds: Option<u64>,
+
     /// Milestone 5.1: add deterministic jitter in [0, jitterSeconds) to spread retries.
     #[arg(long, default_value_t = 30)]
     jitter_seconds: u64,
 
+    /// Draw backoff jitter from a PRNG instead of `subscriptionId % jitterSeconds`.
+    ///
+    /// Useful when running redundant keepers against the same subscriptions.
+    #[arg(long)]
+    random_jitter: bool,
+
+    /// Circuit breaker: a collect pass in which at least this fraction of the checked
+    /// subscriptions failed (RPC errors, failed sends, reverts) counts as a bad cycle.
+    #[arg(long, default_value_t = 0.9)]
+    breaker_threshold: f64,
+
+    /// Trip the circuit breaker after this many consecutive bad cycles. While it is open the
+    /// keeper only runs a light health check (eth_blockNumber) each poll. 0 disables the breaker.
+    #[arg(long, default_value_t = 0)]
+    breaker_trip_cycles: u32,
+
+    /// How long the circuit breaker stays open once tripped.
+    #[arg(long, default_value_t = 600)]
+    breaker_cooldown_seconds: u64,
+
+    /// Refuse to collect a subscription again within this many seconds of its last successful
+    /// collect (skips count as throttled). 0 disables the guard.
+    #[arg(long, default_value_t = 0)]
+    min_seconds_between_collects: u64,
+
+    /// Never send collect() for the same subscription twice within this many seconds, even across
+    /// restarts: send times are written to `<state-file>.sends.json` before each send. Independent
+    /// of backoff. 0 disables the guard.
+    #[arg(long, default_value_t = 0)]
+    min_resend_seconds: u64,
+
+    /// Address expected to receive collector fees. OpenSub pays `msg.sender`, so a mismatch with the
+    /// signer is logged, as is the waived fee on subscriptions owned by the signer (still collected).
+    #[arg(long)]
+    collector_address: Option<ethers::types::Address>,
+
     /// Disable collect() eth_call simulation guardrail.
     #[arg(long)]
     no_simulate: bool,
 
+    /// When a collect() would fail (insufficient allowance/balance, or a reverted simulation),
+    /// re-run the eth_call with the subscriber's token balance and allowance overridden to cover
+    /// the price, and add whether it then succeeds to the failure reason. Needs an RPC that
+    /// supports eth_call state overrides.
+    #[arg(long)]
+    simulate_with_overrides: bool,
+
+    /// Storage slot of the token's `balanceOf` mapping, for --simulate-with-overrides
+    /// (3 for this repo's MockERC20; 0 for OpenZeppelin ERC20).
+    #[arg(long, default_value_t = 3)]
+    token_balance_slot: u64,
+
+    /// Storage slot of the token's `allowance` mapping, for --simulate-with-overrides
+    /// (4 for this repo's MockERC20; 1 for OpenZeppelin ERC20).
+    #[arg(long, default_value_t = 4)]
+    token_allowance_slot: u64,
+
     /// Ignore persisted per-subscription backoff and check everything every cycle.
     ///
     /// Useful for debugging. Not recommended for normal operation.
@@ -162,54 +654,243 @@ struct Args {
     #[arg(long, default_value = "keeper-rs/state/state.json")]
     state_file: PathBuf,
 
+    /// How many times to retry the startup RPC checks (chainId, OpenSub code) before exiting.
+    #[arg(long, default_value_t = 5)]
+    startup_retries: u32,
+
+    /// Initial delay (seconds) between startup retries; doubles after each attempt.
+    #[arg(long, default_value_t = 2)]
+    startup_retry_delay_seconds: u64,
+
     /// Run a single scan+collect cycle and exit.
     #[arg(long)]
     once: bool,
 
+    /// Scan from this block instead of the deployment's startBlock (also the floor for all
+    /// later scans in this run). A state file already scanned past it keeps its cursor unless
+    /// `--reset-scan` is given too.
+    #[arg(long)]
+    from_block: Option<u64>,
+
+    /// With `--from-block`: rewind the persisted scan cursor so `[from-block, head]` is scanned
+    /// again (e.g. after a missed-event incident). Known subscription ids are kept.
+    #[arg(long, requires = "from_block")]
+    reset_scan: bool,
+
+    /// Backfill mode: keep scanning until caught up with the confirmed head, do one collect pass,
+    /// then exit. Reports blocks scanned and subscriptions discovered.
+    #[arg(long, conflicts_with = "once")]
+    catch_up: bool,
+
+    /// Cleanup after a crash: reconcile the in-flight / confirming txs in the state file, apply
+    /// their outcomes (success, revert backoff), print what was finalized or dropped, and exit.
+    /// No scan, no collect. Implies --once.
+    #[arg(long, conflicts_with_all = ["catch_up", "inspect"])]
+    reconcile_only: bool,
+
     /// Don't send transactions; only print what would be done.
     #[arg(long)]
     dry_run: bool,
+
+    /// Print each subscription's decision for the cycle (collect, not-due, skip-in-flight,
+    /// skip-backoff, skip-untagged, skip-throttled, precheck-fail, reverted, pruned) and, for failures, the
+    /// backoff it leads to. With --dry-run, shows the backoff that would be recorded without
+    /// persisting it. Included in the --json summary as `explain`.
+    #[arg(long)]
+    explain: bool,
+
+    /// Run prechecks plus the collect() eth_call simulation for every eligible subscription and
+    /// report the results. Never sends and never updates backoff/in-flight state.
+    #[arg(long)]
+    simulate_only: bool,
+
+    /// With `--once`: print a single JSON summary of the cycle to stdout (logs go to stderr).
+    /// With `--inspect` / `--reconcile-only` / `--list-due`: print the report as JSON.
+    #[arg(long, requires = "json_source")]
+    json: bool,
+
+    /// With `--simulate-only`: write the per-subscription report as JSON to this path each cycle.
+    #[arg(long, requires = "simulate_only")]
+    simulate_report: Option<PathBuf>,
+
+    /// With `--simulate-only`: run isDue, the prechecks and the collect() simulation against the
+    /// state at this block, e.g. to see why a past collect reverted. Pass the block before the one
+    /// the tx was mined in to see the state it ran against. Needs an archive RPC.
+    #[arg(long, requires = "simulate_only", conflicts_with = "due_confirmations")]
+    simulate_at_block: Option<u64>,
+
+    /// Pin isDue, the prechecks and the collect() simulation to the state at this block, so a
+    /// cycle's decisions are reproducible. Sends still go to the live chain (combine with
+    /// `--dry-run` to send nothing). Needs an archive RPC for old blocks. Requires `--once` or
+    /// `--dry-run`: looping on the same block would re-send collects that already went through.
+    #[arg(
+        long,
+        requires = "pinned_run",
+        conflicts_with_all = ["due_confirmations", "simulate_at_block"]
+    )]
+    pin_block: Option<u64>,
+
+    /// Operational switch, re-read every cycle: if the file contains `pause`, skip the collect
+    /// step (scanning and reconciliation continue); `resume` or a missing file runs normally.
+    #[arg(long)]
+    control_file: Option<PathBuf>,
+
+    /// Only send collects during this time of day, e.g. `22:00-06:00` (may cross midnight).
+    /// Scanning and reconciliation run at all times; due subscriptions wait for the window.
+    #[arg(long)]
+    collect_window: Option<String>,
+
+    /// Timezone of --collect-window: `UTC` or a fixed offset such as `+02:00` (no DST).
+    #[arg(long, default_value = "UTC", requires = "collect_window")]
+    timezone: String,
+
+    /// Append one JSON line per keeper action (send, success, revert, precheck-fail, throttle).
+    #[arg(long)]
+    audit_log: Option<PathBuf>,
+
+    /// Rotate the audit log to `<path>.1` once it exceeds this many bytes (0 = never).
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    audit_log_max_bytes: u64,
+
+    /// POST a JSON notification (subscriptionId, planId, subscriber, startTime, paidThrough) here
+    /// for every newly discovered subscription. Best-effort: delivered in the background, failures
+    /// are logged and not retried.
+    #[arg(long)]
+    new_subscription_webhook_url: Option<String>,
+
+    /// Shell command run (via `sh -c`) after each collect is recorded as a success, e.g. to update
+    /// your own database. `{id}`, `{tx}`, `{planId}` and `{fee}` are substituted (`''` when
+    /// unknown). Runs in the background with a 30s limit; failures are logged, never retried.
+    #[arg(long)]
+    post_collect_cmd: Option<String>,
+
+    /// Gasless operation: send collect() as an ERC-4337 userOp from --relayer-account through
+    /// --relayer-bundler-url instead of as a plain transaction. The keeper key only signs; the
+    /// account pays for gas, or a paymaster sponsors it (--relayer-paymaster-url).
+    #[arg(
+        long,
+        conflicts_with_all = ["monitor_only", "force_pending"],
+        requires_all = ["relayer_account", "relayer_bundler_url"]
+    )]
+    relayer_mode: bool,
+
+    /// Deployed SimpleAccount owned by the keeper key that sends relayed collects (and so receives
+    /// the collector fees).
+    #[arg(long, requires = "relayer_mode")]
+    relayer_account: Option<ethers::types::Address>,
+
+    /// ERC-4337 bundler RPC URL for --relayer-mode.
+    #[arg(long, requires = "relayer_mode")]
+    relayer_bundler_url: Option<String>,
+
+    /// EntryPoint used by --relayer-account. Defaults to the canonical address of the EntryPoint
+    /// version in use.
+    #[arg(long)]
+    relayer_entrypoint: Option<ethers::types::Address>,
+
+    /// EntryPoint version of --relayer-account (0.6 or 0.7). Defaults to the deployment's
+    /// `entryPointVersion`, then 0.6.
+    #[arg(long, requires = "relayer_mode")]
+    relayer_entrypoint_version: Option<EntryPointVersion>,
+
+    /// ERC-7677 paymaster URL that sponsors relayed collects.
+    #[arg(long, requires_all = ["relayer_mode", "relayer_policy_id"])]
+    relayer_paymaster_url: Option<String>,
+
+    /// Paymaster policy id (e.g. an Alchemy Gas Manager policy) for --relayer-paymaster-url.
+    #[arg(long, requires = "relayer_paymaster_url")]
+    relayer_policy_id: Option<String>,
+
+    /// Experimental (built with `--features eip7702`): send each cycle's collects as one
+    /// `executeBatch` tx from the keeper EOA to itself, through its EIP-7702 delegation to this
+    /// implementation (e.g. a `Simple7702Account`). Falls back to one tx per collect when the EOA
+    /// is not delegated to it or the chain has no EIP-7702.
+    #[cfg_attr(
+        feature = "eip7702",
+        arg(long, value_name = "DELEGATE", conflicts_with_all = ["relayer_mode", "monitor_only"])
+    )]
+    #[cfg_attr(not(feature = "eip7702"), arg(skip))]
+    eip7702: Option<ethers::types::Address>,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+/// `--relayer-mode` settings, or `None` when collects go out as plain transactions.
+fn relayer_config(args: &Args, deployment: &DeploymentArtifact) -> Result<Option<RelayerConfig>> {
+    if !args.relayer_mode {
+        return Ok(None);
+    }
+    let account = args
+        .relayer_account
+        .ok_or_else(|| eyre!("--relayer-mode requires --relayer-account"))?;
+    let bundler_url = args
+        .relayer_bundler_url
+        .clone()
+        .ok_or_else(|| eyre!("--relayer-mode requires --relayer-bundler-url"))?;
+    let entrypoint_version = args
+        .relayer_entrypoint_version
+        .or(deployment.entry_point_version)
+        .unwrap_or_default();
+    Ok(Some(RelayerConfig {
+        account,
+        entrypoint: args
+            .relayer_entrypoint
+            .unwrap_or_else(|| entrypoint_version.canonical_address()),
+        entrypoint_version,
+        bundler_url,
+        paymaster: args
+            .relayer_paymaster_url
+            .clone()
+            .zip(args.relayer_policy_id.clone()),
+        client_options: rpc_client_options(args),
+    }))
+}
 
-    let args = Args::parse();
+/// `--rpc-pool-*`, shared by the provider and the relayer's bundler / paymaster clients.
+fn rpc_client_options(args: &Args) -> ClientOptions {
+    ClientOptions {
+        pool_max_idle_per_host: args.rpc_pool_max_idle,
+        pool_idle_timeout: args.rpc_pool_idle_timeout.map(Duration::from_secs),
+        ..Default::default()
+    }
+}
 
-    let deployment = DeploymentArtifact::load(&args.deployment)?;
+/// Chain RPC provider over an HTTP client with the configured connection pool (batching
+/// `eth_call`s with `--json-rpc-batch`).
+fn connect_provider(cfg: &KeeperConfig) -> Result<Provider<RpcTransport>> {
+    let url = reqwest::Url::parse(&cfg.rpc_url)
+        .map_err(|e| eyre!("invalid RPC URL '{}': {e}", cfg.rpc_url))?;
+    let client = cfg
+        .rpc_client
+        .client()
+        .map_err(|e| eyre!("failed to build RPC HTTP client: {e:#}"))?;
+    Ok(Provider::new(RpcTransport::new(
+        url,
+        client,
+        cfg.json_rpc_batch,
+    )))
+}
 
-    let ignore_backoff = args.ignore_backoff;
+/// The signing wallet, or `None` in `--monitor-only` mode (the key env var is not read at all).
+fn load_wallet(cfg: &KeeperConfig) -> Result<Option<LocalWallet>> {
+    if cfg.monitor_only {
+        return Ok(None);
+    }
 
-    let cfg = KeeperConfig::from_cli_and_deployment(
-        &deployment,
-        args.rpc_url,
-        args.private_key_env,
-        args.poll_seconds,
-        args.log_chunk,
-        args.confirmations,
-        args.state_file,
-        args.max_concurrency,
-        args.gas_limit,
-        args.max_txs_per_cycle,
-        args.tx_timeout_seconds,
-        args.pending_ttl_seconds,
-        args.backoff_base_seconds,
-        args.backoff_max_seconds,
-        args.plan_inactive_backoff_seconds,
-        args.rpc_error_backoff_seconds,
-        args.jitter_seconds,
-        args.force_pending,
-        !args.no_simulate,
-        args.once,
-        args.dry_run,
-    )?;
+    if let Some(path) = cfg.keystore.as_deref() {
+        let password = std::env::var(&cfg.keystore_password_env).map_err(|_| {
+            eyre!(
+                "missing keystore password env var '{}'. Set it in your shell before running.",
+                cfg.keystore_password_env
+            )
+        })?;
+        let wallet = LocalWallet::decrypt_keystore(path, password)
+            .map_err(|e| eyre!("failed to decrypt keystore {}: {e}", path.display()))?
+            .with_chain_id(cfg.chain_id);
+        return Ok(Some(wallet));
+    }
 
     let private_key = std::env::var(&cfg.private_key_env).map_err(|_| {
         eyre!(
-            "missing private key env var '{}'. Set it in your shell before running.",
+            "missing private key env var '{}'. Set it in your shell before running (or use --monitor-only).",
             cfg.private_key_env
         )
     })?;
@@ -218,13 +899,164 @@ async fn main() -> Result<()> {
         .parse::<LocalWallet>()
         .map_err(|e| eyre!("invalid private key in {}: {e}", cfg.private_key_env))?
         .with_chain_id(cfg.chain_id);
+    Ok(Some(wallet))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    // In `--json` mode stdout carries exactly one JSON object, so logs go to stderr.
+    let json_mode = args.json;
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(move || -> Box<dyn std::io::Write> {
+            if json_mode {
+                Box::new(std::io::stderr())
+            } else {
+                Box::new(std::io::stdout())
+            }
+        })
+        .init();
+
+    let deployment = DeploymentArtifact::load(&args.deployment)?;
+
+    let ignore_backoff = args.ignore_backoff;
+    let (inspect, inspect_due, list_due) = (args.inspect, args.inspect_due, args.list_due);
+    let (offset, limit) = (args.offset as usize, args.limit.map(|l| l as usize));
+    let (tag_ids, untag_ids) = (args.tag_id.clone(), args.untag_id.clone());
+    let relayer = relayer_config(&args, &deployment)?;
+    let rpc_client = rpc_client_options(&args);
+    let collect_window = args
+        .collect_window
+        .as_deref()
+        .map(|window| CollectWindow::parse(window, &args.timezone))
+        .transpose()?;
+
+    let mut cfg = KeeperConfig::from_cli_and_deployment(
+        &deployment,
+        config::CliOptions {
+            rpc_override: args.rpc_url,
+            rpc_client,
+            json_rpc_batch: args.json_rpc_batch,
+            private_key_env: args.private_key_env,
+            keystore: args.keystore,
+            keystore_password_env: args.keystore_password_env,
+            poll_seconds: args
+                .poll_seconds
+                .unwrap_or(if args.local_dev { 2 } else { 30 }),
+            startup_splay_seconds: args.startup_splay_seconds,
+            poll_splay_seconds: args.poll_splay_seconds,
+            log_chunk: args.log_chunk,
+            scan_confirmations: args.scan_confirmations,
+            collect_confirmations: args.collect_confirmations,
+            due_confirmations: args.due_confirmations,
+            local_due_margin: (!args.no_local_due_filter).then_some(args.local_due_margin_seconds),
+            prune_inactive: args.prune_inactive,
+            multicall_address: args.multicall_address,
+            collect_order: args.collect_order,
+            max_tracked_subscriptions: args.max_tracked_subscriptions.map(|n| n as usize),
+            eviction_policy: args.eviction_policy,
+            state_file: args.state_file,
+            max_concurrency: args.max_concurrency,
+            gas_limit: args.gas_limit,
+            gas_limit_overrides: args
+                .gas_limits_file
+                .as_deref()
+                .map(GasLimitOverrides::load)
+                .transpose()?
+                .unwrap_or_default(),
+            max_txs_per_cycle: args.max_txs_per_cycle,
+            daily_gas_budget_wei: args.daily_gas_budget_wei,
+            tx_timeout_seconds: args.tx_timeout_seconds.unwrap_or(if args.local_dev {
+                10
+            } else {
+                120
+            }),
+            cycle_deadline_seconds: args.cycle_deadline_seconds,
+            pending_ttl_seconds: args.pending_ttl_seconds,
+            backoff_base_seconds: args.backoff_base_seconds,
+            backoff_max_seconds: args.backoff_max_seconds,
+            plan_inactive_backoff_seconds: args.plan_inactive_backoff_seconds,
+            rpc_error_backoff_seconds: args.rpc_error_backoff_seconds,
+            kind_backoff: config::KindBackoffOverrides {
+                insufficient_allowance: args.insufficient_allowance_backoff_seconds,
+                insufficient_balance: args.insufficient_balance_backoff_seconds,
+                simulation_revert: args.simulation_revert_backoff_seconds,
+                mined_revert: args.mined_revert_backoff_seconds,
+                unknown: args.unknown_backoff_seconds,
+            },
+            jitter_seconds: args.jitter_seconds,
+            random_jitter: args.random_jitter,
+            breaker: BreakerConfig {
+                threshold: args.breaker_threshold,
+                trip_cycles: args.breaker_trip_cycles,
+                cooldown: Duration::from_secs(args.breaker_cooldown_seconds),
+            },
+            min_seconds_between_collects: args.min_seconds_between_collects,
+            min_resend_seconds: args.min_resend_seconds,
+            collector_address: args.collector_address,
+            fee_recipient: args.fee_recipient,
+            startup_retries: args.startup_retries,
+            startup_retry_delay_seconds: args.startup_retry_delay_seconds,
+            force_pending: args.force_pending,
+            simulate: !args.no_simulate,
+            simulate_overrides: args.simulate_with_overrides.then(|| Erc20Slots {
+                balance: args.token_balance_slot.into(),
+                allowance: args.token_allowance_slot.into(),
+            }),
+            once: args.once,
+            catch_up: args.catch_up,
+            reconcile_only: args.reconcile_only,
+            dry_run: args.dry_run,
+            explain: args.explain,
+            simulate_only: args.simulate_only,
+            simulate_report: args.simulate_report,
+            simulate_at_block: args.simulate_at_block,
+            pin_block: args.pin_block,
+            control_file: args.control_file,
+            collect_window,
+            only_tag: args.only_tag,
+            audit_log: args.audit_log,
+            audit_log_max_bytes: args.audit_log_max_bytes,
+            new_subscription_webhook_url: args.new_subscription_webhook_url,
+            post_collect_cmd: args.post_collect_cmd,
+            relayer,
+            eip7702: args.eip7702,
+            from_block: args.from_block,
+            reset_scan: args.reset_scan,
+            local_dev: args.local_dev,
+            monitor_only: args.monitor_only,
+            nonce_manager: !args.no_nonce_manager,
+        },
+    )?;
+
+    if inspect {
+        return run_inspect(&cfg, inspect_due, json_mode).await;
+    }
+    if list_due {
+        return run_list_due(&cfg, offset, limit, json_mode).await;
+    }
+    if !tag_ids.is_empty() || !untag_ids.is_empty() {
+        return run_tags(&cfg, &tag_ids, &untag_ids);
+    }
+
+    let wallet = load_wallet(&cfg)?;
 
     // Provider + signer.
-    let provider =
-        Provider::<Http>::try_from(cfg.rpc_url.as_str())?.interval(Duration::from_millis(800));
+    let provider = connect_provider(&cfg)?.interval(cfg.provider_poll_interval);
 
     // Hard safety check: ensure we're connected to the expected chain.
-    let remote_chain_id = provider.get_chainid().await?.as_u64();
+    //
+    // Only the RPC calls are retried; a chainId mismatch or missing code fails immediately.
+    let rpc = &provider;
+    let remote_chain_id = retry_startup(
+        "eth_chainId",
+        cfg.startup_retries,
+        cfg.startup_retry_delay,
+        || async move { Ok(rpc.get_chainid().await?.as_u64()) },
+    )
+    .await?;
     if remote_chain_id != cfg.chain_id {
         return Err(eyre!(
             "RPC chainId mismatch: deployment expects {}, but RPC reports {}. Refusing to run.",
@@ -234,7 +1066,14 @@ async fn main() -> Result<()> {
     }
 
     // Ensure OpenSub has code at the configured address.
-    let code = provider.get_code(cfg.opensub, None).await?;
+    let opensub_addr = cfg.opensub;
+    let code = retry_startup(
+        "eth_getCode",
+        cfg.startup_retries,
+        cfg.startup_retry_delay,
+        || async move { Ok(rpc.get_code(opensub_addr, None).await?) },
+    )
+    .await?;
     if code.0.is_empty() {
         return Err(eyre!(
             "no contract code found at OpenSub address {:?}. Check deployments JSON and RPC.",
@@ -242,24 +1081,223 @@ async fn main() -> Result<()> {
         ));
     }
 
-    let signer = SignerMiddleware::new(provider, wallet.clone());
-    let client = NonceManagerMiddleware::new(signer, wallet.address());
-    let client = Arc::new(client);
+    if let Some(multicall) = cfg.multicall_address {
+        let code = retry_startup(
+            "eth_getCode",
+            cfg.startup_retries,
+            cfg.startup_retry_delay,
+            || async move { Ok(rpc.get_code(multicall, None).await?) },
+        )
+        .await?;
+        if code.0.is_empty() {
+            return Err(eyre!(
+                "no contract code found at --multicall-address {multicall:?}. Check the address for this chain."
+            ));
+        }
+    }
+
+    if let Some(block) = cfg.simulate_at_block {
+        collector::check_state_at_block(&provider, cfg.opensub, block, "--simulate-at-block")
+            .await?;
+        tracing::info!(
+            block,
+            "--simulate-at-block: simulating against historical state"
+        );
+    }
+
+    if let Some(block) = cfg.pin_block {
+        collector::check_state_at_block(&provider, cfg.opensub, block, "--pin-block").await?;
+        tracing::info!(block, "--pin-block: reads pinned to this block");
+    }
+
+    if let Some(recipient) = cfg.fee_recipient {
+        if opensub::has_collect_fee_recipient(&code) {
+            tracing::info!(fee_recipient = ?recipient, "routing collector fees via collect(id, feeRecipient)");
+        } else {
+            tracing::warn!(
+                fee_recipient = ?recipient,
+                opensub = ?cfg.opensub,
+                "--fee-recipient is not supported by the deployed OpenSub (no collect(uint256,address)); fees go to the signer"
+            );
+            cfg.fee_recipient = None;
+        }
+    }
+
+    if let (Some(delegate), Some(eoa)) = (cfg.eip7702, wallet.as_ref().map(|w| w.address())) {
+        let status = retry_startup(
+            "EIP-7702 delegation check",
+            cfg.startup_retries,
+            cfg.startup_retry_delay,
+            || async move { eip7702::check_delegation(rpc, eoa, delegate).await },
+        )
+        .await?;
+        match status {
+            eip7702::DelegationStatus::Delegated => {
+                tracing::info!(eoa = ?eoa, delegate = ?delegate, "--eip7702: batching collects through the delegated keeper EOA");
+            }
+            eip7702::DelegationStatus::DelegatedElsewhere(target) => {
+                tracing::warn!(eoa = ?eoa, delegate = ?delegate, delegated_to = ?target, "--eip7702: keeper EOA is delegated to another implementation; sending collects one by one");
+                cfg.eip7702 = None;
+            }
+            eip7702::DelegationStatus::NotDelegated => {
+                tracing::warn!(eoa = ?eoa, "--eip7702: keeper EOA is not delegated ({}); sending collects one by one", eip7702::authorization_unsupported(delegate));
+                cfg.eip7702 = None;
+            }
+            eip7702::DelegationStatus::Unsupported => {
+                tracing::warn!(
+                    "--eip7702: the chain shows no EIP-7702 support; sending collects one by one"
+                );
+                cfg.eip7702 = None;
+            }
+        }
+    }
+
+    // Keep file handle alive.
+    let _lock_guard = lock_state_file(&cfg.state_file)?;
+
+    tracing::info!(
+        chain_id = cfg.chain_id,
+        opensub = ?cfg.opensub,
+        start_block = cfg.start_block,
+        signer = ?wallet.as_ref().map(|w| w.address()),
+        relayer_account = ?cfg.relayer.as_ref().map(|r| r.account),
+        relayer_entrypoint_version = ?cfg.relayer.as_ref().map(|r| r.entrypoint_version.to_string()),
+        monitor_only = cfg.monitor_only,
+        nonce_manager = cfg.nonce_manager,
+        dry_run = cfg.dry_run,
+        simulate = cfg.simulate,
+        simulate_only = cfg.simulate_only,
+        ignore_backoff,
+        force_pending = cfg.force_pending,
+        once = cfg.once,
+        catch_up = cfg.catch_up,
+        reconcile_only = cfg.reconcile_only,
+        "keeper starting"
+    );
+
+    // Relayed collects are sent (and paid their fee) from the smart account, not the key.
+    let fee_payee = cfg
+        .relayer
+        .as_ref()
+        .map(|r| r.account)
+        .or(wallet.as_ref().map(|w| w.address()));
+    if let (Some(collector), Some(payee)) = (cfg.collector_address, fee_payee) {
+        if collector != payee {
+            tracing::warn!(
+                collector_address = ?collector,
+                signer = ?payee,
+                "--collector-address differs from the signer; OpenSub pays collector fees to the signer"
+            );
+        }
+    }
+
+    let mut state = KeeperState::load_or_init(&cfg.state_file, cfg.start_block)?;
+    if cfg.reset_scan {
+        tracing::warn!(
+            previous_last_scanned_block = state.last_scanned_block,
+            from_block = cfg.start_block,
+            "--reset-scan: rescanning from --from-block"
+        );
+        state.reset_scan(cfg.start_block);
+        state.save(&cfg.state_file)?;
+    } else if let Some(last_scanned_block) = from_block_ignored(&cfg, &state) {
+        tracing::warn!(
+            from_block = cfg.start_block,
+            last_scanned_block,
+            "--from-block is behind the persisted scan cursor and changes nothing; add --reset-scan to rescan from it"
+        );
+    }
+
+    let audit_log = cfg
+        .audit_log
+        .clone()
+        .map(|path| AuditLog::open(path, cfg.audit_log_max_bytes))
+        .transpose()?;
+
+    let webhook = cfg
+        .new_subscription_webhook_url
+        .clone()
+        .map(|url| NewSubscriptionWebhook::spawn(url, cfg.chain_id, cfg.opensub))
+        .transpose()?;
+
+    let result = if cfg.monitor_only {
+        run_keeper(
+            &cfg,
+            Arc::new(provider),
+            state,
+            audit_log.as_ref(),
+            webhook.as_ref(),
+            None,
+            ignore_backoff,
+            json_mode,
+        )
+        .await
+    } else {
+        let wallet = wallet.expect("wallet is loaded unless --monitor-only");
+        let relayer = cfg
+            .relayer
+            .clone()
+            .map(|rc| Relayer::new(rc, wallet.clone(), cfg.chain_id))
+            .transpose()?
+            .map(Arc::new);
+        let signer = SignerMiddleware::new(provider, wallet.clone());
+        if cfg.nonce_manager {
+            let client = NonceManagerMiddleware::new(signer, wallet.address());
+            run_keeper(
+                &cfg,
+                Arc::new(client),
+                state,
+                audit_log.as_ref(),
+                webhook.as_ref(),
+                relayer,
+                ignore_backoff,
+                json_mode,
+            )
+            .await
+        } else {
+            // `--no-nonce-manager`: SignerMiddleware fills each tx from the pending nonce.
+            run_keeper(
+                &cfg,
+                Arc::new(signer),
+                state,
+                audit_log.as_ref(),
+                webhook.as_ref(),
+                relayer,
+                ignore_backoff,
+                json_mode,
+            )
+            .await
+        }
+    };
 
+    if let Some(webhook) = webhook {
+        webhook.close().await;
+    }
+    result
+}
+
+/// The persisted scan cursor when it is already past `--from-block` (given without
+/// `--reset-scan`): scanning resumes from the cursor, so the flag changes nothing this run.
+fn from_block_ignored(cfg: &KeeperConfig, state: &KeeperState) -> Option<u64> {
+    let from_block = cfg.from_block.filter(|_| !cfg.reset_scan)?;
+    (state.last_scanned_block >= from_block).then_some(state.last_scanned_block)
+}
+
+/// Single-instance guard: lock alongside the state file.
+/// This prevents two keepers from running concurrently with the same signer/state.
+fn lock_state_file(state_file: &Path) -> Result<std::fs::File> {
     // Ensure the state directory exists before we create/lock the lockfile.
     //
     // Without this, a first-time run can fail when the state parent directory
     // (e.g. keeper-rs/state/) does not yet exist.
-    if let Some(parent) = cfg.state_file.parent() {
+    if let Some(parent) = state_file.parent() {
         if !parent.as_os_str().is_empty() {
             std::fs::create_dir_all(parent)
                 .map_err(|e| eyre!("failed to create state directory {}: {e}", parent.display()))?;
         }
     }
 
-    // Single-instance guard: lock alongside the state file.
-    // This prevents two keepers from running concurrently with the same signer/state.
-    let lock_path = cfg.state_file.with_extension("lock");
+    let lock_path = state_file.with_extension("lock");
     let lock_file = OpenOptions::new()
         .create(true)
         .read(true)
@@ -273,67 +1311,252 @@ async fn main() -> Result<()> {
             lock_path.display()
         )
     })?;
-    // Keep file handle alive.
-    let _lock_guard = lock_file;
+    Ok(lock_file)
+}
 
-    tracing::info!(
-        chain_id = cfg.chain_id,
-        opensub = ?cfg.opensub,
-        start_block = cfg.start_block,
-        signer = ?wallet.address(),
-        dry_run = cfg.dry_run,
-        simulate = cfg.simulate,
-        ignore_backoff,
-        force_pending = cfg.force_pending,
-        once = cfg.once,
-        "keeper starting"
-    );
+/// `--tag-id` / `--untag-id`: edit the state file's tags, print the result and exit. Holds the
+/// state lock so a running keeper can't overwrite the change.
+fn run_tags(
+    cfg: &KeeperConfig,
+    tag_ids: &[(u64, String)],
+    untag_ids: &[(u64, Option<String>)],
+) -> Result<()> {
+    let _lock_guard = lock_state_file(&cfg.state_file)?;
+    let mut state = KeeperState::load(&cfg.state_file)?;
+    if let Some((id, _)) = tag_ids
+        .iter()
+        .find(|(id, _)| !state.subscription_ids.contains(id) && !state.evicted.contains(id))
+    {
+        return Err(eyre!(
+            "subscription {id} is not in the state file {}; tags are only kept for known ids",
+            cfg.state_file.display()
+        ));
+    }
 
-    let mut state = KeeperState::load_or_init(&cfg.state_file, cfg.start_block)?;
+    for (id, tag) in untag_ids {
+        if !state.remove_tag(*id, tag.as_deref()) {
+            tracing::warn!(subscription_id = id, tag = ?tag, "--untag-id: nothing to remove");
+        }
+    }
+    for (id, tag) in tag_ids {
+        state.add_tag(*id, tag);
+    }
+    state.save(&cfg.state_file)?;
+
+    let touched: std::collections::BTreeSet<u64> = untag_ids
+        .iter()
+        .map(|(id, _)| *id)
+        .chain(tag_ids.iter().map(|(id, _)| *id))
+        .collect();
+    for id in touched {
+        match state.tags.get(&id) {
+            Some(tags) => println!("#{id} {}", tags.join(", ")),
+            None => println!("#{id} (no tags)"),
+        }
+    }
+    Ok(())
+}
+
+/// `--inspect`: print the derived state report and exit. Reads the state file only.
+async fn run_inspect(cfg: &KeeperConfig, check_due: bool, json_mode: bool) -> Result<()> {
+    let state = KeeperState::load(&cfg.state_file)?;
+
+    let due = if check_due {
+        let provider = connect_provider(cfg)?;
+        let opensub = OpenSub::new(cfg.opensub, Arc::new(provider));
+        Some(inspect::due_now(&opensub, &state, cfg.max_concurrency).await)
+    } else {
+        None
+    };
 
+    let report = inspect::build_report(&state, now_unix(), due);
+    if json_mode {
+        let out = serde_json::to_string(&report)
+            .map_err(|e| eyre!("failed to serialize inspect report: {e}"))?;
+        println!("{out}");
+    } else {
+        println!("state file: {}", cfg.state_file.display());
+        inspect::print_report(&report);
+    }
+    Ok(())
+}
+
+/// `--list-due`: print who is due right now and exit. Reads the state file and calls view
+/// functions only. `--offset` / `--limit` select a page of the id-ordered result.
+async fn run_list_due(
+    cfg: &KeeperConfig,
+    offset: usize,
+    limit: Option<usize>,
+    json_mode: bool,
+) -> Result<()> {
+    let state = KeeperState::load(&cfg.state_file)?;
+    let provider = connect_provider(cfg)?;
+    let opensub = OpenSub::new(cfg.opensub, Arc::new(provider));
+
+    let due = inspect::list_due(&opensub, &state.subscription_ids, cfg.max_concurrency).await;
+    let (shown, next_offset) = inspect::page(&due, offset, limit);
+    let now = now_unix();
+    if json_mode {
+        let out = serde_json::to_string(&serde_json::json!({
+            "now": now,
+            "checked": state.subscription_ids.len(),
+            "total": due.len(),
+            "offset": offset,
+            "nextOffset": next_offset,
+            "due": shown,
+        }))
+        .map_err(|e| eyre!("failed to serialize due list: {e}"))?;
+        println!("{out}");
+    } else {
+        println!(
+            "checked {} subscriptions from {}",
+            state.subscription_ids.len(),
+            cfg.state_file.display()
+        );
+        inspect::print_due(shown, due.len(), now);
+        if let Some(next) = next_offset {
+            println!(
+                "showing {} from offset {offset}; next page: --offset {next}",
+                shown.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The keeper loop: reconcile, scan, collect, repeat.
+///
+/// `client` is the signing client, or a plain provider in `--monitor-only` mode (which forces
+/// `dry_run`, so nothing is ever sent).
+#[allow(clippy::too_many_arguments)]
+async fn run_keeper<M: Middleware + 'static>(
+    cfg: &KeeperConfig,
+    client: Arc<M>,
+    mut state: KeeperState,
+    audit_log: Option<&AuditLog>,
+    webhook: Option<&NewSubscriptionWebhook>,
+    relayer: Option<Arc<Relayer>>,
+    ignore_backoff: bool,
+    json_mode: bool,
+) -> Result<()>
+where
+    <M as Middleware>::Error: 'static,
+{
     let opensub = OpenSub::new(cfg.opensub, client.clone());
 
+    if !cfg.startup_splay.is_zero() {
+        let delay = splay(cfg.startup_splay);
+        tracing::info!(delay_ms = delay.as_millis() as u64, "startup splay");
+        tokio::time::sleep(delay).await;
+    }
+
+    let mut breaker = CircuitBreaker::new(cfg.breaker);
+    let post_collect = cfg.post_collect_cmd.clone().map(PostCollectHook::new);
+    let send_guard = if cfg.min_resend_seconds > 0 {
+        let path = send_guard_path(&cfg.state_file);
+        Some(Arc::new(SendGuard::load(
+            path,
+            cfg.min_resend_seconds,
+            now_unix(),
+        )?))
+    } else {
+        None
+    };
+
     loop {
+        if breaker.is_open(now_unix()) {
+            // Cooldown: only check that the RPC answers. No reconcile, scan or sends.
+            let open_until = breaker.state().open_until;
+            match client.get_block_number().await {
+                Ok(head) => tracing::warn!(
+                    head = head.as_u64(),
+                    open_until,
+                    "circuit breaker open; health check ok, waiting out the cooldown"
+                ),
+                Err(err) => tracing::warn!(
+                    error = %err,
+                    open_until,
+                    "circuit breaker open; health check failed"
+                ),
+            }
+            tokio::time::sleep(cfg.poll_interval + splay(cfg.poll_splay)).await;
+            continue;
+        }
+
         // 0) Reconcile any in-flight txs from previous cycles (or restarts).
+        let mut in_flight_before = state.in_flight.clone();
+        in_flight_before.extend(state.confirming.clone());
+
+        // Confirming txs first: one that was reorged out goes back to in-flight and is re-checked
+        // right below.
+        let confirmed = state
+            .reconcile_confirming(client.as_ref(), cfg.collect_confirmations)
+            .await?;
         let reconcile = state
-            .reconcile_in_flight(client.as_ref(), cfg.pending_ttl)
+            .reconcile_in_flight(
+                client.as_ref(),
+                cfg.pending_ttl,
+                Some(cfg.opensub),
+                cfg.log_chunk_size,
+                cfg.collect_confirmations,
+                cfg.daily_gas_budget_wei.is_some(),
+            )
             .await?;
 
         let ReconcileOutcome {
-            cleared,
-            finalized_success,
-            finalized_revert,
+            mut cleared,
+            mut finalized_success,
+            mut finalized_revert,
         } = reconcile;
+        cleared += confirmed.cleared;
+        finalized_success.extend(confirmed.finalized_success);
+        finalized_revert.extend(confirmed.finalized_revert);
 
         if cleared > 0 {
             tracing::info!(cleared, "cleared in-flight txs");
         }
 
+        if let Some(log) = audit_log {
+            let finalized = |action: AuditAction, id: u64| {
+                let event = AuditEvent::new(action, id).reason("in-flight tx finalized");
+                match in_flight_before
+                    .get(&id)
+                    .and_then(|t| H256::from_str(&t.tx_hash).ok())
+                {
+                    Some(tx_hash) => event.tx(tx_hash),
+                    None => event,
+                }
+            };
+            log.record_all(
+                finalized_success
+                    .iter()
+                    .map(|id| finalized(AuditAction::Success, *id))
+                    .chain(
+                        finalized_revert
+                            .iter()
+                            .map(|id| finalized(AuditAction::Revert, *id)),
+                    ),
+            );
+        }
+
         // If a previously pending tx finalized, treat it as a success/failure so we don't keep
         // stale backoff state forever.
         //
-        // In dry-run mode, we do not persist these updates.
-        if !cfg.dry_run {
+        // In dry-run / simulate-only mode, we do not persist these updates.
+        if cfg.persists_outcomes() {
             let now = now_unix();
             let mut dirty = cleared > 0;
 
-            for id in finalized_success {
+            for &id in &finalized_success {
                 dirty = true;
                 state.note_success(id);
             }
 
-            for id in finalized_revert {
+            for &id in &finalized_revert {
                 dirty = true;
 
-                let prev = state
-                    .retries
-                    .get(&id)
-                    .map(|r| r.consecutive_failures)
-                    .unwrap_or(0);
-                let consecutive = prev.saturating_add(1);
-                let backoff_s =
-                    compute_backoff_seconds(&cfg, FailureKind::MinedRevert, consecutive, id);
-                let next_retry_at = now.saturating_add(backoff_s);
+                let (consecutive, backoff_s, next_retry_at) =
+                    next_retry(cfg, &state, FailureKind::MinedRevert, id, now);
 
                 tracing::warn!(
                     subscription_id = id,
@@ -355,77 +1578,294 @@ async fn main() -> Result<()> {
             if dirty {
                 state.save(&cfg.state_file)?;
             }
+
+            if let Some(hook) = post_collect.as_ref() {
+                for &id in &finalized_success {
+                    hook.run(&Collected {
+                        subscription_id: id,
+                        plan_id: None,
+                        tx_hash: in_flight_before
+                            .get(&id)
+                            .and_then(|t| H256::from_str(&t.tx_hash).ok()),
+                        collector_fee: None,
+                    });
+                }
+            }
+        }
+
+        if cfg.reconcile_only {
+            let summary = ReconcileSummary::new(
+                &in_flight_before,
+                &state,
+                &finalized_success,
+                &finalized_revert,
+            );
+            if json_mode {
+                let out = serde_json::to_string(&summary)
+                    .map_err(|e| eyre!("failed to serialize reconcile summary: {e}"))?;
+                println!("{out}");
+            } else {
+                summary.print();
+            }
+            break;
         }
 
         // 1) Scan for new subscriptions.
-        let newly = scanner::scan_new_subscriptions(
-            client.as_ref(),
-            cfg.opensub,
-            cfg.start_block,
-            cfg.confirmations,
-            cfg.log_chunk_size,
-            &mut state,
-        )
-        .await?;
+        let mut scan_stats = ScanStats::default();
+        let scan_from = state
+            .last_scanned_block
+            .saturating_add(1)
+            .max(cfg.start_block);
+        let scanned = if cfg.catch_up {
+            scanner::catch_up_subscriptions(
+                client.as_ref(),
+                cfg.opensub,
+                cfg.start_block,
+                cfg.scan_confirmations,
+                cfg.log_chunk_size,
+                &mut state,
+                &mut scan_stats,
+            )
+            .await
+            .map(|report| {
+                tracing::info!(
+                    iterations = report.iterations,
+                    blocks_scanned = report.blocks_scanned,
+                    discovered = report.discovered.len(),
+                    last_scanned_block = state.last_scanned_block,
+                    "catch-up scan complete"
+                );
+                report.discovered
+            })
+        } else {
+            scanner::scan_new_subscriptions(
+                client.as_ref(),
+                cfg.opensub,
+                cfg.start_block,
+                cfg.scan_confirmations,
+                cfg.log_chunk_size,
+                &mut state,
+                &mut scan_stats,
+            )
+            .await
+        };
+        tracing::info!(
+            get_logs_calls = scan_stats.get_logs_calls,
+            retries = scan_stats.retries,
+            chunk_reductions = scan_stats.chunk_reductions,
+            final_chunk_size = scan_stats.final_chunk_size,
+            duration_ms = scan_stats.duration_ms,
+            "scan stats"
+        );
+
+        let discovered = match scanned {
+            Ok(discovered) => discovered,
+            // Provider limits, throttling and timeouts usually clear up on their own; keep the
+            // process alive. Single-shot modes still fail so callers see the error.
+            Err(err) if err.should_backoff() && !cfg.once && !cfg.catch_up => {
+                tracing::warn!(
+                    kind = err.label(),
+                    error = %err,
+                    backoff_s = cfg.rpc_error_backoff.as_secs(),
+                    "scan failed; backing off before the next cycle"
+                );
+                // Keep whatever progress the scan made before failing.
+                state.save(&cfg.state_file)?;
+                tokio::time::sleep(cfg.rpc_error_backoff).await;
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // `--max-tracked-subscriptions`: pick evicted ids back up if they were active in the
+        // range just scanned or are due now, then trim the set back to the cap.
+        if let Some(max) = cfg.max_tracked_subscriptions {
+            if state.last_scanned_block >= scan_from {
+                scanner::rediscover_active(
+                    client.as_ref(),
+                    cfg.opensub,
+                    scan_from,
+                    state.last_scanned_block,
+                    cfg.log_chunk_size,
+                    &mut state,
+                    &mut scan_stats,
+                )
+                .await;
+            }
+            scanner::recheck_evicted(
+                &opensub,
+                &mut state,
+                scanner::EVICTED_RECHECK_BATCH,
+                cfg.max_concurrency,
+            )
+            .await;
+            let evicted = state.evict_to(max, cfg.eviction_policy, now_unix());
+            if !evicted.is_empty() {
+                tracing::info!(
+                    evicted = evicted.len(),
+                    remaining = state.subscription_ids.len(),
+                    max,
+                    policy = ?cfg.eviction_policy,
+                    "evicted subscriptions over --max-tracked-subscriptions"
+                );
+            }
+        }
 
+        let pruned = state.compact();
+        if pruned > 0 {
+            tracing::info!(pruned, "compacted retry state for untracked subscriptions");
+        }
         state.save(&cfg.state_file)?;
 
+        // Notify only once the ids are persisted, so a restart can't announce them twice.
+        if let Some(webhook) = webhook {
+            webhook.notify(&discovered);
+        }
+
+        let mut summary = CycleSummary {
+            discovered: discovered.len(),
+            last_scanned_block: state.last_scanned_block,
+            scan: scan_stats,
+            ..Default::default()
+        };
+
         // 2) Collect due payments.
         // Skip ids that have an in-flight tx; prevents duplicate collects while a tx is pending.
         let now = now_unix();
         let total_known = state.subscription_ids.len();
+        summary.paused = control_paused(cfg.control_file.as_deref());
+        summary.gas_spent_24h_wei = state.rolling_gas_spend(now);
+        summary.gas_budget_exhausted = cfg
+            .daily_gas_budget_wei
+            .is_some_and(|budget| summary.gas_spent_24h_wei >= budget);
         let mut skipped_in_flight = 0usize;
         let mut skipped_backoff = 0usize;
+        let mut skipped_untagged = 0usize;
+        let mut explained = Vec::new();
 
         let ids: Vec<u64> = state
             .subscription_ids
             .iter()
             .copied()
             .filter(|id| {
-                if state.in_flight.contains_key(id) {
-                    skipped_in_flight += 1;
+                if !tags::selected(&state, cfg.only_tag.as_deref(), *id) {
+                    skipped_untagged += 1;
+                    if cfg.explain {
+                        explained.push(Explanation::untagged(*id));
+                    }
                     return false;
                 }
-                if !ignore_backoff && state.should_skip_due_to_backoff(*id, now) {
+                let skip = if state.is_tracked(*id) {
+                    skipped_in_flight += 1;
+                    true
+                } else if !ignore_backoff && state.should_skip_due_to_backoff(*id, now) {
                     skipped_backoff += 1;
-                    return false;
+                    true
+                } else {
+                    false
+                };
+                if skip && cfg.explain {
+                    explained.push(Explanation::skipped(&state, *id));
                 }
-                true
+                !skip
             })
             .collect();
-        if total_known == 0 {
+        summary.skipped_untagged = skipped_untagged;
+        let outside_window = cfg.collect_window.filter(|w| !w.contains(now));
+        if summary.paused {
+            tracing::info!(total_known, "paused; skipping collect");
+        } else if let Some(window) = outside_window {
+            // No on-chain checks until the window opens; the cached paidThrough says who waits.
+            summary.deferred = ids
+                .iter()
+                .filter(|id| state.paid_through.get(id).is_some_and(|pt| *pt <= now))
+                .count();
+            tracing::info!(
+                total_known,
+                deferred = summary.deferred,
+                opens_in_secs = window.seconds_until_open(now),
+                "outside --collect-window; deferring collects"
+            );
+        } else if summary.gas_budget_exhausted {
+            tracing::warn!(
+                total_known,
+                gas_spent_24h_wei = %summary.gas_spent_24h_wei,
+                daily_gas_budget_wei = %cfg.daily_gas_budget_wei.unwrap_or_default(),
+                "daily gas budget exhausted; not sending until the 24h window rolls over"
+            );
+        } else if total_known == 0 {
             tracing::info!("no subscriptions known yet");
         } else if ids.is_empty() {
             tracing::info!(
                 total_known,
                 skipped_in_flight,
                 skipped_backoff,
+                skipped_untagged,
                 "no subscriptions eligible this cycle"
             );
         } else {
             tracing::info!(
                 total_known,
                 checking = ids.len(),
-                newly,
+                newly = discovered.len(),
                 skipped_in_flight,
                 skipped_backoff,
+                skipped_untagged,
                 "checking subscriptions"
             );
             let outcome = collect_due(
                 opensub.clone(),
                 cfg.opensub,
                 client.clone(),
-                ids,
-                cfg.max_concurrency,
-                cfg.gas_limit,
-                cfg.max_txs_per_cycle,
-                cfg.tx_timeout,
-                cfg.force_pending,
-                cfg.simulate,
-                cfg.dry_run,
+                ids.clone(),
+                CollectOptions {
+                    max_concurrency: cfg.max_concurrency,
+                    gas_limit: cfg.gas_limit,
+                    gas_limit_overrides: cfg.gas_limit_overrides.clone(),
+                    max_txs_per_cycle: cfg.max_txs_per_cycle,
+                    gas_budget: cfg
+                        .daily_gas_budget_wei
+                        .map(|budget| budget.saturating_sub(summary.gas_spent_24h_wei)),
+                    tx_timeout: cfg.tx_timeout,
+                    cycle_deadline: cfg.cycle_deadline,
+                    force_pending: cfg.force_pending,
+                    simulate: cfg.simulate,
+                    simulate_only: cfg.simulate_only,
+                    dry_run: cfg.dry_run,
+                    min_seconds_between_collects: cfg.min_seconds_between_collects,
+                    last_success: state.last_success.clone(),
+                    collector_address: cfg.collector_address,
+                    collect_confirmations: cfg.collect_confirmations,
+                    due_confirmations: cfg.due_confirmations,
+                    fee_recipient: cfg.fee_recipient,
+                    simulate_overrides: cfg.simulate_overrides,
+                    relayer: relayer.clone(),
+                    // The cached paidThrough reflects now, not a historical block.
+                    local_due: cfg
+                        .local_due_margin
+                        .filter(|_| cfg.read_block().is_none())
+                        .map(|margin| collector::LocalDueFilter {
+                            margin,
+                            paid_through: state.paid_through.clone(),
+                        }),
+                    send_guard: send_guard.clone(),
+                    prune_inactive: cfg.prune_inactive,
+                    pin_block: cfg.read_block(),
+                    multicall_address: cfg.multicall_address,
+                    collect_order: cfg.collect_order,
+                    eip7702_batch: cfg.eip7702.is_some(),
+                },
             )
             .await?;
 
+            if cfg.explain {
+                explained.extend(explain_checked(&ids, &outcome, |f| {
+                    let (consecutive, _, next_retry_at) =
+                        next_retry(cfg, &state, f.kind, f.subscription_id, now);
+                    (consecutive, next_retry_at)
+                }));
+            }
+
             let pending_len = outcome.pending.len();
             let successes_len = outcome.successes.len();
             let failures_len = outcome.failures.len();
@@ -434,54 +1874,128 @@ async fn main() -> Result<()> {
                 stats,
                 pending,
                 successes,
+                confirming,
                 failures,
+                simulations,
+                audit,
+                paid_through,
+                collected,
+                pruned,
+                would_collect: _,
             } = outcome;
 
-            // In dry-run mode, we intentionally do not persist pending txs or backoff updates.
-            // This keeps `--dry-run` side-effect free (beyond advancing scan progress).
-            if !cfg.dry_run {
+            for (id, paid_through) in paid_through {
+                match paid_through {
+                    Some(pt) => {
+                        state.paid_through.insert(id, pt);
+                    }
+                    None => {
+                        state.paid_through.remove(&id);
+                    }
+                }
+            }
+
+            if let Some(log) = audit_log {
+                log.record_all(audit);
+            }
+
+            summary.stats = stats.clone();
+            summary.succeeded = successes.clone();
+            summary.failed = failures.iter().map(|f| f.subscription_id).collect();
+            summary.pending = pending.iter().map(|p| p.subscription_id).collect();
+
+            if cfg.simulate_only {
+                let report = simulation_report(simulations, &failures);
+                let would_succeed = report.iter().filter(|r| r.would_succeed).count();
+                tracing::info!(
+                    reported = report.len(),
+                    would_succeed,
+                    would_fail = report.len() - would_succeed,
+                    "simulate-only report"
+                );
+                if let Some(path) = cfg.simulate_report.as_ref() {
+                    write_simulation_report(path, &report)?;
+                }
+            }
+
+            // In dry-run / simulate-only mode, we intentionally do not persist pending txs or
+            // backoff updates. This keeps them side-effect free (beyond advancing scan progress).
+            if cfg.persists_outcomes() {
                 // Record any txs that are still pending.
+                // Anything sent this pass is mined after the block we just scanned up to.
+                let sent_block = Some(state.last_scanned_block);
                 for p in pending {
-                    state.mark_in_flight(p.subscription_id, p.tx_hash);
+                    state.mark_in_flight(p.subscription_id, p.tx_hash, p.nonce, p.from, sent_block);
                 }
 
+                // Mined but not yet confirmed deep enough.
+                for p in confirming {
+                    state.mark_confirming(
+                        p.subscription_id,
+                        p.tx_hash,
+                        p.nonce,
+                        p.from,
+                        p.mined_block,
+                    );
+                }
+
+                state.record_gas_spend(now, stats.gas_spent_wei);
+
                 // Successes clear backoff.
                 for id in successes {
                     state.note_success(id);
                 }
 
                 // Failures set/update backoff.
-                if !failures.is_empty() {
-                    for f in failures {
-                        let prev = state
-                            .retries
-                            .get(&f.subscription_id)
-                            .map(|r| r.consecutive_failures)
-                            .unwrap_or(0);
-                        let consecutive = prev.saturating_add(1);
-                        let backoff_s =
-                            compute_backoff_seconds(&cfg, f.kind, consecutive, f.subscription_id);
-                        let next_retry_at = now.saturating_add(backoff_s);
-
-                        tracing::warn!(
-                            subscription_id = f.subscription_id,
-                            kind = ?f.kind,
-                            consecutive,
-                            backoff_s,
-                            next_retry_at,
-                            reason = f.reason.as_deref().unwrap_or(""),
-                            "collect failed; backing off"
-                        );
-
-                        state.note_failure(f.subscription_id, f.kind, next_retry_at, f.reason);
+                record_failures(cfg, &mut state, failures, now);
+
+                // `--prune-inactive`: ended subscriptions leave the working set for good.
+                if !pruned.is_empty() {
+                    for id in &pruned {
+                        state.forget_subscription(*id);
                     }
+                    tracing::info!(
+                        pruned = pruned.len(),
+                        remaining = state.subscription_ids.len(),
+                        "pruned ended subscriptions"
+                    );
                 }
 
                 state.save(&cfg.state_file)?;
+
+                if let Some(hook) = post_collect.as_ref() {
+                    for c in &collected {
+                        hook.run(c);
+                    }
+                }
+            }
+
+            match breaker.record_cycle(stats.checked, stats.failed, now_unix()) {
+                BreakerTransition::None => {}
+                BreakerTransition::Bad { bad_cycles } => tracing::warn!(
+                    bad_cycles,
+                    trip_cycles = cfg.breaker.trip_cycles,
+                    checked = stats.checked,
+                    failed = stats.failed,
+                    "cycle failure ratio at or above --breaker-threshold"
+                ),
+                BreakerTransition::Tripped { open_until } => tracing::error!(
+                    open_until,
+                    cooldown_s = cfg.breaker.cooldown.as_secs(),
+                    checked = stats.checked,
+                    failed = stats.failed,
+                    "circuit breaker tripped; pausing reconcile, scan and collect until the cooldown ends"
+                ),
+                BreakerTransition::Reset => {
+                    tracing::info!("circuit breaker reset; cycle healthy again")
+                }
             }
 
             tracing::info!(
                 ?stats,
+                merchant_amount_total = %stats.merchant_amount_total,
+                collector_fee_total = %stats.collector_fee_total,
+                expected_fee_total = %stats.expected_fee_total,
                 pending = pending_len,
                 successes = successes_len,
                 failures = failures_len,
@@ -489,12 +2003,703 @@ async fn main() -> Result<()> {
             );
         }
 
+        summary.breaker = breaker.state();
+
+        for e in &explained {
+            tracing::info!(
+                subscription_id = e.subscription_id,
+                decision = ?e.decision,
+                failure_kind = ?e.failure_kind,
+                consecutive_failures = e.consecutive_failures,
+                next_retry_at = e.next_retry_at,
+                reason = e.reason.as_deref().unwrap_or(""),
+                "explain"
+            );
+        }
+        summary.explain = explained;
+
         if cfg.once {
+            if json_mode {
+                let out = serde_json::to_string(&summary)
+                    .map_err(|e| eyre!("failed to serialize cycle summary: {e}"))?;
+                println!("{out}");
+            }
+            break;
+        }
+
+        if cfg.catch_up {
+            tracing::info!("catch-up complete; exiting");
             break;
         }
 
-        tokio::time::sleep(cfg.poll_interval).await;
+        tokio::time::sleep(cfg.poll_interval + splay(cfg.poll_splay)).await;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compute_backoff_seconds, connect_provider, control_paused, from_block_ignored, load_wallet,
+        next_retry, record_failures, relayer_config, retry_startup, rpc_client_options, run_keeper,
+        splay, Args, ClientOptions, CycleSummary,
+    };
+    use crate::collect_window::CollectWindow;
+    use crate::collector::{CollectOutcome, FailureRecord};
+    use crate::config::KeeperConfig;
+    use crate::deployments::DeploymentArtifact;
+    use crate::explain::{explain_checked, Decision, Explanation};
+    use crate::relayer::Relayer;
+    use crate::state::{FailureKind, KeeperState};
+    use clap::Parser;
+    use ethers::middleware::NonceManagerMiddleware;
+    use ethers::prelude::SignerMiddleware;
+    use ethers::providers::{Middleware, MockProvider, Provider};
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers::types::{Log, TransactionReceipt, H256, U256, U64};
+    use eyre::eyre;
+    use opensub_aa::types::EntryPointVersion;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A fresh, empty `<tmp>/opensub-keeper-<name>-<pid>` directory.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("opensub-keeper-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A `--once` config that scans up to the head, with its state file in `dir`.
+    fn once_config(dir: &Path) -> KeeperConfig {
+        let mut cfg = KeeperConfig::test_default();
+        cfg.once = true;
+        cfg.scan_confirmations = 0;
+        cfg.state_file = dir.join("state.json");
+        cfg
+    }
+
+    /// A provider that answers one scan only: head 10, then an empty getLogs for [1..=10]
+    /// (responses pop from the back). Any collect-side call hits an empty mock.
+    fn scan_only_provider() -> Provider<MockProvider> {
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        mock.push::<U64, _>(U64::from(10)).unwrap();
+        provider
+    }
+
+    /// One `run_keeper` call without audit log, webhook or relayer.
+    async fn run_keeper_plain<M: Middleware + 'static>(
+        cfg: &KeeperConfig,
+        client: Arc<M>,
+        state: KeeperState,
+    ) -> eyre::Result<()>
+    where
+        <M as Middleware>::Error: 'static,
+    {
+        run_keeper(cfg, client, state, None, None, None, false, false).await
+    }
+
+    #[test]
+    fn relayer_picks_the_op_builder_for_the_configured_entrypoint_version() {
+        let deployment = |version: Option<&str>| -> DeploymentArtifact {
+            serde_json::from_value(serde_json::json!({
+                "chainId": 84532,
+                "openSub": "0x0000000000000000000000000000000000000001",
+                "startBlock": 1,
+                "entryPointVersion": version,
+            }))
+            .unwrap()
+        };
+        let args = |extra: &[&str]| {
+            let mut argv = vec![
+                "opensub-keeper",
+                "--relayer-mode",
+                "--relayer-account",
+                "0x00000000000000000000000000000000000000aa",
+                "--relayer-bundler-url",
+                "http://127.0.0.1:4337",
+            ];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap()
+        };
+        let wallet = || LocalWallet::new(&mut rand::thread_rng());
+
+        // Nothing configured: v0.6 at its canonical address.
+        let rc = relayer_config(&args(&[]), &deployment(None))
+            .unwrap()
+            .unwrap();
+        assert_eq!(rc.entrypoint_version, EntryPointVersion::V06);
+        assert_eq!(rc.entrypoint, EntryPointVersion::V06.canonical_address());
+        assert!(Relayer::new(rc, wallet(), 84532).is_ok());
+
+        // The deployment's version is passed through, and selects the v0.7 builder, which is not
+        // available yet: startup fails instead of sending v0.6-shaped ops to a v0.7 EntryPoint.
+        let rc = relayer_config(&args(&[]), &deployment(Some("0.7")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(rc.entrypoint_version, EntryPointVersion::V07);
+        assert_eq!(rc.entrypoint, EntryPointVersion::V07.canonical_address());
+        let err = Relayer::new(rc, wallet(), 84532).err().unwrap();
+        assert!(err.to_string().contains("v0.7"), "{err}");
+
+        // The CLI flag wins over the deployment; an explicit address is kept as is.
+        let custom = "0x00000000000000000000000000000000000000ee";
+        let rc = relayer_config(
+            &args(&[
+                "--relayer-entrypoint-version",
+                "0.6",
+                "--relayer-entrypoint",
+                custom,
+            ]),
+            &deployment(Some("0.7")),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(rc.entrypoint_version, EntryPointVersion::V06);
+        assert_eq!(rc.entrypoint, custom.parse().unwrap());
+    }
+
+    #[test]
+    fn rpc_pool_flags_reach_the_provider_and_relayer_clients() {
+        let args = |extra: &[&str]| {
+            let mut argv = vec![
+                "opensub-keeper",
+                "--relayer-mode",
+                "--relayer-account",
+                "0x00000000000000000000000000000000000000aa",
+                "--relayer-bundler-url",
+                "http://127.0.0.1:4337",
+            ];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap()
+        };
+        let deployment: DeploymentArtifact = serde_json::from_value(serde_json::json!({
+            "chainId": 84532,
+            "openSub": "0x0000000000000000000000000000000000000001",
+            "startBlock": 1,
+        }))
+        .unwrap();
+
+        // Unset: reqwest's defaults.
+        assert_eq!(rpc_client_options(&args(&[])), ClientOptions::default());
+
+        let args = args(&["--rpc-pool-max-idle", "16", "--rpc-pool-idle-timeout", "30"]);
+        let options = rpc_client_options(&args);
+        assert_eq!(options.pool_max_idle_per_host, Some(16));
+        assert_eq!(options.pool_idle_timeout, Some(Duration::from_secs(30)));
+
+        let rc = relayer_config(&args, &deployment).unwrap().unwrap();
+        assert_eq!(rc.client_options, options);
+        Relayer::new(rc, LocalWallet::new(&mut rand::thread_rng()), 84532).unwrap();
+
+        let cfg = KeeperConfig {
+            rpc_client: options,
+            ..KeeperConfig::test_default()
+        };
+        connect_provider(&cfg).unwrap();
+    }
+
+    #[test]
+    fn splay_stays_within_bound() {
+        assert_eq!(splay(Duration::ZERO), Duration::ZERO);
+
+        let bound = Duration::from_secs(3);
+        for _ in 0..1_000 {
+            assert!(splay(bound) < bound);
+        }
+        // Sub-millisecond bounds round down to "disabled" rather than panicking.
+        assert_eq!(splay(Duration::from_micros(500)), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn retry_startup_succeeds_after_transient_failures() {
+        let calls = AtomicU32::new(0);
+        let out = retry_startup("mock", 3, Duration::ZERO, || {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n < 2 {
+                    Err(eyre!("connection refused"))
+                } else {
+                    Ok(84532u64)
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(out, 84532);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_startup_fails_after_exhausting_retries() {
+        let calls = AtomicU32::new(0);
+        let res: eyre::Result<u64> = retry_startup("mock", 2, Duration::ZERO, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(eyre!("connection refused")) }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn random_jitter_stays_within_window_and_cap() {
+        let mut cfg = KeeperConfig::test_default();
+        cfg.random_jitter = true;
+        cfg.backoff_base = Duration::from_secs(100);
+        cfg.backoff_max = Duration::from_secs(1_000);
+        cfg.jitter = Duration::from_secs(30);
+
+        for _ in 0..500 {
+            // First failure: base + jitter, well below the cap.
+            let b = compute_backoff_seconds(&cfg, FailureKind::Unknown, 1, 7);
+            assert!((100..130).contains(&b), "backoff {b} outside [100, 130)");
+
+            // Many failures: the cap must still hold with jitter applied.
+            let capped = compute_backoff_seconds(&cfg, FailureKind::Unknown, 40, 7);
+            assert_eq!(capped, 1_000);
+        }
+    }
+
+    #[test]
+    fn deterministic_jitter_is_reproducible() {
+        let mut cfg = KeeperConfig::test_default();
+        cfg.backoff_base = Duration::from_secs(100);
+        cfg.backoff_max = Duration::from_secs(1_000);
+        cfg.jitter = Duration::from_secs(30);
+
+        assert_eq!(
+            compute_backoff_seconds(&cfg, FailureKind::Unknown, 1, 37),
+            100 + 37 % 30
+        );
+    }
+
+    #[test]
+    fn each_failure_kind_resolves_its_own_base() {
+        let mut cfg = KeeperConfig::test_default();
+        cfg.jitter = Duration::ZERO;
+        cfg.backoff_base = Duration::from_secs(300);
+        cfg.plan_inactive_backoff = Duration::from_secs(1800);
+        cfg.rpc_error_backoff = Duration::from_secs(30);
+        cfg.insufficient_allowance_backoff = Some(Duration::from_secs(600));
+        cfg.insufficient_balance_backoff = Some(Duration::from_secs(7200));
+        cfg.simulation_revert_backoff = Some(Duration::from_secs(60));
+        cfg.mined_revert_backoff = Some(Duration::from_secs(900));
+        cfg.unknown_backoff = Some(Duration::from_secs(120));
+
+        let cases = [
+            (FailureKind::PlanInactive, 1800),
+            (FailureKind::RpcError, 30),
+            (FailureKind::InsufficientAllowance, 600),
+            (FailureKind::InsufficientBalance, 7200),
+            (FailureKind::SimulationRevert, 60),
+            (FailureKind::MinedRevert, 900),
+            (FailureKind::Unknown, 120),
+        ];
+        for (kind, expected) in cases {
+            assert_eq!(
+                compute_backoff_seconds(&cfg, kind, 1, 0),
+                expected,
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unset_kind_backoff_falls_back_to_backoff_base() {
+        let mut cfg = KeeperConfig::test_default();
+        cfg.jitter = Duration::ZERO;
+        cfg.backoff_base = Duration::from_secs(300);
+
+        for kind in [
+            FailureKind::InsufficientAllowance,
+            FailureKind::InsufficientBalance,
+            FailureKind::SimulationRevert,
+            FailureKind::MinedRevert,
+            FailureKind::Unknown,
+        ] {
+            assert_eq!(compute_backoff_seconds(&cfg, kind, 1, 0), 300, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn explained_next_retry_matches_recorded_backoff() {
+        let mut cfg = KeeperConfig::test_default();
+        cfg.explain = true;
+        cfg.dry_run = true;
+        let now = 1_700_000_000;
+
+        let mut state = KeeperState::load_or_init(
+            std::env::temp_dir().join("opensub-keeper-explain-missing.json"),
+            0,
+        )
+        .unwrap();
+        state.subscription_ids = vec![1, 2, 3, 4, 5];
+        state.mark_in_flight(2, H256::repeat_byte(0x02), None, None, None);
+        state.note_failure(3, FailureKind::RpcError, now + 60, None);
+        // Failed twice before, backoff already over: checked again this cycle.
+        state.note_failure(4, FailureKind::InsufficientBalance, now - 1, None);
+        state.note_failure(4, FailureKind::InsufficientBalance, now - 1, None);
+
+        let skipped: Vec<Explanation> = [2, 3]
+            .iter()
+            .map(|id| Explanation::skipped(&state, *id))
+            .collect();
+        assert_eq!(skipped[0].decision, Decision::SkipInFlight);
+        assert_eq!(skipped[1].decision, Decision::SkipBackoff);
+        assert_eq!(skipped[1].next_retry_at, Some(now + 60));
+
+        let outcome = CollectOutcome {
+            failures: vec![FailureRecord {
+                subscription_id: 4,
+                kind: FailureKind::InsufficientBalance,
+                reason: Some("balance 0 < price".to_string()),
+            }],
+            would_collect: vec![1],
+            ..Default::default()
+        };
+        let explained = explain_checked(&[1, 4, 5], &outcome, |f| {
+            let (consecutive, _, next_retry_at) =
+                next_retry(&cfg, &state, f.kind, f.subscription_id, now);
+            (consecutive, next_retry_at)
+        });
+        let decisions: Vec<Decision> = explained.iter().map(|e| e.decision).collect();
+        assert_eq!(
+            decisions,
+            vec![Decision::Collect, Decision::PrecheckFail, Decision::NotDue]
+        );
+        // Explaining persisted nothing.
+        assert_eq!(state.retries[&4].consecutive_failures, 2);
+
+        // The real path records exactly what was explained.
+        record_failures(&cfg, &mut state, outcome.failures.clone(), now);
+        let retry = &state.retries[&4];
+        assert_eq!(explained[1].consecutive_failures, Some(3));
+        assert_eq!(
+            explained[1].consecutive_failures,
+            Some(retry.consecutive_failures)
+        );
+        assert_eq!(explained[1].next_retry_at, Some(retry.next_retry_at));
+        assert_eq!(
+            retry.next_retry_at,
+            now + compute_backoff_seconds(&cfg, FailureKind::InsufficientBalance, 3, 4)
+        );
+    }
+
+    #[tokio::test]
+    async fn reconcile_only_applies_in_flight_receipts_without_scanning() {
+        let dir = test_dir("reconcile-only");
+
+        let mut cfg = KeeperConfig::test_default();
+        cfg.reconcile_only = true;
+        cfg.once = true;
+        cfg.state_file = dir.join("state.json");
+
+        let mut state = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        state.subscription_ids = vec![1, 2, 3];
+        state.last_scanned_block = 7;
+        state.mark_in_flight(1, H256::repeat_byte(0x01), None, None, None);
+        state.mark_in_flight(2, H256::repeat_byte(0x02), None, None, None);
+        // Past the pending TTL: dropped without a receipt call.
+        state.mark_in_flight(3, H256::repeat_byte(0x03), None, None, None);
+        state.in_flight.get_mut(&3).unwrap().sent_at = 1;
+        state.save(&cfg.state_file).unwrap();
+
+        // One receipt per in-flight tx (popped from the back: id 1 succeeded, id 2 reverted).
+        // Scanning or collecting would hit an empty mock and fail the run.
+        let (provider, mock) = Provider::mocked();
+        let receipt = |status: u64| TransactionReceipt {
+            status: Some(U64::from(status)),
+            block_number: Some(U64::from(5)),
+            ..Default::default()
+        };
+        mock.push(receipt(0)).unwrap();
+        mock.push(receipt(1)).unwrap();
+
+        let provider = Arc::new(provider);
+        run_keeper_plain(&cfg, provider.clone(), state)
+            .await
+            .unwrap();
+
+        let saved = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        assert!(saved.in_flight.is_empty());
+        assert!(saved.last_success.contains_key(&1));
+        assert!(!saved.retries.contains_key(&1));
+        assert_eq!(
+            saved.retries[&2].last_failure_kind,
+            FailureKind::MinedRevert
+        );
+        assert_eq!(saved.retries[&2].consecutive_failures, 1);
+        assert!(!saved.retries.contains_key(&3));
+        assert_eq!(saved.last_scanned_block, 7);
+        assert_eq!(saved.subscription_ids, vec![1, 2, 3]);
+        assert!(
+            provider.get_block_number().await.is_err(),
+            "all responses consumed"
+        );
+    }
+
+    #[test]
+    fn keystore_decrypts_to_the_signer_address() {
+        let dir = test_dir("keystore");
+
+        let key = [0x42u8; 32];
+        let expected = LocalWallet::from_bytes(&key).unwrap().address();
+        LocalWallet::encrypt_keystore(
+            &dir,
+            &mut rand::thread_rng(),
+            key,
+            "correct horse",
+            Some("keeper.json"),
+        )
+        .unwrap();
+
+        let mut cfg = KeeperConfig::test_default();
+        cfg.private_key_env = "OPENSUB_KEEPER_TEST_KEY_THAT_IS_NEVER_SET".to_string();
+        cfg.keystore = Some(dir.join("keeper.json"));
+        cfg.keystore_password_env = "OPENSUB_KEEPER_TEST_KEYSTORE_PASSWORD".to_string();
+        std::env::set_var(&cfg.keystore_password_env, "correct horse");
+
+        let wallet = load_wallet(&cfg).unwrap().unwrap();
+        assert_eq!(wallet.address(), expected);
+        assert_eq!(wallet.chain_id(), cfg.chain_id);
+
+        std::env::set_var(&cfg.keystore_password_env, "wrong");
+        let err = load_wallet(&cfg).unwrap_err();
+        assert!(
+            err.to_string().contains("failed to decrypt keystore"),
+            "{err}"
+        );
+
+        // Either a keystore or a raw key env var, not both.
+        assert!(Args::try_parse_from([
+            "opensub-keeper",
+            "--keystore",
+            "keeper.json",
+            "--private-key-env",
+            "OTHER_KEY",
+        ])
+        .is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn from_block_behind_an_existing_cursor_is_reported() {
+        let dir = test_dir("from-block");
+        let state_file = dir.join("state.json");
+
+        let mut cfg = KeeperConfig::test_default();
+        cfg.from_block = Some(100);
+        cfg.start_block = 100;
+
+        // A fresh state file starts at --from-block: it takes effect.
+        let fresh = KeeperState::load_or_init(&state_file, cfg.start_block).unwrap();
+        assert_eq!(fresh.last_scanned_block, 99);
+        assert_eq!(from_block_ignored(&cfg, &fresh), None);
+
+        // An existing one already scanned past it keeps its cursor...
+        let mut scanned = fresh;
+        scanned.last_scanned_block = 500;
+        scanned.save(&state_file).unwrap();
+        let existing = KeeperState::load_or_init(&state_file, cfg.start_block).unwrap();
+        assert_eq!(existing.last_scanned_block, 500);
+        assert_eq!(from_block_ignored(&cfg, &existing), Some(500));
+
+        // ...unless --reset-scan rewinds it, or --from-block is ahead of the cursor anyway.
+        cfg.reset_scan = true;
+        assert_eq!(from_block_ignored(&cfg, &existing), None);
+        cfg.reset_scan = false;
+        cfg.from_block = Some(600);
+        cfg.start_block = 600;
+        assert_eq!(from_block_ignored(&cfg, &existing), None);
+        cfg.from_block = None;
+        assert_eq!(from_block_ignored(&cfg, &existing), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pin_block_needs_once_or_dry_run() {
+        let parse = |extra: &[&str]| {
+            Args::try_parse_from(
+                ["opensub-keeper", "--pin-block", "1234"]
+                    .iter()
+                    .chain(extra),
+            )
+        };
+        // A loop would re-read isDue at the same block and re-send collects already sent.
+        let err = parse(&[]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(parse(&["--once"]).is_ok());
+        assert!(parse(&["--dry-run"]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn monitor_only_runs_a_cycle_without_a_key() {
+        let dir = test_dir("monitor");
+
+        let mut cfg = once_config(&dir);
+        cfg.private_key_env = "OPENSUB_KEEPER_TEST_KEY_THAT_IS_NEVER_SET".to_string();
+        cfg.monitor_only = true;
+        cfg.dry_run = true;
+        assert!(!cfg.persists_outcomes());
+        assert!(load_wallet(&cfg).unwrap().is_none());
+
+        // Plain provider, no signer.
+        let state = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        run_keeper_plain(&cfg, Arc::new(scan_only_provider()), state)
+            .await
+            .unwrap();
+
+        let saved = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        assert_eq!(saved.last_scanned_block, 10);
+    }
+
+    #[tokio::test]
+    async fn signer_clients_run_with_and_without_the_nonce_manager() {
+        let dir = test_dir("nonce");
+
+        let mut cfg = once_config(&dir);
+        cfg.dry_run = true;
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+
+        let mocked = || SignerMiddleware::new(scan_only_provider(), wallet.clone());
+        let state = || KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+
+        // Default: local nonce allocation.
+        let client = NonceManagerMiddleware::new(mocked(), wallet.address());
+        run_keeper_plain(&cfg, Arc::new(client), state())
+            .await
+            .unwrap();
+
+        // `--no-nonce-manager`: the bare signer.
+        std::fs::remove_file(&cfg.state_file).unwrap();
+        run_keeper_plain(&cfg, Arc::new(mocked()), state())
+            .await
+            .unwrap();
+        assert_eq!(state().last_scanned_block, 10);
+
+        let args = Args::try_parse_from(["opensub-keeper", "--no-nonce-manager"]).unwrap();
+        assert!(args.no_nonce_manager);
+        assert!(
+            Args::try_parse_from(["opensub-keeper", "--no-nonce-manager", "--monitor-only"])
+                .is_err()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn paused_control_file_skips_collect_but_keeps_scanning() {
+        let dir = test_dir("control");
+        let control = dir.join("control");
+
+        assert!(!control_paused(None));
+        assert!(!control_paused(Some(&control)));
+        std::fs::write(&control, "resume\n").unwrap();
+        assert!(!control_paused(Some(&control)));
+        std::fs::write(&control, "pause\n").unwrap();
+        assert!(control_paused(Some(&control)));
+
+        let mut cfg = once_config(&dir);
+        cfg.control_file = Some(control);
+
+        let mut state = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        state.subscription_ids = vec![1, 2];
+
+        // Only the scan is mocked; any collect-side call (prechecks, simulation, send) would fail
+        // the cycle.
+        run_keeper_plain(&cfg, Arc::new(scan_only_provider()), state)
+            .await
+            .unwrap();
+
+        let saved = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        assert_eq!(saved.last_scanned_block, 10);
+        assert!(saved.in_flight.is_empty());
+        assert!(saved.retries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn outside_collect_window_defers_collect_but_keeps_scanning() {
+        let dir = test_dir("window");
+
+        // A one-hour window starting two hours from now.
+        let now = super::now_unix();
+        let hour = (now / 3_600 + 2) % 24;
+        let window = format!("{hour:02}:00-{:02}:00", (hour + 1) % 24);
+        let mut cfg = once_config(&dir);
+        cfg.collect_window = Some(CollectWindow::parse(&window, "UTC").unwrap());
+        assert!(!cfg.collect_window.unwrap().contains(now));
+
+        let mut state = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        state.subscription_ids = vec![1, 2];
+        state.paid_through.insert(1, now - 60);
+
+        // Only the scan is mocked; any collect-side call would fail the cycle.
+        run_keeper_plain(&cfg, Arc::new(scan_only_provider()), state)
+            .await
+            .unwrap();
+
+        let saved = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        assert_eq!(saved.last_scanned_block, 10);
+        assert!(saved.in_flight.is_empty());
+        assert!(saved.retries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn exhausted_daily_gas_budget_halts_sends() {
+        let dir = test_dir("gas-budget");
+
+        let mut cfg = once_config(&dir);
+        cfg.daily_gas_budget_wei = Some(U256::from(1_000));
+
+        let now = super::now_unix();
+        let mut state = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        state.subscription_ids = vec![1];
+        // Outside the window: pruned, does not count.
+        state.record_gas_spend(now - 2 * 24 * 60 * 60, U256::from(5_000));
+        state.record_gas_spend(now - 60, U256::from(600));
+        state.record_gas_spend(now - 30, U256::from(400));
+
+        // Only the scan is mocked; a collect attempt would hit an empty mock and record a failure.
+        run_keeper_plain(&cfg, Arc::new(scan_only_provider()), state)
+            .await
+            .unwrap();
+
+        let mut saved = KeeperState::load_or_init(&cfg.state_file, cfg.start_block).unwrap();
+        assert_eq!(saved.last_scanned_block, 10);
+        assert!(saved.retries.is_empty());
+        assert_eq!(saved.rolling_gas_spend(now), U256::from(1_000));
+    }
+
+    #[test]
+    fn cycle_summary_json_shape() {
+        let mut summary = CycleSummary {
+            discovered: 2,
+            last_scanned_block: 1234,
+            succeeded: vec![1, 3],
+            failed: vec![4],
+            pending: vec![5],
+            ..Default::default()
+        };
+        summary.stats.checked = 4;
+        summary.stats.sent = 3;
+
+        let v = serde_json::to_value(&summary).unwrap();
+        assert_eq!(v["discovered"], 2);
+        assert_eq!(v["lastScannedBlock"], 1234);
+        assert_eq!(v["succeeded"], serde_json::json!([1, 3]));
+        assert_eq!(v["failed"], serde_json::json!([4]));
+        assert_eq!(v["pending"], serde_json::json!([5]));
+        assert_eq!(v["stats"]["checked"], 4);
+        assert_eq!(v["stats"]["sent"], 3);
+        assert!(v["stats"].get("collectorFeeTotal").is_some());
+        assert_eq!(v["scan"]["getLogsCalls"], 0);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::opensub::{OpenSub, OPENSUB_ABI};
+use ethers::abi::{Detokenize, Function, Token};
+use ethers::contract::abigen;
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockId, Bytes, U256};
+use eyre::{eyre, Result};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Multicall3 (same address on most chains: 0xcA11bde05977b3631167028862bE2a173976CA11).
+abigen!(
+    Multicall3,
+    r#"[
+        struct Call3 { address target; bool allowFailure; bytes callData; }
+        struct Result { bool success; bytes returnData; }
+        function aggregate3(Call3[] calls) payable returns (Result[] returnData)
+    ]"#
+);
+
+/// Calls per `aggregate3`. Keeps each eth_call well below provider response and gas caps.
+const CALLS_PER_BATCH: usize = 200;
+
+/// `subscriptions(id)`: (planId, subscriber, status, startTime, paidThrough, lastChargedAt).
+pub type SubscriptionRow = (U256, Address, u8, U256, U256, U256);
+
+/// `plans(id)`: (merchant, token, price, interval, collectorFeeBps, active, createdAt).
+pub type PlanRow = (Address, Address, U256, U256, U256, bool, U256);
+
+/// `--multicall-address`: `subscriptions()` / `plans()` rows read up front in a few
+/// `aggregate3` calls. Ids missing here (a failed sub-call, or no pre-pass) are read per call.
+#[derive(Debug, Clone, Default)]
+pub struct Prefetched {
+    pub subscriptions: BTreeMap<u64, SubscriptionRow>,
+    pub plans: BTreeMap<U256, PlanRow>,
+}
+
+/// Read `subscriptions(id)` for every id, then `plans(planId)` once per distinct plan of the
+/// Active ones, batched through Multicall3.
+pub async fn prefetch<M: Middleware + 'static>(
+    multicall: &Multicall3<M>,
+    opensub: &OpenSub<M>,
+    ids: &[u64],
+    block: Option<BlockId>,
+) -> Result<Prefetched> {
+    let subscriptions_fn = function("subscriptions")?;
+    let plans_fn = function("plans")?;
+
+    let calls = ids
+        .iter()
+        .map(|id| encode_call(subscriptions_fn, U256::from(*id)))
+        .collect::<Result<Vec<_>>>()?;
+    let results = aggregate(multicall, opensub.address(), calls, block).await?;
+    let subscriptions = decode_subscriptions(ids, results)?;
+
+    let plan_ids: Vec<U256> = subscriptions
+        .values()
+        .filter(|s| s.2 == crate::opensub::STATUS_ACTIVE)
+        .map(|s| s.0)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let calls = plan_ids
+        .iter()
+        .map(|plan_id| encode_call(plans_fn, *plan_id))
+        .collect::<Result<Vec<_>>>()?;
+    let results = aggregate(multicall, opensub.address(), calls, block).await?;
+    let plans = decode_plans(&plan_ids, results)?;
+
+    Ok(Prefetched {
+        subscriptions,
+        plans,
+    })
+}
+
+/// Pair `aggregate3` results with the ids they were requested for. Failed sub-calls are left out
+/// so the caller reads them again individually.
+pub fn decode_subscriptions(
+    ids: &[u64],
+    results: Vec<(bool, Bytes)>,
+) -> Result<BTreeMap<u64, SubscriptionRow>> {
+    decode_rows(function("subscriptions")?, ids, results)
+}
+
+/// Like [`decode_subscriptions`], for `plans(planId)`.
+pub fn decode_plans(
+    plan_ids: &[U256],
+    results: Vec<(bool, Bytes)>,
+) -> Result<BTreeMap<U256, PlanRow>> {
+    decode_rows(function("plans")?, plan_ids, results)
+}
+
+fn decode_rows<K: Ord + Copy + std::fmt::Debug, R: Detokenize>(
+    function: &Function,
+    keys: &[K],
+    results: Vec<(bool, Bytes)>,
+) -> Result<BTreeMap<K, R>> {
+    if results.len() != keys.len() {
+        return Err(eyre!(
+            "aggregate3 returned {} results for {} {}() calls",
+            results.len(),
+            keys.len(),
+            function.name
+        ));
+    }
+    let mut rows = BTreeMap::new();
+    for (key, (success, data)) in keys.iter().zip(results) {
+        if !success {
+            tracing::debug!(key = ?key, "{}() failed inside aggregate3; reading it individually", function.name);
+            continue;
+        }
+        match function
+            .decode_output(&data)
+            .map_err(|e| e.to_string())
+            .and_then(|tokens| R::from_tokens(tokens).map_err(|e| e.to_string()))
+        {
+            Ok(row) => {
+                rows.insert(*key, row);
+            }
+            Err(err) => {
+                tracing::debug!(key = ?key, error = %err, "undecodable {}() result in aggregate3", function.name);
+            }
+        }
+    }
+    Ok(rows)
+}
+
+async fn aggregate<M: Middleware + 'static>(
+    multicall: &Multicall3<M>,
+    target: Address,
+    calls: Vec<Bytes>,
+    block: Option<BlockId>,
+) -> Result<Vec<(bool, Bytes)>> {
+    let mut results = Vec::with_capacity(calls.len());
+    for chunk in calls.chunks(CALLS_PER_BATCH) {
+        let batch = chunk
+            .iter()
+            .map(|data| Call3 {
+                target,
+                allow_failure: true,
+                call_data: data.clone(),
+            })
+            .collect::<Vec<_>>();
+        let mut call = multicall.aggregate_3(batch);
+        if let Some(b) = block {
+            call = call.block(b);
+        }
+        results.extend(
+            call.call()
+                .await
+                .map_err(|e| eyre!("aggregate3 failed: {e}"))?,
+        );
+    }
+    Ok(results)
+}
+
+fn function(name: &str) -> Result<&'static Function> {
+    OPENSUB_ABI
+        .function(name)
+        .map_err(|e| eyre!("OpenSub ABI has no {name}(): {e}"))
+}
+
+fn encode_call(function: &Function, arg: U256) -> Result<Bytes> {
+    function
+        .encode_input(&[Token::Uint(arg)])
+        .map(Bytes::from)
+        .map_err(|e| eyre!("failed to encode {}(): {e}", function.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_plans, decode_subscriptions, Aggregate3Return};
+    use ethers::abi::{encode, AbiDecode, Token};
+    use ethers::types::{Address, Bytes, U256};
+
+    #[test]
+    fn decodes_aggregated_subscriptions_and_plans() {
+        let subscriber = Address::repeat_byte(0x55);
+        let subscription = |plan_id: u64, status: u64, paid_through: u64| {
+            Token::Tuple(vec![
+                Token::Bool(true),
+                Token::Bytes(encode(&[
+                    Token::Uint(U256::from(plan_id)),
+                    Token::Address(subscriber),
+                    Token::Uint(U256::from(status)),
+                    Token::Uint(U256::from(1_000u64)),
+                    Token::Uint(U256::from(paid_through)),
+                    Token::Uint(U256::from(1_000u64)),
+                ])),
+            ])
+        };
+        // Raw `aggregate3` return data: ids 1 and 3 succeeded, 2 reverted inside the batch.
+        let raw = encode(&[Token::Array(vec![
+            subscription(7, 1, 2_000),
+            Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+            subscription(7, 3, 1_500),
+        ])]);
+        let results = aggregate3_return(&raw);
+
+        let subscriptions = decode_subscriptions(&[1, 2, 3], results).unwrap();
+        assert_eq!(subscriptions.len(), 2);
+        assert_eq!(
+            subscriptions[&1],
+            (
+                U256::from(7),
+                subscriber,
+                1,
+                U256::from(1_000),
+                U256::from(2_000),
+                U256::from(1_000)
+            )
+        );
+        assert_eq!(subscriptions[&3].2, 3);
+        assert!(!subscriptions.contains_key(&2));
+
+        let token = Address::repeat_byte(0x70);
+        let raw = encode(&[Token::Array(vec![Token::Tuple(vec![
+            Token::Bool(true),
+            Token::Bytes(encode(&[
+                Token::Address(Address::repeat_byte(0x4d)),
+                Token::Address(token),
+                Token::Uint(U256::from(10_000_000u64)),
+                Token::Uint(U256::from(2_592_000u64)),
+                Token::Uint(U256::from(100u64)),
+                Token::Bool(true),
+                Token::Uint(U256::from(900u64)),
+            ])),
+        ])])]);
+        let plans = decode_plans(&[U256::from(7)], aggregate3_return(&raw)).unwrap();
+        let plan = plans[&U256::from(7)];
+        assert_eq!(plan.1, token);
+        assert_eq!(plan.2, U256::from(10_000_000u64));
+        assert!(plan.5);
+
+        // A result count that doesn't match the calls is an error, not a silent partial read.
+        assert!(decode_plans(&[U256::from(7), U256::from(8)], aggregate3_return(&raw)).is_err());
+    }
+
+    /// Decode raw `aggregate3` output the way the generated binding does.
+    fn aggregate3_return(raw: &[u8]) -> Vec<(bool, Bytes)> {
+        <Aggregate3Return as AbiDecode>::decode(raw)
+            .unwrap()
+            .return_data
+    }
+}
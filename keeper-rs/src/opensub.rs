@@ -1,16 +1,172 @@
-use ethers::contract::abigen;
+use ethers::contract::{abigen, FunctionCall};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use opensub_aa::encoding::bytecode_has_selector;
+use std::sync::Arc;
 
 // Minimal ABI for the keeper bot.
 //
 // Note: we intentionally declare the `uint40` / `uint16` return values as `uint256` in the binding
 // to keep decoding simple and avoid edge cases. ABI encoding is still 32-byte words, so decoding as
 // uint256 is safe.
+//
+// Events and custom errors keep their exact Solidity types, since those feed into topic0 /
+// selectors.
 abigen!(
     OpenSub,
     r#"[
         function isDue(uint256 subscriptionId) view returns (bool)
         function collect(uint256 subscriptionId) returns (uint256 merchantAmount, uint256 collectorFee)
+        function collect(uint256 subscriptionId, address feeRecipient) returns (uint256 merchantAmount, uint256 collectorFee)
         function subscriptions(uint256) view returns (uint256 planId, address subscriber, uint8 status, uint256 startTime, uint256 paidThrough, uint256 lastChargedAt)
         function plans(uint256) view returns (address merchant, address token, uint256 price, uint256 interval, uint256 collectorFeeBps, bool active, uint256 createdAt)
+        error InvalidParameters()
+        error InvalidPlan(uint256 planId)
+        error PlanInactive(uint256 planId)
+        error Unauthorized()
+        error AlreadySubscribed(uint256 planId, address subscriber)
+        error InvalidSubscription(uint256 subscriptionId)
+        error NotDue(uint40 paidThrough)
+        error SubscriptionNotActive(uint256 subscriptionId)
+        event Charged(uint256 indexed subscriptionId, uint256 indexed planId, address indexed subscriber, address token, uint256 amount, uint256 collectorFee, address collector, uint40 chargedAt, uint40 paidThrough)
     ]"#
 );
+
+/// OpenSub `SubscriptionStatus` values the keeper cares about (`subscriptions()` field 2).
+pub const STATUS_ACTIVE: u8 = 1;
+/// `cancel(id, atPeriodEnd = true)`: renewal is off and access ends at `paidThrough`.
+pub const STATUS_NON_RENEWING: u8 = 2;
+pub const STATUS_CANCELLED: u8 = 3;
+
+/// Whether the subscriber scheduled a cancel at period end. Such a subscription is never due
+/// again (`collect()` reverts with `SubscriptionNotActive`), though it keeps access until
+/// `paidThrough` and `unscheduleCancel()` can still make it Active before then.
+pub fn cancel_scheduled(status: u8) -> bool {
+    status == STATUS_NON_RENEWING
+}
+
+/// `collect(id)`, or the `collect(id, feeRecipient)` overload when a fee recipient is configured.
+///
+/// Callers must only pass `Some` after checking the deployed contract has the overload
+/// (`has_collect_fee_recipient`); the base OpenSub only implements `collect(uint256)`.
+pub fn collect_call<M: Middleware>(
+    opensub: &OpenSub<M>,
+    subscription_id: U256,
+    fee_recipient: Option<Address>,
+) -> FunctionCall<Arc<M>, M, (U256, U256)> {
+    match fee_recipient {
+        Some(recipient) => opensub.collect_with_fee_recipient(subscription_id, recipient),
+        None => opensub.collect(subscription_id),
+    }
+}
+
+/// Whether the deployed runtime code dispatches `collect(uint256,address)`.
+pub fn has_collect_fee_recipient(code: &[u8]) -> bool {
+    let selector = ethers::utils::id("collect(uint256,address)");
+    // The PUSH4 scan lives in opensub-aa (`encoding::bytecode_has_selector`), shared with the
+    // CLI's `cancelAfter` probe.
+    bytecode_has_selector(code, selector)
+}
+
+/// Human-readable form of OpenSub revert data (custom errors or `Error(string)`), e.g.
+/// `NotDue(paidThrough=1700000000)`. `None` if the data doesn't match the ABI.
+pub fn decode_revert(data: &[u8]) -> Option<String> {
+    use ethers::abi::AbiDecode;
+
+    let reason = match OpenSubErrors::decode(data).ok()? {
+        OpenSubErrors::InvalidParameters(_) => "InvalidParameters()".to_string(),
+        OpenSubErrors::InvalidPlan(e) => format!("InvalidPlan(planId={})", e.plan_id),
+        OpenSubErrors::PlanInactive(e) => format!("PlanInactive(planId={})", e.plan_id),
+        OpenSubErrors::Unauthorized(_) => "Unauthorized()".to_string(),
+        OpenSubErrors::AlreadySubscribed(e) => format!(
+            "AlreadySubscribed(planId={}, subscriber={:#x})",
+            e.plan_id, e.subscriber
+        ),
+        OpenSubErrors::InvalidSubscription(e) => {
+            format!("InvalidSubscription(subscriptionId={})", e.subscription_id)
+        }
+        OpenSubErrors::NotDue(e) => format!("NotDue(paidThrough={})", e.paid_through),
+        OpenSubErrors::SubscriptionNotActive(e) => {
+            format!(
+                "SubscriptionNotActive(subscriptionId={})",
+                e.subscription_id
+            )
+        }
+        OpenSubErrors::RevertString(s) => s,
+    };
+    Some(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_call, decode_revert, has_collect_fee_recipient, NotDue, OpenSub};
+    use ethers::abi::{encode, AbiEncode, Token};
+    use ethers::providers::Provider;
+    use ethers::types::{Address, U256};
+    use ethers::utils::{id, keccak256};
+    use std::sync::Arc;
+
+    #[test]
+    fn fee_recipient_selects_collect_overload() {
+        let (provider, _mock) = Provider::mocked();
+        let opensub = OpenSub::new(Address::repeat_byte(0x01), Arc::new(provider));
+        let recipient = Address::repeat_byte(0xfe);
+
+        let plain = collect_call(&opensub, U256::from(7), None)
+            .calldata()
+            .unwrap();
+        assert_eq!(&plain[..4], &id("collect(uint256)"));
+        assert_eq!(plain.len(), 4 + 32);
+
+        let routed = collect_call(&opensub, U256::from(7), Some(recipient))
+            .calldata()
+            .unwrap();
+        assert_eq!(&routed[..4], &id("collect(uint256,address)"));
+        assert_eq!(
+            &routed[4..],
+            &encode(&[Token::Uint(U256::from(7)), Token::Address(recipient)])[..]
+        );
+    }
+
+    #[test]
+    fn detects_collect_overload_in_bytecode() {
+        let overload = id("collect(uint256,address)");
+        // DUP1 PUSH4 <selector> EQ
+        let mut code = vec![0x80, 0x63];
+        code.extend_from_slice(&overload);
+        code.push(0x14);
+        assert!(has_collect_fee_recipient(&code));
+
+        // Only the base collect(uint256) dispatcher.
+        let mut base = vec![0x80, 0x63];
+        base.extend_from_slice(&id("collect(uint256)"));
+        base.push(0x14);
+        assert!(!has_collect_fee_recipient(&base));
+    }
+
+    #[test]
+    fn decodes_not_due_custom_error() {
+        // Raw revert data as returned by eth_call: selector ++ abi.encode(paidThrough).
+        let mut data = keccak256("NotDue(uint40)")[..4].to_vec();
+        data.extend(encode(&[Token::Uint(U256::from(1_700_000_000u64))]));
+
+        assert_eq!(
+            decode_revert(&data).as_deref(),
+            Some("NotDue(paidThrough=1700000000)")
+        );
+        // Same bytes as the generated binding encodes.
+        assert_eq!(
+            data,
+            NotDue {
+                paid_through: 1_700_000_000
+            }
+            .encode()
+        );
+    }
+
+    #[test]
+    fn unknown_revert_data_is_not_decoded() {
+        assert_eq!(decode_revert(&[0xde, 0xad, 0xbe, 0xef]), None);
+        assert_eq!(decode_revert(&[]), None);
+    }
+}
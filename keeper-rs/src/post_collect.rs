@@ -0,0 +1,179 @@
+use crate::collector::Collected;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How long a hook may run before it is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a running hook is checked for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What an unknown placeholder value becomes: an empty, shell-quoted argument.
+const EMPTY_ARG: &str = "''";
+
+/// `--post-collect-cmd`: a shell command run after each recorded success.
+///
+/// Each run gets its own thread, so a slow or hanging hook never holds up the keeper loop; it is
+/// killed after `HOOK_TIMEOUT`. Output is discarded (stdout may carry `--json` output) and
+/// failures are only logged. Dropping the hook waits for commands still running, so a `--once`
+/// run doesn't exit mid-hook.
+#[derive(Debug)]
+pub struct PostCollectHook {
+    template: String,
+    running: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl PostCollectHook {
+    pub fn new(template: String) -> Self {
+        Self {
+            template,
+            running: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Spawn the hook for `collected` and return immediately.
+    pub fn run(&self, collected: &Collected) {
+        let command = render(&self.template, collected);
+        let subscription_id = collected.subscription_id;
+        let spawned = std::thread::Builder::new()
+            .name("post-collect-hook".to_string())
+            .spawn(move || run_to_completion(subscription_id, &command));
+        match spawned {
+            Ok(handle) => {
+                let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+                running.retain(|h| !h.is_finished());
+                running.push(handle);
+            }
+            Err(err) => {
+                tracing::warn!(subscription_id, error = %err, "failed to start post-collect hook thread");
+            }
+        }
+    }
+}
+
+impl Drop for PostCollectHook {
+    fn drop(&mut self) {
+        let running = std::mem::take(self.running.get_mut().unwrap_or_else(|e| e.into_inner()));
+        for handle in running {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Substitute `{id}`, `{tx}`, `{planId}` and `{fee}` in `template`. Values that are not known
+/// (e.g. plan and fee for an in-flight tx that finalized later) become `''`, an explicit empty
+/// argument, so the positional arguments after them don't shift.
+///
+/// Every known value is a decimal number or 0x-hex, so substituting into a shell command is safe.
+pub fn render(template: &str, collected: &Collected) -> String {
+    let arg = |value: Option<String>| value.unwrap_or_else(|| EMPTY_ARG.to_string());
+    template
+        .replace("{id}", &collected.subscription_id.to_string())
+        .replace("{tx}", &arg(collected.tx_hash.map(|h| format!("{h:?}"))))
+        .replace("{planId}", &arg(collected.plan_id.map(|p| p.to_string())))
+        .replace(
+            "{fee}",
+            &arg(collected.collector_fee.map(|f| f.to_string())),
+        )
+}
+
+fn run_to_completion(subscription_id: u64, command: &str) {
+    let mut child = match Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(err) => {
+            tracing::warn!(subscription_id, error = %err, "failed to spawn post-collect hook");
+            return;
+        }
+    };
+
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => {
+                tracing::debug!(subscription_id, "post-collect hook finished");
+                return;
+            }
+            Ok(Some(status)) => {
+                tracing::warn!(subscription_id, %status, "post-collect hook exited non-zero");
+                return;
+            }
+            Ok(None) if started.elapsed() >= HOOK_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                tracing::warn!(
+                    subscription_id,
+                    timeout_s = HOOK_TIMEOUT.as_secs(),
+                    "post-collect hook timed out; killed"
+                );
+                return;
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(err) => {
+                tracing::warn!(subscription_id, error = %err, "failed to wait for post-collect hook");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::collector::Collected;
+    use ethers::types::{H256, U256};
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let collected = Collected {
+            subscription_id: 42,
+            plan_id: Some(U256::from(3)),
+            tx_hash: Some(H256::repeat_byte(0xab)),
+            collector_fee: Some(U256::from(10_000)),
+        };
+        assert_eq!(
+            render(
+                "./record.sh --sub {id} --plan {planId} --tx {tx} --fee {fee} # {id}",
+                &collected
+            ),
+            format!(
+                "./record.sh --sub 42 --plan 3 --tx 0x{} --fee 10000 # 42",
+                "ab".repeat(32)
+            )
+        );
+
+        // Unknown values become explicit empty arguments, so later ones keep their position;
+        // unknown placeholders are left alone.
+        let finalized_later = Collected {
+            subscription_id: 7,
+            plan_id: None,
+            tx_hash: Some(H256::repeat_byte(0xab)),
+            collector_fee: None,
+        };
+        let command = render(
+            "printf '<%s>' {id} {planId} {fee} {tx} {other}",
+            &finalized_later,
+        );
+        assert_eq!(
+            command,
+            format!("printf '<%s>' 7 '' '' 0x{} {{other}}", "ab".repeat(32))
+        );
+        let out = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            format!("<7><><><0x{}><{{other}}>", "ab".repeat(32))
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use ethers::prelude::LocalWallet;
+use ethers::providers::Middleware;
+use ethers::signers::Signer;
+use ethers::types::{Address, Bytes, H256, U256};
+use eyre::{eyre, Result};
+use opensub_aa::bundler::BundlerClient;
+use opensub_aa::encoding::{self, parse_h256, parse_u256_quantity};
+use opensub_aa::http::ClientOptions;
+use opensub_aa::paymaster::PaymasterClient;
+use opensub_aa::types::{EntryPointVersion, UserOperation};
+use opensub_aa::userop;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// `--relayer-mode` settings.
+#[derive(Debug, Clone)]
+pub struct RelayerConfig {
+    /// Deployed SimpleAccount owned by the keeper key; collect() is sent from here.
+    pub account: Address,
+    pub entrypoint: Address,
+    /// Decides how ops are laid out and hashed; must match `entrypoint`.
+    pub entrypoint_version: EntryPointVersion,
+    pub bundler_url: String,
+    /// ERC-7677 paymaster URL + policy id; without them the account pays for its own gas.
+    pub paymaster: Option<(String, String)>,
+    /// `--rpc-pool-*` for the bundler and paymaster clients.
+    pub client_options: ClientOptions,
+}
+
+/// Sends collect() as a single-call userOp from the keeper's smart account, so the keeper EOA
+/// needs no ETH.
+///
+/// Every op uses the subscription id as its 2D nonce key: concurrent collects for different
+/// subscriptions don't queue behind each other, and two ops for the same subscription can never
+/// both land.
+pub struct Relayer {
+    config: RelayerConfig,
+    wallet: LocalWallet,
+    chain_id: u64,
+    bundler: BundlerClient,
+    paymaster: Option<(PaymasterClient, String)>,
+}
+
+/// Outcome of a relayed collect, from `eth_getUserOperationReceipt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayedCollect {
+    /// Whether the inner `execute` call succeeded (the bundle tx itself can succeed regardless).
+    pub success: bool,
+    /// Bundle transaction that included the op.
+    pub tx_hash: Option<H256>,
+    pub block_number: Option<u64>,
+    /// Gas cost charged to the account (or the paymaster), in wei.
+    pub actual_gas_cost: U256,
+}
+
+impl RelayedCollect {
+    fn from_receipt(receipt: &Value) -> Result<Self> {
+        let success = receipt
+            .get("success")
+            .and_then(Value::as_bool)
+            .ok_or_else(|| eyre!("userOp receipt has no `success` field"))?;
+        let inner = receipt.get("receipt");
+        let tx_hash = inner
+            .and_then(|r| r.get("transactionHash"))
+            .and_then(Value::as_str)
+            .map(parse_h256)
+            .transpose()
+            .map_err(|e| eyre!("invalid transactionHash in userOp receipt: {e:#}"))?;
+        let block_number = inner
+            .and_then(|r| r.get("blockNumber"))
+            .and_then(Value::as_str)
+            .map(parse_u256_quantity)
+            .transpose()
+            .map_err(|e| eyre!("invalid blockNumber in userOp receipt: {e:#}"))?
+            .map(|b| b.low_u64());
+        let actual_gas_cost = receipt
+            .get("actualGasCost")
+            .and_then(Value::as_str)
+            .map(parse_u256_quantity)
+            .transpose()
+            .map_err(|e| eyre!("invalid actualGasCost in userOp receipt: {e:#}"))?
+            .unwrap_or_default();
+        Ok(Self {
+            success,
+            tx_hash,
+            block_number,
+            actual_gas_cost,
+        })
+    }
+}
+
+/// `account.execute(opensub, 0, collectCalldata)`: the callData of a relayed collect.
+pub fn execute_call_data(opensub: Address, collect_calldata: Bytes) -> Result<Bytes> {
+    userop::encode_execute(opensub, U256::zero(), collect_calldata)
+        .map_err(|e| eyre!("failed to encode execute calldata: {e:#}"))
+}
+
+impl Relayer {
+    /// Fails if the configured EntryPoint version has no op builder.
+    pub fn new(config: RelayerConfig, wallet: LocalWallet, chain_id: u64) -> Result<Self> {
+        userop::ensure_supported(config.entrypoint_version).map_err(|e| eyre!("{e:#}"))?;
+        let bundler = BundlerClient::new(config.bundler_url.clone())
+            .with_client_options(&config.client_options)
+            .map_err(|e| eyre!("{e:#}"))?;
+        let paymaster = config
+            .paymaster
+            .clone()
+            .map(|(url, policy_id)| {
+                PaymasterClient::new(url)
+                    .with_client_options(&config.client_options)
+                    .map(|client| (client, policy_id))
+            })
+            .transpose()
+            .map_err(|e| eyre!("{e:#}"))?;
+        Ok(Self {
+            config,
+            wallet,
+            chain_id,
+            bundler,
+            paymaster,
+        })
+    }
+
+    /// The smart account collect() is sent from (OpenSub's `msg.sender`).
+    pub fn account(&self) -> Address {
+        self.config.account
+    }
+
+    pub fn sponsored(&self) -> bool {
+        self.paymaster.is_some()
+    }
+
+    /// Build, sponsor (if configured), sign and submit the userOp. Returns the userOpHash.
+    ///
+    /// `call_gas_limit` replaces the bundler's estimate for the inner call (`--gas-limit` /
+    /// `--gas-limits-file`).
+    pub async fn submit_collect<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        subscription_id: u64,
+        opensub: Address,
+        collect_calldata: Bytes,
+        call_gas_limit: Option<u64>,
+    ) -> Result<H256> {
+        let entrypoint = self.config.entrypoint;
+        let nonce = userop::fetch_entrypoint_nonce(
+            client.clone(),
+            entrypoint,
+            self.account(),
+            U256::from(subscription_id),
+        )
+        .await
+        .map_err(|e| eyre!("{e:#}"))?;
+        let gas_price = client
+            .get_gas_price()
+            .await
+            .map_err(|e| eyre!("failed to fetch gas price: {e}"))?;
+
+        let mut op = UserOperation {
+            sender: self.account(),
+            nonce,
+            // The account must already be deployed; the relayer never deploys it.
+            init_code: Bytes::default(),
+            call_data: execute_call_data(opensub, collect_calldata)?,
+            call_gas_limit: U256::zero(),
+            verification_gas_limit: U256::zero(),
+            pre_verification_gas: U256::zero(),
+            max_fee_per_gas: gas_price,
+            max_priority_fee_per_gas: gas_price,
+            paymaster_and_data: Bytes::default(),
+            signature: Bytes::from(vec![0u8; 65]),
+        };
+
+        if let Some((pm, policy_id)) = self.paymaster.as_ref() {
+            op.paymaster_and_data = pm
+                .get_paymaster_stub_data(
+                    encoding::user_op_to_paymaster_json(&op),
+                    entrypoint,
+                    self.chain_id,
+                    policy_id,
+                    None,
+                )
+                .await
+                .map_err(|e| eyre!("{e:#}"))?;
+        }
+        self.sign(client.clone(), &mut op).await?;
+
+        let est = self
+            .bundler
+            .estimate_user_operation_gas(encoding::user_op_to_json(&op), entrypoint)
+            .await
+            .map_err(|e| eyre!("{e:#}"))?;
+        op.call_gas_limit = call_gas_limit.map(U256::from).unwrap_or(est.call_gas_limit);
+        op.verification_gas_limit = est.verification_gas_limit;
+        op.pre_verification_gas = est.pre_verification_gas;
+
+        if let Some((pm, policy_id)) = self.paymaster.as_ref() {
+            op.paymaster_and_data = pm
+                .get_paymaster_data(
+                    encoding::user_op_to_paymaster_json(&op),
+                    entrypoint,
+                    self.chain_id,
+                    policy_id,
+                    None,
+                )
+                .await
+                .map_err(|e| eyre!("{e:#}"))?;
+        }
+        self.sign(client, &mut op).await?;
+
+        self.bundler
+            .send_user_operation(encoding::user_op_to_json(&op), entrypoint)
+            .await
+            .map_err(|e| eyre!("{e:#}"))
+    }
+
+    /// Poll the bundler for the op's receipt.
+    pub async fn wait(&self, user_op_hash: H256, timeout: Duration) -> Result<RelayedCollect> {
+        let receipt = self
+            .bundler
+            .wait_user_operation_receipt(user_op_hash, timeout)
+            .await
+            .map_err(|e| eyre!("{e:#}"))?;
+        RelayedCollect::from_receipt(&receipt)
+    }
+
+    /// SimpleAccount signature: eth-sign over `EntryPoint.getUserOpHash(op)`.
+    async fn sign<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        op: &mut UserOperation,
+    ) -> Result<()> {
+        let hash = userop::user_op_hash(
+            client,
+            self.config.entrypoint,
+            self.config.entrypoint_version,
+            op,
+        )
+        .await
+        .map_err(|e| eyre!("{e:#}"))?;
+        let sig = self
+            .wallet
+            .sign_message(hash.as_bytes())
+            .await
+            .map_err(|e| eyre!("failed to sign userOpHash: {e}"))?;
+        op.signature = Bytes::from(sig.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{execute_call_data, RelayedCollect};
+    use crate::opensub::{collect_call, OpenSub};
+    use ethers::abi::{AbiParser, Token};
+    use ethers::providers::Provider;
+    use ethers::types::{Address, H256, U256};
+    use std::sync::Arc;
+
+    #[test]
+    fn relayed_collect_wraps_collect_in_execute() {
+        let (provider, _mock) = Provider::mocked();
+        let opensub_address = Address::repeat_byte(0x01);
+        let opensub = OpenSub::new(opensub_address, Arc::new(provider));
+        let collect = collect_call(&opensub, U256::from(7), None)
+            .calldata()
+            .unwrap();
+
+        let data = execute_call_data(opensub_address, collect.clone()).unwrap();
+
+        let execute = AbiParser::default()
+            .parse_function("function execute(address dest, uint256 value, bytes func)")
+            .unwrap();
+        assert_eq!(&data[..4], execute.short_signature().as_slice());
+        assert_eq!(
+            execute.decode_input(&data[4..]).unwrap(),
+            vec![
+                Token::Address(opensub_address),
+                Token::Uint(U256::zero()),
+                Token::Bytes(collect.to_vec()),
+            ]
+        );
+        // collect(uint256) with id 7.
+        assert_eq!(&collect[..4], &ethers::utils::id("collect(uint256)")[..]);
+        assert_eq!(U256::from_big_endian(&collect[4..36]), U256::from(7));
+    }
+
+    #[test]
+    fn receipt_reports_inner_call_outcome() {
+        let receipt = serde_json::json!({
+            "success": false,
+            "actualGasCost": "0x5208",
+            "receipt": {
+                "transactionHash": format!("{:?}", H256::repeat_byte(0x11)),
+                "blockNumber": "0x10"
+            }
+        });
+        let out = RelayedCollect::from_receipt(&receipt).unwrap();
+        assert!(!out.success);
+        assert_eq!(out.tx_hash, Some(H256::repeat_byte(0x11)));
+        assert_eq!(out.block_number, Some(16));
+        assert_eq!(out.actual_gas_cost, U256::from(21_000));
+    }
+}
@@ -1,34 +1,254 @@
+use crate::opensub::{ChargedFilter, OpenSub};
 use crate::state::KeeperState;
+use ethers::contract::{parse_log, EthEvent};
 use ethers::providers::Middleware;
-use ethers::types::{Address, BlockNumber, Filter, H256, U256};
+use ethers::types::{Address, BlockNumber, Filter, Log, Topic, H256, U256};
 use ethers::utils::keccak256;
 use eyre::{eyre, Result};
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
 use std::cmp;
-use std::time::Duration;
+use std::fmt;
+use std::time::{Duration, Instant};
 
 /// Event topic0 for:
 /// Subscribed(uint256 indexed subscriptionId, uint256 indexed planId, address indexed subscriber, uint40 startTime, uint40 paidThrough)
-///
-/// We only need `subscriptionId` (topics[1]) so we avoid decoding log data.
 fn subscribed_topic0() -> H256 {
     H256::from(keccak256(
         "Subscribed(uint256,uint256,address,uint40,uint40)",
     ))
 }
 
+/// Event topic0 for `CancelUnscheduled(uint256 indexed subscriptionId)`.
+fn cancel_unscheduled_topic0() -> H256 {
+    H256::from(keccak256("CancelUnscheduled(uint256)"))
+}
+
+/// A subscription id seen for the first time, decoded from its `Subscribed` log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSubscription {
+    pub subscription_id: u64,
+    pub plan_id: U256,
+    pub subscriber: Address,
+    pub start_time: u64,
+    pub paid_through: u64,
+    pub block_number: Option<u64>,
+    pub tx_hash: Option<H256>,
+}
+
+/// Decode a `Subscribed` log: the three indexed params from topics, `startTime` / `paidThrough`
+/// from the data (two ABI words). `None` if the log doesn't have that shape or the id is past u64.
+fn decode_subscribed(log: &Log) -> Option<NewSubscription> {
+    if log.topics.len() < 4 || log.data.len() < 64 {
+        return None;
+    }
+    let id = U256::from_big_endian(log.topics[1].as_bytes());
+    if id > U256::from(u64::MAX) {
+        tracing::warn!(subscription_id = ?id, "subscriptionId exceeds u64::MAX; skipping");
+        return None;
+    }
+    // uint40 values: the low 8 bytes of each word are enough.
+    let word = |i: usize| U256::from_big_endian(&log.data[i * 32..(i + 1) * 32]).low_u64();
+
+    Some(NewSubscription {
+        subscription_id: id.as_u64(),
+        plan_id: U256::from_big_endian(log.topics[2].as_bytes()),
+        subscriber: Address::from(log.topics[3]),
+        start_time: word(0),
+        paid_through: word(1),
+        block_number: log.block_number.map(|b| b.as_u64()),
+        tx_hash: log.transaction_hash,
+    })
+}
+
+/// Scanner counters for one keeper cycle, to help tune `--log-chunk` and spot slow providers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanStats {
+    /// `eth_getLogs` requests issued, including failed attempts.
+    pub get_logs_calls: u64,
+    /// `eth_getLogs` attempts that failed and were retried on the same range.
+    pub retries: u64,
+    /// Times the chunk size was halved after a range kept failing.
+    pub chunk_reductions: u64,
+    /// Chunk size in effect when the scan finished (0 if nothing was scanned).
+    pub final_chunk_size: u64,
+    /// Wall time spent scanning, including the head lookup.
+    pub duration_ms: u64,
+}
+
+/// Where a scan failed. `range` is `None` when the head lookup itself failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanContext {
+    pub method: &'static str,
+    pub range: Option<(u64, u64)>,
+    pub chunk: u64,
+    pub opensub: Address,
+    /// The underlying provider error.
+    pub message: String,
+}
+
+/// A scan that gave up after retries and chunk shrinking, classified from the provider error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanError {
+    /// The provider refused the range or result size (`--log-chunk` too large for it).
+    LogLimit(ScanContext),
+    /// HTTP 429 / provider throttling.
+    RateLimited(ScanContext),
+    /// Request timed out or the connection failed.
+    Timeout(ScanContext),
+    /// Rejected credentials (bad or missing API key). Retrying will not help.
+    Auth(ScanContext),
+    Other(ScanContext),
+}
+
+impl ScanError {
+    /// Classify a provider error message (matched case-insensitively).
+    pub fn classify(ctx: ScanContext) -> Self {
+        let msg = ctx.message.to_ascii_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| msg.contains(n));
+
+        if has(&[
+            "unauthorized",
+            "forbidden",
+            "api key",
+            "apikey",
+            "must be authenticated",
+            "authentication",
+        ]) {
+            ScanError::Auth(ctx)
+        } else if has(&[
+            "rate limit",
+            "too many requests",
+            "429",
+            "capacity exceeded",
+        ]) {
+            ScanError::RateLimited(ctx)
+        } else if has(&[
+            "query returned more than",
+            "block range",
+            "range is too large",
+            "range too large",
+            "too many results",
+            "response size",
+            "limit exceeded",
+        ]) {
+            ScanError::LogLimit(ctx)
+        } else if has(&[
+            "timed out",
+            "timeout",
+            "deadline exceeded",
+            "connection refused",
+            "connection reset",
+            "connection closed",
+            "error sending request",
+        ]) {
+            ScanError::Timeout(ctx)
+        } else {
+            ScanError::Other(ctx)
+        }
+    }
+
+    pub fn context(&self) -> &ScanContext {
+        match self {
+            ScanError::LogLimit(ctx)
+            | ScanError::RateLimited(ctx)
+            | ScanError::Timeout(ctx)
+            | ScanError::Auth(ctx)
+            | ScanError::Other(ctx) => ctx,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScanError::LogLimit(_) => "provider log limit",
+            ScanError::RateLimited(_) => "rate limited",
+            ScanError::Timeout(_) => "timeout",
+            ScanError::Auth(_) => "auth",
+            ScanError::Other(_) => "scan error",
+        }
+    }
+
+    /// Whether a long-running keeper should back off and try again next cycle instead of exiting.
+    pub fn should_backoff(&self) -> bool {
+        matches!(
+            self,
+            ScanError::LogLimit(_) | ScanError::RateLimited(_) | ScanError::Timeout(_)
+        )
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ctx = self.context();
+        write!(f, "{} during {}", self.label(), ctx.method)?;
+        if let Some((from, to)) = ctx.range {
+            write!(f, " [{from}, {to}]")?;
+        }
+        write!(
+            f,
+            " (chunk {}, OpenSub {:?}): {}",
+            ctx.chunk, ctx.opensub, ctx.message
+        )
+    }
+}
+
+impl std::error::Error for ScanError {}
+
 pub async fn scan_new_subscriptions<M: Middleware>(
     client: &M,
     opensub: Address,
     start_block: u64,
-    confirmations: u64,
+    scan_confirmations: u64,
     log_chunk_size: u64,
     state: &mut KeeperState,
-) -> Result<usize>
+    stats: &mut ScanStats,
+) -> Result<Vec<NewSubscription>, ScanError>
 where
     <M as Middleware>::Error: 'static,
 {
-    let latest = client.get_block_number().await?.as_u64();
-    let target = latest.saturating_sub(confirmations);
+    let started = Instant::now();
+    let result = scan_range(
+        client,
+        opensub,
+        start_block,
+        scan_confirmations,
+        log_chunk_size,
+        state,
+        stats,
+    )
+    .await;
+    stats.duration_ms += started.elapsed().as_millis() as u64;
+    result
+}
+
+async fn scan_range<M: Middleware>(
+    client: &M,
+    opensub: Address,
+    start_block: u64,
+    scan_confirmations: u64,
+    log_chunk_size: u64,
+    state: &mut KeeperState,
+    stats: &mut ScanStats,
+) -> Result<Vec<NewSubscription>, ScanError>
+where
+    <M as Middleware>::Error: 'static,
+{
+    let context = |method, range, chunk, err: &dyn fmt::Display| ScanContext {
+        method,
+        range,
+        chunk,
+        opensub,
+        message: err.to_string(),
+    };
+
+    let latest = client
+        .get_block_number()
+        .await
+        .map_err(|e| ScanError::classify(context("eth_blockNumber", None, log_chunk_size, &e)))?
+        .as_u64();
+    let target = latest.saturating_sub(scan_confirmations);
 
     // Determine scan start.
     let mut from = state.last_scanned_block.saturating_add(1);
@@ -40,21 +260,21 @@ where
             target,
             "no new blocks to scan (waiting for confirmations)"
         );
-        return Ok(0);
+        return Ok(Vec::new());
     }
 
     let topic0 = subscribed_topic0();
 
     // We'll accumulate in a BTreeSet to keep deterministic ordering.
     let mut ids = state.ids_set();
-    let before_total = ids.len();
+    let mut discovered = Vec::new();
 
     let mut chunk = log_chunk_size.max(1);
 
     tracing::info!(
         from,
         to = target,
-        confirmations,
+        scan_confirmations,
         chunk,
         "scanning for Subscribed logs"
     );
@@ -64,18 +284,25 @@ where
         let end = cmp::min(cursor.saturating_add(chunk - 1), target);
 
         // We may need to shrink the chunk size if the RPC rejects large ranges.
-        let logs = match fetch_logs_with_retries(client, opensub, topic0, cursor, end).await {
+        stats.final_chunk_size = chunk;
+        let logs = match fetch_logs_with_retries(client, opensub, topic0, cursor, end, stats).await
+        {
             Ok(logs) => logs,
             Err(err) => {
-                // Shrink range and retry (down to 10-block chunks).
-                if chunk <= 10 {
+                let err =
+                    ScanError::classify(context("eth_getLogs", Some((cursor, end)), chunk, &err));
+                // Shrink range and retry (down to 10-block chunks). A smaller range can't fix
+                // rejected credentials.
+                if chunk <= 10 || matches!(err, ScanError::Auth(_)) {
                     return Err(err);
                 }
                 chunk = cmp::max(10, chunk / 2);
+                stats.chunk_reductions += 1;
                 tracing::warn!(
                     cursor,
                     end,
                     chunk,
+                    kind = err.label(),
                     "log fetch failed; reducing chunk size and retrying"
                 );
                 continue;
@@ -83,15 +310,15 @@ where
         };
 
         for log in logs {
-            if log.topics.len() < 2 {
+            let Some(sub) = decode_subscribed(&log) else {
                 continue;
+            };
+            if ids.insert(sub.subscription_id) {
+                state
+                    .paid_through
+                    .insert(sub.subscription_id, sub.paid_through);
+                discovered.push(sub);
             }
-            let id_u256 = U256::from_big_endian(log.topics[1].as_bytes());
-            if id_u256 > U256::from(u64::MAX) {
-                tracing::warn!(subscription_id = ?id_u256, "subscriptionId exceeds u64::MAX; skipping");
-                continue;
-            }
-            ids.insert(id_u256.as_u64());
         }
 
         // Advance and record scan progress.
@@ -101,26 +328,192 @@ where
 
     state.set_ids_from_set(ids);
 
-    let after_total = state.subscription_ids.len();
-    let discovered = after_total.saturating_sub(before_total);
-
     tracing::info!(
-        discovered,
+        discovered = discovered.len(),
         last_scanned_block = state.last_scanned_block,
-        total = after_total,
+        total = state.subscription_ids.len(),
         "scan complete"
     );
 
     Ok(discovered)
 }
 
+/// `--max-tracked-subscriptions`: re-add ids that show up alive in `from..=to` (a `Charged` or
+/// `CancelUnscheduled` log) but are not tracked, typically because they were evicted. `Charged`
+/// also refreshes the cached `paidThrough`.
+///
+/// Best-effort: a range that keeps failing is logged and skipped, since the `Subscribed` scan
+/// has already moved past it.
+pub async fn rediscover_active<M: Middleware>(
+    client: &M,
+    opensub: Address,
+    from: u64,
+    to: u64,
+    log_chunk_size: u64,
+    state: &mut KeeperState,
+    stats: &mut ScanStats,
+) -> Vec<u64>
+where
+    <M as Middleware>::Error: 'static,
+{
+    let topics = vec![ChargedFilter::signature(), cancel_unscheduled_topic0()];
+    let mut ids = state.ids_set();
+    let mut rediscovered = Vec::new();
+
+    let chunk = log_chunk_size.max(1);
+    let mut cursor = from;
+    while cursor <= to {
+        let end = cmp::min(cursor.saturating_add(chunk - 1), to);
+        let logs = match fetch_logs_with_retries(
+            client,
+            opensub,
+            topics.clone(),
+            cursor,
+            end,
+            stats,
+        )
+        .await
+        {
+            Ok(logs) => logs,
+            Err(err) => {
+                tracing::warn!(from = cursor, to = end, error = %err, "rediscovery log fetch failed; skipping range");
+                cursor = end.saturating_add(1);
+                continue;
+            }
+        };
+        for log in logs {
+            let Some(topic) = log.topics.get(1) else {
+                continue;
+            };
+            let id = U256::from_big_endian(topic.as_bytes());
+            if id > U256::from(u64::MAX) {
+                continue;
+            }
+            let id = id.as_u64();
+            if log.topics[0] == ChargedFilter::signature() {
+                if let Ok(charged) = parse_log::<ChargedFilter>(log.clone()) {
+                    state.paid_through.insert(id, charged.paid_through);
+                }
+            }
+            if ids.insert(id) {
+                rediscovered.push(id);
+            }
+        }
+        cursor = end.saturating_add(1);
+    }
+
+    if !rediscovered.is_empty() {
+        state.readmit(&rediscovered);
+        tracing::info!(
+            rediscovered = rediscovered.len(),
+            total = state.subscription_ids.len(),
+            "re-added subscriptions seen active in logs"
+        );
+    }
+    rediscovered
+}
+
+/// Evicted ids re-checked with `isDue` per scan under `--max-tracked-subscriptions`.
+pub const EVICTED_RECHECK_BATCH: usize = 100;
+
+/// `--max-tracked-subscriptions`: `isDue` for the next `batch` evicted ids (see
+/// `KeeperState::evicted_batch`), re-adding the due ones. This brings back an evicted id that
+/// falls due without emitting a log in the scanned range. Failed calls are logged and retried on
+/// the id's next turn.
+pub async fn recheck_evicted<M: Middleware>(
+    opensub: &OpenSub<M>,
+    state: &mut KeeperState,
+    batch: usize,
+    max_concurrency: usize,
+) -> Vec<u64> {
+    let ids = state.evicted_batch(batch);
+    let mut due: Vec<u64> = stream::iter(ids)
+        .map(|id| async move {
+            match opensub.is_due(U256::from(id)).call().await {
+                Ok(due) => due.then_some(id),
+                Err(err) => {
+                    tracing::warn!(subscription_id = id, error = %err, "isDue call for an evicted id failed");
+                    None
+                }
+            }
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .filter_map(|id| async move { id })
+        .collect()
+        .await;
+    due.sort_unstable();
+
+    if !due.is_empty() {
+        state.readmit(&due);
+        tracing::info!(
+            readmitted = due.len(),
+            total = state.subscription_ids.len(),
+            "re-added evicted subscriptions that are due"
+        );
+    }
+    due
+}
+
+/// Totals for `--catch-up`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CatchUpReport {
+    pub iterations: usize,
+    pub blocks_scanned: u64,
+    pub discovered: Vec<NewSubscription>,
+}
+
+/// Repeat `scan_new_subscriptions` until a pass makes no progress, i.e. `last_scanned_block` has
+/// reached the confirmed head as of that pass (the head keeps moving while a long backfill runs).
+pub async fn catch_up_subscriptions<M: Middleware>(
+    client: &M,
+    opensub: Address,
+    start_block: u64,
+    scan_confirmations: u64,
+    log_chunk_size: u64,
+    state: &mut KeeperState,
+    stats: &mut ScanStats,
+) -> Result<CatchUpReport, ScanError>
+where
+    <M as Middleware>::Error: 'static,
+{
+    let first = state.last_scanned_block.saturating_add(1).max(start_block);
+    let mut report = CatchUpReport::default();
+
+    loop {
+        let before = state.last_scanned_block;
+        report.discovered.extend(
+            scan_new_subscriptions(
+                client,
+                opensub,
+                start_block,
+                scan_confirmations,
+                log_chunk_size,
+                state,
+                stats,
+            )
+            .await?,
+        );
+        report.iterations += 1;
+        if state.last_scanned_block == before {
+            break;
+        }
+    }
+
+    report.blocks_scanned = state
+        .last_scanned_block
+        .saturating_add(1)
+        .saturating_sub(first);
+    Ok(report)
+}
+
 async fn fetch_logs_with_retries<M: Middleware>(
     client: &M,
     opensub: Address,
-    topic0: H256,
+    topic0: impl Into<Topic>,
     from: u64,
     to: u64,
-) -> Result<Vec<ethers::types::Log>>
+    stats: &mut ScanStats,
+) -> Result<Vec<Log>>
 where
     <M as Middleware>::Error: 'static,
 {
@@ -138,12 +531,14 @@ where
     let mut delay = Duration::from_millis(200);
 
     for attempt in 1..=3 {
+        stats.get_logs_calls += 1;
         match client.get_logs(&filter).await {
             Ok(logs) => return Ok(logs),
             Err(err) => {
                 if attempt == 3 {
                     return Err(err.into());
                 }
+                stats.retries += 1;
                 tracing::warn!(
                     attempt,
                     from,
@@ -160,3 +555,502 @@ where
 
     Err(eyre!("unreachable"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        cancel_unscheduled_topic0, catch_up_subscriptions, recheck_evicted, rediscover_active,
+        scan_new_subscriptions, subscribed_topic0, ScanContext, ScanError, ScanStats,
+    };
+    use crate::opensub::{ChargedFilter, OpenSub};
+    use crate::state::{EvictionPolicy, KeeperState};
+    use ethers::abi::{encode, Token};
+    use ethers::contract::EthEvent;
+    use ethers::providers::{JsonRpcError, Middleware, MockResponse, Provider};
+    use ethers::types::{Address, BlockNumber, Bytes, Filter, Log, H256, U256, U64};
+    use std::sync::Arc;
+
+    fn subscribed_log(id: u64) -> Log {
+        Log {
+            topics: vec![
+                subscribed_topic0(),
+                H256::from_low_u64_be(id),
+                H256::from_low_u64_be(id * 10),
+                H256::from(Address::repeat_byte(id as u8)),
+            ],
+            data: encode(&[
+                Token::Uint(U256::from(1_700_000_000u64)),
+                Token::Uint(U256::from(1_700_000_000u64 + 30 * 86_400)),
+            ])
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn catch_up_advances_to_head_across_iterations() {
+        let (provider, mock) = Provider::mocked();
+        let mut state = KeeperState {
+            last_scanned_block: 0,
+            subscription_ids: Vec::new(),
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+
+        // Call order: head 100 -> logs [1..=98], head 150 (moved during the first pass) ->
+        // logs [99..=148], head 150 -> nothing left. Responses pop from the back.
+        mock.push::<U64, _>(U64::from(150)).unwrap();
+        mock.push::<Vec<Log>, _>(vec![subscribed_log(2)]).unwrap();
+        mock.push::<U64, _>(U64::from(150)).unwrap();
+        mock.push::<Vec<Log>, _>(vec![subscribed_log(1)]).unwrap();
+        mock.push::<U64, _>(U64::from(100)).unwrap();
+
+        let report = catch_up_subscriptions(
+            &provider,
+            Address::repeat_byte(0x01),
+            1,
+            2,
+            1_000,
+            &mut state,
+            &mut ScanStats::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.iterations, 3);
+        assert_eq!(report.blocks_scanned, 148);
+        assert_eq!(
+            report
+                .discovered
+                .iter()
+                .map(|s| s.subscription_id)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(state.last_scanned_block, 148);
+        assert_eq!(state.subscription_ids, vec![1, 2]);
+        assert!(
+            provider.get_block_number().await.is_err(),
+            "all responses consumed"
+        );
+    }
+
+    #[tokio::test]
+    async fn charged_and_unscheduled_logs_rediscover_evicted_ids() {
+        let (provider, mock) = Provider::mocked();
+        let mut state = KeeperState {
+            last_scanned_block: 200,
+            subscription_ids: vec![1, 5],
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+
+        let charged = |id: u64, paid_through: u64| Log {
+            topics: vec![
+                ChargedFilter::signature(),
+                H256::from_low_u64_be(id),
+                H256::from_low_u64_be(7),
+                H256::from(Address::repeat_byte(0xaa)),
+            ],
+            data: encode(&[
+                Token::Address(Address::repeat_byte(0x70)),
+                Token::Uint(U256::from(1_000_000u64)),
+                Token::Uint(U256::from(10_000u64)),
+                Token::Address(Address::repeat_byte(0xcc)),
+                Token::Uint(U256::from(paid_through - 30 * 86_400)),
+                Token::Uint(U256::from(paid_through)),
+            ])
+            .into(),
+            ..Default::default()
+        };
+        let unscheduled = Log {
+            topics: vec![cancel_unscheduled_topic0(), H256::from_low_u64_be(3)],
+            ..Default::default()
+        };
+        // One chunk: 5 is already tracked, 2 and 3 were evicted.
+        mock.push::<Vec<Log>, _>(vec![
+            charged(5, 1_800_000_000),
+            charged(2, 1_700_000_000),
+            unscheduled,
+        ])
+        .unwrap();
+
+        let mut stats = ScanStats::default();
+        let rediscovered = rediscover_active(
+            &provider,
+            Address::repeat_byte(0x01),
+            101,
+            200,
+            1_000,
+            &mut state,
+            &mut stats,
+        )
+        .await;
+
+        assert_eq!(rediscovered, vec![2, 3]);
+        assert_eq!(state.subscription_ids, vec![1, 2, 3, 5]);
+        assert_eq!(state.paid_through.get(&2), Some(&1_700_000_000));
+        assert_eq!(state.paid_through.get(&5), Some(&1_800_000_000));
+        assert_eq!(state.paid_through.get(&3), None);
+        assert_eq!(stats.get_logs_calls, 1);
+    }
+
+    #[tokio::test]
+    async fn an_evicted_id_that_falls_due_comes_back() {
+        let (provider, mock) = Provider::mocked();
+        let mut state = KeeperState {
+            last_scanned_block: 200,
+            subscription_ids: vec![1, 2, 3],
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+        // 2 and 3 have a paidThrough ahead, so they are evicted; by the next scan 3 is due.
+        state.paid_through.insert(2, 9_000);
+        state.paid_through.insert(3, 8_000);
+        assert_eq!(
+            state.evict_to(1, EvictionPolicy::FurthestFromDue, 5_000),
+            vec![2, 3]
+        );
+
+        // isDue(2) = false, isDue(3) = true. Responses pop from the back.
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Bool(true)])))
+            .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Bool(false)])))
+            .unwrap();
+
+        let opensub = OpenSub::new(Address::repeat_byte(0x01), Arc::new(provider));
+        let readmitted = recheck_evicted(&opensub, &mut state, 10, 1).await;
+
+        assert_eq!(readmitted, vec![3]);
+        assert_eq!(state.subscription_ids, vec![1, 3]);
+        assert_eq!(state.evicted, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn new_ids_are_reported_once_with_plan_and_subscriber() {
+        let (provider, mock) = Provider::mocked();
+        let mut state = KeeperState {
+            last_scanned_block: 0,
+            subscription_ids: vec![1],
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+
+        // Already known (1), new (2, 3), a repeat of 3, and a log that doesn't decode.
+        let mut truncated = subscribed_log(4);
+        truncated.data = Default::default();
+        mock.push::<Vec<Log>, _>(vec![
+            subscribed_log(1),
+            subscribed_log(2),
+            subscribed_log(3),
+            subscribed_log(3),
+            truncated,
+        ])
+        .unwrap();
+        mock.push::<U64, _>(U64::from(50)).unwrap();
+
+        let discovered = scan_new_subscriptions(
+            &provider,
+            Address::repeat_byte(0x01),
+            1,
+            0,
+            1_000,
+            &mut state,
+            &mut ScanStats::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            discovered
+                .iter()
+                .map(|s| s.subscription_id)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(discovered[1].plan_id, U256::from(30));
+        assert_eq!(discovered[1].subscriber, Address::repeat_byte(3));
+        assert_eq!(discovered[1].start_time, 1_700_000_000);
+        assert_eq!(discovered[1].paid_through, 1_700_000_000 + 30 * 86_400);
+        assert_eq!(state.subscription_ids, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn reset_scan_rescans_from_block() {
+        let (provider, mock) = Provider::mocked();
+        let opensub = Address::repeat_byte(0x01);
+        let mut state = KeeperState {
+            last_scanned_block: 500,
+            subscription_ids: vec![1],
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+
+        // --from-block 100 --reset-scan
+        state.reset_scan(100);
+
+        mock.push::<Vec<Log>, _>(vec![subscribed_log(7)]).unwrap();
+        mock.push::<U64, _>(U64::from(600)).unwrap();
+
+        let discovered = scan_new_subscriptions(
+            &provider,
+            opensub,
+            100,
+            0,
+            1_000,
+            &mut state,
+            &mut ScanStats::default(),
+        )
+        .await
+        .unwrap();
+
+        mock.assert_request("eth_blockNumber", ()).unwrap();
+        let expected = Filter::new()
+            .address(opensub)
+            .topic0(subscribed_topic0())
+            .from_block(BlockNumber::Number(100u64.into()))
+            .to_block(BlockNumber::Number(600u64.into()));
+        mock.assert_request("eth_getLogs", [expected]).unwrap();
+
+        assert_eq!(discovered.len(), 1);
+        assert_eq!(state.subscription_ids, vec![1, 7]);
+        assert_eq!(state.last_scanned_block, 600);
+    }
+
+    #[tokio::test]
+    async fn scan_target_stays_scan_confirmations_behind_head() {
+        let (provider, mock) = Provider::mocked();
+        let opensub = Address::repeat_byte(0x01);
+        let mut state = KeeperState {
+            last_scanned_block: 50,
+            subscription_ids: Vec::new(),
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+
+        // Second pass: head 95 -> target 83, already scanned. First pass: head 100 -> logs.
+        mock.push::<U64, _>(U64::from(95)).unwrap();
+        mock.push::<Vec<Log>, _>(vec![subscribed_log(3)]).unwrap();
+        mock.push::<U64, _>(U64::from(100)).unwrap();
+
+        for _ in 0..2 {
+            scan_new_subscriptions(
+                &provider,
+                opensub,
+                1,
+                12,
+                1_000,
+                &mut state,
+                &mut ScanStats::default(),
+            )
+            .await
+            .unwrap();
+        }
+
+        mock.assert_request("eth_blockNumber", ()).unwrap();
+        let expected = Filter::new()
+            .address(opensub)
+            .topic0(subscribed_topic0())
+            .from_block(BlockNumber::Number(51u64.into()))
+            .to_block(BlockNumber::Number(88u64.into()));
+        mock.assert_request("eth_getLogs", [expected]).unwrap();
+        mock.assert_request("eth_blockNumber", ()).unwrap();
+
+        assert_eq!(state.subscription_ids, vec![3]);
+        assert_eq!(state.last_scanned_block, 88);
+    }
+
+    #[tokio::test]
+    async fn scan_stats_count_calls_retries_and_chunk_reductions() {
+        let (provider, mock) = Provider::mocked();
+        let mut state = KeeperState {
+            last_scanned_block: 0,
+            subscription_ids: Vec::new(),
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+        let rpc_error = || {
+            MockResponse::Error(JsonRpcError {
+                code: -32005,
+                message: "query returned more than 10000 results".to_string(),
+                data: None,
+            })
+        };
+
+        // Head 40 with a 20-block chunk: [1..=20] fails all 3 attempts, the chunk halves to 10, then
+        // [1..=10], [11..=20], [21..=30], [31..=40] succeed. Responses pop from the back.
+        for id in [4, 3, 2, 1] {
+            mock.push::<Vec<Log>, _>(vec![subscribed_log(id)]).unwrap();
+        }
+        for _ in 0..3 {
+            mock.push_response(rpc_error());
+        }
+        mock.push::<U64, _>(U64::from(40)).unwrap();
+
+        let mut stats = ScanStats::default();
+        let discovered = scan_new_subscriptions(
+            &provider,
+            Address::repeat_byte(0x01),
+            1,
+            0,
+            20,
+            &mut state,
+            &mut stats,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(discovered.len(), 4);
+        assert_eq!(state.last_scanned_block, 40);
+        assert_eq!(stats.get_logs_calls, 7);
+        assert_eq!(stats.retries, 2);
+        assert_eq!(stats.chunk_reductions, 1);
+        assert_eq!(stats.final_chunk_size, 10);
+        assert!(stats.duration_ms > 0, "retry backoff is included");
+    }
+
+    #[test]
+    fn provider_errors_map_to_scan_error_variants() {
+        let classify = |message: &str| {
+            ScanError::classify(ScanContext {
+                method: "eth_getLogs",
+                range: Some((1, 2_000)),
+                chunk: 2_000,
+                opensub: Address::zero(),
+                message: message.to_string(),
+            })
+        };
+
+        let cases = [
+            (
+                "(code: -32005, message: query returned more than 10000 results, data: None)",
+                "provider log limit",
+            ),
+            (
+                "(code: -32600, message: eth_getLogs is limited to a 10,000 block range, data: None)",
+                "provider log limit",
+            ),
+            (
+                "(code: -32602, message: Log response size exceeded., data: None)",
+                "provider log limit",
+            ),
+            (
+                "HTTP error 429 Too Many Requests: rate limit exceeded",
+                "rate limited",
+            ),
+            (
+                "(code: 429, message: Your app has exceeded its compute units per second capacity, data: None)",
+                "rate limited",
+            ),
+            ("operation timed out", "timeout"),
+            (
+                "HTTP error: error sending request for url (http://127.0.0.1:8545/): connection refused",
+                "timeout",
+            ),
+            (
+                "Deserialization Error: expected value at line 1 column 1. Response: Must be authenticated!",
+                "auth",
+            ),
+            ("(code: -32000, message: invalid API key, data: None)", "auth"),
+            ("(code: -32000, message: header not found, data: None)", "scan error"),
+        ];
+        for (message, label) in cases {
+            let err = classify(message);
+            assert_eq!(err.label(), label, "{message}");
+            assert_eq!(
+                err.should_backoff(),
+                !matches!(err, ScanError::Auth(_) | ScanError::Other(_)),
+                "{message}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_error_carries_range_chunk_and_address() {
+        let (provider, mock) = Provider::mocked();
+        let opensub = Address::repeat_byte(0x01);
+        let mut state = KeeperState {
+            last_scanned_block: 0,
+            subscription_ids: Vec::new(),
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+
+        // Auth failures give up after the per-range retries, without shrinking the chunk.
+        for _ in 0..3 {
+            mock.push_response(MockResponse::Error(JsonRpcError {
+                code: -32000,
+                message: "Unauthorized: invalid API key".to_string(),
+                data: None,
+            }));
+        }
+        mock.push::<U64, _>(U64::from(40)).unwrap();
+
+        let mut stats = ScanStats::default();
+        let err = scan_new_subscriptions(&provider, opensub, 1, 0, 20, &mut state, &mut stats)
+            .await
+            .unwrap_err();
+
+        let ScanError::Auth(ctx) = &err else {
+            panic!("expected auth error, got {err:?}");
+        };
+        assert_eq!(ctx.method, "eth_getLogs");
+        assert_eq!(ctx.range, Some((1, 20)));
+        assert_eq!(ctx.chunk, 20);
+        assert_eq!(ctx.opensub, opensub);
+        assert_eq!(stats.chunk_reductions, 0);
+        assert_eq!(state.last_scanned_block, 0);
+
+        let msg = err.to_string();
+        assert!(msg.starts_with("auth during eth_getLogs [1, 20] (chunk 20, OpenSub 0x0101"));
+        assert!(msg.contains("invalid API key"));
+    }
+}
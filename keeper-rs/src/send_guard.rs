@@ -0,0 +1,100 @@
+use eyre::{eyre, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where the send guard lives next to the state file (`state.json` -> `state.sends.json`).
+pub fn send_guard_path(state_file: &Path) -> PathBuf {
+    state_file.with_extension("sends.json")
+}
+
+/// `--min-resend-seconds`: when each subscription was last sent a collect().
+///
+/// Written to disk *before* the tx goes out, so a keeper that crashes between sending and
+/// persisting the outcome still refuses a second send after a restart. Deliberately separate
+/// from the backoff state: it only looks at sends, never at why they failed.
+pub struct SendGuard {
+    path: PathBuf,
+    window: u64,
+    sends: Mutex<BTreeMap<u64, u64>>,
+}
+
+impl SendGuard {
+    /// Load (or start) the guard file, dropping entries already outside the window.
+    pub fn load(path: PathBuf, window: u64, now: u64) -> Result<Self> {
+        let mut sends: BTreeMap<u64, u64> = if path.exists() {
+            let raw = fs::read_to_string(&path)
+                .map_err(|e| eyre!("failed to read send guard {}: {e}", path.display()))?;
+            serde_json::from_str(&raw)
+                .map_err(|e| eyre!("failed to parse send guard {}: {e}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        sends.retain(|_, at| now.saturating_sub(*at) < window);
+        Ok(Self {
+            path,
+            window,
+            sends: Mutex::new(sends),
+        })
+    }
+
+    /// Time of the last send for `subscription_id`, if it is still within the window at `now`.
+    pub fn recently_sent(&self, subscription_id: u64, now: u64) -> Option<u64> {
+        let sends = self.sends.lock().unwrap_or_else(|e| e.into_inner());
+        sends
+            .get(&subscription_id)
+            .copied()
+            .filter(|at| now.saturating_sub(*at) < self.window)
+    }
+
+    /// Record a send at `now` and persist the guard. Callers must not send if this fails.
+    pub fn record(&self, subscription_id: u64, now: u64) -> Result<()> {
+        let mut sends = self.sends.lock().unwrap_or_else(|e| e.into_inner());
+        sends.retain(|_, at| now.saturating_sub(*at) < self.window);
+        sends.insert(subscription_id, now);
+
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| eyre!("failed to create directory {}: {e}", parent.display()))?;
+        }
+        let json = serde_json::to_string_pretty(&*sends)
+            .map_err(|e| eyre!("failed to serialize send guard: {e}"))?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)
+            .map_err(|e| eyre!("failed to write {}: {e}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &self.path)
+            .map_err(|e| eyre!("failed to replace {}: {e}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{send_guard_path, SendGuard};
+
+    #[test]
+    fn restart_within_the_window_still_blocks_a_resend() {
+        let dir =
+            std::env::temp_dir().join(format!("opensub-keeper-send-guard-{}", std::process::id()));
+        let path = send_guard_path(&dir.join("state.json"));
+        assert_eq!(path, dir.join("state.sends.json"));
+
+        let guard = SendGuard::load(path.clone(), 300, 1_000).unwrap();
+        assert_eq!(guard.recently_sent(7, 1_000), None);
+        guard.record(7, 1_000).unwrap();
+        drop(guard);
+
+        // "Restart": a fresh guard from the same file, 10s later.
+        let guard = SendGuard::load(path.clone(), 300, 1_010).unwrap();
+        assert_eq!(guard.recently_sent(7, 1_010), Some(1_000));
+        assert_eq!(guard.recently_sent(8, 1_010), None);
+        // Window over.
+        assert_eq!(guard.recently_sent(7, 1_300), None);
+
+        // Expired entries are dropped on load.
+        let guard = SendGuard::load(path, 300, 2_000).unwrap();
+        assert_eq!(guard.recently_sent(7, 1_010), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,20 @@
+use crate::opensub::ChargedFilter;
+use ethers::contract::EthEvent;
 use ethers::providers::Middleware;
-use ethers::types::H256;
+use ethers::types::{
+    Address, Filter, GethDebugBuiltInTracerType, GethDebugTracerType, GethDebugTracingOptions,
+    GethTrace, GethTraceFrame, TransactionReceipt, H256, U256,
+};
 use eyre::{eyre, Result};
+use opensub_aa::now_unix;
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Reverse,
     collections::{BTreeMap, BTreeSet},
     fs,
     path::Path,
     str::FromStr,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::Duration,
 };
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -15,6 +22,8 @@ use std::{
 pub enum FailureKind {
     RpcError,
     PlanInactive,
+    /// The plan row fails a sanity check (`collectorFeeBps` above 10000).
+    MalformedPlan,
     InsufficientAllowance,
     InsufficientBalance,
     SimulationRevert,
@@ -23,6 +32,20 @@ pub enum FailureKind {
     Unknown,
 }
 
+/// Cap on `RetryInfo::consecutive_failures`.
+///
+/// Backoff is `base * 2^(failures - 1)` with the exponent clamped at 63, so counting further
+/// carries no information.
+pub const MAX_CONSECUTIVE_FAILURES: u32 = 64;
+
+/// Longest `RetryInfo::last_failure_reason` kept in state (in chars, before the `...` marker).
+const MAX_FAILURE_REASON_CHARS: usize = 240;
+const TRUNCATED_MARKER: &str = "...";
+
+/// Consecutive failures after which `--eviction-policy furthest-from-due` treats an id as
+/// long-failing and evicts it before any other.
+pub const EVICT_FAILING_AFTER: u32 = 10;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RetryInfo {
@@ -50,6 +73,34 @@ impl Default for RetryInfo {
 pub struct InFlightTx {
     pub tx_hash: String,
     pub sent_at: u64,
+    /// Sender nonce of the tx, if known at send time.
+    ///
+    /// Lets reconcile detect a tx that was dropped or replaced (the sender's mined nonce moved
+    /// past it without a receipt) instead of waiting out the full pending TTL.
+    #[serde(default)]
+    pub nonce: Option<u64>,
+    /// Sender address of the tx, if known at send time.
+    #[serde(default)]
+    pub from: Option<String>,
+    /// A block known to precede the tx (the scanned head when it was sent).
+    ///
+    /// Bounds the `Charged` log query used to reconcile many in-flight txs with one RPC call.
+    #[serde(default)]
+    pub sent_block: Option<u64>,
+    /// Block the tx was mined in (only set while it waits in `confirming`).
+    #[serde(default)]
+    pub mined_block: Option<u64>,
+}
+
+/// Rolling window for `--daily-gas-budget-wei`.
+pub const GAS_SPEND_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Gas paid (wei) for collect() receipts observed at `at` (unix seconds).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GasSpend {
+    pub at: u64,
+    pub wei: U256,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,6 +119,11 @@ pub struct KeeperState {
     #[serde(default)]
     pub in_flight: BTreeMap<u64, InFlightTx>,
 
+    /// Successfully mined collect() txs waiting for `--collect-confirmations`, keyed by
+    /// subscriptionId. Like `in_flight`, these ids are not recollected.
+    #[serde(default)]
+    pub confirming: BTreeMap<u64, InFlightTx>,
+
     /// Per-subscription retry/backoff state.
     ///
     /// This is a Milestone 5.1 guardrail: if collect() would revert (plan inactive, insufficient
@@ -75,6 +131,45 @@ pub struct KeeperState {
     /// hammering RPCs.
     #[serde(default)]
     pub retries: BTreeMap<u64, RetryInfo>,
+
+    /// Unix timestamp of the last successful collect per subscription.
+    ///
+    /// Used by `--min-seconds-between-collects` to refuse collecting the same subscription again
+    /// too soon (e.g. misconfigured plans with tiny intervals).
+    #[serde(default)]
+    pub last_success: BTreeMap<u64, u64>,
+
+    /// Gas cost (`gasUsed * effectiveGasPrice`) of mined collect() txs over the last
+    /// `GAS_SPEND_WINDOW_SECS`, for `--daily-gas-budget-wei`. Older entries are pruned.
+    #[serde(default)]
+    pub gas_spend: Vec<GasSpend>,
+
+    /// Last known `paidThrough` per subscription (from `Subscribed` logs and `subscriptions()`
+    /// reads), for the local due pre-filter. Dropped once a collect succeeds.
+    #[serde(default)]
+    pub paid_through: BTreeMap<u64, u64>,
+
+    /// Operator tags per subscription (`--tag-id` / `--untag-id`), for `--only-tag` and reports.
+    /// Each id's tags are sorted and unique; ids without tags have no entry.
+    #[serde(default)]
+    pub tags: BTreeMap<u64, Vec<String>>,
+
+    /// Ids dropped by `--max-tracked-subscriptions`, in the order they are re-checked with
+    /// `isDue` (see `evicted_batch`). Their tags are kept so a re-added id gets them back.
+    #[serde(default)]
+    pub evicted: Vec<u64>,
+}
+
+/// `--eviction-policy`: which ids `--max-tracked-subscriptions` drops first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EvictionPolicy {
+    /// Ids that won't need a collect soon: long-failing ids first, then those with the latest
+    /// cached `paidThrough`, then the most recently collected. Overdue ids and ids never seen due
+    /// go last, since those are the ones a collect is owed for.
+    #[default]
+    FurthestFromDue,
+    /// Lowest (earliest created) ids first.
+    Oldest,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -85,14 +180,19 @@ pub struct ReconcileOutcome {
 }
 
 impl KeeperState {
+    /// Read an existing state file (never creates one).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = fs::read_to_string(path)
+            .map_err(|e| eyre!("failed to read state file {}: {e}", path.display()))?;
+        serde_json::from_str(&raw)
+            .map_err(|e| eyre!("failed to parse state file {}: {e}", path.display()))
+    }
+
     pub fn load_or_init(path: impl AsRef<Path>, start_block: u64) -> Result<Self> {
         let path = path.as_ref();
         if path.exists() {
-            let raw = fs::read_to_string(path)
-                .map_err(|e| eyre!("failed to read state file {}: {e}", path.display()))?;
-            let st: KeeperState = serde_json::from_str(&raw)
-                .map_err(|e| eyre!("failed to parse state file {}: {e}", path.display()))?;
-            return Ok(st);
+            return Self::load(path);
         }
 
         if let Some(parent) = path.parent() {
@@ -104,7 +204,13 @@ impl KeeperState {
             last_scanned_block: start_block.saturating_sub(1),
             subscription_ids: Vec::new(),
             in_flight: BTreeMap::new(),
+            confirming: BTreeMap::new(),
             retries: BTreeMap::new(),
+            last_success: BTreeMap::new(),
+            gas_spend: Vec::new(),
+            paid_through: BTreeMap::new(),
+            tags: BTreeMap::new(),
+            evicted: Vec::new(),
         };
         init.save(path)?;
         Ok(init)
@@ -148,6 +254,11 @@ impl KeeperState {
         Ok(())
     }
 
+    /// Force the next scan to start at `from_block` (known subscription ids are kept).
+    pub fn reset_scan(&mut self, from_block: u64) {
+        self.last_scanned_block = from_block.saturating_sub(1);
+    }
+
     pub fn ids_set(&self) -> BTreeSet<u64> {
         self.subscription_ids.iter().copied().collect()
     }
@@ -156,17 +267,40 @@ impl KeeperState {
         self.subscription_ids = ids.into_iter().collect();
     }
 
-    pub fn mark_in_flight(&mut self, subscription_id: u64, tx_hash: H256) {
-        let now = now_unix();
+    pub fn mark_in_flight(
+        &mut self,
+        subscription_id: u64,
+        tx_hash: H256,
+        nonce: Option<U256>,
+        from: Option<Address>,
+        sent_block: Option<u64>,
+    ) {
         self.in_flight.insert(
             subscription_id,
-            InFlightTx {
-                tx_hash: format!("{:#x}", tx_hash),
-                sent_at: now,
-            },
+            new_tracked_tx(tx_hash, nonce, from, sent_block, None),
         );
     }
 
+    /// Track a collect() tx that was mined successfully but still needs more confirmations.
+    pub fn mark_confirming(
+        &mut self,
+        subscription_id: u64,
+        tx_hash: H256,
+        nonce: Option<U256>,
+        from: Option<Address>,
+        mined_block: Option<u64>,
+    ) {
+        self.confirming.insert(
+            subscription_id,
+            new_tracked_tx(tx_hash, nonce, from, None, mined_block),
+        );
+    }
+
+    pub fn is_tracked(&self, subscription_id: u64) -> bool {
+        self.in_flight.contains_key(&subscription_id)
+            || self.confirming.contains_key(&subscription_id)
+    }
+
     pub fn should_skip_due_to_backoff(&self, subscription_id: u64, now: u64) -> bool {
         self.retries
             .get(&subscription_id)
@@ -177,6 +311,87 @@ impl KeeperState {
     pub fn note_success(&mut self, subscription_id: u64) {
         // On success, clear any previous backoff.
         self.retries.remove(&subscription_id);
+        self.last_success.insert(subscription_id, now_unix());
+        // The next period's paidThrough is unknown until re-read.
+        self.paid_through.remove(&subscription_id);
+    }
+
+    /// Stop tracking a subscription that can no longer be collected (`--prune-inactive`).
+    pub fn forget_subscription(&mut self, subscription_id: u64) {
+        self.subscription_ids.retain(|id| *id != subscription_id);
+        self.retries.remove(&subscription_id);
+        self.last_success.remove(&subscription_id);
+        self.paid_through.remove(&subscription_id);
+        self.tags.remove(&subscription_id);
+    }
+
+    /// `--max-tracked-subscriptions`: forget ids beyond `max`, picked by `policy`, and return
+    /// them. Ids with a tracked tx are never evicted, so the set can stay above `max` while
+    /// those settle. Evicted ids move to `evicted` (keeping their tags) for later re-checks.
+    pub fn evict_to(&mut self, max: usize, policy: EvictionPolicy, now: u64) -> Vec<u64> {
+        let excess = self.subscription_ids.len().saturating_sub(max);
+        if excess == 0 {
+            return Vec::new();
+        }
+        let mut candidates: Vec<u64> = self
+            .subscription_ids
+            .iter()
+            .copied()
+            .filter(|id| !self.is_tracked(*id))
+            .collect();
+        match policy {
+            EvictionPolicy::FurthestFromDue => {
+                candidates.sort_by_key(|id| (self.eviction_rank(*id, now), *id))
+            }
+            EvictionPolicy::Oldest => candidates.sort_unstable(),
+        }
+        candidates.truncate(excess);
+
+        let evicted: BTreeSet<u64> = candidates.iter().copied().collect();
+        self.subscription_ids.retain(|id| !evicted.contains(id));
+        for id in &evicted {
+            self.retries.remove(id);
+            self.last_success.remove(id);
+            self.paid_through.remove(id);
+        }
+        self.evicted.extend(candidates.iter().copied());
+        candidates
+    }
+
+    /// Sort key for `EvictionPolicy::FurthestFromDue`; lower is evicted first.
+    fn eviction_rank(&self, id: u64, now: u64) -> (u8, Reverse<u64>) {
+        let failures = self.retries.get(&id).map_or(0, |r| r.consecutive_failures);
+        if failures >= EVICT_FAILING_AFTER {
+            return (0, Reverse(u64::from(failures)));
+        }
+        match (self.paid_through.get(&id), self.last_success.get(&id)) {
+            (Some(&paid_through), _) if paid_through > now => (1, Reverse(paid_through)),
+            // Collected, and the next paidThrough not read yet: a whole period away.
+            (None, Some(&at)) => (2, Reverse(at)),
+            // Overdue, or never seen due: keep.
+            (paid_through, _) => (3, Reverse(paid_through.copied().unwrap_or(0))),
+        }
+    }
+
+    /// The next `n` evicted ids to re-check, rotated to the back of `evicted` so every evicted id
+    /// gets its turn.
+    pub fn evicted_batch(&mut self, n: usize) -> Vec<u64> {
+        let n = n.min(self.evicted.len());
+        let batch: Vec<u64> = self.evicted.drain(..n).collect();
+        self.evicted.extend(batch.iter().copied());
+        batch
+    }
+
+    /// Track `ids` again (after an eviction) and drop them from `evicted`.
+    pub fn readmit(&mut self, ids: &[u64]) {
+        if ids.is_empty() {
+            return;
+        }
+        let readmitted: BTreeSet<u64> = ids.iter().copied().collect();
+        self.evicted.retain(|id| !readmitted.contains(id));
+        let mut tracked = self.ids_set();
+        tracked.extend(readmitted);
+        self.set_ids_from_set(tracked);
     }
 
     pub fn note_failure(
@@ -187,37 +402,138 @@ impl KeeperState {
         reason: Option<String>,
     ) {
         let entry = self.retries.entry(subscription_id).or_default();
-        entry.consecutive_failures = entry.consecutive_failures.saturating_add(1);
+        entry.consecutive_failures = entry
+            .consecutive_failures
+            .saturating_add(1)
+            .min(MAX_CONSECUTIVE_FAILURES);
         entry.next_retry_at = next_retry_at;
         entry.last_failure_kind = kind;
         // Keep the reason small to avoid bloating state.
-        entry.last_failure_reason = reason.map(|s| {
-            const MAX: usize = 240;
-            // Avoid slicing by bytes (can panic on non-UTF8-boundary indices).
-            let mut out: String = s.chars().take(MAX).collect();
-            if out.len() < s.len() {
-                out.push_str("...");
+        entry.last_failure_reason = reason.map(|s| truncate_reason(&s));
+    }
+
+    /// Drop retry entries for ids no longer in `subscription_ids` and clamp what the rest store.
+    ///
+    /// Older state files (or ones carried over from another deployment) can hold unbounded
+    /// failure counts, long reasons and entries for ids the keeper no longer tracks. Returns the
+    /// number of pruned entries.
+    pub fn compact(&mut self) -> usize {
+        let ids = self.ids_set();
+        let before = self.retries.len();
+        self.retries.retain(|id, _| ids.contains(id));
+        self.paid_through.retain(|id, _| ids.contains(id));
+        self.tags
+            .retain(|id, _| ids.contains(id) || self.evicted.contains(id));
+
+        for retry in self.retries.values_mut() {
+            retry.consecutive_failures = retry.consecutive_failures.min(MAX_CONSECUTIVE_FAILURES);
+            // Reasons written by `note_failure` are already short; only re-truncate oversize ones so
+            // compaction stays idempotent.
+            if let Some(reason) = retry.last_failure_reason.as_mut() {
+                if reason.chars().count() > MAX_FAILURE_REASON_CHARS + TRUNCATED_MARKER.len() {
+                    *reason = truncate_reason(reason);
+                }
             }
-            out
-        });
+        }
+
+        before - self.retries.len()
+    }
+
+    /// `--tag-id`: tag a subscription. Returns false if it already had `tag`.
+    pub fn add_tag(&mut self, subscription_id: u64, tag: &str) -> bool {
+        let tags = self.tags.entry(subscription_id).or_default();
+        match tags.binary_search_by(|t| t.as_str().cmp(tag)) {
+            Ok(_) => false,
+            Err(at) => {
+                tags.insert(at, tag.to_string());
+                true
+            }
+        }
     }
 
+    /// `--untag-id`: remove `tag` from a subscription, or all of its tags when `tag` is `None`.
+    /// Returns whether anything was removed.
+    pub fn remove_tag(&mut self, subscription_id: u64, tag: Option<&str>) -> bool {
+        let Some(tags) = self.tags.get_mut(&subscription_id) else {
+            return false;
+        };
+        let removed = match tag {
+            Some(tag) => {
+                let before = tags.len();
+                tags.retain(|t| t != tag);
+                tags.len() != before
+            }
+            None => !tags.is_empty(),
+        };
+        if tag.is_none() || tags.is_empty() {
+            self.tags.remove(&subscription_id);
+        }
+        removed
+    }
+
+    pub fn has_tag(&self, subscription_id: u64, tag: &str) -> bool {
+        self.tags
+            .get(&subscription_id)
+            .is_some_and(|tags| tags.iter().any(|t| t == tag))
+    }
+
+    /// Record gas paid for mined collect() txs (zero amounts are not stored).
+    pub fn record_gas_spend(&mut self, at: u64, wei: U256) {
+        if !wei.is_zero() {
+            self.gas_spend.push(GasSpend { at, wei });
+        }
+    }
+
+    /// Gas spent in the `GAS_SPEND_WINDOW_SECS` before `now`, pruning entries that fell out of it.
+    pub fn rolling_gas_spend(&mut self, now: u64) -> U256 {
+        let window_start = now.saturating_sub(GAS_SPEND_WINDOW_SECS);
+        self.gas_spend.retain(|g| g.at > window_start);
+        self.gas_spend
+            .iter()
+            .fold(U256::zero(), |acc, g| acc.saturating_add(g.wei))
+    }
+
+    /// Clear in-flight txs that finalized, expired, or were dropped.
+    ///
+    /// With `opensub` set, entries that recorded a `sent_block` are first matched against a
+    /// batched `Charged` log query (split into `log_chunk_size` block ranges); only the rest fall
+    /// back to per-tx receipt calls.
+    ///
+    /// With `confirmations > 1`, successful receipts move to `confirming` instead of finalizing.
+    ///
+    /// Gas paid is recorded for every receipt fetched. A `Charged` match needs no receipt, so one
+    /// is fetched for its gas only with `charged_gas` (i.e. when a gas budget is configured).
     pub async fn reconcile_in_flight<M: Middleware>(
         &mut self,
         client: &M,
         ttl: Duration,
+        opensub: Option<Address>,
+        log_chunk_size: u64,
+        confirmations: u64,
+        charged_gas: bool,
     ) -> Result<ReconcileOutcome> {
         if self.in_flight.is_empty() {
             return Ok(ReconcileOutcome::default());
         }
 
+        let charged = match opensub {
+            Some(opensub) => {
+                charged_tx_hashes(client, opensub, &self.in_flight, log_chunk_size).await
+            }
+            None => BTreeMap::new(),
+        };
+
         let now = now_unix();
         let ttl_s = ttl.as_secs();
 
         let mut kept = BTreeMap::new();
         let mut cleared = 0usize;
-        let mut finalized_success = Vec::<u64>::new();
-        let mut finalized_revert = Vec::<u64>::new();
+        let mut finalized = Finalized::new(confirmations);
+        // Mined nonce per sender, fetched at most once per reconcile.
+        let mut mined_nonces = BTreeMap::<Address, u64>::new();
+        // Gas paid by the txs mined here, each counted once (an EIP-7702 batch tx settles many ids).
+        let mut gas_spent = U256::zero();
+        let mut gas_counted = BTreeSet::<H256>::new();
 
         for (sub_id, inflight) in self.in_flight.iter() {
             // Drop very old pending txs so the keeper can retry.
@@ -246,25 +562,108 @@ impl KeeperState {
                 }
             };
 
+            // A `Charged` log for this subscription emitted by this very tx means it succeeded.
+            if let Some(block) = charged.get(&(*sub_id, tx_hash)) {
+                tracing::info!(
+                    subscription_id = *sub_id,
+                    tx = %inflight.tx_hash,
+                    block = *block,
+                    "in-flight tx matched a Charged log; clearing"
+                );
+                finalized.mined(*sub_id, inflight, true, *block);
+                cleared += 1;
+                // The log settles the outcome; the receipt is only needed for the gas paid.
+                if charged_gas && !gas_counted.contains(&tx_hash) {
+                    match client.get_transaction_receipt(tx_hash).await {
+                        Ok(Some(rcpt)) => {
+                            gas_counted.insert(tx_hash);
+                            gas_spent = gas_spent.saturating_add(receipt_gas_cost(&rcpt));
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            tracing::warn!(
+                                subscription_id = *sub_id,
+                                tx = %inflight.tx_hash,
+                                error = %err,
+                                "failed to fetch receipt for the gas paid by a Charged-matched tx"
+                            );
+                        }
+                    }
+                }
+                continue;
+            }
+
             match client.get_transaction_receipt(tx_hash).await {
                 Ok(Some(rcpt)) => {
-                    let status = rcpt.status.unwrap_or_default().as_u64();
-                    tracing::info!(
-                        subscription_id = *sub_id,
-                        tx = %inflight.tx_hash,
-                        status,
-                        block = rcpt.block_number.map(|b| b.as_u64()),
-                        "in-flight tx finalized; clearing"
-                    );
-                    if status == 1 {
-                        finalized_success.push(*sub_id);
-                    } else {
-                        finalized_revert.push(*sub_id);
+                    if gas_counted.insert(tx_hash) {
+                        gas_spent = gas_spent.saturating_add(receipt_gas_cost(&rcpt));
                     }
+                    let ok = receipt_succeeded(client, &rcpt).await;
+                    finalize_receipt(*sub_id, inflight, &rcpt, ok, &mut finalized);
                     cleared += 1;
                 }
                 Ok(None) => {
-                    kept.insert(*sub_id, inflight.clone());
+                    // No receipt yet. If the sender's mined nonce already moved past this tx, it
+                    // was dropped or replaced and will never be mined: clear it early.
+                    let superseded = match inflight_sender(inflight) {
+                        Some((from, nonce)) => {
+                            let mined = match mined_nonces.get(&from) {
+                                Some(n) => Some(*n),
+                                None => match client.get_transaction_count(from, None).await {
+                                    Ok(n) => {
+                                        let n = n.low_u64();
+                                        mined_nonces.insert(from, n);
+                                        Some(n)
+                                    }
+                                    Err(err) => {
+                                        tracing::warn!(
+                                            from = ?from,
+                                            error = %err,
+                                            "failed to fetch sender nonce for in-flight reconcile"
+                                        );
+                                        None
+                                    }
+                                },
+                            };
+                            mined.map(|m| nonce_superseded(m, nonce)).unwrap_or(false)
+                        }
+                        None => false,
+                    };
+
+                    if !superseded {
+                        kept.insert(*sub_id, inflight.clone());
+                        continue;
+                    }
+
+                    // The tx may have been mined between the two calls; check once more.
+                    match client.get_transaction_receipt(tx_hash).await {
+                        Ok(Some(rcpt)) => {
+                            if gas_counted.insert(tx_hash) {
+                                gas_spent = gas_spent.saturating_add(receipt_gas_cost(&rcpt));
+                            }
+                            let ok = receipt_succeeded(client, &rcpt).await;
+                            finalize_receipt(*sub_id, inflight, &rcpt, ok, &mut finalized);
+                            cleared += 1;
+                        }
+                        Ok(None) => {
+                            tracing::warn!(
+                                subscription_id = *sub_id,
+                                tx = %inflight.tx_hash,
+                                nonce = inflight.nonce,
+                                "sender nonce advanced past in-flight tx without a receipt (dropped or replaced); clearing"
+                            );
+                            cleared += 1;
+                        }
+                        Err(err) => {
+                            tracing::warn!(
+                                subscription_id = *sub_id,
+                                tx = %inflight.tx_hash,
+                                error = %err,
+                                "failed to fetch receipt for in-flight tx; keeping"
+                            );
+                            kept.insert(*sub_id, inflight.clone());
+                        }
+                    }
                 }
                 Err(err) => {
                     tracing::warn!(
@@ -279,17 +678,876 @@ impl KeeperState {
         }
 
         self.in_flight = kept;
+        self.confirming.extend(finalized.confirming);
+        self.record_gas_spend(now, gas_spent);
         Ok(ReconcileOutcome {
             cleared,
-            finalized_success,
-            finalized_revert,
+            finalized_success: finalized.success,
+            finalized_revert: finalized.revert,
         })
     }
+
+    /// Finalize `confirming` txs that reached `confirmations`.
+    ///
+    /// A tx whose receipt disappeared was reorged out: it goes back to `in_flight` (keeping its
+    /// original send time, so the pending TTL / nonce checks still apply).
+    pub async fn reconcile_confirming<M: Middleware>(
+        &mut self,
+        client: &M,
+        confirmations: u64,
+    ) -> Result<ReconcileOutcome> {
+        if self.confirming.is_empty() {
+            return Ok(ReconcileOutcome::default());
+        }
+
+        let head = match client.get_block_number().await {
+            Ok(b) => b.as_u64(),
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to fetch head for confirming txs; keeping");
+                return Ok(ReconcileOutcome::default());
+            }
+        };
+
+        let mut out = ReconcileOutcome::default();
+        let mut kept = BTreeMap::new();
+
+        for (sub_id, tx) in std::mem::take(&mut self.confirming) {
+            let Ok(tx_hash) = H256::from_str(&tx.tx_hash) else {
+                out.cleared += 1;
+                continue;
+            };
+
+            match client.get_transaction_receipt(tx_hash).await {
+                Ok(Some(rcpt)) => {
+                    let block = rcpt.block_number.map(|b| b.as_u64());
+                    if !receipt_succeeded(client, &rcpt).await {
+                        // Re-mined after a reorg, this time reverting.
+                        tracing::warn!(subscription_id = sub_id, tx = %tx.tx_hash, "confirming tx now reverted");
+                        out.finalized_revert.push(sub_id);
+                        out.cleared += 1;
+                    } else if block
+                        .map(|b| confirmations_reached(head, b, confirmations))
+                        .unwrap_or(false)
+                    {
+                        tracing::info!(
+                            subscription_id = sub_id,
+                            tx = %tx.tx_hash,
+                            block,
+                            confirmations,
+                            "collect tx confirmed"
+                        );
+                        out.finalized_success.push(sub_id);
+                        out.cleared += 1;
+                    } else {
+                        kept.insert(
+                            sub_id,
+                            InFlightTx {
+                                mined_block: block,
+                                ..tx
+                            },
+                        );
+                    }
+                }
+                Ok(None) => {
+                    tracing::warn!(
+                        subscription_id = sub_id,
+                        tx = %tx.tx_hash,
+                        mined_block = tx.mined_block,
+                        "confirming tx receipt disappeared (reorg); tracking as in-flight again"
+                    );
+                    self.in_flight.insert(
+                        sub_id,
+                        InFlightTx {
+                            mined_block: None,
+                            ..tx
+                        },
+                    );
+                    out.cleared += 1;
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        subscription_id = sub_id,
+                        tx = %tx.tx_hash,
+                        error = %err,
+                        "failed to fetch receipt for confirming tx; keeping"
+                    );
+                    kept.insert(sub_id, tx);
+                }
+            }
+        }
+
+        self.confirming = kept;
+        Ok(out)
+    }
 }
 
-fn now_unix() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_else(|_| Duration::from_secs(0))
-        .as_secs()
+/// `(subscriptionId, txHash) -> block` of `Charged` events emitted since the oldest `sent_block`,
+/// queried `log_chunk_size` blocks at a time like the scanner.
+///
+/// Best-effort: on RPC failure whatever was not matched yet falls back to per-tx receipts.
+async fn charged_tx_hashes<M: Middleware>(
+    client: &M,
+    opensub: Address,
+    in_flight: &BTreeMap<u64, InFlightTx>,
+    log_chunk_size: u64,
+) -> BTreeMap<(u64, H256), Option<u64>> {
+    let mut out = BTreeMap::new();
+    let Some(from_block) = in_flight.values().filter_map(|t| t.sent_block).min() else {
+        return out;
+    };
+    let ids: Vec<H256> = in_flight
+        .iter()
+        .filter(|(_, t)| t.sent_block.is_some())
+        .map(|(id, _)| H256::from_low_u64_be(*id))
+        .collect();
+
+    let head = match client.get_block_number().await {
+        Ok(b) => b.as_u64(),
+        Err(err) => {
+            tracing::warn!(
+                error = %err,
+                "failed to fetch head for the Charged log query; falling back to per-tx receipts"
+            );
+            return out;
+        }
+    };
+
+    let chunk = log_chunk_size.max(1);
+    let mut cursor = from_block;
+    while cursor <= head {
+        let end = std::cmp::min(cursor.saturating_add(chunk - 1), head);
+        let filter = Filter::new()
+            .address(opensub)
+            .topic0(ChargedFilter::signature())
+            .topic1(ids.clone())
+            .from_block(cursor)
+            .to_block(end);
+
+        match client.get_logs(&filter).await {
+            Ok(logs) => {
+                for log in logs {
+                    let (Some(topic), Some(tx_hash)) = (log.topics.get(1), log.transaction_hash)
+                    else {
+                        continue;
+                    };
+                    let id = U256::from_big_endian(topic.as_bytes());
+                    if id <= U256::from(u64::MAX) {
+                        out.insert((id.as_u64(), tx_hash), log.block_number.map(|b| b.as_u64()));
+                    }
+                }
+            }
+            Err(err) => {
+                tracing::warn!(
+                    from_block = cursor,
+                    to_block = end,
+                    error = %err,
+                    "batched Charged log query failed; falling back to per-tx receipts"
+                );
+                break;
+            }
+        }
+        cursor = end.saturating_add(1);
+    }
+    out
+}
+
+fn finalize_receipt(
+    subscription_id: u64,
+    inflight: &InFlightTx,
+    rcpt: &TransactionReceipt,
+    ok: bool,
+    finalized: &mut Finalized,
+) {
+    let block = rcpt.block_number.map(|b| b.as_u64());
+    tracing::info!(
+        subscription_id,
+        tx = %inflight.tx_hash,
+        ok,
+        block,
+        "in-flight tx finalized; clearing"
+    );
+    finalized.mined(subscription_id, inflight, ok, block);
+}
+
+/// Where in-flight txs that got mined end up during one reconcile.
+struct Finalized {
+    confirmations: u64,
+    success: Vec<u64>,
+    revert: Vec<u64>,
+    /// Successful txs still short of `confirmations`; moved to `KeeperState::confirming`.
+    confirming: BTreeMap<u64, InFlightTx>,
+}
+
+impl Finalized {
+    fn new(confirmations: u64) -> Self {
+        Self {
+            confirmations,
+            success: Vec::new(),
+            revert: Vec::new(),
+            confirming: BTreeMap::new(),
+        }
+    }
+
+    fn mined(&mut self, subscription_id: u64, inflight: &InFlightTx, ok: bool, block: Option<u64>) {
+        if !ok {
+            self.revert.push(subscription_id);
+        } else if self.confirmations > 1 {
+            let mut tx = inflight.clone();
+            tx.mined_block = block;
+            self.confirming.insert(subscription_id, tx);
+        } else {
+            self.success.push(subscription_id);
+        }
+    }
+}
+
+/// Native token paid for a mined tx: `gasUsed * effectiveGasPrice` (zero if the node omits either).
+pub fn receipt_gas_cost(rcpt: &TransactionReceipt) -> U256 {
+    match (rcpt.gas_used, rcpt.effective_gas_price) {
+        (Some(used), Some(price)) => used.saturating_mul(price),
+        _ => U256::zero(),
+    }
+}
+
+/// Whether a mined tx succeeded.
+///
+/// Receipts normally carry `status`. Some (pre-Byzantium-style, a few L2s) omit it, and treating
+/// that as a revert would back off a subscription that was in fact charged. Without `status`:
+/// - any log means success (a reverted tx keeps none; a successful collect() emits `Charged`);
+/// - `gasUsed` equal to the tx gas limit means it ran out of gas;
+/// - otherwise a `callTracer` trace decides, and without one (no debug namespace) a tx with no
+///   logs is taken as reverted.
+pub async fn receipt_succeeded<M: Middleware>(client: &M, rcpt: &TransactionReceipt) -> bool {
+    if let Some(status) = rcpt.status {
+        return status.as_u64() == 1;
+    }
+    let tx_hash = rcpt.transaction_hash;
+    if !rcpt.logs.is_empty() {
+        tracing::info!(tx = ?tx_hash, "receipt has no status; logs present, treating as success");
+        return true;
+    }
+
+    match client.get_transaction(tx_hash).await {
+        Ok(Some(tx)) if rcpt.gas_used.is_some_and(|used| used >= tx.gas) => {
+            tracing::info!(tx = ?tx_hash, gas = %tx.gas, "receipt has no status; all gas used, treating as reverted");
+            return false;
+        }
+        Ok(_) => {}
+        Err(err) => {
+            tracing::debug!(tx = ?tx_hash, error = %err, "failed to fetch tx for statusless receipt");
+        }
+    }
+
+    let options = GethDebugTracingOptions {
+        tracer: Some(GethDebugTracerType::BuiltInTracer(
+            GethDebugBuiltInTracerType::CallTracer,
+        )),
+        ..Default::default()
+    };
+    match client.debug_trace_transaction(tx_hash, options).await {
+        Ok(trace) => {
+            let error = match trace {
+                GethTrace::Known(GethTraceFrame::CallTracer(frame)) => frame.error,
+                GethTrace::Known(_) => None,
+                GethTrace::Unknown(value) => value
+                    .get("error")
+                    .and_then(|e| e.as_str())
+                    .map(str::to_string),
+            };
+            tracing::info!(tx = ?tx_hash, error = ?error, "receipt has no status; decided by callTracer trace");
+            error.is_none()
+        }
+        Err(err) => {
+            tracing::warn!(tx = ?tx_hash, error = %err, "receipt has no status and no logs, and the trace failed; treating as reverted");
+            false
+        }
+    }
+}
+
+/// Cut a failure reason to `MAX_FAILURE_REASON_CHARS` characters, marking it when truncated.
+fn truncate_reason(s: &str) -> String {
+    // Avoid slicing by bytes (can panic on non-UTF8-boundary indices).
+    let mut out: String = s.chars().take(MAX_FAILURE_REASON_CHARS).collect();
+    if out.len() < s.len() {
+        out.push_str(TRUNCATED_MARKER);
+    }
+    out
+}
+
+/// Whether a tx mined in `mined_block` has `required` confirmations at `head` (the inclusion block
+/// counts as the first).
+fn confirmations_reached(head: u64, mined_block: u64, required: u64) -> bool {
+    head >= mined_block && head - mined_block + 1 >= required
+}
+
+fn new_tracked_tx(
+    tx_hash: H256,
+    nonce: Option<U256>,
+    from: Option<Address>,
+    sent_block: Option<u64>,
+    mined_block: Option<u64>,
+) -> InFlightTx {
+    InFlightTx {
+        tx_hash: format!("{:#x}", tx_hash),
+        sent_at: now_unix(),
+        nonce: nonce
+            .filter(|n| *n <= U256::from(u64::MAX))
+            .map(|n| n.as_u64()),
+        from: from.map(|a| format!("{:#x}", a)),
+        sent_block,
+        mined_block,
+    }
+}
+
+/// Sender + nonce recorded for an in-flight tx (older state files have neither).
+fn inflight_sender(inflight: &InFlightTx) -> Option<(Address, u64)> {
+    let from = Address::from_str(inflight.from.as_deref()?).ok()?;
+    Some((from, inflight.nonce?))
+}
+
+/// A tx with `tx_nonce` can no longer be mined once the sender's mined nonce count is past it.
+fn nonce_superseded(mined_nonce: u64, tx_nonce: u64) -> bool {
+    mined_nonce > tx_nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        confirmations_reached, nonce_superseded, now_unix, receipt_gas_cost, receipt_succeeded,
+        EvictionPolicy, FailureKind, KeeperState, EVICT_FAILING_AFTER, GAS_SPEND_WINDOW_SECS,
+        MAX_CONSECUTIVE_FAILURES,
+    };
+    use crate::opensub::ChargedFilter;
+    use ethers::contract::EthEvent;
+    use ethers::providers::{Middleware, Provider};
+    use ethers::types::{Address, Log, Transaction, TransactionReceipt, H256, U256, U64};
+    use std::time::Duration;
+
+    fn state_with_in_flight(nonce: u64, from: Address) -> KeeperState {
+        let mut st = KeeperState {
+            last_scanned_block: 0,
+            subscription_ids: vec![1],
+            in_flight: Default::default(),
+            confirming: Default::default(),
+            retries: Default::default(),
+            last_success: Default::default(),
+            gas_spend: Default::default(),
+            paid_through: Default::default(),
+            tags: Default::default(),
+            evicted: Default::default(),
+        };
+        st.mark_in_flight(
+            1,
+            H256::repeat_byte(0x42),
+            Some(U256::from(nonce)),
+            Some(from),
+            None,
+        );
+        st
+    }
+
+    #[test]
+    fn nonce_superseded_only_when_mined_nonce_is_past_tx() {
+        assert!(!nonce_superseded(5, 5));
+        assert!(!nonce_superseded(4, 5));
+        assert!(nonce_superseded(6, 5));
+    }
+
+    #[tokio::test]
+    async fn reconcile_clears_tx_when_sender_nonce_advanced_past_it() {
+        let (provider, mock) = Provider::mocked();
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+
+        // Responses are popped from the back: receipt (none), nonce (6), receipt re-check (none).
+        mock.push::<Option<()>, _>(None).unwrap();
+        mock.push::<U256, _>(U256::from(6)).unwrap();
+        mock.push::<Option<()>, _>(None).unwrap();
+
+        let out = st
+            .reconcile_in_flight(&provider, Duration::from_secs(900), None, 2000, 1, false)
+            .await
+            .unwrap();
+
+        assert_eq!(out.cleared, 1);
+        assert!(out.finalized_success.is_empty());
+        assert!(out.finalized_revert.is_empty());
+        assert!(st.in_flight.is_empty());
+        assert!(
+            provider.get_block_number().await.is_err(),
+            "all responses consumed"
+        );
+    }
+
+    #[tokio::test]
+    async fn reconcile_keeps_tx_while_sender_nonce_has_not_reached_it() {
+        let (provider, mock) = Provider::mocked();
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+
+        mock.push::<U256, _>(U256::from(5)).unwrap();
+        mock.push::<Option<()>, _>(None).unwrap();
+
+        let out = st
+            .reconcile_in_flight(&provider, Duration::from_secs(900), None, 2000, 1, false)
+            .await
+            .unwrap();
+
+        assert_eq!(out.cleared, 0);
+        assert_eq!(st.in_flight.len(), 1);
+    }
+
+    #[test]
+    fn exceeding_the_tracked_cap_evicts_by_policy() {
+        let now = 5_000;
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+        st.subscription_ids = vec![1, 2, 3, 4, 5, 6, 7];
+        // 1 has a tx in flight. 2 was collected long ago and 5 recently; 3 is overdue; 4 has a
+        // paidThrough coming up; 6 was never seen due; 7 is overdue but keeps failing.
+        st.last_success.insert(2, 1_000);
+        st.paid_through.insert(3, 2_000);
+        st.paid_through.insert(4, 9_000);
+        st.last_success.insert(5, 4_000);
+        st.paid_through.insert(7, 1_500);
+        st.note_failure(3, FailureKind::InsufficientBalance, 3_000, None);
+        for _ in 0..EVICT_FAILING_AFTER {
+            st.note_failure(7, FailureKind::InsufficientBalance, 3_000, None);
+        }
+        st.add_tag(4, "vip");
+
+        let mut far = st.clone();
+        // Under the cap: nothing happens.
+        assert!(far
+            .evict_to(7, EvictionPolicy::FurthestFromDue, now)
+            .is_empty());
+        assert_eq!(
+            far.evict_to(3, EvictionPolicy::FurthestFromDue, now),
+            vec![7, 4, 5, 2]
+        );
+        // The overdue and the never-collected ids are kept.
+        assert_eq!(far.subscription_ids, vec![1, 3, 6]);
+        assert_eq!(far.evicted, vec![7, 4, 5, 2]);
+        // Evicted ids leave no per-id state behind, except their tags.
+        assert!(!far.retries.contains_key(&7));
+        assert!(!far.last_success.contains_key(&2));
+        assert!(!far.paid_through.contains_key(&4));
+        far.compact();
+        assert_eq!(far.tags[&4], vec!["vip"]);
+
+        let mut oldest = st.clone();
+        assert_eq!(
+            oldest.evict_to(3, EvictionPolicy::Oldest, now),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(oldest.subscription_ids, vec![1, 6, 7]);
+
+        // In-flight ids are kept even when that leaves the set over the cap.
+        let mut tight = st;
+        assert_eq!(
+            tight.evict_to(0, EvictionPolicy::Oldest, now),
+            vec![2, 3, 4, 5, 6, 7]
+        );
+        assert_eq!(tight.subscription_ids, vec![1]);
+    }
+
+    #[test]
+    fn evicted_ids_are_rechecked_in_turn_and_readmitted() {
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+        st.subscription_ids = vec![1, 2, 3, 4, 5];
+        st.add_tag(3, "vip");
+        assert_eq!(st.evict_to(1, EvictionPolicy::Oldest, 0), vec![2, 3, 4, 5]);
+
+        // Batches rotate through the evicted ids.
+        assert_eq!(st.evicted_batch(3), vec![2, 3, 4]);
+        assert_eq!(st.evicted_batch(3), vec![5, 2, 3]);
+        assert_eq!(st.evicted, vec![4, 5, 2, 3]);
+
+        // A readmitted id is tracked again, with its tags.
+        st.readmit(&[3]);
+        assert_eq!(st.subscription_ids, vec![1, 3]);
+        assert_eq!(st.evicted, vec![4, 5, 2]);
+        assert!(st.has_tag(3, "vip"));
+    }
+
+    #[tokio::test]
+    async fn statusless_receipt_resolves_from_logs_gas_and_trace() {
+        let tx_hash = H256::repeat_byte(0x42);
+        let statusless = |logs: Vec<Log>| TransactionReceipt {
+            transaction_hash: tx_hash,
+            block_number: Some(U64::from(100)),
+            gas_used: Some(U256::from(60_000)),
+            status: None,
+            logs,
+            ..Default::default()
+        };
+
+        // Logs present: the in-flight collect is finalized as a success, not a revert.
+        let (provider, mock) = Provider::mocked();
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+        let log = Log {
+            address: Address::repeat_byte(0x01),
+            topics: vec![ChargedFilter::signature()],
+            ..Default::default()
+        };
+        mock.push(statusless(vec![log])).unwrap();
+        let out = st
+            .reconcile_in_flight(&provider, Duration::from_secs(900), None, 2000, 1, false)
+            .await
+            .unwrap();
+        assert_eq!(out.finalized_success, vec![1]);
+        assert!(out.finalized_revert.is_empty());
+
+        // No logs and every unit of gas used: out of gas.
+        let (provider, mock) = Provider::mocked();
+        mock.push(Transaction {
+            gas: U256::from(60_000),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!receipt_succeeded(&provider, &statusless(vec![])).await);
+
+        // No logs, gas to spare: the callTracer trace decides.
+        let trace = |error: Option<&str>| {
+            serde_json::json!({
+                "type": "CALL",
+                "from": Address::repeat_byte(0x0a),
+                "to": Address::repeat_byte(0x01),
+                "gas": "0x186a0",
+                "gasUsed": "0xea60",
+                "input": "0x",
+                "error": error,
+            })
+        };
+        for (error, ok) in [(None, true), (Some("execution reverted"), false)] {
+            let (provider, mock) = Provider::mocked();
+            mock.push(trace(error)).unwrap();
+            mock.push(Transaction {
+                gas: U256::from(100_000),
+                ..Default::default()
+            })
+            .unwrap();
+            assert_eq!(receipt_succeeded(&provider, &statusless(vec![])).await, ok);
+        }
+
+        // A receipt with `status` never takes the fallback.
+        let (provider, _mock) = Provider::mocked();
+        let mut with_status = statusless(vec![]);
+        with_status.status = Some(U64::from(1));
+        assert!(receipt_succeeded(&provider, &with_status).await);
+    }
+
+    #[tokio::test]
+    async fn reconcile_clears_tx_matched_by_charged_log() {
+        let opensub = Address::repeat_byte(0x01);
+        let tx_hash = H256::repeat_byte(0x42);
+        let log = Log {
+            address: opensub,
+            topics: vec![
+                ChargedFilter::signature(),
+                H256::from_low_u64_be(1),
+                H256::from_low_u64_be(1),
+                H256::from(Address::repeat_byte(0xaa)),
+            ],
+            transaction_hash: Some(tx_hash),
+            ..Default::default()
+        };
+        let receipt = TransactionReceipt {
+            transaction_hash: tx_hash,
+            gas_used: Some(U256::from(50_000)),
+            effective_gas_price: Some(U256::from(3)),
+            ..Default::default()
+        };
+
+        for charged_gas in [true, false] {
+            let (provider, mock) = Provider::mocked();
+            let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+            st.in_flight.get_mut(&1).unwrap().sent_block = Some(100);
+
+            // The head, the batched log query, then the receipt for the gas paid. Responses pop
+            // from the back.
+            mock.push(receipt.clone()).unwrap();
+            mock.push::<Vec<Log>, _>(vec![log.clone()]).unwrap();
+            mock.push(U64::from(150)).unwrap();
+
+            let out = st
+                .reconcile_in_flight(
+                    &provider,
+                    Duration::from_secs(900),
+                    Some(opensub),
+                    2000,
+                    1,
+                    charged_gas,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(out.cleared, 1);
+            assert_eq!(out.finalized_success, vec![1]);
+            assert!(st.in_flight.is_empty());
+            if charged_gas {
+                assert_eq!(st.rolling_gas_spend(now_unix()), U256::from(150_000));
+            } else {
+                // Without a gas budget the log alone settles the tx; the receipt is never fetched.
+                assert_eq!(st.rolling_gas_spend(now_unix()), U256::zero());
+                assert!(provider
+                    .get_transaction_receipt(tx_hash)
+                    .await
+                    .unwrap()
+                    .is_some());
+            }
+            assert!(
+                provider.get_block_number().await.is_err(),
+                "all responses consumed"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn gas_of_a_batch_tx_is_counted_once() {
+        let (provider, mock) = Provider::mocked();
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+        // One EIP-7702 batch tx settled both subscriptions.
+        st.mark_in_flight(2, H256::repeat_byte(0x42), Some(U256::from(5)), None, None);
+
+        for _ in 0..2 {
+            mock.push(TransactionReceipt {
+                transaction_hash: H256::repeat_byte(0x42),
+                status: Some(U64::from(1)),
+                gas_used: Some(U256::from(80_000)),
+                effective_gas_price: Some(U256::from(2)),
+                ..Default::default()
+            })
+            .unwrap();
+        }
+
+        let out = st
+            .reconcile_in_flight(&provider, Duration::from_secs(900), None, 2000, 1, false)
+            .await
+            .unwrap();
+
+        assert_eq!(out.finalized_success, vec![1, 2]);
+        assert_eq!(st.rolling_gas_spend(now_unix()), U256::from(160_000));
+    }
+
+    #[tokio::test]
+    async fn charged_log_query_is_split_into_log_chunk_ranges() {
+        let (provider, mock) = Provider::mocked();
+        let opensub = Address::repeat_byte(0x01);
+        let tx_hash = H256::repeat_byte(0x42);
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+        st.in_flight.get_mut(&1).unwrap().sent_block = Some(100);
+
+        let log = Log {
+            address: opensub,
+            topics: vec![
+                ChargedFilter::signature(),
+                H256::from_low_u64_be(1),
+                H256::from_low_u64_be(1),
+                H256::from(Address::repeat_byte(0xaa)),
+            ],
+            transaction_hash: Some(tx_hash),
+            ..Default::default()
+        };
+        // Head 4100 with 2000-block chunks: 100..=2099, 2100..=4099, 4100..=4100. The log is only
+        // in the last range. Responses pop from the back.
+        mock.push::<Vec<Log>, _>(vec![log]).unwrap();
+        mock.push::<Vec<Log>, _>(vec![]).unwrap();
+        mock.push::<Vec<Log>, _>(vec![]).unwrap();
+        mock.push(U64::from(4100)).unwrap();
+
+        let out = st
+            .reconcile_in_flight(
+                &provider,
+                Duration::from_secs(900),
+                Some(opensub),
+                2000,
+                1,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(out.finalized_success, vec![1]);
+        assert!(st.in_flight.is_empty());
+        assert!(
+            provider.get_block_number().await.is_err(),
+            "one query per chunk, all responses consumed"
+        );
+    }
+
+    #[test]
+    fn tags_are_added_removed_and_forgotten_with_the_id() {
+        let mut st = state_with_in_flight(0, Address::zero());
+        st.subscription_ids = vec![1, 2, 3];
+
+        assert!(st.add_tag(1, "vip"));
+        assert!(st.add_tag(1, "disputed"));
+        assert!(!st.add_tag(1, "vip"), "already tagged");
+        assert_eq!(st.tags[&1], vec!["disputed", "vip"]);
+        assert!(st.has_tag(1, "vip"));
+        assert!(!st.has_tag(2, "vip"));
+
+        assert!(st.remove_tag(1, Some("vip")));
+        assert!(!st.remove_tag(1, Some("vip")), "already removed");
+        assert_eq!(st.tags[&1], vec!["disputed"]);
+        // Removing the last tag drops the entry.
+        assert!(st.remove_tag(1, Some("disputed")));
+        assert!(!st.tags.contains_key(&1));
+
+        // Without a tag, every tag of the id goes.
+        st.add_tag(2, "a");
+        st.add_tag(2, "b");
+        assert!(st.remove_tag(2, None));
+        assert!(!st.remove_tag(2, None));
+        assert!(st.tags.is_empty());
+
+        // Tags go with the id.
+        st.add_tag(3, "vip");
+        st.forget_subscription(3);
+        assert!(st.tags.is_empty());
+    }
+
+    #[test]
+    fn compaction_prunes_stale_retries_and_caps_counters() {
+        let mut st = state_with_in_flight(0, Address::zero());
+        st.subscription_ids = vec![1, 2];
+        for id in [1, 2, 3, 4] {
+            st.note_failure(
+                id,
+                FailureKind::InsufficientBalance,
+                100,
+                Some("x".repeat(300)),
+            );
+        }
+        // As written by an older keeper: unbounded count, untruncated reason.
+        let legacy = st.retries.get_mut(&2).unwrap();
+        legacy.consecutive_failures = 10_000;
+        legacy.last_failure_reason = Some("y".repeat(1_000));
+
+        assert_eq!(st.compact(), 2);
+        assert_eq!(st.retries.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(
+            st.retries[&2].consecutive_failures,
+            MAX_CONSECUTIVE_FAILURES
+        );
+        assert_eq!(
+            st.retries[&2].last_failure_reason.as_ref().unwrap().len(),
+            243
+        );
+
+        // Already-compact state is left alone.
+        let before = st.retries[&1].last_failure_reason.clone();
+        assert_eq!(st.compact(), 0);
+        assert_eq!(st.retries[&1].last_failure_reason, before);
+
+        // note_failure itself never counts past the cap.
+        for _ in 0..5 {
+            st.note_failure(2, FailureKind::InsufficientBalance, 100, None);
+        }
+        assert_eq!(
+            st.retries[&2].consecutive_failures,
+            MAX_CONSECUTIVE_FAILURES
+        );
+    }
+
+    #[test]
+    fn gas_spend_rolls_over_after_24h() {
+        let mut st = state_with_in_flight(0, Address::zero());
+        let t0 = 1_700_000_000u64;
+
+        let mut rcpt = TransactionReceipt {
+            gas_used: Some(U256::from(50_000)),
+            effective_gas_price: Some(U256::from(2_000_000_000u64)),
+            ..Default::default()
+        };
+        st.record_gas_spend(t0, receipt_gas_cost(&rcpt));
+        rcpt.gas_used = Some(U256::from(25_000));
+        st.record_gas_spend(t0 + 3_600, receipt_gas_cost(&rcpt));
+        // Nothing to account for (e.g. a node that omits effectiveGasPrice).
+        st.record_gas_spend(t0 + 3_600, U256::zero());
+        assert_eq!(st.gas_spend.len(), 2);
+
+        assert_eq!(
+            st.rolling_gas_spend(t0 + 3_600),
+            U256::from(150_000_000_000_000u64)
+        );
+        // Exactly 24h after the first spend it has left the window; the second remains.
+        assert_eq!(
+            st.rolling_gas_spend(t0 + GAS_SPEND_WINDOW_SECS),
+            U256::from(50_000_000_000_000u64)
+        );
+        assert_eq!(st.gas_spend.len(), 1);
+        assert_eq!(
+            st.rolling_gas_spend(t0 + 3_600 + GAS_SPEND_WINDOW_SECS),
+            U256::zero()
+        );
+        assert!(st.gas_spend.is_empty());
+    }
+
+    #[test]
+    fn confirmations_count_the_inclusion_block() {
+        assert!(confirmations_reached(100, 100, 1));
+        assert!(!confirmations_reached(101, 100, 3));
+        assert!(confirmations_reached(102, 100, 3));
+        // Head behind the mined block (lagging RPC node).
+        assert!(!confirmations_reached(99, 100, 1));
+    }
+
+    fn mined_receipt(block: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: H256::repeat_byte(0x42),
+            status: Some(U64::from(1)),
+            block_number: Some(U64::from(block)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn confirming_tx_becomes_confirmed_after_enough_blocks() {
+        let (provider, mock) = Provider::mocked();
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+
+        // In-flight -> mined at block 100 -> confirming (3 confirmations required).
+        mock.push::<TransactionReceipt, _>(mined_receipt(100))
+            .unwrap();
+        let out = st
+            .reconcile_in_flight(&provider, Duration::from_secs(900), None, 2000, 3, false)
+            .await
+            .unwrap();
+        assert!(out.finalized_success.is_empty());
+        assert!(st.in_flight.is_empty());
+        assert_eq!(st.confirming[&1].mined_block, Some(100));
+        assert!(st.is_tracked(1));
+
+        // Head 101: two confirmations, keep waiting. (Calls: head, then receipt.)
+        mock.push::<TransactionReceipt, _>(mined_receipt(100))
+            .unwrap();
+        mock.push::<U64, _>(U64::from(101)).unwrap();
+        let out = st.reconcile_confirming(&provider, 3).await.unwrap();
+        assert!(out.finalized_success.is_empty());
+        assert_eq!(st.confirming.len(), 1);
+
+        // Head 102: confirmed.
+        mock.push::<TransactionReceipt, _>(mined_receipt(100))
+            .unwrap();
+        mock.push::<U64, _>(U64::from(102)).unwrap();
+        let out = st.reconcile_confirming(&provider, 3).await.unwrap();
+        assert_eq!(out.finalized_success, vec![1]);
+        assert!(st.confirming.is_empty());
+        assert!(!st.is_tracked(1));
+    }
+
+    #[tokio::test]
+    async fn reorged_confirming_tx_goes_back_to_in_flight() {
+        let (provider, mock) = Provider::mocked();
+        let mut st = state_with_in_flight(5, Address::repeat_byte(0x0a));
+        let tx = st.in_flight.remove(&1).unwrap();
+        st.confirming.insert(1, tx);
+
+        mock.push::<Option<()>, _>(None).unwrap();
+        mock.push::<U64, _>(U64::from(101)).unwrap();
+        let out = st.reconcile_confirming(&provider, 3).await.unwrap();
+
+        assert!(out.finalized_success.is_empty());
+        assert!(st.confirming.is_empty());
+        assert_eq!(st.in_flight[&1].mined_block, None);
+    }
 }
@@ -0,0 +1,82 @@
+use crate::state::KeeperState;
+
+/// `--tag-id <id>=<tag>`.
+pub fn parse_tag(raw: &str) -> Result<(u64, String), String> {
+    let (id, tag) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected <id>=<tag>, got '{raw}'"))?;
+    Ok((parse_id(id)?, parse_tag_name(tag)?))
+}
+
+/// `--untag-id <id>=<tag>`, or `<id>` for all of its tags.
+pub fn parse_untag(raw: &str) -> Result<(u64, Option<String>), String> {
+    match raw.split_once('=') {
+        Some((id, tag)) => Ok((parse_id(id)?, Some(parse_tag_name(tag)?))),
+        None => Ok((parse_id(raw)?, None)),
+    }
+}
+
+fn parse_id(raw: &str) -> Result<u64, String> {
+    raw.trim()
+        .parse()
+        .map_err(|_| format!("invalid subscription id '{raw}'"))
+}
+
+/// Tags are free-form labels without whitespace (e.g. `vip`, `disputed`).
+fn parse_tag_name(raw: &str) -> Result<String, String> {
+    let tag = raw.trim();
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err(format!(
+            "invalid tag '{raw}': expected a non-empty label without spaces"
+        ));
+    }
+    Ok(tag.to_string())
+}
+
+/// `--only-tag`: whether `subscription_id` takes part in the cycle.
+pub fn selected(state: &KeeperState, only_tag: Option<&str>, subscription_id: u64) -> bool {
+    only_tag.is_none_or(|tag| state.has_tag(subscription_id, tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_tag, parse_untag, selected};
+    use crate::state::KeeperState;
+
+    #[test]
+    fn parses_tag_arguments() {
+        assert_eq!(parse_tag("7=vip"), Ok((7, "vip".to_string())));
+        assert_eq!(parse_tag(" 7 = vip "), Ok((7, "vip".to_string())));
+        assert_eq!(parse_untag("7=vip"), Ok((7, Some("vip".to_string()))));
+        assert_eq!(parse_untag("7"), Ok((7, None)));
+        for bad in ["7", "7=", "x=vip", "7=two words"] {
+            assert!(parse_tag(bad).is_err(), "{bad}");
+        }
+        assert!(parse_untag("x").is_err());
+    }
+
+    #[test]
+    fn only_tag_selects_tagged_ids() {
+        let mut state = KeeperState::load_or_init(
+            std::env::temp_dir().join("opensub-keeper-tags-missing.json"),
+            0,
+        )
+        .unwrap();
+        state.subscription_ids = vec![1, 2, 3];
+        state.add_tag(1, "vip");
+        state.add_tag(2, "disputed");
+
+        let pick = |tag| -> Vec<u64> {
+            state
+                .subscription_ids
+                .iter()
+                .copied()
+                .filter(|id| selected(&state, tag, *id))
+                .collect()
+        };
+        assert_eq!(pick(None), vec![1, 2, 3]);
+        assert_eq!(pick(Some("vip")), vec![1]);
+        assert_eq!(pick(Some("disputed")), vec![2]);
+        assert!(pick(Some("unknown")).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+use async_trait::async_trait;
+use ethers::providers::{Http, HttpClientError, JsonRpcClient, JsonRpcError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// How long the first queued `eth_call` waits for others to join its batch.
+const BATCH_WINDOW: Duration = Duration::from_millis(5);
+
+/// Most calls per batch request. Providers cap batch sizes (often at 100 or below).
+const MAX_BATCH_SIZE: usize = 50;
+
+/// Chain RPC transport: plain HTTP, or with `--json-rpc-batch` HTTP that coalesces concurrent
+/// `eth_call`s into JSON-RPC batch requests. Every other method goes out on its own.
+#[derive(Debug, Clone)]
+pub struct RpcTransport {
+    http: Http,
+    batcher: Option<Arc<Batcher>>,
+}
+
+impl RpcTransport {
+    pub fn new(url: reqwest::Url, client: reqwest::Client, batch: bool) -> Self {
+        let http = Http::new_with_client(url.clone(), client.clone());
+        let batcher = batch.then(|| {
+            Arc::new(Batcher {
+                http: http.clone(),
+                url,
+                client,
+                next_id: AtomicU64::new(1),
+                queue: Mutex::new(Vec::new()),
+            })
+        });
+        Self { http, batcher }
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcTransport {
+    type Error = HttpClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, HttpClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let batcher = match &self.batcher {
+            Some(b) if method == "eth_call" => b,
+            _ => return self.http.request(method, params).await,
+        };
+        let params = serde_json::to_value(params).map_err(|err| HttpClientError::SerdeJson {
+            err,
+            text: "eth_call params".to_string(),
+        })?;
+        let result = batcher.enqueue(params).await?;
+        serde_json::from_value(result.clone()).map_err(|err| HttpClientError::SerdeJson {
+            err,
+            text: result.to_string(),
+        })
+    }
+}
+
+type Reply = oneshot::Sender<Result<Value, HttpClientError>>;
+
+/// `eth_call`s waiting for the current batch window to close.
+#[derive(Debug)]
+struct Batcher {
+    /// Fallback for calls whose batch was rejected.
+    http: Http,
+    url: reqwest::Url,
+    client: reqwest::Client,
+    next_id: AtomicU64,
+    queue: Mutex<Vec<(Value, Reply)>>,
+}
+
+impl Batcher {
+    async fn enqueue(self: &Arc<Self>, params: Value) -> Result<Value, HttpClientError> {
+        let (reply, response) = oneshot::channel();
+        let full = {
+            let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+            queue.push((params, reply));
+            if queue.len() == 1 {
+                // The first call of a window flushes it once the window closes.
+                let batcher = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(BATCH_WINDOW).await;
+                    let calls = batcher.take();
+                    batcher.flush(calls).await;
+                });
+            }
+            (queue.len() >= MAX_BATCH_SIZE).then(|| std::mem::take(&mut *queue))
+        };
+        if let Some(calls) = full {
+            let batcher = self.clone();
+            tokio::spawn(async move { batcher.flush(calls).await });
+        }
+        response.await.map_err(|_| HttpClientError::SerdeJson {
+            err: serde::de::Error::custom("batched eth_call was dropped"),
+            text: String::new(),
+        })?
+    }
+
+    fn take(&self) -> Vec<(Value, Reply)> {
+        std::mem::take(&mut *self.queue.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Send `calls` as one batch and hand each caller its own response. A provider that rejects
+    /// batches (or drops an entry) gets those calls again one by one.
+    async fn flush(&self, calls: Vec<(Value, Reply)>) {
+        if calls.is_empty() {
+            return;
+        }
+        let first_id = self
+            .next_id
+            .fetch_add(calls.len() as u64, Ordering::Relaxed);
+        let payload: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (params, _))| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": first_id + i as u64,
+                    "method": "eth_call",
+                    "params": params,
+                })
+            })
+            .collect();
+
+        let mut responses = match self.send(&payload).await {
+            Ok(responses) => responses,
+            Err(err) => {
+                tracing::debug!(calls = calls.len(), error = %err, "JSON-RPC batch rejected; sending its calls one by one");
+                Vec::new()
+            }
+        };
+
+        let mut unanswered = Vec::new();
+        for (i, (params, reply)) in calls.into_iter().enumerate() {
+            let id = first_id + i as u64;
+            let entry = responses
+                .iter()
+                .position(|r| r.get("id").and_then(Value::as_u64) == Some(id))
+                .map(|at| responses.swap_remove(at));
+            match entry.map(batch_entry) {
+                Some(Some(result)) => {
+                    let _ = reply.send(result);
+                }
+                _ => unanswered.push((params, reply)),
+            }
+        }
+
+        futures::future::join_all(unanswered.into_iter().map(|(params, reply)| async move {
+            let result = self.http.request::<Value, Value>("eth_call", params).await;
+            let _ = reply.send(result);
+        }))
+        .await;
+    }
+
+    async fn send(&self, payload: &[Value]) -> Result<Vec<Value>, String> {
+        let response = self
+            .client
+            .post(self.url.as_ref())
+            .json(payload)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+        // A provider without batch support answers with a single error object.
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(responses)) => Ok(responses),
+            _ => Err(String::from_utf8_lossy(&body).into_owned()),
+        }
+    }
+}
+
+/// The result or JSON-RPC error of one batch response entry; `None` if it is malformed.
+fn batch_entry(mut entry: Value) -> Option<Result<Value, HttpClientError>> {
+    if let Some(error) = entry.get_mut("error").map(Value::take) {
+        let error: JsonRpcError = serde_json::from_value(error).ok()?;
+        return Some(Err(HttpClientError::JsonRpcError(error)));
+    }
+    entry.get_mut("result").map(Value::take).map(Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RpcTransport;
+    use ethers::providers::{Middleware, Provider};
+    use ethers::types::transaction::eip2718::TypedTransaction;
+    use ethers::types::{Address, Bytes, TransactionRequest, U64};
+    use serde_json::Value;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Local JSON-RPC server that records every HTTP request body. `eth_call` answers with its
+    /// `data` echoed back; a batch is rejected with a single error object if `batches` is false.
+    fn recording_server(batches: bool) -> (reqwest::Url, Arc<Mutex<Vec<Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = bodies.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { return };
+                let recorded = recorded.clone();
+                std::thread::spawn(move || {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    while let Ok(n) = stream.read(&mut chunk) {
+                        if n == 0 {
+                            return;
+                        }
+                        buf.extend_from_slice(&chunk[..n]);
+                        let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                            continue;
+                        };
+                        let head = String::from_utf8_lossy(&buf[..end]).to_lowercase();
+                        let length: usize = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .map_or(0, |v| v.trim().parse().unwrap());
+                        if buf.len() < end + 4 + length {
+                            continue;
+                        }
+                        let body: Value =
+                            serde_json::from_slice(&buf[end + 4..end + 4 + length]).unwrap();
+                        buf.drain(..end + 4 + length);
+                        recorded.lock().unwrap().push(body.clone());
+
+                        let answer = |request: &Value| match request["method"].as_str() {
+                            Some("eth_call") => serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": request["id"],
+                                "result": request["params"][0]["data"],
+                            }),
+                            _ => serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": request["id"],
+                                "result": "0x7",
+                            }),
+                        };
+                        // Batch responses may come back in any order.
+                        let response = match &body {
+                            Value::Array(_) if !batches => serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": null,
+                                "error": {"code": -32600, "message": "batch requests not supported"},
+                            }),
+                            Value::Array(requests) => {
+                                Value::Array(requests.iter().rev().map(answer).collect())
+                            }
+                            request => answer(request),
+                        };
+                        let response = response.to_string();
+                        let reply = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{response}",
+                            response.len()
+                        );
+                        if stream.write_all(reply.as_bytes()).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (url.parse().unwrap(), bodies)
+    }
+
+    fn call(data: u8) -> TypedTransaction {
+        TransactionRequest::new()
+            .to(Address::repeat_byte(0x01))
+            .data(vec![data])
+            .into()
+    }
+
+    async fn five_calls(provider: &Provider<RpcTransport>) {
+        let results = futures::future::join_all(
+            (1..=5u8).map(|i| async move { (i, provider.call(&call(i), None).await.unwrap()) }),
+        )
+        .await;
+        for (i, result) in results {
+            assert_eq!(result, Bytes::from(vec![i]));
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_eth_calls_share_one_batch_request() {
+        let (url, bodies) = recording_server(true);
+        let provider = Provider::new(RpcTransport::new(url, reqwest::Client::new(), true));
+
+        five_calls(&provider).await;
+        {
+            let bodies = bodies.lock().unwrap();
+            assert_eq!(
+                bodies.len(),
+                1,
+                "one HTTP request for all calls: {bodies:?}"
+            );
+            assert_eq!(bodies[0].as_array().unwrap().len(), 5);
+        }
+
+        // Anything but eth_call is sent on its own, right away.
+        assert_eq!(provider.get_block_number().await.unwrap(), U64::from(7));
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[1]["method"], "eth_blockNumber");
+    }
+
+    #[tokio::test]
+    async fn rejected_batch_falls_back_to_single_calls() {
+        let (url, bodies) = recording_server(false);
+        let provider = Provider::new(RpcTransport::new(url, reqwest::Client::new(), true));
+
+        five_calls(&provider).await;
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 6);
+        assert!(bodies[0].is_array());
+        assert!(bodies[1..].iter().all(|b| b["method"] == "eth_call"));
+    }
+
+    #[tokio::test]
+    async fn without_batching_every_call_is_its_own_request() {
+        let (url, bodies) = recording_server(true);
+        let provider = Provider::new(RpcTransport::new(url, reqwest::Client::new(), false));
+
+        five_calls(&provider).await;
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 5);
+        assert!(bodies.iter().all(|b| b.is_object()));
+    }
+}
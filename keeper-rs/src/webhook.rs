@@ -0,0 +1,140 @@
+use crate::scanner::NewSubscription;
+use ethers::types::Address;
+use eyre::{eyre, Result};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Per-request timeout for webhook deliveries.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long shutdown waits for queued deliveries before giving up on them.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// JSON body POSTed to `--new-subscription-webhook-url`, one per newly discovered subscription.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSubscriptionPayload {
+    pub event: &'static str,
+    pub chain_id: u64,
+    pub opensub: Address,
+    #[serde(flatten)]
+    pub subscription: NewSubscription,
+}
+
+impl NewSubscriptionPayload {
+    pub fn new(chain_id: u64, opensub: Address, subscription: NewSubscription) -> Self {
+        Self {
+            event: "subscription.discovered",
+            chain_id,
+            opensub,
+            subscription,
+        }
+    }
+}
+
+/// Best-effort notifier for newly discovered subscriptions.
+///
+/// Deliveries run on a background task so the keeper loop never waits on the endpoint; failures
+/// are logged and dropped (no retries). `close` gives queued deliveries a bounded amount of time
+/// to finish, so a `--once` run doesn't exit with notifications still in the queue.
+pub struct NewSubscriptionWebhook {
+    chain_id: u64,
+    opensub: Address,
+    tx: mpsc::UnboundedSender<NewSubscriptionPayload>,
+    sender: JoinHandle<()>,
+}
+
+impl NewSubscriptionWebhook {
+    pub fn spawn(url: String, chain_id: u64, opensub: Address) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| eyre!("failed to build webhook HTTP client: {e}"))?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sender = tokio::spawn(deliver_loop(rx, http, url));
+        Ok(Self {
+            chain_id,
+            opensub,
+            tx,
+            sender,
+        })
+    }
+
+    pub fn notify(&self, discovered: &[NewSubscription]) {
+        for sub in discovered {
+            let payload = NewSubscriptionPayload::new(self.chain_id, self.opensub, sub.clone());
+            // The sender only goes away if it panicked; notifications are best-effort.
+            let _ = self.tx.send(payload);
+        }
+    }
+
+    pub async fn close(self) {
+        drop(self.tx);
+        if tokio::time::timeout(DRAIN_TIMEOUT, self.sender)
+            .await
+            .is_err()
+        {
+            tracing::warn!("new-subscription webhook still delivering at shutdown; giving up");
+        }
+    }
+}
+
+async fn deliver_loop(
+    mut rx: mpsc::UnboundedReceiver<NewSubscriptionPayload>,
+    http: reqwest::Client,
+    url: String,
+) {
+    while let Some(payload) = rx.recv().await {
+        let subscription_id = payload.subscription.subscription_id;
+        match http.post(&url).json(&payload).send().await {
+            Ok(resp) if resp.status().is_success() => {
+                tracing::debug!(subscription_id, "new-subscription webhook delivered");
+            }
+            Ok(resp) => {
+                tracing::warn!(subscription_id, status = %resp.status(), "new-subscription webhook rejected");
+            }
+            Err(err) => {
+                tracing::warn!(subscription_id, error = %err, "new-subscription webhook failed");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NewSubscriptionPayload;
+    use crate::scanner::NewSubscription;
+    use ethers::types::{Address, H256, U256};
+
+    #[test]
+    fn payload_flattens_the_decoded_subscription() {
+        let payload = NewSubscriptionPayload::new(
+            84532,
+            Address::repeat_byte(0x01),
+            NewSubscription {
+                subscription_id: 7,
+                plan_id: U256::from(2),
+                subscriber: Address::repeat_byte(0xaa),
+                start_time: 1_700_000_000,
+                paid_through: 1_702_592_000,
+                block_number: Some(123),
+                tx_hash: Some(H256::repeat_byte(0x42)),
+            },
+        );
+
+        let v = serde_json::to_value(&payload).unwrap();
+        assert_eq!(v["event"], "subscription.discovered");
+        assert_eq!(v["chainId"], 84532);
+        assert_eq!(v["subscriptionId"], 7);
+        assert_eq!(v["planId"], "0x2");
+        assert_eq!(
+            v["subscriber"],
+            format!("{:#x}", Address::repeat_byte(0xaa))
+        );
+        assert_eq!(v["paidThrough"], 1_702_592_000);
+        assert_eq!(v["blockNumber"], 123);
+    }
+}